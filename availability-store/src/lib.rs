@@ -26,7 +26,7 @@ use futures::prelude::*;
 use futures::{channel::{mpsc, oneshot}, task::Spawn};
 use keystore::KeyStorePtr;
 use polkadot_primitives::{
-	Hash, Block,
+	BlockNumber, Hash, Block,
 	parachain::{
 		PoVBlock, AbridgedCandidateReceipt, ErasureChunk,
 		ParachainHost, AvailableData, OmittedValidationData,
@@ -296,6 +296,36 @@ impl Store {
 		self.inner.get_erasure_chunk(candidate_hash, validator_index)
 	}
 
+	/// Adds an erasure chunk of a candidate to storage, without requiring the candidate
+	/// receipt to be known.
+	///
+	/// The chunk should be checked for validity against the root of encoding
+	/// and its proof prior to calling this. The data of the candidate is kept until
+	/// it is pruned by `block_finalized`.
+	pub fn store_erasure_chunk(&self, candidate_hash: &Hash, chunk: ErasureChunk) -> io::Result<()> {
+		self.inner.store_erasure_chunk(candidate_hash, chunk)
+	}
+
+	/// Adds the PoV block of a candidate to storage, without requiring the candidate
+	/// receipt to be known.
+	///
+	/// The PoV block is kept until it is pruned by `block_finalized`.
+	pub fn store_pov_block(&self, candidate_hash: &Hash, pov_block: PoVBlock) -> io::Result<()> {
+		self.inner.store_pov_block(candidate_hash, pov_block)
+	}
+
+	/// Query the PoV block of a candidate by its hash.
+	pub fn pov_block(&self, candidate_hash: &Hash) -> Option<PoVBlock> {
+		self.inner.pov_block(candidate_hash)
+	}
+
+	/// Note that a block with the given number has been finalized, pruning the data of
+	/// candidates stored with `store_erasure_chunk` or `store_pov_block` which is past the
+	/// acceptance period.
+	pub fn block_finalized(&self, number: BlockNumber, acceptance_period: BlockNumber) -> io::Result<()> {
+		self.inner.block_finalized(number, acceptance_period)
+	}
+
 	/// Note a validator's index and a number of validators at a relay parent in the
 	/// store.
 	///
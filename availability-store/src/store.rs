@@ -20,9 +20,9 @@ use kvdb::{KeyValueDB, DBTransaction};
 use codec::{Encode, Decode};
 use polkadot_erasure_coding as erasure;
 use polkadot_primitives::{
-	Hash,
+	BlockNumber, Hash,
 	parachain::{
-		ErasureChunk, AvailableData, AbridgedCandidateReceipt,
+		ErasureChunk, AvailableData, AbridgedCandidateReceipt, PoVBlock,
 	},
 };
 use parking_lot::Mutex;
@@ -64,9 +64,23 @@ fn candidates_with_relay_parent_key(relay_block: &Hash) -> Vec<u8> {
 	(relay_block, 4i8).encode()
 }
 
+fn pov_block_key(candidate_hash: &Hash) -> Vec<u8> {
+	(candidate_hash, 5i8).encode()
+}
+
 // meta keys
 const AWAITED_CHUNKS_KEY: [u8; 14] = *b"awaited_chunks";
 
+const PRUNING_RECORDS_KEY: [u8; 15] = *b"pruning_records";
+
+/// The key of the pruning records of the candidates stored while the block with the given
+/// number was the last finalized, or before any block was finalized.
+fn pruning_records_key(stored_at: Option<BlockNumber>) -> Vec<u8> {
+	(PRUNING_RECORDS_KEY, stored_at).encode()
+}
+
+const LAST_FINALIZED_KEY: [u8; 14] = *b"last_finalized";
+
 fn validator_index_and_n_validators_key(relay_parent: &Hash) -> Vec<u8> {
 	(relay_parent, 1i8).encode()
 }
@@ -292,6 +306,132 @@ impl Store {
 		}
 	}
 
+	/// Store an erasure chunk of a candidate, regardless of whether the candidate receipt is known.
+	///
+	/// Unlike `add_erasure_chunks`, this neither touches the awaited frontier nor attempts to
+	/// reconstruct the available data. Instead, a pruning record is kept for the candidate so
+	/// that its data is removed by `block_finalized` once it is no longer needed.
+	pub(crate) fn store_erasure_chunk(&self, candidate_hash: &Hash, chunk: ErasureChunk) -> io::Result<()> {
+		let mut tx = DBTransaction::new();
+		let dbkey = erasure_chunks_key(candidate_hash);
+		let av_chunks_key = available_chunks_key(candidate_hash);
+
+		let mut v: Vec<ErasureChunk> = self.query_inner(columns::DATA, &dbkey).unwrap_or_default();
+		let mut have_chunks: Vec<u32> = self.query_inner(columns::META, &av_chunks_key).unwrap_or_default();
+
+		if have_chunks.contains(&chunk.index) {
+			return Ok(());
+		}
+
+		// the first chunk of the candidate comes with its pruning record.
+		if have_chunks.is_empty() && self.pov_block(candidate_hash).is_none() {
+			self.note_pruning_record(&mut tx, candidate_hash);
+		}
+
+		have_chunks.push(chunk.index);
+		v.push(chunk);
+
+		tx.put_vec(columns::DATA, &dbkey, v.encode());
+		tx.put_vec(columns::META, &av_chunks_key, have_chunks.encode());
+
+		self.inner.write(tx)
+	}
+
+	/// Store the PoV block of a candidate, regardless of whether the candidate receipt is known.
+	///
+	/// Like chunks stored with `store_erasure_chunk`, the PoV block is removed by
+	/// `block_finalized` once it is no longer needed.
+	pub(crate) fn store_pov_block(&self, candidate_hash: &Hash, pov_block: PoVBlock) -> io::Result<()> {
+		if self.pov_block(candidate_hash).is_some() {
+			return Ok(());
+		}
+
+		let mut tx = DBTransaction::new();
+
+		let have_chunks: Option<Vec<u32>> = self.query_inner(columns::META, &available_chunks_key(candidate_hash));
+		if have_chunks.map_or(true, |chunks| chunks.is_empty()) {
+			self.note_pruning_record(&mut tx, candidate_hash);
+		}
+
+		tx.put_vec(columns::DATA, &pov_block_key(candidate_hash), pov_block.encode());
+
+		self.inner.write(tx)
+	}
+
+	/// Query the PoV block of a candidate, either stored on its own or as part of the
+	/// execution data.
+	pub(crate) fn pov_block(&self, candidate_hash: &Hash) -> Option<PoVBlock> {
+		self.query_inner(columns::DATA, &pov_block_key(candidate_hash))
+			.or_else(|| self.execution_data(candidate_hash).map(|data| data.pov_block))
+	}
+
+	/// Keep a pruning record for the candidate, at the last finalized block.
+	fn note_pruning_record(&self, tx: &mut DBTransaction, candidate_hash: &Hash) {
+		let stored_at: Option<BlockNumber> = self.query_inner(columns::META, &LAST_FINALIZED_KEY);
+		let records_key = pruning_records_key(stored_at);
+
+		let mut records: Vec<Hash> = self.query_inner(columns::META, &records_key).unwrap_or_default();
+		records.push(*candidate_hash);
+		tx.put_vec(columns::META, &records_key, records.encode());
+	}
+
+	/// Note that a block with the given number has been finalized.
+	///
+	/// This prunes the data of all candidates stored with `store_erasure_chunk` or
+	/// `store_pov_block` while a block
+	/// more than `acceptance_period` blocks older than the given one was the last finalized.
+	///
+	/// Pruning records are kept by the number of the last finalized block at the time of
+	/// storing, so only the records of the block numbers leaving the acceptance period are read.
+	pub(crate) fn block_finalized(&self, number: BlockNumber, acceptance_period: BlockNumber) -> io::Result<()> {
+		let last_finalized: Option<BlockNumber> = self.query_inner(columns::META, &LAST_FINALIZED_KEY);
+		if last_finalized.map_or(false, |last| last >= number) {
+			return Ok(());
+		}
+
+		let mut tx = DBTransaction::new();
+
+		match last_finalized {
+			// data stored before any block was finalized is considered stored at this one.
+			None => {
+				let records: Option<Vec<Hash>> = self.query_inner(columns::META, &pruning_records_key(None));
+				if let Some(records) = records {
+					tx.delete(columns::META, &pruning_records_key(None));
+					tx.put_vec(columns::META, &pruning_records_key(Some(number)), records.encode());
+				}
+			}
+			// records are only stored at finalized block numbers up to `last`, and those more
+			// than `acceptance_period` blocks older than `last` have been pruned already.
+			Some(last) => {
+				let start = last.saturating_sub(acceptance_period);
+				let end = number.saturating_sub(acceptance_period).min(last.saturating_add(1));
+
+				for stored_at in start..end {
+					let records_key = pruning_records_key(Some(stored_at));
+					let records: Vec<Hash> = match self.query_inner(columns::META, &records_key) {
+						Some(records) => records,
+						None => continue,
+					};
+
+					for candidate in records {
+						tx.delete(columns::DATA, execution_data_key(&candidate).as_slice());
+						tx.delete(columns::DATA, &erasure_chunks_key(&candidate));
+						tx.delete(columns::DATA, &candidate_key(&candidate));
+						tx.delete(columns::DATA, &pov_block_key(&candidate));
+
+						tx.delete(columns::META, &available_chunks_key(&candidate));
+					}
+
+					tx.delete(columns::META, &records_key);
+				}
+			}
+		}
+
+		tx.put_vec(columns::META, &LAST_FINALIZED_KEY, number.encode());
+
+		self.inner.write(tx)
+	}
+
 	/// Queries an erasure chunk by its block's relay-parent, the candidate hash, and index.
 	pub fn get_erasure_chunk(
 		&self,
@@ -351,6 +491,7 @@ impl Store {
 			tx.delete(columns::DATA, execution_data_key(&candidate).as_slice());
 			tx.delete(columns::DATA, &erasure_chunks_key(&candidate));
 			tx.delete(columns::DATA, &candidate_key(&candidate));
+			tx.delete(columns::DATA, &pov_block_key(&candidate));
 
 			tx.delete(columns::META, &available_chunks_key(&candidate));
 		}
@@ -620,4 +761,106 @@ mod tests {
 
 		assert_eq!(store.awaited_chunks().unwrap().len(), 0);
 	}
+
+	#[test]
+	fn stored_chunks_are_pruned_after_acceptance_period() {
+		let candidate_hash = [1; 32].into();
+		let chunk = ErasureChunk {
+			chunk: vec![10, 20, 30],
+			index: 2,
+			proof: vec![],
+		};
+
+		let acceptance_period = 10;
+		let store = Store::new_in_memory();
+
+		store.block_finalized(5, acceptance_period).unwrap();
+		store.store_erasure_chunk(&candidate_hash, chunk.clone()).unwrap();
+
+		// the receipt isn't needed to query the chunk.
+		assert_eq!(store.get_erasure_chunk(&candidate_hash, 2), Some(chunk.clone()));
+
+		store.block_finalized(15, acceptance_period).unwrap();
+		assert_eq!(store.get_erasure_chunk(&candidate_hash, 2), Some(chunk));
+
+		store.block_finalized(16, acceptance_period).unwrap();
+		assert!(store.get_erasure_chunk(&candidate_hash, 2).is_none());
+	}
+
+	#[test]
+	fn chunks_stored_before_finality_are_kept_for_acceptance_period() {
+		let candidate_hash = [1; 32].into();
+		let chunk = ErasureChunk {
+			chunk: vec![10, 20, 30],
+			index: 0,
+			proof: vec![],
+		};
+
+		let acceptance_period = 10;
+		let store = Store::new_in_memory();
+
+		store.store_erasure_chunk(&candidate_hash, chunk.clone()).unwrap();
+
+		store.block_finalized(100, acceptance_period).unwrap();
+		assert_eq!(store.get_erasure_chunk(&candidate_hash, 0), Some(chunk));
+
+		store.block_finalized(111, acceptance_period).unwrap();
+		assert!(store.get_erasure_chunk(&candidate_hash, 0).is_none());
+	}
+
+	#[test]
+	fn chunks_are_pruned_by_the_block_they_were_stored_at() {
+		let chunk = ErasureChunk {
+			chunk: vec![10, 20, 30],
+			index: 0,
+			proof: vec![],
+		};
+		let (candidate_a, candidate_b) = ([1; 32].into(), [2; 32].into());
+
+		let acceptance_period = 10;
+		let store = Store::new_in_memory();
+
+		store.block_finalized(5, acceptance_period).unwrap();
+		store.store_erasure_chunk(&candidate_a, chunk.clone()).unwrap();
+
+		store.block_finalized(8, acceptance_period).unwrap();
+		store.store_erasure_chunk(&candidate_b, chunk.clone()).unwrap();
+
+		store.block_finalized(16, acceptance_period).unwrap();
+		assert!(store.get_erasure_chunk(&candidate_a, 0).is_none());
+		assert_eq!(store.get_erasure_chunk(&candidate_b, 0), Some(chunk.clone()));
+
+		// finality may skip blocks.
+		store.block_finalized(100, acceptance_period).unwrap();
+		assert!(store.get_erasure_chunk(&candidate_b, 0).is_none());
+		assert!(store.query_inner::<Vec<Hash>>(columns::META, &pruning_records_key(Some(8))).is_none());
+	}
+
+	#[test]
+	fn stored_pov_block_is_pruned_with_the_chunks() {
+		let candidate_hash = [1; 32].into();
+		let pov_block = PoVBlock { block_data: BlockData(vec![1, 2, 3]) };
+		let chunk = ErasureChunk {
+			chunk: vec![10, 20, 30],
+			index: 0,
+			proof: vec![],
+		};
+
+		let acceptance_period = 10;
+		let store = Store::new_in_memory();
+
+		store.block_finalized(5, acceptance_period).unwrap();
+		store.store_pov_block(&candidate_hash, pov_block.clone()).unwrap();
+		store.store_erasure_chunk(&candidate_hash, chunk).unwrap();
+
+		assert_eq!(store.pov_block(&candidate_hash), Some(pov_block));
+		assert_eq!(
+			store.query_inner::<Vec<Hash>>(columns::META, &pruning_records_key(Some(5))),
+			Some(vec![candidate_hash]),
+		);
+
+		store.block_finalized(16, acceptance_period).unwrap();
+		assert!(store.pov_block(&candidate_hash).is_none());
+		assert!(store.get_erasure_chunk(&candidate_hash, 0).is_none());
+	}
 }
@@ -0,0 +1,339 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The Availability Store subsystem.
+//!
+//! This wraps the availability store database, answering queries for PoVs and erasure chunks
+//! and storing them on behalf of other subsystems. Stored data is pruned once it is past the
+//! acceptance period, as blocks are finalized.
+
+use polkadot_subsystem::{
+	Subsystem, SubsystemContext, SpawnedSubsystem, SubsystemResult,
+	FromOverseer, OverseerSignal,
+};
+use polkadot_subsystem::messages::AvailabilityStoreMessage;
+use polkadot_primitives::{BlockNumber, Hash};
+use polkadot_primitives::parachain::ErasureChunk;
+use polkadot_availability_store::{ErasureNetworking, Store};
+
+use futures::prelude::*;
+use std::pin::Pin;
+
+pub use polkadot_availability_store::Config;
+
+const LOG_TARGET: &str = "availability_store";
+
+/// The availability store subsystem.
+#[derive(Clone)]
+pub struct AvailabilityStoreSubsystem {
+	store: Store,
+	acceptance_period: BlockNumber,
+}
+
+impl AvailabilityStoreSubsystem {
+	/// Create a new availability store subsystem wrapping the given store.
+	///
+	/// Stored data is pruned once a block `acceptance_period` blocks past the last finalized
+	/// block at the time of storing is finalized.
+	pub fn new(store: Store, acceptance_period: BlockNumber) -> Self {
+		AvailabilityStoreSubsystem { store, acceptance_period }
+	}
+
+	/// Open the on-disk store described by `config` and wrap it in a new availability store
+	/// subsystem.
+	#[cfg(not(target_os = "unknown"))]
+	pub fn open(config: Config, acceptance_period: BlockNumber) -> std::io::Result<Self> {
+		Ok(Self::new(Store::new(config, NoNetworking)?, acceptance_period))
+	}
+
+	/// Create a new availability store subsystem wrapping a store kept in memory.
+	pub fn new_in_memory(acceptance_period: BlockNumber) -> Self {
+		Self::new(Store::new_in_memory(NoNetworking), acceptance_period)
+	}
+}
+
+/// The networking of a store owned by the subsystem.
+///
+/// Chunks are fetched and distributed by the availability distribution subsystem, so the store
+/// itself never goes to the network.
+#[derive(Clone)]
+struct NoNetworking;
+
+impl ErasureNetworking for NoNetworking {
+	type Error = ();
+
+	fn fetch_erasure_chunk(
+		&self,
+		_candidate_hash: &Hash,
+		_index: u32,
+	) -> Pin<Box<dyn Future<Output = Result<ErasureChunk, Self::Error>> + Send>> {
+		future::pending().boxed()
+	}
+
+	fn distribute_erasure_chunk(&self, _candidate_hash: Hash, _chunk: ErasureChunk) {}
+}
+
+impl<C> Subsystem<C> for AvailabilityStoreSubsystem
+	where C: SubsystemContext<Message = AvailabilityStoreMessage>
+{
+	fn start(self, ctx: C) -> SpawnedSubsystem {
+		SpawnedSubsystem(run(ctx, self.store, self.acceptance_period).map(|_| ()).boxed())
+	}
+}
+
+async fn run(
+	mut ctx: impl SubsystemContext<Message = AvailabilityStoreMessage>,
+	store: Store,
+	acceptance_period: BlockNumber,
+) -> SubsystemResult<()> {
+	loop {
+		match ctx.recv().await? {
+			FromOverseer::Signal(OverseerSignal::ActiveLeaves(_)) => {}
+			FromOverseer::Signal(OverseerSignal::BlockFinalized(hash, number)) => {
+				if let Err(e) = store.block_finalized(number, acceptance_period) {
+					log::warn!(
+						target: LOG_TARGET,
+						"Failed to prune availability data on finality of block {}: {:?}",
+						hash,
+						e,
+					);
+				}
+			}
+			FromOverseer::Signal(OverseerSignal::Conclude) => return Ok(()),
+			FromOverseer::Communication { msg } => process_message(&store, msg),
+		}
+	}
+}
+
+fn process_message(store: &Store, msg: AvailabilityStoreMessage) {
+	match msg {
+		AvailabilityStoreMessage::QueryPoV(candidate_hash, response_sender) => {
+			let _ = response_sender.send(store.pov_block(&candidate_hash));
+		}
+		AvailabilityStoreMessage::QueryChunk(candidate_hash, validator_index, response_sender) => {
+			let chunk = store.get_erasure_chunk(&candidate_hash, validator_index as usize);
+			let _ = response_sender.send(chunk);
+		}
+		AvailabilityStoreMessage::StoreChunk(candidate_hash, validator_index, chunk) => {
+			if chunk.index != validator_index {
+				log::debug!(
+					target: LOG_TARGET,
+					"Refusing to store chunk {} of candidate {} under validator index {}",
+					chunk.index,
+					candidate_hash,
+					validator_index,
+				);
+
+				return;
+			}
+
+			if let Err(e) = store.store_erasure_chunk(&candidate_hash, chunk) {
+				log::warn!(
+					target: LOG_TARGET,
+					"Failed to store chunk of candidate {}: {:?}",
+					candidate_hash,
+					e,
+				);
+			}
+		}
+		AvailabilityStoreMessage::StorePoV(candidate_hash, pov) => {
+			if let Err(e) = store.store_pov_block(&candidate_hash, pov) {
+				log::warn!(
+					target: LOG_TARGET,
+					"Failed to store PoV of candidate {}: {:?}",
+					candidate_hash,
+					e,
+				);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::{self, ThreadPool};
+	use futures::channel::oneshot;
+	use polkadot_primitives::parachain::{BlockData, PoVBlock};
+
+	const ACCEPTANCE_PERIOD: BlockNumber = 10;
+
+	struct TestHarness {
+		virtual_overseer: subsystem_test::TestSubsystemContextHandle<AvailabilityStoreMessage>,
+	}
+
+	fn test_harness<T: Future<Output=()>>(test: impl FnOnce(TestHarness) -> T) {
+		let pool = ThreadPool::new().unwrap();
+
+		let (context, virtual_overseer) = subsystem_test::make_subsystem_context(pool);
+
+		let store = Store::new_in_memory(NoNetworking);
+		let subsystem = run(context, store, ACCEPTANCE_PERIOD)
+			.map_err(|_| panic!("subsystem execution failed"))
+			.map(|_| ());
+
+		let test_fut = test(TestHarness { virtual_overseer });
+
+		futures::pin_mut!(test_fut);
+		futures::pin_mut!(subsystem);
+
+		executor::block_on(future::select(test_fut, subsystem));
+	}
+
+	fn test_chunk(index: u32) -> ErasureChunk {
+		ErasureChunk {
+			chunk: vec![1, 2, 3],
+			index,
+			proof: vec![vec![4, 5, 6]],
+		}
+	}
+
+	async fn query_chunk(
+		virtual_overseer: &mut subsystem_test::TestSubsystemContextHandle<AvailabilityStoreMessage>,
+		candidate_hash: Hash,
+		index: u32,
+	) -> Option<ErasureChunk> {
+		let (tx, rx) = oneshot::channel();
+		virtual_overseer.send(FromOverseer::Communication {
+			msg: AvailabilityStoreMessage::QueryChunk(candidate_hash, index, tx),
+		}).await;
+
+		rx.await.unwrap()
+	}
+
+	async fn query_pov(
+		virtual_overseer: &mut subsystem_test::TestSubsystemContextHandle<AvailabilityStoreMessage>,
+		candidate_hash: Hash,
+	) -> Option<PoVBlock> {
+		let (tx, rx) = oneshot::channel();
+		virtual_overseer.send(FromOverseer::Communication {
+			msg: AvailabilityStoreMessage::QueryPoV(candidate_hash, tx),
+		}).await;
+
+		rx.await.unwrap()
+	}
+
+	#[test]
+	fn stored_chunk_can_be_queried() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let candidate_hash = Hash::from([1; 32]);
+			let chunk = test_chunk(3);
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: AvailabilityStoreMessage::StoreChunk(candidate_hash, 3, chunk.clone()),
+			}).await;
+
+			assert_eq!(query_chunk(&mut virtual_overseer, candidate_hash, 3).await, Some(chunk));
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn missing_data_is_answered_with_none() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let candidate_hash = Hash::from([1; 32]);
+
+			assert_eq!(query_chunk(&mut virtual_overseer, candidate_hash, 0).await, None);
+
+			assert_eq!(query_pov(&mut virtual_overseer, candidate_hash).await, None);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn chunk_with_mismatching_index_is_not_stored() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let candidate_hash = Hash::from([1; 32]);
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: AvailabilityStoreMessage::StoreChunk(candidate_hash, 1, test_chunk(2)),
+			}).await;
+
+			assert_eq!(query_chunk(&mut virtual_overseer, candidate_hash, 1).await, None);
+			assert_eq!(query_chunk(&mut virtual_overseer, candidate_hash, 2).await, None);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn chunks_are_pruned_after_acceptance_period() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let candidate_hash = Hash::from([1; 32]);
+			let chunk = test_chunk(0);
+
+			virtual_overseer.send(FromOverseer::Signal(
+				OverseerSignal::BlockFinalized(Hash::from([2; 32]), 1),
+			)).await;
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: AvailabilityStoreMessage::StoreChunk(candidate_hash, 0, chunk.clone()),
+			}).await;
+
+			virtual_overseer.send(FromOverseer::Signal(
+				OverseerSignal::BlockFinalized(Hash::from([3; 32]), 1 + ACCEPTANCE_PERIOD),
+			)).await;
+
+			assert_eq!(query_chunk(&mut virtual_overseer, candidate_hash, 0).await, Some(chunk));
+
+			virtual_overseer.send(FromOverseer::Signal(
+				OverseerSignal::BlockFinalized(Hash::from([4; 32]), 2 + ACCEPTANCE_PERIOD),
+			)).await;
+
+			assert_eq!(query_chunk(&mut virtual_overseer, candidate_hash, 0).await, None);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn stored_pov_can_be_queried_until_pruned() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let candidate_hash = Hash::from([1; 32]);
+			let pov = PoVBlock { block_data: BlockData(vec![1, 2, 3]) };
+
+			virtual_overseer.send(FromOverseer::Signal(
+				OverseerSignal::BlockFinalized(Hash::from([2; 32]), 1),
+			)).await;
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: AvailabilityStoreMessage::StorePoV(candidate_hash, pov.clone()),
+			}).await;
+
+			assert_eq!(query_pov(&mut virtual_overseer, candidate_hash).await, Some(pov));
+
+			virtual_overseer.send(FromOverseer::Signal(
+				OverseerSignal::BlockFinalized(Hash::from([3; 32]), 2 + ACCEPTANCE_PERIOD),
+			)).await;
+
+			assert_eq!(query_pov(&mut virtual_overseer, candidate_hash).await, None);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+}
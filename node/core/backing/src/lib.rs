@@ -0,0 +1,1245 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The Candidate Backing subsystem.
+//!
+//! This subsystem spawns one job per relay-parent that we are working on. Each job keeps a
+//! statement table for its relay-parent, seconds and validates candidates of the para that
+//! we are assigned to, and tallies statements from other validators until candidates are
+//! backed. Backed candidates are passed on to the Provisioner and to any registered watchers.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::channel::mpsc;
+use futures::prelude::*;
+use futures::select;
+use futures::stream::FuturesUnordered;
+use keystore::KeyStorePtr;
+
+use polkadot_subsystem::{
+	Subsystem, SubsystemContext, SpawnedSubsystem, SubsystemResult,
+	FromOverseer, OverseerSignal, ActiveLeavesUpdate, RequestError,
+};
+use polkadot_subsystem::jobs::{JobEvent, JobManager, JobMessage, JobSender};
+use polkadot_subsystem::messages::{
+	AllMessages, AvailabilityDistributionMessage, AvailabilityStoreMessage, CandidateBackingMessage,
+	CandidateSelectionMessage, CandidateValidationMessage, NewBackedCandidate, PoVDistributionMessage,
+	ProvisionableData, ProvisionerMessage, RuntimeApiMessage, RuntimeApiRequest, RuntimeApiSender,
+	StatementDistributionMessage, ValidationFailed,
+};
+use polkadot_node_primitives::{
+	Statement, SignedFullStatement, ValidationResult, ValidationOutputs, CoreState,
+};
+use polkadot_primitives::Hash;
+use polkadot_primitives::parachain::{
+	v2::AbridgedCandidateReceipt, AvailableData, BackedCandidate, CoreIndex, ErasureChunk, Id as ParaId,
+	PoVBlock, SigningContext, ValidatorId, ValidatorIndex, ValidatorPair, ValidatorSignature,
+	ValidityAttestation,
+};
+use polkadot_erasure_coding as erasure;
+use statement_table::{
+	generic::AttestedCandidate as TableAttestedCandidate,
+	Context as TableContextTrait,
+	Table,
+	SignedStatement as TableSignedStatement,
+	Statement as TableStatement,
+	Summary as TableSummary,
+};
+
+const LOG_TARGET: &str = "candidate_backing";
+
+/// How long to wait for the runtime API to answer a request.
+const RUNTIME_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long to wait for the validation of a candidate.
+const VALIDATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait for the PoV of a candidate to be fetched from the validators of our group.
+const POV_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The candidate backing subsystem.
+#[derive(Clone)]
+pub struct CandidateBackingSubsystem {
+	keystore: KeyStorePtr,
+}
+
+impl CandidateBackingSubsystem {
+	/// Create a new candidate backing subsystem, which signs statements with the
+	/// validator keys found in the given keystore.
+	pub fn new(keystore: KeyStorePtr) -> Self {
+		CandidateBackingSubsystem { keystore }
+	}
+}
+
+impl<C> Subsystem<C> for CandidateBackingSubsystem
+	where C: SubsystemContext<Message = CandidateBackingMessage>
+{
+	fn start(self, ctx: C) -> SpawnedSubsystem {
+		SpawnedSubsystem(run(ctx, self.keystore).map(|_| ()).boxed())
+	}
+}
+
+enum Incoming<M> {
+	Overseer(SubsystemResult<FromOverseer<M>>),
+	FromJobs(Option<JobEvent>),
+}
+
+async fn run(
+	mut ctx: impl SubsystemContext<Message = CandidateBackingMessage>,
+	keystore: KeyStorePtr,
+) -> SubsystemResult<()> {
+	let mut jobs = JobManager::new();
+
+	loop {
+		let incoming = {
+			let from_overseer = ctx.recv().fuse();
+			futures::pin_mut!(from_overseer);
+
+			select! {
+				msg = from_overseer => Incoming::Overseer(msg),
+				event = jobs.next() => Incoming::FromJobs(event),
+			}
+		};
+
+		match incoming {
+			Incoming::Overseer(msg) => match msg? {
+				FromOverseer::Signal(OverseerSignal::ActiveLeaves(ActiveLeavesUpdate { activated, deactivated })) => {
+					for relay_parent in activated {
+						let keystore = keystore.clone();
+						jobs.spawn_job(&mut ctx, relay_parent, move |rx_to, sender| {
+							run_job(relay_parent, keystore, rx_to, sender)
+						}).await?;
+					}
+
+					for relay_parent in deactivated {
+						jobs.stop_job(relay_parent);
+					}
+				}
+				FromOverseer::Signal(OverseerSignal::BlockFinalized(..)) => {}
+				FromOverseer::Signal(OverseerSignal::Conclude) => {
+					jobs.stop_all();
+
+					return Ok(());
+				}
+				FromOverseer::Communication { msg } => {
+					if let Err(msg) = jobs.send_msg(msg) {
+						log::trace!(
+							target: LOG_TARGET,
+							"Dropping message for relay-parent {} we are not working on",
+							msg.relay_parent(),
+						);
+					}
+				}
+			},
+			Incoming::FromJobs(Some(JobEvent::Message(msg))) => ctx.send_message(msg).await?,
+			Incoming::FromJobs(Some(JobEvent::Failed(relay_parent, e))) => {
+				log::warn!(target: LOG_TARGET, "Backing job for relay-parent {} failed: {:?}", relay_parent, e);
+			}
+			// the job manager is a never-ending stream, so this is unreachable.
+			Incoming::FromJobs(None) => return Ok(()),
+		}
+	}
+}
+
+/// Our own validator identity in the context of a relay-parent.
+struct LocalValidator {
+	index: ValidatorIndex,
+	key: Arc<ValidatorPair>,
+	/// The para we are assigned to, if any.
+	assignment: Option<ParaId>,
+}
+
+/// The context of the statement table of a job.
+struct TableContext {
+	signing_context: SigningContext,
+	validators: Vec<ValidatorId>,
+	groups: HashMap<ParaId, Vec<ValidatorIndex>>,
+	local_validator: Option<LocalValidator>,
+}
+
+impl TableContextTrait for TableContext {
+	fn is_member_of(&self, authority: ValidatorIndex, group: &ParaId) -> bool {
+		self.groups.get(group).map_or(false, |g| g.contains(&authority))
+	}
+
+	fn requisite_votes(&self, group: &ParaId) -> usize {
+		self.groups.get(group).map_or(usize::max_value(), |g| minimum_votes(g.len()))
+	}
+}
+
+/// The number of validity votes required from a group of the given size for a candidate
+/// to be backed: a simple majority.
+fn minimum_votes(group_len: usize) -> usize {
+	group_len / 2 + 1
+}
+
+/// Finds the first key we are capable of signing with out of the given set of validators,
+/// if any, along with its index.
+fn signing_key(validators: &[ValidatorId], keystore: &KeyStorePtr) -> Option<(ValidatorIndex, Arc<ValidatorPair>)> {
+	let keystore = keystore.read();
+	validators.iter()
+		.enumerate()
+		.find_map(|(i, v)| {
+			keystore.key_pair::<ValidatorPair>(&v).ok().map(|pair| (i as ValidatorIndex, Arc::new(pair)))
+		})
+}
+
+/// The outcome of a validation run in the background of a job.
+enum BackgroundValidation {
+	/// A candidate we were asked to second, validated with the PoV we were given.
+	Second(AbridgedCandidateReceipt, PoVBlock, Result<ValidationResult, ValidationFailed>),
+	/// A candidate seconded by another validator of our group, validated with the PoV we fetched.
+	/// This is `None` if the PoV could not be fetched.
+	Attest(AbridgedCandidateReceipt, Option<(PoVBlock, Result<ValidationResult, ValidationFailed>)>),
+}
+
+type BackgroundValidationFuture = future::BoxFuture<'static, SubsystemResult<BackgroundValidation>>;
+
+/// The state of a job for a single relay-parent.
+struct CandidateBackingJob {
+	parent: Hash,
+	sender: JobSender,
+	table: Table<TableContext>,
+	table_context: TableContext,
+	/// The candidate we have seconded, if any. We second at most one candidate per relay-parent.
+	seconded: Option<Hash>,
+	/// Candidates we have issued a statement about.
+	issued_statements: HashSet<Hash>,
+	/// Candidates seconded by other validators which are being validated in the background.
+	attesting: HashSet<Hash>,
+	/// Validations running in the background, which report back to the job when done.
+	background_validations: FuturesUnordered<BackgroundValidationFuture>,
+	/// Candidates which have been backed, in the order they were backed.
+	backed: Vec<BackedCandidate>,
+	/// Hashes of the candidates in `backed`.
+	backed_hashes: HashSet<Hash>,
+	watchers: Vec<mpsc::Sender<NewBackedCandidate>>,
+}
+
+enum JobIncoming {
+	FromSubsystem(Option<CandidateBackingMessage>),
+	Validated(Option<SubsystemResult<BackgroundValidation>>),
+}
+
+async fn run_job(
+	parent: Hash,
+	keystore: KeyStorePtr,
+	mut rx_to: mpsc::UnboundedReceiver<CandidateBackingMessage>,
+	sender: JobSender,
+) -> SubsystemResult<()> {
+	let (validators, signing_context, (validator_groups, group_rotation_info), cores) = future::try_join4(
+		request_from_runtime(parent, sender.clone(), RuntimeApiRequest::Validators),
+		request_from_runtime(parent, sender.clone(), RuntimeApiRequest::SigningContext),
+		request_from_runtime(parent, sender.clone(), RuntimeApiRequest::ValidatorGroups),
+		request_from_runtime(parent, sender.clone(), RuntimeApiRequest::AvailabilityCores),
+	).await?;
+
+	// candidates can only be backed for paras scheduled on a core, by the group assigned to it.
+	let n_cores = cores.len();
+	let mut groups: HashMap<ParaId, Vec<ValidatorIndex>> = HashMap::new();
+	for (core_index, core) in cores.into_iter().enumerate() {
+		if let CoreState::Scheduled(para_id) = core {
+			let group_index = group_rotation_info.group_for_core(CoreIndex(core_index as u32), n_cores);
+			if let Some(group) = validator_groups.get(group_index.0 as usize) {
+				groups.insert(para_id, group.clone());
+			}
+		}
+	}
+
+	let local_validator = signing_key(&validators, &keystore).map(|(index, key)| {
+		let assignment = groups.iter()
+			.find(|(_, group)| group.contains(&index))
+			.map(|(para_id, _)| *para_id);
+
+		LocalValidator { index, key, assignment }
+	});
+
+	let mut job = CandidateBackingJob {
+		parent,
+		sender,
+		table: Table::default(),
+		table_context: TableContext {
+			signing_context,
+			validators,
+			groups,
+			local_validator,
+		},
+		seconded: None,
+		issued_statements: HashSet::new(),
+		attesting: HashSet::new(),
+		background_validations: FuturesUnordered::new(),
+		backed: Vec::new(),
+		backed_hashes: HashSet::new(),
+		watchers: Vec::new(),
+	};
+
+	loop {
+		let incoming = select! {
+			msg = rx_to.next() => JobIncoming::FromSubsystem(msg),
+			validated = job.background_validations.next() => JobIncoming::Validated(validated),
+		};
+
+		match incoming {
+			JobIncoming::FromSubsystem(Some(msg)) => job.process_msg(msg).await?,
+			JobIncoming::FromSubsystem(None) => return Ok(()),
+			JobIncoming::Validated(Some(validated)) => job.process_validated(validated?).await?,
+			// no validations are running; the stream is polled again once one is started.
+			JobIncoming::Validated(None) => {}
+		}
+	}
+}
+
+impl CandidateBackingJob {
+	async fn process_msg(&mut self, msg: CandidateBackingMessage) -> SubsystemResult<()> {
+		match msg {
+			CandidateBackingMessage::Second(_, candidate, pov) => {
+				if self.seconded.is_some() {
+					return Ok(());
+				}
+
+				// we only second candidates of the para we are assigned to.
+				if self.assignment() != Some(candidate.parachain_index) {
+					return Ok(());
+				}
+
+				if self.issued_statements.contains(&candidate.hash()) {
+					return Ok(());
+				}
+
+				let validation = request_candidate_validation(
+					self.parent,
+					self.sender.clone(),
+					candidate.clone(),
+					pov.clone(),
+				);
+				self.background_validations.push(async move {
+					Ok(BackgroundValidation::Second(candidate, pov, validation.await?))
+				}.boxed());
+			}
+			CandidateBackingMessage::Statement(_, statement) => {
+				if self.check_statement_signature(&statement).is_err() {
+					log::debug!(
+						target: LOG_TARGET,
+						"Dropping statement with a bad signature from validator {}",
+						statement.validator_index(),
+					);
+
+					return Ok(());
+				}
+
+				self.import_statement(&statement).await?;
+
+				if let Statement::Seconded(candidate) = statement.payload() {
+					let candidate_hash = candidate.hash();
+					if self.assignment() == Some(candidate.parachain_index)
+						&& !self.issued_statements.contains(&candidate_hash)
+						&& self.attesting.insert(candidate_hash)
+					{
+						self.start_attesting(candidate.clone());
+					}
+				}
+			}
+			CandidateBackingMessage::RegisterBackingWatcher(_, mut watcher) => {
+				for backed in &self.backed {
+					// the watcher may have hung up or be full already; a hung up watcher is pruned on
+					// the next notification.
+					let _ = watcher.try_send(NewBackedCandidate(backed.clone()));
+				}
+
+				self.watchers.push(watcher);
+			}
+		}
+
+		Ok(())
+	}
+
+	fn assignment(&self) -> Option<ParaId> {
+		self.table_context.local_validator.as_ref().and_then(|v| v.assignment)
+	}
+
+	/// Fetch the PoV of a candidate seconded by another validator of our group and validate it
+	/// in the background.
+	fn start_attesting(&mut self, candidate: AbridgedCandidateReceipt) {
+		let parent = self.parent;
+		let sender = self.sender.clone();
+
+		self.background_validations.push(async move {
+			let pov = match request_pov(parent, sender.clone(), candidate.clone()).await? {
+				Some(pov) => pov,
+				None => return Ok(BackgroundValidation::Attest(candidate, None)),
+			};
+
+			let result = request_candidate_validation(parent, sender, candidate.clone(), pov.clone()).await?;
+
+			Ok(BackgroundValidation::Attest(candidate, Some((pov, result))))
+		}.boxed());
+	}
+
+	/// Act on the outcome of a validation which ran in the background.
+	async fn process_validated(&mut self, validated: BackgroundValidation) -> SubsystemResult<()> {
+		match validated {
+			BackgroundValidation::Second(candidate, pov, result) => self.second(candidate, pov, result).await,
+			BackgroundValidation::Attest(candidate, validated) => {
+				let candidate_hash = candidate.hash();
+				self.attesting.remove(&candidate_hash);
+
+				match validated {
+					Some((pov, result)) => self.vote(candidate, pov, result).await,
+					None => {
+						log::debug!(target: LOG_TARGET, "PoV for candidate {} is not available", candidate_hash);
+						Ok(())
+					}
+				}
+			}
+		}
+	}
+
+	/// Second a candidate we were asked to second, if it is valid and we haven't seconded or
+	/// voted on any other candidate in the meantime.
+	async fn second(
+		&mut self,
+		candidate: AbridgedCandidateReceipt,
+		pov: PoVBlock,
+		result: Result<ValidationResult, ValidationFailed>,
+	) -> SubsystemResult<()> {
+		let candidate_hash = candidate.hash();
+
+		match result {
+			Ok(ValidationResult::Valid(outputs)) => {
+				if self.seconded.is_some() || self.issued_statements.contains(&candidate_hash) {
+					return Ok(());
+				}
+
+				self.send(AllMessages::PoVDistribution(
+					PoVDistributionMessage::DistributePoV(self.parent, candidate.clone(), pov.clone()),
+				)).await?;
+
+				self.make_pov_available(&candidate, pov, outputs).await?;
+
+				self.seconded = Some(candidate_hash);
+				self.issued_statements.insert(candidate_hash);
+
+				if let Some(statement) = self.sign_statement(Statement::Seconded(candidate)) {
+					self.import_and_distribute(statement).await?;
+				}
+			}
+			Ok(ValidationResult::Invalid(reason)) => {
+				log::debug!(
+					target: LOG_TARGET,
+					"Candidate {} we were asked to second is invalid: {:?}",
+					candidate_hash,
+					reason,
+				);
+
+				self.send(AllMessages::CandidateSelection(
+					CandidateSelectionMessage::Invalid(self.parent, candidate),
+				)).await?;
+			}
+			Err(ValidationFailed(e)) => log::warn!(
+				target: LOG_TARGET,
+				"Failed to validate candidate {}: {}",
+				candidate_hash,
+				e,
+			),
+		}
+
+		Ok(())
+	}
+
+	/// Issue a `Valid` or `Invalid` statement about a candidate seconded by another validator
+	/// of our group.
+	async fn vote(
+		&mut self,
+		candidate: AbridgedCandidateReceipt,
+		pov: PoVBlock,
+		result: Result<ValidationResult, ValidationFailed>,
+	) -> SubsystemResult<()> {
+		let candidate_hash = candidate.hash();
+
+		// we may have seconded the candidate ourselves in the meantime.
+		if self.issued_statements.contains(&candidate_hash) {
+			return Ok(());
+		}
+
+		let statement = match result {
+			Ok(ValidationResult::Valid(outputs)) => {
+				self.make_pov_available(&candidate, pov, outputs).await?;
+				Statement::Valid(candidate_hash)
+			}
+			Ok(ValidationResult::Invalid(reason)) => {
+				log::debug!(
+					target: LOG_TARGET,
+					"Candidate {} is invalid: {:?}",
+					candidate_hash,
+					reason,
+				);
+
+				Statement::Invalid(candidate_hash)
+			}
+			Err(ValidationFailed(e)) => {
+				log::warn!(target: LOG_TARGET, "Failed to validate candidate {}: {}", candidate_hash, e);
+				return Ok(());
+			}
+		};
+
+		self.issued_statements.insert(candidate_hash);
+
+		if let Some(statement) = self.sign_statement(statement) {
+			self.import_and_distribute(statement).await?;
+		}
+
+		Ok(())
+	}
+
+	fn sign_statement(&self, statement: Statement) -> Option<SignedFullStatement> {
+		let local_validator = self.table_context.local_validator.as_ref()?;
+
+		Some(SignedFullStatement::sign(
+			statement,
+			&self.table_context.signing_context,
+			local_validator.index,
+			&local_validator.key,
+		))
+	}
+
+	fn check_statement_signature(&self, statement: &SignedFullStatement) -> Result<(), ()> {
+		let key = self.table_context.validators.get(statement.validator_index() as usize).ok_or(())?;
+
+		statement.check_signature(&self.table_context.signing_context, key)
+	}
+
+	async fn import_and_distribute(&mut self, statement: SignedFullStatement) -> SubsystemResult<()> {
+		self.import_statement(&statement).await?;
+
+		self.send(AllMessages::StatementDistribution(
+			StatementDistributionMessage::Share(self.parent, statement),
+		)).await
+	}
+
+	/// Import a statement into the table, announcing the candidate it refers to if it
+	/// became backed.
+	async fn import_statement(&mut self, statement: &SignedFullStatement) -> SubsystemResult<()> {
+		let summary = self.table.import_statement(&self.table_context, table_statement(statement));
+
+		if let Some(summary) = summary {
+			self.note_backed_if_attested(summary).await?;
+		}
+
+		Ok(())
+	}
+
+	async fn note_backed_if_attested(&mut self, summary: TableSummary) -> SubsystemResult<()> {
+		if self.backed_hashes.contains(&summary.candidate) {
+			return Ok(());
+		}
+
+		let attested = match self.table.attested_candidate(&summary.candidate, &self.table_context) {
+			Some(attested) => attested,
+			None => return Ok(()),
+		};
+
+		let backed = match self.table_attested_to_backed(attested) {
+			Some(backed) => backed,
+			None => return Ok(()),
+		};
+
+		self.backed_hashes.insert(summary.candidate);
+		self.backed.push(backed.clone());
+
+		// watchers which don't keep up miss notifications rather than holding up the job.
+		let mut watchers = Vec::with_capacity(self.watchers.len());
+		for mut watcher in self.watchers.drain(..) {
+			match watcher.try_send(NewBackedCandidate(backed.clone())) {
+				Err(e) if e.is_disconnected() => {}
+				Err(_) => {
+					log::debug!(
+						target: LOG_TARGET,
+						"Backing watcher is full, dropping notification of candidate {}",
+						summary.candidate,
+					);
+
+					watchers.push(watcher);
+				}
+				Ok(()) => watchers.push(watcher),
+			}
+		}
+		self.watchers = watchers;
+
+		self.send(AllMessages::Provisioner(ProvisionerMessage::ProvisionableData(
+			ProvisionableData::BackedCandidate(backed),
+		))).await
+	}
+
+	/// Convert an attested candidate into a backed candidate, with the votes ordered by the
+	/// position of the voters within the group.
+	fn table_attested_to_backed(
+		&self,
+		attested: TableAttestedCandidate<ParaId, AbridgedCandidateReceipt, ValidatorIndex, ValidatorSignature>,
+	) -> Option<BackedCandidate> {
+		let TableAttestedCandidate { candidate, validity_votes, group_id } = attested;
+		let group = self.table_context.groups.get(&group_id)?;
+
+		let mut votes: Vec<(usize, ValidityAttestation)> = validity_votes.into_iter()
+			.filter_map(|(index, attestation)| {
+				let position = group.iter().position(|v| *v == index)?;
+				let attestation = match attestation {
+					statement_table::generic::ValidityAttestation::Implicit(s) => ValidityAttestation::Implicit(s),
+					statement_table::generic::ValidityAttestation::Explicit(s) => ValidityAttestation::Explicit(s),
+				};
+
+				Some((position, attestation))
+			})
+			.collect();
+
+		votes.sort_by_key(|(position, _)| *position);
+
+		let mut validator_indices = bitvec::bitvec![bitvec::order::Lsb0, u8; 0; group.len()];
+		for (position, _) in &votes {
+			validator_indices.set(*position, true);
+		}
+
+		Some(BackedCandidate {
+			candidate,
+			validity_votes: votes.into_iter().map(|(_, attestation)| attestation).collect(),
+			validator_indices,
+		})
+	}
+
+	/// Store the PoV of a valid candidate, then erasure-code its available data, store the
+	/// chunks and distribute them, so that the candidate can be made available to other validators.
+	async fn make_pov_available(
+		&mut self,
+		candidate: &AbridgedCandidateReceipt,
+		pov_block: PoVBlock,
+		outputs: ValidationOutputs,
+	) -> SubsystemResult<()> {
+		let candidate_hash = candidate.hash();
+
+		self.send(AllMessages::AvailabilityStore(
+			AvailabilityStoreMessage::StorePoV(candidate_hash, pov_block.clone()),
+		)).await?;
+
+		let available_data = AvailableData {
+			pov_block,
+			omitted_validation: outputs.omitted_validation,
+		};
+
+		let chunks = match erasure::obtain_chunks(self.table_context.validators.len(), &available_data) {
+			Ok(chunks) => chunks,
+			Err(e) => {
+				log::warn!(target: LOG_TARGET, "Failed to erasure-code candidate {}: {:?}", candidate_hash, e);
+				return Ok(());
+			}
+		};
+
+		let branches = erasure::branches(chunks.as_ref());
+		for (index, (proof, chunk)) in branches.enumerate() {
+			let chunk = ErasureChunk {
+				chunk: chunk.to_vec(),
+				index: index as u32,
+				proof,
+			};
+
+			self.send(AllMessages::AvailabilityStore(
+				AvailabilityStoreMessage::StoreChunk(candidate_hash, index as ValidatorIndex, chunk.clone()),
+			)).await?;
+
+			self.send(AllMessages::AvailabilityDistribution(
+				AvailabilityDistributionMessage::DistributeChunk(candidate.clone(), chunk),
+			)).await?;
+		}
+
+		Ok(())
+	}
+
+	async fn send(&mut self, msg: AllMessages) -> SubsystemResult<()> {
+		self.sender.send_message(msg).await
+	}
+}
+
+/// Convert a signed statement into the representation used by the statement table.
+fn table_statement(statement: &SignedFullStatement) -> TableSignedStatement {
+	let table_statement = match statement.payload() {
+		Statement::Seconded(candidate) => TableStatement::Candidate(candidate.clone()),
+		Statement::Valid(hash) => TableStatement::Valid(*hash),
+		Statement::Invalid(hash) => TableStatement::Invalid(*hash),
+	};
+
+	TableSignedStatement {
+		statement: table_statement,
+		signature: statement.signature().clone(),
+		sender: statement.validator_index(),
+	}
+}
+
+/// Validate a candidate. A validation which was canceled or timed out only fails for this
+/// candidate, so it is reported as `ValidationFailed` rather than as an error of the job.
+async fn request_candidate_validation(
+	relay_parent: Hash,
+	mut sender: JobSender,
+	candidate: AbridgedCandidateReceipt,
+	pov: PoVBlock,
+) -> SubsystemResult<Result<ValidationResult, ValidationFailed>> {
+	let res = sender.request(
+		|tx| AllMessages::CandidateValidation(
+			CandidateValidationMessage::Validate(relay_parent, candidate, pov, tx)
+		),
+		VALIDATION_TIMEOUT,
+	).await;
+
+	match res {
+		Ok(result) => Ok(result),
+		Err(e @ RequestError::Canceled) | Err(e @ RequestError::Timeout) =>
+			Ok(Err(ValidationFailed(format!("validation request failed: {:?}", e)))),
+		Err(e @ RequestError::Send) => Err(e.into()),
+	}
+}
+
+/// Fetch the PoV of a candidate from the validators of our group. Yields `None` if the fetch
+/// was canceled, which happens when the relay-parent leaves our view, or timed out.
+async fn request_pov(
+	relay_parent: Hash,
+	mut sender: JobSender,
+	candidate: AbridgedCandidateReceipt,
+) -> SubsystemResult<Option<PoVBlock>> {
+	let res = sender.request(
+		|tx| AllMessages::PoVDistribution(PoVDistributionMessage::FetchPoV(relay_parent, candidate, tx)),
+		POV_FETCH_TIMEOUT,
+	).await;
+
+	match res {
+		Ok(pov) => Ok(Some(pov)),
+		Err(RequestError::Canceled) | Err(RequestError::Timeout) => Ok(None),
+		Err(e @ RequestError::Send) => Err(e.into()),
+	}
+}
+
+async fn request_from_runtime<T>(
+	relay_parent: Hash,
+	mut sender: JobSender,
+	request: impl FnOnce(RuntimeApiSender<T>) -> RuntimeApiRequest,
+) -> SubsystemResult<T> {
+	let response = sender.request(
+		|tx| AllMessages::RuntimeApi(RuntimeApiMessage::Request(relay_parent, request(tx))),
+		RUNTIME_REQUEST_TIMEOUT,
+	).await?;
+
+	Ok(response?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::{self, ThreadPool};
+	use futures::future;
+	use assert_matches::assert_matches;
+	use sp_keyring::sr25519::Keyring;
+
+	use polkadot_node_primitives::InvalidCandidate;
+	use polkadot_primitives::parachain::{BlockData, GroupRotationInfo};
+
+	type VirtualOverseer = subsystem_test::TestSubsystemContextHandle<CandidateBackingMessage>;
+
+	struct TestHarness {
+		virtual_overseer: VirtualOverseer,
+	}
+
+	fn test_harness<T: Future<Output=()>>(keystore: KeyStorePtr, test: impl FnOnce(TestHarness) -> T) {
+		let pool = ThreadPool::new().unwrap();
+
+		let (context, virtual_overseer) = subsystem_test::make_subsystem_context(pool);
+
+		let subsystem = run(context, keystore)
+			.map_err(|_| panic!("subsystem execution failed"))
+			.map(|_| ());
+
+		let test_fut = test(TestHarness { virtual_overseer });
+
+		futures::pin_mut!(test_fut);
+		futures::pin_mut!(subsystem);
+
+		executor::block_on(future::select(test_fut, subsystem));
+	}
+
+	fn validator_pair(keyring: Keyring) -> ValidatorPair {
+		keystore::Store::new_in_memory()
+			.write()
+			.insert_ephemeral_from_seed::<ValidatorPair>(&keyring.to_seed())
+			.expect("Insert key into keystore")
+	}
+
+	struct TestState {
+		relay_parent: Hash,
+		para_id: ParaId,
+		validators: Vec<ValidatorId>,
+		validator_groups: (Vec<Vec<ValidatorIndex>>, GroupRotationInfo),
+		availability_cores: Vec<CoreState>,
+		signing_context: SigningContext,
+		keystore: KeyStorePtr,
+	}
+
+	impl Default for TestState {
+		fn default() -> Self {
+			let para_id = ParaId::from(1);
+			let validators = vec![
+				ValidatorId::from(Keyring::Alice.public()),
+				ValidatorId::from(Keyring::Bob.public()),
+				ValidatorId::from(Keyring::Charlie.public()),
+			];
+			// all of us are in the group of the only core, which our para is scheduled on.
+			let validator_groups = (vec![vec![0, 1, 2]], GroupRotationInfo::default());
+			let availability_cores = vec![CoreState::Scheduled(para_id)];
+
+			// we are Alice.
+			let keystore = keystore::Store::new_in_memory();
+			keystore.write().insert_ephemeral_from_seed::<ValidatorPair>(&Keyring::Alice.to_seed())
+				.expect("Insert key into keystore");
+
+			TestState {
+				relay_parent: Hash::from([1; 32]),
+				para_id,
+				validators,
+				validator_groups,
+				availability_cores,
+				signing_context: SigningContext::default(),
+				keystore,
+			}
+		}
+	}
+
+	impl TestState {
+		fn candidate(&self, pov: &PoVBlock) -> AbridgedCandidateReceipt {
+			AbridgedCandidateReceipt {
+				parachain_index: self.para_id,
+				relay_parent: self.relay_parent,
+				pov_block_hash: pov.hash(),
+				..Default::default()
+			}
+		}
+	}
+
+	fn test_pov() -> PoVBlock {
+		PoVBlock {
+			block_data: BlockData(vec![1, 2, 3]),
+		}
+	}
+
+	fn test_outputs() -> ValidationOutputs {
+		ValidationOutputs {
+			head_data: Default::default(),
+			commitments: Default::default(),
+			omitted_validation: Default::default(),
+		}
+	}
+
+	async fn start_work(virtual_overseer: &mut VirtualOverseer, test_state: &TestState) {
+		virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+			ActiveLeavesUpdate::start_work(test_state.relay_parent),
+		))).await;
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::Validators(tx),
+			)) if r == test_state.relay_parent => {
+				tx.send(Ok(test_state.validators.clone())).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::SigningContext(tx),
+			)) if r == test_state.relay_parent => {
+				tx.send(Ok(test_state.signing_context.clone())).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::ValidatorGroups(tx),
+			)) if r == test_state.relay_parent => {
+				tx.send(Ok(test_state.validator_groups.clone())).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::AvailabilityCores(tx),
+			)) if r == test_state.relay_parent => {
+				tx.send(Ok(test_state.availability_cores.clone())).unwrap();
+			}
+		);
+	}
+
+	async fn expect_stored_chunks(
+		virtual_overseer: &mut VirtualOverseer,
+		test_state: &TestState,
+		candidate_hash: Hash,
+		pov: &PoVBlock,
+	) {
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::AvailabilityStore(AvailabilityStoreMessage::StorePoV(h, p))
+				if h == candidate_hash && &p == pov
+		);
+
+		for i in 0..test_state.validators.len() {
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::AvailabilityStore(AvailabilityStoreMessage::StoreChunk(
+					h,
+					index,
+					chunk,
+				)) if h == candidate_hash && index == i as ValidatorIndex => {
+					assert_eq!(chunk.index, i as u32);
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::AvailabilityDistribution(AvailabilityDistributionMessage::DistributeChunk(
+					candidate,
+					chunk,
+				)) if candidate.hash() == candidate_hash => {
+					assert_eq!(chunk.index, i as u32);
+				}
+			);
+		}
+	}
+
+	#[test]
+	fn seconding_valid_candidate_distributes_statement() {
+		let test_state = TestState::default();
+
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			start_work(&mut virtual_overseer, &test_state).await;
+
+			let pov = test_pov();
+			let candidate = test_state.candidate(&pov);
+			let candidate_hash = candidate.hash();
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: CandidateBackingMessage::Second(test_state.relay_parent, candidate.clone(), pov.clone()),
+			}).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateValidation(CandidateValidationMessage::Validate(
+					r,
+					c,
+					p,
+					tx,
+				)) if r == test_state.relay_parent && c == candidate && p == pov => {
+					tx.send(Ok(ValidationResult::Valid(test_outputs()))).unwrap();
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::PoVDistribution(PoVDistributionMessage::DistributePoV(r, c, p))
+					if r == test_state.relay_parent && c == candidate && p == pov
+			);
+
+			expect_stored_chunks(&mut virtual_overseer, &test_state, candidate_hash, &pov).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::StatementDistribution(StatementDistributionMessage::Share(
+					r,
+					statement,
+				)) if r == test_state.relay_parent => {
+					assert_eq!(statement.validator_index(), 0);
+					assert_eq!(statement.payload(), &Statement::Seconded(candidate));
+					assert!(
+						statement.check_signature(&test_state.signing_context, &test_state.validators[0]).is_ok()
+					);
+				}
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn seconding_invalid_candidate_reports_to_candidate_selection() {
+		let test_state = TestState::default();
+
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			start_work(&mut virtual_overseer, &test_state).await;
+
+			let pov = test_pov();
+			let candidate = test_state.candidate(&pov);
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: CandidateBackingMessage::Second(test_state.relay_parent, candidate.clone(), pov),
+			}).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateValidation(CandidateValidationMessage::Validate(_, _, _, tx)) => {
+					tx.send(Ok(ValidationResult::Invalid(InvalidCandidate::HeadDataMismatch))).unwrap();
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateSelection(CandidateSelectionMessage::Invalid(r, c))
+					if r == test_state.relay_parent && c == candidate
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn failed_validation_request_keeps_job_alive() {
+		let test_state = TestState::default();
+
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			start_work(&mut virtual_overseer, &test_state).await;
+
+			let pov = test_pov();
+			let candidate = test_state.candidate(&pov);
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: CandidateBackingMessage::Second(test_state.relay_parent, candidate.clone(), pov.clone()),
+			}).await;
+
+			// dropping the responder cancels the request, as a timeout would.
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateValidation(CandidateValidationMessage::Validate(_, _, _, tx)) => {
+					drop(tx);
+				}
+			);
+
+			// nothing was seconded, so the job still seconds the candidate when asked again.
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: CandidateBackingMessage::Second(test_state.relay_parent, candidate.clone(), pov),
+			}).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateValidation(CandidateValidationMessage::Validate(_, c, _, _))
+					if c == candidate
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn candidate_seconded_by_peer_is_validated_and_backed() {
+		let test_state = TestState::default();
+
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			start_work(&mut virtual_overseer, &test_state).await;
+
+			let pov = test_pov();
+			let candidate = test_state.candidate(&pov);
+			let candidate_hash = candidate.hash();
+
+			let (watcher_tx, mut watcher_rx) = mpsc::channel(4);
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: CandidateBackingMessage::RegisterBackingWatcher(test_state.relay_parent, watcher_tx),
+			}).await;
+
+			// a watcher which doesn't keep up doesn't hold up the job.
+			let (mut full_watcher_tx, _full_watcher_rx) = mpsc::channel(0);
+			full_watcher_tx.try_send(NewBackedCandidate(BackedCandidate {
+				candidate: Default::default(),
+				validity_votes: Vec::new(),
+				validator_indices: Default::default(),
+			})).unwrap();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: CandidateBackingMessage::RegisterBackingWatcher(test_state.relay_parent, full_watcher_tx),
+			}).await;
+
+			let seconded = SignedFullStatement::sign(
+				Statement::Seconded(candidate.clone()),
+				&test_state.signing_context,
+				1,
+				&validator_pair(Keyring::Bob),
+			);
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: CandidateBackingMessage::Statement(test_state.relay_parent, seconded),
+			}).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::PoVDistribution(PoVDistributionMessage::FetchPoV(r, c, tx))
+					if r == test_state.relay_parent && c == candidate =>
+				{
+					tx.send(pov.clone()).unwrap();
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateValidation(CandidateValidationMessage::Validate(_, c, _, tx))
+					if c == candidate =>
+				{
+					tx.send(Ok(ValidationResult::Valid(test_outputs()))).unwrap();
+				}
+			);
+
+			expect_stored_chunks(&mut virtual_overseer, &test_state, candidate_hash, &pov).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::Provisioner(ProvisionerMessage::ProvisionableData(
+					ProvisionableData::BackedCandidate(backed),
+				)) => {
+					assert_eq!(backed.candidate, candidate);
+					assert_eq!(backed.validator_indices, bitvec::bitvec![bitvec::order::Lsb0, u8; 1, 1, 0]);
+
+					// votes are ordered by position in the group: our explicit vote, then Bob's implicit one.
+					assert_eq!(backed.validity_votes.len(), 2);
+					assert_matches!(backed.validity_votes[0], ValidityAttestation::Explicit(_));
+					assert_matches!(backed.validity_votes[1], ValidityAttestation::Implicit(_));
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::StatementDistribution(StatementDistributionMessage::Share(_, statement)) => {
+					assert_eq!(statement.payload(), &Statement::Valid(candidate_hash));
+				}
+			);
+
+			let NewBackedCandidate(backed) = watcher_rx.next().await.unwrap();
+			assert_eq!(backed.candidate, candidate);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn pending_validation_does_not_hold_up_job() {
+		let test_state = TestState::default();
+
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			start_work(&mut virtual_overseer, &test_state).await;
+
+			let pov = test_pov();
+			let candidate = test_state.candidate(&pov);
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: CandidateBackingMessage::Second(test_state.relay_parent, candidate.clone(), pov.clone()),
+			}).await;
+
+			let validation_tx = assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateValidation(CandidateValidationMessage::Validate(_, c, _, tx))
+					if c == candidate => tx
+			);
+
+			// while our candidate is being validated, we start validating one seconded by Bob.
+			let other_pov = PoVBlock {
+				block_data: BlockData(vec![4, 5, 6]),
+			};
+			let other_candidate = test_state.candidate(&other_pov);
+
+			let seconded = SignedFullStatement::sign(
+				Statement::Seconded(other_candidate.clone()),
+				&test_state.signing_context,
+				1,
+				&validator_pair(Keyring::Bob),
+			);
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: CandidateBackingMessage::Statement(test_state.relay_parent, seconded),
+			}).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::PoVDistribution(PoVDistributionMessage::FetchPoV(_, c, _))
+					if c == other_candidate
+			);
+
+			// the result of the first validation is still acted on.
+			validation_tx.send(Ok(ValidationResult::Invalid(InvalidCandidate::HeadDataMismatch))).unwrap();
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateSelection(CandidateSelectionMessage::Invalid(_, c))
+					if c == candidate
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn statement_with_bad_signature_is_ignored() {
+		let test_state = TestState::default();
+
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			start_work(&mut virtual_overseer, &test_state).await;
+
+			let pov = test_pov();
+			let candidate = test_state.candidate(&pov);
+
+			// signed by Charlie, but claiming to be from Bob.
+			let seconded = SignedFullStatement::sign(
+				Statement::Seconded(candidate.clone()),
+				&test_state.signing_context,
+				1,
+				&validator_pair(Keyring::Charlie),
+			);
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: CandidateBackingMessage::Statement(test_state.relay_parent, seconded),
+			}).await;
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: CandidateBackingMessage::Second(test_state.relay_parent, candidate.clone(), pov),
+			}).await;
+
+			// the bad statement caused no work, so the first thing we see is the validation
+			// of the candidate we were asked to second.
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateValidation(CandidateValidationMessage::Validate(_, c, _, _))
+					if c == candidate
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+}
@@ -0,0 +1,448 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The Bitfield Signing subsystem.
+//!
+//! For each new relay-chain head, this subsystem determines which of the candidates pending
+//! availability we hold our erasure chunk of, and signs and distributes a bitfield attesting
+//! to that.
+
+use std::time::Duration;
+
+use futures::prelude::*;
+use futures::select;
+use keystore::KeyStorePtr;
+
+use polkadot_subsystem::{
+	Subsystem, SubsystemContext, SpawnedSubsystem, SubsystemResult,
+	FromOverseer, OverseerSignal, ActiveLeavesUpdate, RequestError,
+};
+use polkadot_subsystem::jobs::{JobEvent, JobManager, JobSender};
+use polkadot_subsystem::messages::{
+	AllMessages, AvailabilityStoreMessage, BitfieldDistributionMessage, BitfieldSigningMessage,
+	RuntimeApiMessage, RuntimeApiRequest, RuntimeApiSender,
+};
+use polkadot_node_primitives::CoreState;
+use polkadot_primitives::Hash;
+use polkadot_primitives::parachain::{
+	AvailabilityBitfield, SignedAvailabilityBitfield, ValidatorId, ValidatorIndex, ValidatorPair,
+};
+
+const LOG_TARGET: &str = "bitfield_signing";
+
+/// How long to wait after the onset of a relay-chain head before determining availability,
+/// to give availability distribution a chance to deliver our chunks.
+const JOB_DELAY: Duration = Duration::from_millis(1500);
+
+/// How long to wait for the runtime API or the availability store to answer a request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The bitfield signing subsystem.
+#[derive(Clone)]
+pub struct BitfieldSigningSubsystem {
+	keystore: KeyStorePtr,
+}
+
+impl BitfieldSigningSubsystem {
+	/// Create a new bitfield signing subsystem, which signs bitfields with the
+	/// validator keys found in the given keystore.
+	pub fn new(keystore: KeyStorePtr) -> Self {
+		BitfieldSigningSubsystem { keystore }
+	}
+}
+
+impl<C> Subsystem<C> for BitfieldSigningSubsystem
+	where C: SubsystemContext<Message = BitfieldSigningMessage>
+{
+	fn start(self, ctx: C) -> SpawnedSubsystem {
+		SpawnedSubsystem(run(ctx, self.keystore, JOB_DELAY).map(|_| ()).boxed())
+	}
+}
+
+enum Incoming<M> {
+	Overseer(SubsystemResult<FromOverseer<M>>),
+	FromJobs(Option<JobEvent>),
+}
+
+async fn run(
+	mut ctx: impl SubsystemContext<Message = BitfieldSigningMessage>,
+	keystore: KeyStorePtr,
+	delay: Duration,
+) -> SubsystemResult<()> {
+	let mut jobs = JobManager::<BitfieldSigningMessage>::new();
+
+	loop {
+		let incoming = {
+			let from_overseer = ctx.recv().fuse();
+			futures::pin_mut!(from_overseer);
+
+			select! {
+				msg = from_overseer => Incoming::Overseer(msg),
+				event = jobs.next() => Incoming::FromJobs(event),
+			}
+		};
+
+		match incoming {
+			Incoming::Overseer(msg) => match msg? {
+				FromOverseer::Signal(OverseerSignal::ActiveLeaves(ActiveLeavesUpdate { activated, deactivated })) => {
+					for relay_parent in activated {
+						let keystore = keystore.clone();
+						jobs.spawn_job(&mut ctx, relay_parent, move |_, sender| {
+							run_job(relay_parent, keystore, delay, sender)
+						}).await?;
+					}
+
+					for relay_parent in deactivated {
+						jobs.stop_job(relay_parent);
+					}
+				}
+				FromOverseer::Signal(OverseerSignal::BlockFinalized(..)) => {}
+				FromOverseer::Signal(OverseerSignal::Conclude) => {
+					jobs.stop_all();
+
+					return Ok(());
+				}
+				FromOverseer::Communication { msg } => match msg {},
+			},
+			Incoming::FromJobs(Some(JobEvent::Message(msg))) => ctx.send_message(msg).await?,
+			Incoming::FromJobs(Some(JobEvent::Failed(relay_parent, e))) => {
+				log::warn!(
+					target: LOG_TARGET,
+					"Bitfield signing job for relay-parent {} failed: {:?}",
+					relay_parent,
+					e,
+				);
+			}
+			// the job manager is a never-ending stream, so this is unreachable.
+			Incoming::FromJobs(None) => return Ok(()),
+		}
+	}
+}
+
+/// Finds the first key we are capable of signing with out of the given set of validators,
+/// if any, along with its index.
+fn signing_key(validators: &[ValidatorId], keystore: &KeyStorePtr) -> Option<(ValidatorIndex, ValidatorPair)> {
+	let keystore = keystore.read();
+	validators.iter()
+		.enumerate()
+		.find_map(|(i, v)| {
+			keystore.key_pair::<ValidatorPair>(&v).ok().map(|pair| (i as ValidatorIndex, pair))
+		})
+}
+
+async fn run_job(
+	relay_parent: Hash,
+	keystore: KeyStorePtr,
+	delay: Duration,
+	mut sender: JobSender,
+) -> SubsystemResult<()> {
+	futures_timer::Delay::new(delay).await;
+
+	let (validators, signing_context, cores) = future::try_join3(
+		request_from_runtime(relay_parent, sender.clone(), RuntimeApiRequest::Validators),
+		request_from_runtime(relay_parent, sender.clone(), RuntimeApiRequest::SigningContext),
+		request_from_runtime(relay_parent, sender.clone(), RuntimeApiRequest::AvailabilityCores),
+	).await?;
+
+	let (validator_index, key) = match signing_key(&validators, &keystore) {
+		Some(key) => key,
+		// we are not a validator at this relay-parent.
+		None => return Ok(()),
+	};
+
+	let mut bitfield = bitvec::bitvec![bitvec::order::Lsb0, u8; 0; cores.len()];
+	for (core_index, core) in cores.into_iter().enumerate() {
+		let candidate_hash = match core {
+			CoreState::Occupied(candidate_hash) => candidate_hash,
+			CoreState::Scheduled(_) | CoreState::Free => continue,
+		};
+
+		let res = sender.request(
+			|tx| AllMessages::AvailabilityStore(
+				AvailabilityStoreMessage::QueryChunk(candidate_hash, validator_index, tx),
+			),
+			REQUEST_TIMEOUT,
+		).await;
+
+		let chunk = match res {
+			Ok(chunk) => chunk,
+			// we can't tell whether we hold the chunk, so we don't attest to it.
+			Err(e @ RequestError::Canceled) | Err(e @ RequestError::Timeout) => {
+				log::debug!(
+					target: LOG_TARGET,
+					"Failed to query our chunk of candidate {}: {:?}",
+					candidate_hash,
+					e,
+				);
+
+				None
+			}
+			Err(e @ RequestError::Send) => return Err(e.into()),
+		};
+
+		if chunk.is_some() {
+			bitfield.set(core_index, true);
+		}
+	}
+
+	let signed_bitfield = SignedAvailabilityBitfield::sign(
+		AvailabilityBitfield(bitfield),
+		&signing_context,
+		validator_index,
+		&key,
+	);
+
+	sender.send_message(AllMessages::BitfieldDistribution(
+		BitfieldDistributionMessage::DistributeBitfield(relay_parent, signed_bitfield),
+	)).await
+}
+
+async fn request_from_runtime<T>(
+	relay_parent: Hash,
+	mut sender: JobSender,
+	request: impl FnOnce(RuntimeApiSender<T>) -> RuntimeApiRequest,
+) -> SubsystemResult<T> {
+	let response = sender.request(
+		|tx| AllMessages::RuntimeApi(RuntimeApiMessage::Request(relay_parent, request(tx))),
+		REQUEST_TIMEOUT,
+	).await?;
+
+	Ok(response?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::{self, ThreadPool};
+	use futures::future;
+	use assert_matches::assert_matches;
+	use sp_keyring::sr25519::Keyring;
+
+	use polkadot_primitives::parachain::SigningContext;
+
+	type VirtualOverseer = subsystem_test::TestSubsystemContextHandle<BitfieldSigningMessage>;
+
+	struct TestHarness {
+		virtual_overseer: VirtualOverseer,
+	}
+
+	fn test_harness<T: Future<Output=()>>(keystore: KeyStorePtr, test: impl FnOnce(TestHarness) -> T) {
+		let pool = ThreadPool::new().unwrap();
+
+		let (context, virtual_overseer) = subsystem_test::make_subsystem_context(pool);
+
+		let subsystem = run(context, keystore, Duration::from_millis(0))
+			.map_err(|_| panic!("subsystem execution failed"))
+			.map(|_| ());
+
+		let test_fut = test(TestHarness { virtual_overseer });
+
+		futures::pin_mut!(test_fut);
+		futures::pin_mut!(subsystem);
+
+		executor::block_on(future::select(test_fut, subsystem));
+	}
+
+	fn validators() -> Vec<ValidatorId> {
+		vec![
+			ValidatorId::from(Keyring::Alice.public()),
+			ValidatorId::from(Keyring::Bob.public()),
+		]
+	}
+
+	fn keystore_with(keyring: Keyring) -> KeyStorePtr {
+		let keystore = keystore::Store::new_in_memory();
+		keystore.write().insert_ephemeral_from_seed::<ValidatorPair>(&keyring.to_seed())
+			.expect("Insert key into keystore");
+
+		keystore
+	}
+
+	async fn answer_runtime_requests(
+		virtual_overseer: &mut VirtualOverseer,
+		relay_parent: Hash,
+		cores: Vec<CoreState>,
+	) {
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::Validators(tx),
+			)) if r == relay_parent => {
+				tx.send(Ok(validators())).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::SigningContext(tx),
+			)) if r == relay_parent => {
+				tx.send(Ok(SigningContext::default())).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::AvailabilityCores(tx),
+			)) if r == relay_parent => {
+				tx.send(Ok(cores)).unwrap();
+			}
+		);
+	}
+
+	#[test]
+	fn bitfield_reflects_stored_chunks() {
+		test_harness(keystore_with(Keyring::Bob), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let candidate_a = Hash::from([2; 32]);
+			let candidate_b = Hash::from([3; 32]);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+				ActiveLeavesUpdate::start_work(relay_parent),
+			))).await;
+
+			answer_runtime_requests(
+				&mut virtual_overseer,
+				relay_parent,
+				vec![CoreState::Occupied(candidate_a), CoreState::Free, CoreState::Occupied(candidate_b)],
+			).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::AvailabilityStore(AvailabilityStoreMessage::QueryChunk(
+					h,
+					1,
+					tx,
+				)) if h == candidate_a => {
+					tx.send(None).unwrap();
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::AvailabilityStore(AvailabilityStoreMessage::QueryChunk(
+					h,
+					1,
+					tx,
+				)) if h == candidate_b => {
+					tx.send(Some(Default::default())).unwrap();
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::BitfieldDistribution(BitfieldDistributionMessage::DistributeBitfield(
+					r,
+					signed_bitfield,
+				)) if r == relay_parent => {
+					assert_eq!(signed_bitfield.validator_index(), 1);
+					assert_eq!(
+						signed_bitfield.payload().0,
+						bitvec::bitvec![bitvec::order::Lsb0, u8; 0, 0, 1],
+					);
+					assert!(signed_bitfield.check_signature(&SigningContext::default(), &validators()[1]).is_ok());
+				}
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn failed_chunk_query_marks_chunk_unavailable() {
+		test_harness(keystore_with(Keyring::Bob), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let candidate_a = Hash::from([2; 32]);
+			let candidate_b = Hash::from([3; 32]);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+				ActiveLeavesUpdate::start_work(relay_parent),
+			))).await;
+
+			answer_runtime_requests(
+				&mut virtual_overseer,
+				relay_parent,
+				vec![CoreState::Occupied(candidate_a), CoreState::Occupied(candidate_b)],
+			).await;
+
+			// dropping the responder cancels the request, as a timeout would.
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::AvailabilityStore(AvailabilityStoreMessage::QueryChunk(h, 1, tx))
+					if h == candidate_a =>
+				{
+					drop(tx);
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::AvailabilityStore(AvailabilityStoreMessage::QueryChunk(h, 1, tx))
+					if h == candidate_b =>
+				{
+					tx.send(Some(Default::default())).unwrap();
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::BitfieldDistribution(BitfieldDistributionMessage::DistributeBitfield(
+					r,
+					signed_bitfield,
+				)) if r == relay_parent => {
+					assert_eq!(
+						signed_bitfield.payload().0,
+						bitvec::bitvec![bitvec::order::Lsb0, u8; 0, 1],
+					);
+				}
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn no_bitfield_if_not_a_validator() {
+		test_harness(keystore_with(Keyring::Charlie), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let next_relay_parent = Hash::from([2; 32]);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+				ActiveLeavesUpdate::start_work(relay_parent),
+			))).await;
+			answer_runtime_requests(&mut virtual_overseer, relay_parent, vec![CoreState::Occupied(Hash::from([3; 32]))]).await;
+
+			// the job for the first relay-parent concludes without querying any chunks,
+			// so the next thing we see is the job for the next relay-parent.
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+				ActiveLeavesUpdate::start_work(next_relay_parent),
+			))).await;
+			answer_runtime_requests(&mut virtual_overseer, next_relay_parent, Vec::new()).await;
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+}
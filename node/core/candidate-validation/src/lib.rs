@@ -0,0 +1,682 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The Candidate Validation subsystem.
+//!
+//! This handles incoming requests from other subsystems to validate candidates
+//! according to a validation function. The validation code and the validation data
+//! are fetched from the state of the relay-parent via the Runtime API subsystem, and
+//! execution of the validation function is delegated to a pool of worker processes.
+
+use polkadot_subsystem::{
+	Subsystem, SubsystemContext, SpawnedSubsystem, SubsystemResult,
+	FromOverseer, OverseerSignal,
+};
+use polkadot_subsystem::jobs::JobSender;
+use polkadot_subsystem::messages::{
+	AllMessages, CandidateValidationMessage, RuntimeApiMessage, RuntimeApiRequest, RuntimeApiSender,
+	RuntimeApiError, ValidationFailed,
+};
+use polkadot_node_primitives::{ValidationResult, ValidationOutputs, InvalidCandidate};
+use polkadot_primitives::Hash;
+use polkadot_primitives::parachain::{
+	v2::AbridgedCandidateReceipt, v2::CandidateCommitments, PoVBlock, OmittedValidationData,
+	AvailableData, ValidationCode,
+};
+use polkadot_parachain::wasm_executor::{self, ExecutionMode};
+use polkadot_parachain::primitives::ValidationParams;
+use polkadot_erasure_coding as erasure;
+
+use futures::channel::{mpsc, oneshot};
+use futures::prelude::*;
+use futures::select;
+
+use std::thread;
+use std::time::Duration;
+
+pub use polkadot_parachain::wasm_executor::ValidationPool;
+
+const LOG_TARGET: &str = "candidate_validation";
+
+/// How long to wait for the runtime API to answer a request.
+const RUNTIME_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The capacity of the channel validation tasks send their messages on.
+const TASK_CHANNEL_CAPACITY: usize = 64;
+
+/// The candidate validation subsystem.
+#[derive(Clone)]
+pub struct CandidateValidationSubsystem {
+	pool: ValidationPool,
+}
+
+impl CandidateValidationSubsystem {
+	/// Create a new candidate validation subsystem, which executes validation functions
+	/// on the given pool of workers.
+	pub fn new(pool: ValidationPool) -> Self {
+		CandidateValidationSubsystem { pool }
+	}
+}
+
+impl<C> Subsystem<C> for CandidateValidationSubsystem
+	where C: SubsystemContext<Message = CandidateValidationMessage>
+{
+	fn start(self, ctx: C) -> SpawnedSubsystem {
+		SpawnedSubsystem(run(ctx, self.pool).map(|_| ()).boxed())
+	}
+}
+
+enum Incoming<M> {
+	Overseer(SubsystemResult<FromOverseer<M>>),
+	FromTasks(Option<AllMessages>),
+}
+
+async fn run(
+	mut ctx: impl SubsystemContext<Message = CandidateValidationMessage>,
+	pool: ValidationPool,
+) -> SubsystemResult<()> {
+	// validation tasks send their runtime API requests here, to be forwarded to the overseer.
+	let (from_tasks_tx, mut from_tasks_rx) = mpsc::channel(TASK_CHANNEL_CAPACITY);
+
+	loop {
+		let incoming = {
+			let from_overseer = ctx.recv().fuse();
+			futures::pin_mut!(from_overseer);
+
+			select! {
+				msg = from_overseer => Incoming::Overseer(msg),
+				msg = from_tasks_rx.next() => Incoming::FromTasks(msg),
+			}
+		};
+
+		match incoming {
+			Incoming::Overseer(msg) => match msg? {
+				FromOverseer::Signal(OverseerSignal::ActiveLeaves(_)) => {}
+				FromOverseer::Signal(OverseerSignal::BlockFinalized(..)) => {}
+				FromOverseer::Signal(OverseerSignal::Conclude) => return Ok(()),
+				FromOverseer::Communication { msg } => match msg {
+					CandidateValidationMessage::Validate(relay_parent, receipt, pov, response_sender) => {
+						let sender = JobSender::new(from_tasks_tx.clone());
+						let pool = pool.clone();
+
+						ctx.spawn(async move {
+							let res = validate_from_chain_state(sender, pool, relay_parent, receipt, pov).await;
+							let _ = response_sender.send(res);
+						}.boxed()).await?;
+					}
+				}
+			},
+			Incoming::FromTasks(Some(msg)) => ctx.send_message(msg).await?,
+			// we hold a sender ourselves, so this is unreachable.
+			Incoming::FromTasks(None) => return Ok(()),
+		}
+	}
+}
+
+/// Make a request to the Runtime API subsystem, converting a failed, dropped or timed out
+/// request into an internal validation failure.
+async fn runtime_api_request<T>(
+	mut sender: JobSender,
+	relay_parent: Hash,
+	request: impl FnOnce(RuntimeApiSender<T>) -> RuntimeApiRequest,
+	what: &str,
+) -> Result<T, ValidationFailed> {
+	let res = sender.request(
+		|tx| AllMessages::RuntimeApi(RuntimeApiMessage::Request(relay_parent, request(tx))),
+		RUNTIME_REQUEST_TIMEOUT,
+	).await;
+
+	match res {
+		Ok(Ok(response)) => Ok(response),
+		Ok(Err(RuntimeApiError(e))) => Err(ValidationFailed(
+			format!("Runtime API request for {} failed: {}", what, e)
+		)),
+		Err(e) => Err(ValidationFailed(format!("Runtime API request for {} failed: {:?}", what, e))),
+	}
+}
+
+/// Fetch all data necessary for validation from the state of the relay-parent and validate
+/// the candidate.
+///
+/// This is run in a task of its own, so that neither the runtime API requests nor the
+/// execution of the validation function hold up the main loop of the subsystem.
+async fn validate_from_chain_state(
+	sender: JobSender,
+	pool: ValidationPool,
+	relay_parent: Hash,
+	receipt: AbridgedCandidateReceipt,
+	pov: PoVBlock,
+) -> Result<ValidationResult, ValidationFailed> {
+	// cheap checks which don't require any chain state go first.
+	if let Err(e) = basic_checks(&receipt, &pov) {
+		return Ok(ValidationResult::Invalid(e));
+	}
+
+	let para_id = receipt.parachain_index;
+
+	let (local_validation, global_validation, validators, validation_code) = future::join4(
+		runtime_api_request(
+			sender.clone(),
+			relay_parent,
+			|tx| RuntimeApiRequest::LocalValidationData(para_id, tx),
+			"local validation data",
+		),
+		runtime_api_request(
+			sender.clone(),
+			relay_parent,
+			RuntimeApiRequest::GlobalValidationSchedule,
+			"global validation schedule",
+		),
+		runtime_api_request(
+			sender.clone(),
+			relay_parent,
+			RuntimeApiRequest::Validators,
+			"validators",
+		),
+		runtime_api_request(
+			sender,
+			relay_parent,
+			|tx| RuntimeApiRequest::ValidationCode(para_id, tx),
+			"validation code",
+		),
+	).await;
+
+	let local_validation = match local_validation? {
+		Some(local_validation) => local_validation,
+		None => return Ok(ValidationResult::Invalid(InvalidCandidate::InactivePara)),
+	};
+	let global_validation = global_validation?;
+	let n_validators = validators?.len();
+	let validation_code = validation_code?
+		.ok_or_else(|| ValidationFailed(format!("No validation code for para {:?}", para_id)))?;
+
+	let omitted_validation = OmittedValidationData {
+		global_validation,
+		local_validation,
+	};
+
+	let candidate_hash = receipt.hash();
+
+	// execution of the validation function blocks until the worker is done, so it gets a
+	// thread of its own rather than one of the executor's.
+	let (tx, rx) = oneshot::channel();
+	thread::Builder::new()
+		.name("candidate-validation".into())
+		.spawn(move || {
+			let res = validate_candidate_exhaustive(
+				ExecutionMode::Remote(&pool),
+				&receipt,
+				pov,
+				omitted_validation,
+				&validation_code,
+				n_validators,
+			);
+
+			let _ = tx.send(res);
+		})
+		.map_err(|e| ValidationFailed(format!("Failed to spawn validation thread: {}", e)))?;
+
+	let res = rx.await
+		.map_err(|_| ValidationFailed("Validation thread exited without a result".into()))?;
+
+	if let Ok(ValidationResult::Invalid(ref reason)) = res {
+		log::debug!(
+			target: LOG_TARGET,
+			"Candidate {} of para {} is invalid: {:?}",
+			candidate_hash,
+			para_id,
+			reason,
+		);
+	}
+
+	res
+}
+
+/// Checks of a candidate which do not require any chain state.
+fn basic_checks(receipt: &AbridgedCandidateReceipt, pov: &PoVBlock) -> Result<(), InvalidCandidate> {
+	if pov.hash() != receipt.pov_block_hash {
+		return Err(InvalidCandidate::PoVHashMismatch);
+	}
+
+	if let Err(()) = receipt.check_signature() {
+		return Err(InvalidCandidate::BadCollatorSignature);
+	}
+
+	Ok(())
+}
+
+/// Whether an error of the wasm executor is an internal error of this node, as opposed
+/// to a fault of the candidate.
+fn is_internal_error(e: &wasm_executor::Error) -> bool {
+	match e {
+		wasm_executor::Error::Io(_) |
+		wasm_executor::Error::System(_) |
+		wasm_executor::Error::External(_) => true,
+		#[cfg(not(any(target_os = "android", target_os = "unknown")))]
+		wasm_executor::Error::SharedMem(_) => true,
+		_ => false,
+	}
+}
+
+/// Compute the erasure-root of the given available data, for the given number of validators.
+fn erasure_root(n_validators: usize, available_data: &AvailableData) -> Result<Hash, ValidationFailed> {
+	let chunks = erasure::obtain_chunks(n_validators, available_data)
+		.map_err(|e| ValidationFailed(format!("Failed to erasure-code available data: {}", e)))?;
+
+	Ok(erasure::branches(&chunks).root())
+}
+
+/// Validate a candidate with all the data necessary for validation already provided.
+///
+/// This executes the validation function and blocks until it is done, so it should be run
+/// on a thread of its own.
+fn validate_candidate_exhaustive(
+	execution_mode: ExecutionMode<'_>,
+	receipt: &AbridgedCandidateReceipt,
+	pov: PoVBlock,
+	omitted_validation: OmittedValidationData,
+	validation_code: &ValidationCode,
+	n_validators: usize,
+) -> Result<ValidationResult, ValidationFailed> {
+	let global_validation = &omitted_validation.global_validation;
+	let local_validation = &omitted_validation.local_validation;
+
+	if receipt.head_data.0.len() > global_validation.max_head_data_size as _ {
+		return Ok(ValidationResult::Invalid(
+			InvalidCandidate::HeadDataTooLarge(receipt.head_data.0.len() as u64)
+		));
+	}
+
+	let params = ValidationParams {
+		parent_head: local_validation.parent_head.clone(),
+		block_data: pov.block_data.clone(),
+		max_code_size: global_validation.max_code_size,
+		max_head_data_size: global_validation.max_head_data_size,
+		relay_chain_height: global_validation.block_number,
+		code_upgrade_allowed: local_validation.code_upgrade_allowed,
+	};
+
+	let result = match wasm_executor::validate_candidate(
+		&validation_code.0,
+		params,
+		execution_mode,
+	) {
+		Ok(result) => result,
+		Err(ref e) if is_internal_error(e) => {
+			return Err(ValidationFailed(format!("Failed to execute validation function: {}", e)));
+		}
+		Err(e) => {
+			return Ok(ValidationResult::Invalid(InvalidCandidate::ExecutionError(e.to_string())));
+		}
+	};
+
+	if result.head_data != receipt.head_data {
+		return Ok(ValidationResult::Invalid(InvalidCandidate::HeadDataMismatch));
+	}
+
+	let available_data = AvailableData {
+		pov_block: pov,
+		omitted_validation,
+	};
+
+	let commitments = CandidateCommitments {
+		// messages are not charged for yet, as in the legacy validation pipeline.
+		fees: 0,
+		upward_messages: result.upward_messages,
+		erasure_root: erasure_root(n_validators, &available_data)?,
+		new_validation_code: result.new_validation_code,
+		processed_downward_messages: result.processed_downward_messages,
+		hrmp: result.hrmp,
+	};
+
+	if commitments != receipt.commitments {
+		return Ok(ValidationResult::Invalid(InvalidCandidate::CommitmentsMismatch));
+	}
+
+	Ok(ValidationResult::Valid(ValidationOutputs {
+		head_data: result.head_data,
+		commitments,
+		omitted_validation: available_data.omitted_validation,
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::{self, ThreadPool};
+	use assert_matches::assert_matches;
+	use sp_core::crypto::Pair;
+
+	use polkadot_primitives::parachain::{
+		BlockData, CollatorPair, GlobalValidationSchedule, HeadData, Id as ParaId, LocalValidationData,
+		collator_signature_payload,
+	};
+	use parity_scale_codec::Encode;
+
+	struct TestHarness {
+		virtual_overseer: subsystem_test::TestSubsystemContextHandle<CandidateValidationMessage>,
+	}
+
+	fn test_harness<T: Future<Output=()>>(test: impl FnOnce(TestHarness) -> T) {
+		let pool = ThreadPool::new().unwrap();
+
+		let (context, virtual_overseer) = subsystem_test::make_subsystem_context(pool);
+
+		let subsystem = run(context, ValidationPool::new())
+			.map_err(|_| panic!("subsystem execution failed"))
+			.map(|_| ());
+
+		let test_fut = test(TestHarness { virtual_overseer });
+
+		futures::pin_mut!(test_fut);
+		futures::pin_mut!(subsystem);
+
+		executor::block_on(future::select(test_fut, subsystem));
+	}
+
+	fn test_pov() -> PoVBlock {
+		PoVBlock {
+			block_data: BlockData(vec![1, 2, 3]),
+		}
+	}
+
+	fn signed_receipt(relay_parent: Hash, para_id: ParaId, pov: &PoVBlock) -> AbridgedCandidateReceipt {
+		let collator = CollatorPair::generate().0;
+		let pov_block_hash = pov.hash();
+		let payload = collator_signature_payload(&relay_parent, &para_id, &pov_block_hash);
+
+		AbridgedCandidateReceipt {
+			parachain_index: para_id,
+			relay_parent,
+			collator: collator.public(),
+			signature: collator.sign(&payload[..]),
+			pov_block_hash,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn pov_hash_mismatch_is_invalid() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::Validate(
+					Default::default(),
+					Default::default(),
+					test_pov(),
+					tx,
+				),
+			}).await;
+
+			assert_matches!(
+				rx.await.unwrap(),
+				Ok(ValidationResult::Invalid(InvalidCandidate::PoVHashMismatch))
+			);
+		});
+	}
+
+	#[test]
+	fn bad_collator_signature_is_invalid() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let pov = test_pov();
+			let receipt = AbridgedCandidateReceipt {
+				pov_block_hash: pov.hash(),
+				..Default::default()
+			};
+
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::Validate(Default::default(), receipt, pov, tx),
+			}).await;
+
+			assert_matches!(
+				rx.await.unwrap(),
+				Ok(ValidationResult::Invalid(InvalidCandidate::BadCollatorSignature))
+			);
+		});
+	}
+
+	#[test]
+	fn inactive_para_is_invalid() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let para_id = ParaId::from(5);
+			let pov = test_pov();
+			let receipt = signed_receipt(relay_parent, para_id, &pov);
+
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::Validate(relay_parent, receipt, pov, tx),
+			}).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					r,
+					RuntimeApiRequest::LocalValidationData(p, tx),
+				)) if r == relay_parent && p == para_id => {
+					tx.send(Ok(None)).unwrap();
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::GlobalValidationSchedule(_),
+				))
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(_, RuntimeApiRequest::Validators(_)))
+			);
+
+			assert_matches!(
+				rx.await.unwrap(),
+				Ok(ValidationResult::Invalid(InvalidCandidate::InactivePara))
+			);
+		});
+	}
+
+	#[test]
+	fn dropped_runtime_request_fails_validation() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let para_id = ParaId::from(5);
+			let pov = test_pov();
+			let receipt = signed_receipt(relay_parent, para_id, &pov);
+
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::Validate(relay_parent, receipt, pov, tx),
+			}).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::LocalValidationData(_, tx),
+				)) => {
+					tx.send(Ok(Some(Default::default()))).unwrap();
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::GlobalValidationSchedule(tx),
+				)) => {
+					tx.send(Ok(GlobalValidationSchedule::default())).unwrap();
+				}
+			);
+
+			// the validators request is dropped.
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(_, RuntimeApiRequest::Validators(_)))
+			);
+
+			assert_matches!(rx.await.unwrap(), Err(ValidationFailed(_)));
+		});
+	}
+
+	#[test]
+	fn failed_runtime_request_fails_validation() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let para_id = ParaId::from(5);
+			let pov = test_pov();
+			let receipt = signed_receipt(relay_parent, para_id, &pov);
+
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: CandidateValidationMessage::Validate(relay_parent, receipt, pov, tx),
+			}).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::LocalValidationData(_, tx),
+				)) => {
+					tx.send(Err(RuntimeApiError("state pruned".into()))).unwrap();
+				}
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::GlobalValidationSchedule(_),
+				))
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(_, RuntimeApiRequest::Validators(_)))
+			);
+
+			assert_matches!(rx.await.unwrap(), Err(ValidationFailed(_)));
+		});
+	}
+
+	#[test]
+	fn adder_candidate_is_valid() {
+		let parent_head = adder::HeadData {
+			number: 0,
+			parent_hash: [0; 32],
+			post_state: adder::hash_state(0),
+		};
+		let block_data = adder::BlockData { state: 0, add: 512 };
+		let new_head = adder::execute(parent_head.hash(), parent_head.clone(), &block_data).unwrap();
+
+		let pov = PoVBlock { block_data: BlockData(block_data.encode()) };
+		let omitted_validation = OmittedValidationData {
+			global_validation: GlobalValidationSchedule {
+				max_code_size: 1024 * 1024,
+				max_head_data_size: 1024,
+				block_number: 1,
+			},
+			local_validation: LocalValidationData {
+				parent_head: HeadData(parent_head.encode()),
+				..Default::default()
+			},
+		};
+
+		let n_validators = 10;
+		let erasure_root = erasure_root(n_validators, &AvailableData {
+			pov_block: pov.clone(),
+			omitted_validation: omitted_validation.clone(),
+		}).unwrap();
+
+		let receipt = AbridgedCandidateReceipt {
+			head_data: HeadData(new_head.encode()),
+			pov_block_hash: pov.hash(),
+			commitments: CandidateCommitments {
+				erasure_root,
+				..Default::default()
+			},
+			..Default::default()
+		};
+
+		let res = validate_candidate_exhaustive(
+			ExecutionMode::Local,
+			&receipt,
+			pov,
+			omitted_validation.clone(),
+			&ValidationCode(adder::WASM_BINARY.to_vec()),
+			n_validators,
+		);
+
+		assert_matches!(res, Ok(ValidationResult::Valid(outputs)) => {
+			assert_eq!(outputs.head_data, receipt.head_data);
+			assert_eq!(outputs.commitments, receipt.commitments);
+			assert_eq!(outputs.omitted_validation, omitted_validation);
+		});
+	}
+
+	#[test]
+	fn adder_candidate_with_wrong_head_data_is_invalid() {
+		let parent_head = adder::HeadData {
+			number: 0,
+			parent_hash: [0; 32],
+			post_state: adder::hash_state(0),
+		};
+		let block_data = adder::BlockData { state: 0, add: 512 };
+
+		let pov = PoVBlock { block_data: BlockData(block_data.encode()) };
+		let omitted_validation = OmittedValidationData {
+			global_validation: GlobalValidationSchedule {
+				max_code_size: 1024 * 1024,
+				max_head_data_size: 1024,
+				block_number: 1,
+			},
+			local_validation: LocalValidationData {
+				parent_head: HeadData(parent_head.encode()),
+				..Default::default()
+			},
+		};
+
+		let receipt = AbridgedCandidateReceipt {
+			head_data: HeadData(parent_head.encode()),
+			pov_block_hash: pov.hash(),
+			..Default::default()
+		};
+
+		let res = validate_candidate_exhaustive(
+			ExecutionMode::Local,
+			&receipt,
+			pov,
+			omitted_validation,
+			&ValidationCode(adder::WASM_BINARY.to_vec()),
+			10,
+		);
+
+		assert_matches!(res, Ok(ValidationResult::Invalid(InvalidCandidate::HeadDataMismatch)));
+	}
+}
@@ -0,0 +1,272 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The block proposer of the node.
+//!
+//! This wraps the basic authorship proposer, putting the signed bitfields and backed candidates
+//! picked by the Provisioner subsystem at the parent block into the inherent data of the
+//! inclusion inherent. Only runtimes with the parachains modules take the inclusion inherent,
+//! so the Provisioner subsystem is only asked for inherent data where the runtime exposes the
+//! v2 parachain host API.
+
+use std::{pin::Pin, sync::Arc, time::{Duration, Instant}};
+
+use futures::prelude::*;
+use futures::channel::oneshot;
+use futures::future::Either;
+use futures_timer::Delay;
+
+use consensus::{Environment, Proposal, Proposer as _, RecordProof};
+use inherents::InherentData;
+use polkadot_overseer::OverseerHandler;
+use polkadot_primitives::{Block, Hash, Header};
+use polkadot_primitives::inclusion_inherent;
+use polkadot_primitives::parachain::ParachainHostV2;
+use polkadot_subsystem::messages::{AllMessages, ProvisionerInherentData, ProvisionerMessage};
+use prometheus_endpoint::Registry as PrometheusRegistry;
+use sc_block_builder::{BlockBuilderApi, BlockBuilderProvider};
+use sp_api::{ApiExt, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_runtime::generic::BlockId;
+use sp_runtime::traits::{DigestFor, HashFor};
+use sp_transaction_pool::TransactionPool;
+
+const LOG_TARGET: &str = "proposer";
+
+/// How long to wait for the inherent data of the Provisioner subsystem, counted from the
+/// creation of the proposer.
+const PROVISIONER_TIMEOUT: Duration = Duration::from_millis(2500);
+
+/// Error type of the proposer.
+#[derive(Debug, derive_more::Display, derive_more::From)]
+pub enum Error {
+	/// Client error
+	Client(sp_blockchain::Error),
+	/// Unexpected error putting the inclusion inherent data
+	#[display(fmt = "Failed to put the inclusion inherent data: {}", _0)]
+	Inherent(inherents::Error),
+}
+
+impl std::error::Error for Error {}
+
+/// The block proposer factory of the node.
+pub struct ProposerFactory<TxPool, Backend, Client> {
+	inner: sc_basic_authorship::ProposerFactory<TxPool, Backend, Client>,
+	client: Arc<Client>,
+	overseer: OverseerHandler,
+}
+
+impl<TxPool, Backend, Client> ProposerFactory<TxPool, Backend, Client> {
+	/// Create a new proposer factory, requesting inherent data from the Provisioner subsystem
+	/// through the given overseer handler.
+	pub fn new(
+		client: Arc<Client>,
+		transaction_pool: Arc<TxPool>,
+		overseer: OverseerHandler,
+		prometheus: Option<&PrometheusRegistry>,
+	) -> Self {
+		ProposerFactory {
+			inner: sc_basic_authorship::ProposerFactory::new(client.clone(), transaction_pool, prometheus),
+			client,
+			overseer,
+		}
+	}
+}
+
+impl<TxPool, Backend, Client> Environment<Block> for ProposerFactory<TxPool, Backend, Client>
+where
+	TxPool: TransactionPool<Block=Block> + 'static,
+	Client: BlockBuilderProvider<Backend, Block, Client> + ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+	Client::Api: BlockBuilderApi<Block> + ApiExt<Block, Error = sp_blockchain::Error>,
+	Backend: sc_client_api::Backend<Block, State = sp_api::StateBackendFor<Client, Block>> + 'static,
+	// Rust bug: https://github.com/rust-lang/rust/issues/24159
+	sp_api::StateBackendFor<Client, Block>: sp_api::StateBackend<HashFor<Block>> + Send,
+{
+	type CreateProposer = Pin<Box<dyn Future<Output = Result<Self::Proposer, Self::Error>> + Send>>;
+	type Proposer = Proposer<TxPool, Backend, Client>;
+	type Error = Error;
+
+	fn init(&mut self, parent_header: &Header) -> Self::CreateProposer {
+		let parent_hash = parent_header.hash();
+		let mut overseer = self.overseer.clone();
+
+		let inner = self.inner.init(parent_header).into_inner();
+		let has_v2 = self.client.runtime_api()
+			.has_api::<dyn ParachainHostV2<Block, Error = ()>>(&BlockId::Hash(parent_hash));
+
+		async move {
+			let inner = inner?;
+
+			// the request is made right away, so that the provisioner has until the proposal
+			// to answer.
+			let inherent_data_request = if has_v2? {
+				Some(request_inherent_data(&mut overseer, parent_hash).await)
+			} else {
+				None
+			};
+
+			Ok(Proposer { inner, parent_hash, inherent_data_request })
+		}.boxed()
+	}
+}
+
+/// The block proposer of the node.
+pub struct Proposer<TxPool: TransactionPool<Block=Block>, Backend, Client> {
+	inner: sc_basic_authorship::Proposer<Backend, Block, Client, TxPool>,
+	parent_hash: Hash,
+	/// The request for the inclusion inherent data, if the runtime takes the inclusion inherent.
+	inherent_data_request: Option<InherentDataRequest>,
+}
+
+impl<TxPool, Backend, Client> consensus::Proposer<Block> for Proposer<TxPool, Backend, Client>
+where
+	TxPool: TransactionPool<Block=Block> + 'static,
+	Client: BlockBuilderProvider<Backend, Block, Client> + ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+	Client::Api: BlockBuilderApi<Block> + ApiExt<Block, Error = sp_blockchain::Error>,
+	Backend: sc_client_api::Backend<Block, State = sp_api::StateBackendFor<Client, Block>> + 'static,
+	// Rust bug: https://github.com/rust-lang/rust/issues/24159
+	sp_api::StateBackendFor<Client, Block>: sp_api::StateBackend<HashFor<Block>> + Send,
+{
+	type Error = Error;
+	type Transaction = sp_api::TransactionFor<Client, Block>;
+	type Proposal = Pin<Box<
+		dyn Future<Output = Result<Proposal<Block, sp_api::TransactionFor<Client, Block>>, Error>> + Send
+	>>;
+
+	fn propose(
+		self,
+		inherent_data: InherentData,
+		inherent_digests: DigestFor<Block>,
+		max_duration: Duration,
+		record_proof: RecordProof,
+	) -> Self::Proposal {
+		let Proposer { inner, parent_hash, inherent_data_request } = self;
+
+		async move {
+			let started = Instant::now();
+
+			let mut inherent_data = inherent_data;
+			if let Some(request) = inherent_data_request {
+				// the provisioner never takes more than half of the time we have to propose.
+				let timeout = std::cmp::min(
+					PROVISIONER_TIMEOUT.checked_sub(request.made_at.elapsed()).unwrap_or_default(),
+					max_duration / 2,
+				);
+				let provisioner_data = await_inherent_data(request.response, parent_hash, timeout).await;
+
+				inherent_data.put_data(inclusion_inherent::INHERENT_IDENTIFIER, &provisioner_data)?;
+			}
+
+			let max_duration = max_duration.checked_sub(started.elapsed()).unwrap_or_default();
+
+			inner.propose(inherent_data, inherent_digests, max_duration, record_proof)
+				.await
+				.map_err(Into::into)
+		}.boxed()
+	}
+}
+
+/// A request for the inclusion inherent data made to the Provisioner subsystem.
+struct InherentDataRequest {
+	response: oneshot::Receiver<ProvisionerInherentData>,
+	made_at: Instant,
+}
+
+/// Request the inclusion inherent data at the parent block from the Provisioner subsystem.
+async fn request_inherent_data(overseer: &mut OverseerHandler, parent_hash: Hash) -> InherentDataRequest {
+	let (tx, rx) = oneshot::channel();
+	let request = AllMessages::Provisioner(ProvisionerMessage::RequestInherentData(parent_hash, tx));
+	if let Err(e) = overseer.send_msg(request).await {
+		// the sender is dropped, so the request yields empty inherent data.
+		log::warn!(target: LOG_TARGET, "Failed to request inherent data from the provisioner: {:?}", e);
+	}
+
+	InherentDataRequest {
+		response: rx,
+		made_at: Instant::now(),
+	}
+}
+
+/// Await the response of the Provisioner subsystem for at most `timeout`.
+///
+/// The inclusion inherent must be part of every block, so empty inherent data is used when
+/// the provisioner doesn't answer in time.
+async fn await_inherent_data(
+	response: oneshot::Receiver<ProvisionerInherentData>,
+	parent_hash: Hash,
+	timeout: Duration,
+) -> ProvisionerInherentData {
+	match future::select(response, Delay::new(timeout)).await {
+		Either::Left((Ok(data), _)) => data,
+		Either::Left((Err(_), _)) => {
+			log::warn!(target: LOG_TARGET, "The provisioner has no inherent data at block {}", parent_hash);
+			empty_inherent_data()
+		}
+		Either::Right(_) => {
+			log::warn!(target: LOG_TARGET, "Timed out waiting for inherent data at block {}", parent_hash);
+			empty_inherent_data()
+		}
+	}
+}
+
+fn empty_inherent_data() -> ProvisionerInherentData {
+	(Vec::new().into(), Vec::new())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor;
+	use polkadot_primitives::parachain::BackedCandidate;
+
+	const TIMEOUT: Duration = Duration::from_secs(5);
+
+	#[test]
+	fn provisioner_answer_is_used() {
+		let (tx, rx) = oneshot::channel();
+		let data: ProvisionerInherentData = (Vec::new().into(), vec![BackedCandidate {
+			candidate: Default::default(),
+			validity_votes: Vec::new(),
+			validator_indices: Default::default(),
+		}]);
+
+		tx.send(data.clone()).unwrap();
+
+		assert_eq!(executor::block_on(await_inherent_data(rx, Hash::default(), TIMEOUT)), data);
+	}
+
+	#[test]
+	fn dropped_provisioner_request_yields_empty_inherent_data() {
+		let (tx, rx) = oneshot::channel();
+		drop(tx);
+
+		assert_eq!(
+			executor::block_on(await_inherent_data(rx, Hash::default(), TIMEOUT)),
+			empty_inherent_data(),
+		);
+	}
+
+	#[test]
+	fn provisioner_timeout_yields_empty_inherent_data() {
+		// the sender is kept alive, but never answers.
+		let (_tx, rx) = oneshot::channel();
+
+		assert_eq!(
+			executor::block_on(await_inherent_data(rx, Hash::default(), Duration::from_millis(10))),
+			empty_inherent_data(),
+		);
+	}
+}
@@ -0,0 +1,520 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The Provisioner subsystem.
+//!
+//! This subsystem spawns one job per relay-parent that we are working on. Each job collects the
+//! provisionable data for its relay-parent, forwards it to any block authors which subscribed
+//! to it, and assembles the signed bitfields and backed candidates for the inclusion inherent
+//! of a block built on top of the relay-parent.
+
+use std::collections::{BTreeMap, HashSet};
+use std::collections::btree_map::Entry;
+use std::time::Duration;
+
+use futures::channel::mpsc;
+use futures::prelude::*;
+use futures::select;
+
+use polkadot_subsystem::{
+	Subsystem, SubsystemContext, SpawnedSubsystem, SubsystemResult,
+	FromOverseer, OverseerSignal, ActiveLeavesUpdate, RequestError,
+};
+use polkadot_subsystem::jobs::{JobEvent, JobManager, JobMessage, JobSender};
+use polkadot_subsystem::messages::{
+	AllMessages, ProvisionableData, ProvisionerInherentData, ProvisionerMessage,
+	RuntimeApiMessage, RuntimeApiRequest,
+};
+use polkadot_node_primitives::CoreState;
+use polkadot_primitives::Hash;
+use polkadot_primitives::parachain::{
+	BackedCandidate, SignedAvailabilityBitfield, ValidatorIndex,
+};
+
+const LOG_TARGET: &str = "provisioner";
+
+/// How long to wait for the runtime API to answer a request.
+const RUNTIME_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The provisioner subsystem.
+#[derive(Clone)]
+pub struct ProvisioningSubsystem;
+
+impl<C> Subsystem<C> for ProvisioningSubsystem
+	where C: SubsystemContext<Message = ProvisionerMessage>
+{
+	fn start(self, ctx: C) -> SpawnedSubsystem {
+		SpawnedSubsystem(run(ctx).map(|_| ()).boxed())
+	}
+}
+
+enum Incoming<M> {
+	Overseer(SubsystemResult<FromOverseer<M>>),
+	FromJobs(Option<JobEvent>),
+}
+
+async fn run(mut ctx: impl SubsystemContext<Message = ProvisionerMessage>) -> SubsystemResult<()> {
+	let mut jobs = JobManager::new();
+
+	loop {
+		let incoming = {
+			let from_overseer = ctx.recv().fuse();
+			futures::pin_mut!(from_overseer);
+
+			select! {
+				msg = from_overseer => Incoming::Overseer(msg),
+				event = jobs.next() => Incoming::FromJobs(event),
+			}
+		};
+
+		match incoming {
+			Incoming::Overseer(msg) => match msg? {
+				FromOverseer::Signal(OverseerSignal::ActiveLeaves(ActiveLeavesUpdate { activated, deactivated })) => {
+					for relay_parent in activated {
+						jobs.spawn_job(&mut ctx, relay_parent, move |rx_to, sender| {
+							run_job(relay_parent, rx_to, sender)
+						}).await?;
+					}
+
+					for relay_parent in deactivated {
+						jobs.stop_job(relay_parent);
+					}
+				}
+				FromOverseer::Signal(OverseerSignal::BlockFinalized(..)) => {}
+				FromOverseer::Signal(OverseerSignal::Conclude) => {
+					jobs.stop_all();
+
+					return Ok(());
+				}
+				FromOverseer::Communication { msg } => {
+					if let Err(msg) = jobs.send_msg(msg) {
+						log::trace!(
+							target: LOG_TARGET,
+							"Dropping message for relay-parent {} we are not working on",
+							msg.relay_parent(),
+						);
+					}
+				}
+			},
+			Incoming::FromJobs(Some(JobEvent::Message(msg))) => ctx.send_message(msg).await?,
+			Incoming::FromJobs(Some(JobEvent::Failed(relay_parent, e))) => {
+				log::warn!(
+					target: LOG_TARGET,
+					"Provisioning job for relay-parent {} failed: {:?}",
+					relay_parent,
+					e,
+				);
+			}
+			// the job manager is a never-ending stream, so this is unreachable.
+			Incoming::FromJobs(None) => return Ok(()),
+		}
+	}
+}
+
+struct ProvisioningJob {
+	relay_parent: Hash,
+	sender: JobSender,
+	/// All provisionable data accepted so far, in the order it was received.
+	provisionable_data: Vec<ProvisionableData>,
+	/// The first bitfield received from each validator.
+	signed_bitfields: BTreeMap<ValidatorIndex, SignedAvailabilityBitfield>,
+	backed_candidates: Vec<BackedCandidate>,
+	/// Hashes of the candidates in `backed_candidates`.
+	backed_hashes: HashSet<Hash>,
+	/// Block authors subscribed to new provisionable data.
+	subscribers: Vec<mpsc::Sender<ProvisionableData>>,
+}
+
+async fn run_job(
+	relay_parent: Hash,
+	mut rx_to: mpsc::UnboundedReceiver<ProvisionerMessage>,
+	sender: JobSender,
+) -> SubsystemResult<()> {
+	let mut job = ProvisioningJob {
+		relay_parent,
+		sender,
+		provisionable_data: Vec::new(),
+		signed_bitfields: BTreeMap::new(),
+		backed_candidates: Vec::new(),
+		backed_hashes: HashSet::new(),
+		subscribers: Vec::new(),
+	};
+
+	while let Some(msg) = rx_to.next().await {
+		job.process_msg(msg).await?;
+	}
+
+	Ok(())
+}
+
+impl ProvisioningJob {
+	async fn process_msg(&mut self, msg: ProvisionerMessage) -> SubsystemResult<()> {
+		match msg {
+			ProvisionerMessage::RequestBlockAuthorshipData(_, mut subscriber) => {
+				for data in self.provisionable_data.iter() {
+					if subscriber.send(data.clone()).await.is_err() {
+						return Ok(());
+					}
+				}
+
+				self.subscribers.push(subscriber);
+			}
+			ProvisionerMessage::RequestInherentData(_, response_sender) => {
+				let relay_parent = self.relay_parent;
+				let res = self.sender.request(
+					|tx| AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						relay_parent,
+						RuntimeApiRequest::AvailabilityCores(tx),
+					)),
+					RUNTIME_REQUEST_TIMEOUT,
+				).await;
+
+				let cores = match res {
+					Ok(Ok(cores)) => cores,
+					Ok(Err(e)) => {
+						log::warn!(
+							target: LOG_TARGET,
+							"Failed to fetch availability cores at relay-parent {}: {:?}",
+							relay_parent,
+							e,
+						);
+
+						Vec::new()
+					}
+					Err(e @ RequestError::Canceled) | Err(e @ RequestError::Timeout) => {
+						log::warn!(
+							target: LOG_TARGET,
+							"Request for availability cores at relay-parent {} failed: {:?}",
+							relay_parent,
+							e,
+						);
+
+						Vec::new()
+					}
+					Err(e @ RequestError::Send) => return Err(e.into()),
+				};
+
+				// without any cores, no bitfields or candidates are selected and the block
+				// author gets empty inherent data.
+				let _ = response_sender.send(self.inherent_data(&cores));
+			}
+			ProvisionerMessage::ProvisionableData(data) => {
+				if !self.note_provisionable_data(&data) {
+					return Ok(());
+				}
+
+				let mut subscribers = Vec::with_capacity(self.subscribers.len());
+				for mut subscriber in self.subscribers.drain(..) {
+					if subscriber.send(data.clone()).await.is_ok() {
+						subscribers.push(subscriber);
+					}
+				}
+				self.subscribers = subscribers;
+
+				self.provisionable_data.push(data);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Note a piece of provisionable data. Returns `false` if it is redundant, that is a second
+	/// bitfield by the same validator or a candidate which was already backed.
+	fn note_provisionable_data(&mut self, data: &ProvisionableData) -> bool {
+		match data {
+			ProvisionableData::Bitfield(_, bitfield) => {
+				match self.signed_bitfields.entry(bitfield.validator_index()) {
+					Entry::Vacant(entry) => {
+						entry.insert(bitfield.clone());
+						true
+					}
+					Entry::Occupied(_) => false,
+				}
+			}
+			ProvisionableData::BackedCandidate(backed) => {
+				if !self.backed_hashes.insert(backed.candidate.hash()) {
+					return false;
+				}
+
+				self.backed_candidates.push(backed.clone());
+				true
+			}
+			ProvisionableData::MisbehaviorReport(..) | ProvisionableData::Dispute(..) => true,
+		}
+	}
+
+	/// Select the bitfields and candidates to include in a block, given the state of the
+	/// availability cores.
+	///
+	/// Bitfields are selected if they have one bit per core and no bits set for cores which are
+	/// not occupied, ordered by validator index. For each core which has a para scheduled, the
+	/// first backed candidate of that para is selected, ordered by core index.
+	fn inherent_data(&self, cores: &[CoreState]) -> ProvisionerInherentData {
+		let bitfields = self.signed_bitfields.values()
+			.filter(|bitfield| {
+				let bits = &bitfield.payload().0;
+				bits.len() == cores.len() && cores.iter().zip(bits.iter()).all(|(core, bit)| {
+					!*bit || match core {
+						CoreState::Occupied(_) => true,
+						CoreState::Scheduled(_) | CoreState::Free => false,
+					}
+				})
+			})
+			.cloned()
+			.collect::<Vec<_>>();
+
+		let candidates = cores.iter()
+			.filter_map(|core| match core {
+				CoreState::Scheduled(para_id) => self.backed_candidates.iter()
+					.find(|backed| backed.candidate.parachain_index == *para_id),
+				CoreState::Occupied(_) | CoreState::Free => None,
+			})
+			.cloned()
+			.collect();
+
+		(bitfields.into(), candidates)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::channel::oneshot;
+	use futures::executor::{self, ThreadPool};
+	use futures::future;
+	use assert_matches::assert_matches;
+	use sp_keyring::sr25519::Keyring;
+
+	use polkadot_primitives::parachain::{
+		v2::AbridgedCandidateReceipt, AvailabilityBitfield, Id as ParaId, SigningContext, ValidatorPair,
+	};
+
+	type VirtualOverseer = subsystem_test::TestSubsystemContextHandle<ProvisionerMessage>;
+
+	struct TestHarness {
+		virtual_overseer: VirtualOverseer,
+	}
+
+	fn test_harness<T: Future<Output=()>>(test: impl FnOnce(TestHarness) -> T) {
+		let pool = ThreadPool::new().unwrap();
+
+		let (context, virtual_overseer) = subsystem_test::make_subsystem_context(pool);
+
+		let subsystem = run(context)
+			.map_err(|_| panic!("subsystem execution failed"))
+			.map(|_| ());
+
+		let test_fut = test(TestHarness { virtual_overseer });
+
+		futures::pin_mut!(test_fut);
+		futures::pin_mut!(subsystem);
+
+		executor::block_on(future::select(test_fut, subsystem));
+	}
+
+	fn signed_bitfield(
+		keyring: Keyring,
+		validator_index: ValidatorIndex,
+		bits: bitvec::vec::BitVec<bitvec::order::Lsb0, u8>,
+	) -> SignedAvailabilityBitfield {
+		let pair = sc_keystore::Store::new_in_memory()
+			.write()
+			.insert_ephemeral_from_seed::<ValidatorPair>(&keyring.to_seed())
+			.expect("Insert key into keystore");
+
+		SignedAvailabilityBitfield::sign(
+			AvailabilityBitfield(bits),
+			&SigningContext::default(),
+			validator_index,
+			&pair,
+		)
+	}
+
+	fn backed_candidate(relay_parent: Hash, para_id: u32) -> BackedCandidate {
+		BackedCandidate {
+			candidate: AbridgedCandidateReceipt {
+				parachain_index: ParaId::from(para_id),
+				relay_parent,
+				..Default::default()
+			},
+			validity_votes: Vec::new(),
+			validator_indices: bitvec::bitvec![bitvec::order::Lsb0, u8; 0, 0, 0],
+		}
+	}
+
+	async fn provide(virtual_overseer: &mut VirtualOverseer, data: ProvisionableData) {
+		virtual_overseer.send(FromOverseer::Communication {
+			msg: ProvisionerMessage::ProvisionableData(data),
+		}).await;
+	}
+
+	#[test]
+	fn provisionable_data_is_sent_to_subscribers() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+				ActiveLeavesUpdate::start_work(relay_parent),
+			))).await;
+
+			let bitfield = signed_bitfield(Keyring::Alice, 0, bitvec::bitvec![bitvec::order::Lsb0, u8; 1]);
+			provide(&mut virtual_overseer, ProvisionableData::Bitfield(relay_parent, bitfield.clone())).await;
+
+			let (tx, mut rx) = mpsc::channel(16);
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: ProvisionerMessage::RequestBlockAuthorshipData(relay_parent, tx),
+			}).await;
+
+			// data received before subscribing is sent as well.
+			assert_matches!(
+				rx.next().await,
+				Some(ProvisionableData::Bitfield(r, b)) if r == relay_parent && b == bitfield
+			);
+
+			let backed = backed_candidate(relay_parent, 1);
+			provide(&mut virtual_overseer, ProvisionableData::BackedCandidate(backed.clone())).await;
+
+			assert_matches!(
+				rx.next().await,
+				Some(ProvisionableData::BackedCandidate(b)) if b == backed
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn inherent_data_respects_validators_and_cores() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+				ActiveLeavesUpdate::start_work(relay_parent),
+			))).await;
+
+			let bitfield_a = signed_bitfield(Keyring::Alice, 0, bitvec::bitvec![bitvec::order::Lsb0, u8; 1, 0, 0]);
+			let bitfield_b = signed_bitfield(Keyring::Bob, 1, bitvec::bitvec![bitvec::order::Lsb0, u8; 0, 0, 0]);
+			let bitfield_b_second = signed_bitfield(Keyring::Bob, 1, bitvec::bitvec![bitvec::order::Lsb0, u8; 1, 0, 0]);
+			let bitfield_wrong_length = signed_bitfield(Keyring::Charlie, 2, bitvec::bitvec![bitvec::order::Lsb0, u8; 1, 0]);
+			let bitfield_unoccupied = signed_bitfield(Keyring::Dave, 3, bitvec::bitvec![bitvec::order::Lsb0, u8; 0, 1, 0]);
+
+			for bitfield in vec![
+				bitfield_b.clone(),
+				bitfield_a.clone(),
+				bitfield_b_second,
+				bitfield_wrong_length,
+				bitfield_unoccupied,
+			] {
+				provide(&mut virtual_overseer, ProvisionableData::Bitfield(relay_parent, bitfield)).await;
+			}
+
+			let backed_1 = backed_candidate(relay_parent, 1);
+			let backed_2 = backed_candidate(relay_parent, 2);
+			let backed_3 = backed_candidate(relay_parent, 3);
+
+			for backed in vec![backed_1.clone(), backed_3, backed_2.clone()] {
+				provide(&mut virtual_overseer, ProvisionableData::BackedCandidate(backed)).await;
+			}
+
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: ProvisionerMessage::RequestInherentData(relay_parent, tx),
+			}).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					r,
+					RuntimeApiRequest::AvailabilityCores(tx),
+				)) if r == relay_parent => {
+					tx.send(Ok(vec![
+						CoreState::Occupied(Hash::from([2; 32])),
+						CoreState::Scheduled(ParaId::from(2)),
+						CoreState::Scheduled(ParaId::from(1)),
+					])).unwrap();
+				}
+			);
+
+			let (bitfields, candidates) = rx.await.unwrap();
+
+			assert_eq!(bitfields.0, vec![bitfield_a, bitfield_b]);
+			assert_eq!(candidates, vec![backed_2, backed_1]);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn failed_cores_request_yields_empty_inherent_data() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+				ActiveLeavesUpdate::start_work(relay_parent),
+			))).await;
+
+			let bitfield = signed_bitfield(Keyring::Alice, 0, bitvec::bitvec![bitvec::order::Lsb0, u8; 1]);
+			provide(&mut virtual_overseer, ProvisionableData::Bitfield(relay_parent, bitfield.clone())).await;
+
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: ProvisionerMessage::RequestInherentData(relay_parent, tx),
+			}).await;
+
+			// dropping the responder cancels the request, as a timeout would.
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::AvailabilityCores(tx),
+				)) => {
+					drop(tx);
+				}
+			);
+
+			let (bitfields, candidates) = rx.await.unwrap();
+			assert!(bitfields.0.is_empty());
+			assert!(candidates.is_empty());
+
+			// the job is still alive and answers the next request.
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: ProvisionerMessage::RequestInherentData(relay_parent, tx),
+			}).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::AvailabilityCores(tx),
+				)) => {
+					tx.send(Ok(vec![CoreState::Occupied(Hash::from([2; 32]))])).unwrap();
+				}
+			);
+
+			let (bitfields, _) = rx.await.unwrap();
+			assert_eq!(bitfields.0, vec![bitfield]);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+}
@@ -0,0 +1,648 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The Runtime API subsystem.
+//!
+//! This provides a clean, ownerless wrapper around the parachain-related runtime APIs. Responses
+//! are cached per block, as many subsystems make the same requests for every relay-parent.
+
+use std::sync::Arc;
+
+use futures::prelude::*;
+use lru::LruCache;
+use sp_api::ProvideRuntimeApi;
+use sp_runtime::generic::BlockId;
+use sp_authority_discovery::{AuthorityDiscoveryApi, AuthorityId as AuthorityDiscoveryId};
+
+use polkadot_subsystem::{
+	Subsystem, SubsystemContext, SpawnedSubsystem, SubsystemResult,
+	FromOverseer, OverseerSignal,
+};
+use polkadot_subsystem::messages::{RuntimeApiMessage, RuntimeApiRequest, RuntimeApiError};
+use polkadot_primitives::{Block, BlockNumber, Hash};
+use polkadot_primitives::parachain::{
+	DutyRoster, GlobalValidationSchedule, Id as ParaId, LocalValidationData, ParachainHost,
+	ParachainHostV2, SigningContext, ValidationCode, ValidatorId, ValidatorIndex, GroupRotationInfo,
+	CoreAssignment, PersistedValidationData, TransientValidationData, HostConfiguration,
+	CoreState as RuntimeCoreState,
+};
+use polkadot_node_primitives::CoreState;
+
+const LOG_TARGET: &str = "runtime_api";
+
+/// The number of responses kept in the cache.
+const CACHE_CAPACITY: usize = 256;
+
+/// The runtime API subsystem.
+///
+/// Requests of the v2 runtime API are only answered if the subsystem was created with
+/// [`with_v2`](#method.with_v2), and with a `RuntimeApiError` otherwise.
+pub struct RuntimeApiSubsystem<Client> {
+	client: Arc<Client>,
+	v2: Option<V2RequestHandler<Client>>,
+}
+
+/// Answers requests of the v2 runtime API, for clients which implement it.
+type V2RequestHandler<Client> = fn(&Client, &mut ResponseCache, Hash, RuntimeApiRequest);
+
+// Implemented by hand, as deriving it would require the client itself to be `Clone`.
+impl<Client> Clone for RuntimeApiSubsystem<Client> {
+	fn clone(&self) -> Self {
+		RuntimeApiSubsystem { client: self.client.clone(), v2: self.v2 }
+	}
+}
+
+impl<Client> RuntimeApiSubsystem<Client>
+	where
+		Client: ProvideRuntimeApi<Block>,
+		Client::Api: ParachainHost<Block> + AuthorityDiscoveryApi<Block>,
+{
+	/// Create a new runtime API subsystem wrapping the given client, which doesn't answer
+	/// requests of the `ParachainHostV2` runtime API.
+	pub fn new(client: Arc<Client>) -> Self {
+		RuntimeApiSubsystem { client, v2: None }
+	}
+}
+
+impl<Client> RuntimeApiSubsystem<Client>
+	where
+		Client: ProvideRuntimeApi<Block>,
+		Client::Api: ParachainHost<Block> + ParachainHostV2<Block> + AuthorityDiscoveryApi<Block>,
+{
+	/// Create a new runtime API subsystem wrapping the given client, which also answers
+	/// requests of the `ParachainHostV2` runtime API.
+	pub fn with_v2(client: Arc<Client>) -> Self {
+		RuntimeApiSubsystem { client, v2: Some(make_v2_request::<Client>) }
+	}
+}
+
+impl<Client, C> Subsystem<C> for RuntimeApiSubsystem<Client>
+	where
+		Client: ProvideRuntimeApi<Block> + Send + Sync + 'static,
+		Client::Api: ParachainHost<Block> + AuthorityDiscoveryApi<Block>,
+		C: SubsystemContext<Message = RuntimeApiMessage>,
+{
+	fn start(self, ctx: C) -> SpawnedSubsystem {
+		SpawnedSubsystem(run(ctx, self).map(|_| ()).boxed())
+	}
+}
+
+/// A runtime API request, without the response channel.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum RequestKey {
+	Validators,
+	SigningContext,
+	DutyRoster,
+	AuthorityDiscoveryKeys,
+	ValidationCode(ParaId),
+	GlobalValidationSchedule,
+	LocalValidationData(ParaId),
+	ValidatorGroups,
+	AvailabilityCores,
+	ScheduledCores,
+	PersistedValidationData(ParaId),
+	TransientValidationData(ParaId),
+	HostConfiguration,
+	ValidationCodeByHash(Hash),
+}
+
+/// A cached response to a runtime API request.
+#[derive(Debug, Clone)]
+enum CachedResponse {
+	Validators(Vec<ValidatorId>),
+	SigningContext(SigningContext),
+	DutyRoster(DutyRoster),
+	AuthorityDiscoveryKeys(Vec<AuthorityDiscoveryId>),
+	ValidationCode(Option<ValidationCode>),
+	GlobalValidationSchedule(GlobalValidationSchedule),
+	LocalValidationData(Option<LocalValidationData>),
+	ValidatorGroups((Vec<Vec<ValidatorIndex>>, GroupRotationInfo)),
+	AvailabilityCores(Vec<RuntimeCoreState>),
+	ScheduledCores(Vec<CoreAssignment>),
+	PersistedValidationData(Option<PersistedValidationData>),
+	TransientValidationData(Option<TransientValidationData>),
+	HostConfiguration(HostConfiguration<BlockNumber>),
+	ValidationCodeByHash(Option<ValidationCode>),
+}
+
+type ResponseCache = LruCache<(Hash, RequestKey), CachedResponse>;
+
+async fn run<Client>(
+	mut ctx: impl SubsystemContext<Message = RuntimeApiMessage>,
+	subsystem: RuntimeApiSubsystem<Client>,
+) -> SubsystemResult<()>
+	where
+		Client: ProvideRuntimeApi<Block>,
+		Client::Api: ParachainHost<Block> + AuthorityDiscoveryApi<Block>,
+{
+	let RuntimeApiSubsystem { client, v2 } = subsystem;
+	let mut cache = ResponseCache::new(CACHE_CAPACITY);
+
+	loop {
+		match ctx.recv().await? {
+			FromOverseer::Signal(OverseerSignal::ActiveLeaves(_)) => {}
+			FromOverseer::Signal(OverseerSignal::BlockFinalized(..)) => {}
+			FromOverseer::Signal(OverseerSignal::Conclude) => return Ok(()),
+			FromOverseer::Communication { msg } => match msg {
+				RuntimeApiMessage::Request(relay_parent, request) => {
+					make_runtime_api_request(&*client, v2, &mut cache, relay_parent, request);
+				}
+			},
+		}
+	}
+}
+
+// Answer from the cache if possible, or query the runtime API and cache the response.
+macro_rules! query {
+	(
+		$client:ident, $cache:ident, $relay_parent:ident,
+		$key:expr, $variant:ident, $api:ident::$api_fn:ident($($param:expr),*)
+	) => {{
+		let key = ($relay_parent, $key);
+		let cached = match $cache.get(&key) {
+			Some(CachedResponse::$variant(response)) => Some(response.clone()),
+			_ => None,
+		};
+
+		match cached {
+			Some(response) => Ok(response),
+			None => match $api::$api_fn(
+				&*$client.runtime_api(),
+				&BlockId::Hash($relay_parent)
+				$(, $param)*
+			) {
+				Ok(response) => {
+					$cache.put(key, CachedResponse::$variant(response.clone()));
+					Ok(response)
+				}
+				Err(e) => {
+					log::warn!(
+						target: LOG_TARGET,
+						"Runtime API request `{}` at {} failed: {:?}",
+						stringify!($api_fn),
+						$relay_parent,
+						e,
+					);
+
+					Err(RuntimeApiError(format!("{:?}", e)))
+				}
+			},
+		}
+	}}
+}
+
+fn make_runtime_api_request<Client>(
+	client: &Client,
+	v2: Option<V2RequestHandler<Client>>,
+	cache: &mut ResponseCache,
+	relay_parent: Hash,
+	request: RuntimeApiRequest,
+)
+	where
+		Client: ProvideRuntimeApi<Block>,
+		Client::Api: ParachainHost<Block> + AuthorityDiscoveryApi<Block>,
+{
+	match request {
+		RuntimeApiRequest::Validators(sender) => {
+			let _ = sender.send(query!(
+				client, cache, relay_parent,
+				RequestKey::Validators,
+				Validators,
+				ParachainHost::validators()
+			));
+		}
+		RuntimeApiRequest::SigningContext(sender) => {
+			let _ = sender.send(query!(
+				client, cache, relay_parent,
+				RequestKey::SigningContext,
+				SigningContext,
+				ParachainHost::signing_context()
+			));
+		}
+		RuntimeApiRequest::DutyRoster(sender) => {
+			let _ = sender.send(query!(
+				client, cache, relay_parent,
+				RequestKey::DutyRoster,
+				DutyRoster,
+				ParachainHost::duty_roster()
+			));
+		}
+		RuntimeApiRequest::AuthorityDiscoveryKeys(sender) => {
+			let _ = sender.send(query!(
+				client, cache, relay_parent,
+				RequestKey::AuthorityDiscoveryKeys,
+				AuthorityDiscoveryKeys,
+				AuthorityDiscoveryApi::authorities()
+			));
+		}
+		RuntimeApiRequest::ValidationCode(para_id, sender) => {
+			let _ = sender.send(query!(
+				client, cache, relay_parent,
+				RequestKey::ValidationCode(para_id),
+				ValidationCode,
+				ParachainHost::parachain_code(para_id)
+			));
+		}
+		RuntimeApiRequest::GlobalValidationSchedule(sender) => {
+			let _ = sender.send(query!(
+				client, cache, relay_parent,
+				RequestKey::GlobalValidationSchedule,
+				GlobalValidationSchedule,
+				ParachainHost::global_validation_schedule()
+			));
+		}
+		RuntimeApiRequest::LocalValidationData(para_id, sender) => {
+			let _ = sender.send(query!(
+				client, cache, relay_parent,
+				RequestKey::LocalValidationData(para_id),
+				LocalValidationData,
+				ParachainHost::local_validation_data(para_id)
+			));
+		}
+		request => match v2 {
+			Some(handler) => handler(client, cache, relay_parent, request),
+			None => reject_v2_request(request),
+		},
+	}
+}
+
+fn make_v2_request<Client>(
+	client: &Client,
+	cache: &mut ResponseCache,
+	relay_parent: Hash,
+	request: RuntimeApiRequest,
+)
+	where
+		Client: ProvideRuntimeApi<Block>,
+		Client::Api: ParachainHostV2<Block>,
+{
+	match request {
+		RuntimeApiRequest::AvailabilityCores(sender) => {
+			let cores = query!(
+				client, cache, relay_parent,
+				RequestKey::AvailabilityCores,
+				AvailabilityCores,
+				ParachainHostV2::availability_cores()
+			);
+
+			let _ = sender.send(cores.map(|cores| cores.into_iter().map(node_core_state).collect()));
+		}
+		RuntimeApiRequest::ValidatorGroups(sender) => {
+			let _ = sender.send(query!(
+				client, cache, relay_parent,
+				RequestKey::ValidatorGroups,
+				ValidatorGroups,
+				ParachainHostV2::validator_groups()
+			));
+		}
+		RuntimeApiRequest::ScheduledCores(sender) => {
+			let _ = sender.send(query!(
+				client, cache, relay_parent,
+				RequestKey::ScheduledCores,
+				ScheduledCores,
+				ParachainHostV2::scheduled_cores()
+			));
+		}
+		RuntimeApiRequest::PersistedValidationData(para_id, sender) => {
+			let _ = sender.send(query!(
+				client, cache, relay_parent,
+				RequestKey::PersistedValidationData(para_id),
+				PersistedValidationData,
+				ParachainHostV2::persisted_validation_data(para_id)
+			));
+		}
+		RuntimeApiRequest::TransientValidationData(para_id, sender) => {
+			let _ = sender.send(query!(
+				client, cache, relay_parent,
+				RequestKey::TransientValidationData(para_id),
+				TransientValidationData,
+				ParachainHostV2::transient_validation_data(para_id)
+			));
+		}
+		RuntimeApiRequest::HostConfiguration(sender) => {
+			let _ = sender.send(query!(
+				client, cache, relay_parent,
+				RequestKey::HostConfiguration,
+				HostConfiguration,
+				ParachainHostV2::host_configuration()
+			));
+		}
+		RuntimeApiRequest::ValidationCodeByHash(hash, sender) => {
+			let _ = sender.send(query!(
+				client, cache, relay_parent,
+				RequestKey::ValidationCodeByHash(hash),
+				ValidationCodeByHash,
+				ParachainHostV2::validation_code_by_hash(hash)
+			));
+		}
+		// legacy requests are answered before reaching this.
+		request => reject_v2_request(request),
+	}
+}
+
+/// Answer a request of the v2 runtime API with an error, as the client doesn't support it.
+/// Other requests are always answered and don't reach this.
+fn reject_v2_request(request: RuntimeApiRequest) {
+	fn unsupported() -> RuntimeApiError {
+		RuntimeApiError("The runtime doesn't implement `ParachainHostV2`".into())
+	}
+
+	match request {
+		RuntimeApiRequest::AvailabilityCores(sender) => { let _ = sender.send(Err(unsupported())); }
+		RuntimeApiRequest::ValidatorGroups(sender) => { let _ = sender.send(Err(unsupported())); }
+		RuntimeApiRequest::ScheduledCores(sender) => { let _ = sender.send(Err(unsupported())); }
+		RuntimeApiRequest::PersistedValidationData(_, sender) => { let _ = sender.send(Err(unsupported())); }
+		RuntimeApiRequest::TransientValidationData(_, sender) => { let _ = sender.send(Err(unsupported())); }
+		RuntimeApiRequest::HostConfiguration(sender) => { let _ = sender.send(Err(unsupported())); }
+		RuntimeApiRequest::ValidationCodeByHash(_, sender) => { let _ = sender.send(Err(unsupported())); }
+		RuntimeApiRequest::Validators(_)
+			| RuntimeApiRequest::SigningContext(_)
+			| RuntimeApiRequest::DutyRoster(_)
+			| RuntimeApiRequest::AuthorityDiscoveryKeys(_)
+			| RuntimeApiRequest::ValidationCode(..)
+			| RuntimeApiRequest::GlobalValidationSchedule(_)
+			| RuntimeApiRequest::LocalValidationData(..) => {}
+	}
+}
+
+/// The node-side state of a core, which only refers to the candidate occupying it by hash.
+fn node_core_state(core: RuntimeCoreState) -> CoreState {
+	match core {
+		RuntimeCoreState::Occupied(occupied) => CoreState::Occupied(occupied.candidate.hash()),
+		RuntimeCoreState::Scheduled(scheduled) => CoreState::Scheduled(scheduled.para_id),
+		RuntimeCoreState::Free => CoreState::Free,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use futures::channel::oneshot;
+	use futures::executor::{self, ThreadPool};
+	use sp_keyring::sr25519::Keyring;
+	use sp_runtime::traits::Block as BlockT;
+
+	use polkadot_primitives::parachain::{
+		v2::AbridgedCandidateReceipt, CollatorId, Retriable, OccupiedCore, ScheduledCore,
+	};
+
+	#[derive(Clone, Default)]
+	struct MockRuntimeApi {
+		validators: Vec<ValidatorId>,
+		validators_calls: Arc<AtomicUsize>,
+		availability_cores: Vec<RuntimeCoreState>,
+	}
+
+	impl ProvideRuntimeApi<Block> for MockRuntimeApi {
+		type Api = Self;
+
+		fn runtime_api<'a>(&'a self) -> sp_api::ApiRef<'a, Self::Api> {
+			self.clone().into()
+		}
+	}
+
+	sp_api::mock_impl_runtime_apis! {
+		impl ParachainHost<Block> for MockRuntimeApi {
+			type Error = sp_blockchain::Error;
+
+			fn validators(&self) -> Vec<ValidatorId> {
+				self.validators_calls.fetch_add(1, Ordering::SeqCst);
+				self.validators.clone()
+			}
+			fn duty_roster() -> DutyRoster { Default::default() }
+			fn active_parachains() -> Vec<(ParaId, Option<(CollatorId, Retriable)>)> { Vec::new() }
+			fn global_validation_schedule() -> GlobalValidationSchedule { Default::default() }
+			fn local_validation_data(_: ParaId) -> Option<LocalValidationData> { None }
+			fn parachain_code(id: ParaId) -> Option<ValidationCode> {
+				if id == ParaId::from(1) {
+					Some(ValidationCode(vec![1, 2, 3]))
+				} else {
+					None
+				}
+			}
+			fn get_heads(_: Vec<<Block as BlockT>::Extrinsic>)
+				-> Option<Vec<polkadot_primitives::parachain::AbridgedCandidateReceipt>>
+			{
+				None
+			}
+			fn signing_context() -> SigningContext { Default::default() }
+			fn downward_messages(_: ParaId) -> Vec<polkadot_primitives::DownwardMessage> { Vec::new() }
+		}
+
+		impl AuthorityDiscoveryApi<Block> for MockRuntimeApi {
+			type Error = sp_blockchain::Error;
+
+			fn authorities() -> Vec<AuthorityDiscoveryId> { Vec::new() }
+		}
+
+		impl ParachainHostV2<Block> for MockRuntimeApi {
+			type Error = sp_blockchain::Error;
+
+			fn validators(&self) -> Vec<ValidatorId> { self.validators.clone() }
+			fn validator_groups() -> (Vec<Vec<ValidatorIndex>>, GroupRotationInfo) {
+				(Vec::new(), Default::default())
+			}
+			fn availability_cores(&self) -> Vec<RuntimeCoreState> { self.availability_cores.clone() }
+			fn scheduled_cores() -> Vec<CoreAssignment> { Vec::new() }
+			fn persisted_validation_data(_: ParaId) -> Option<PersistedValidationData> { None }
+			fn transient_validation_data(_: ParaId) -> Option<TransientValidationData> { None }
+			fn host_configuration() -> HostConfiguration<BlockNumber> {
+				HostConfiguration { max_code_size: 42, ..Default::default() }
+			}
+			fn validation_code(_: ParaId) -> Option<ValidationCode> { None }
+			fn validation_code_by_hash(_: Hash) -> Option<ValidationCode> { None }
+		}
+	}
+
+	struct TestHarness {
+		virtual_overseer: subsystem_test::TestSubsystemContextHandle<RuntimeApiMessage>,
+	}
+
+	fn test_harness<T: Future<Output=()>>(
+		subsystem: RuntimeApiSubsystem<MockRuntimeApi>,
+		test: impl FnOnce(TestHarness) -> T,
+	) {
+		let pool = ThreadPool::new().unwrap();
+
+		let (context, virtual_overseer) = subsystem_test::make_subsystem_context(pool);
+
+		let subsystem = run(context, subsystem)
+			.map_err(|_| panic!("subsystem execution failed"))
+			.map(|_| ());
+
+		let test_fut = test(TestHarness { virtual_overseer });
+
+		futures::pin_mut!(test_fut);
+		futures::pin_mut!(subsystem);
+
+		executor::block_on(future::select(test_fut, subsystem));
+	}
+
+	fn mock_client() -> Arc<MockRuntimeApi> {
+		Arc::new(MockRuntimeApi {
+			validators: vec![
+				ValidatorId::from(Keyring::Alice.public()),
+				ValidatorId::from(Keyring::Bob.public()),
+			],
+			..Default::default()
+		})
+	}
+
+	#[test]
+	fn responses_are_cached_per_block() {
+		let client = mock_client();
+		let validators_calls = client.validators_calls.clone();
+		let expected_validators = client.validators.clone();
+
+		test_harness(RuntimeApiSubsystem::with_v2(client), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let block_a = Hash::from([1; 32]);
+			let block_b = Hash::from([2; 32]);
+
+			for &relay_parent in &[block_a, block_a, block_b] {
+				let (tx, rx) = oneshot::channel();
+				virtual_overseer.send(FromOverseer::Communication {
+					msg: RuntimeApiMessage::Request(relay_parent, RuntimeApiRequest::Validators(tx)),
+				}).await;
+
+				assert_eq!(rx.await.unwrap().unwrap(), expected_validators);
+			}
+
+			assert_eq!(validators_calls.load(Ordering::SeqCst), 2);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn requests_are_answered() {
+		test_harness(RuntimeApiSubsystem::with_v2(mock_client()), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, RuntimeApiRequest::SigningContext(tx)),
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), SigningContext::default());
+
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(
+					relay_parent,
+					RuntimeApiRequest::ValidationCode(ParaId::from(1), tx),
+				),
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), Some(ValidationCode(vec![1, 2, 3])));
+
+			// a para without code is answered with `None`.
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(
+					relay_parent,
+					RuntimeApiRequest::ValidationCode(ParaId::from(2), tx),
+				),
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), None);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn availability_cores_are_answered_with_node_side_core_states() {
+		let candidate = AbridgedCandidateReceipt {
+			parachain_index: ParaId::from(1),
+			..Default::default()
+		};
+		let candidate_hash = candidate.hash();
+
+		let client = Arc::new(MockRuntimeApi {
+			availability_cores: vec![
+				RuntimeCoreState::Occupied(OccupiedCore {
+					para_id: ParaId::from(1),
+					group_responsible: Default::default(),
+					occupied_since: 1,
+					availability: Default::default(),
+					candidate,
+				}),
+				RuntimeCoreState::Scheduled(ScheduledCore { para_id: ParaId::from(2), collator: None }),
+				RuntimeCoreState::Free,
+			],
+			..Default::default()
+		});
+
+		test_harness(RuntimeApiSubsystem::with_v2(client), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, RuntimeApiRequest::AvailabilityCores(tx)),
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), vec![
+				CoreState::Occupied(candidate_hash),
+				CoreState::Scheduled(ParaId::from(2)),
+				CoreState::Free,
+			]);
+
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, RuntimeApiRequest::HostConfiguration(tx)),
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap().max_code_size, 42);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+	#[test]
+	fn v2_requests_are_rejected_without_v2_support() {
+		let client = mock_client();
+		let expected_validators = client.validators.clone();
+
+		test_harness(RuntimeApiSubsystem::new(client), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, RuntimeApiRequest::AvailabilityCores(tx)),
+			}).await;
+
+			assert!(rx.await.unwrap().is_err());
+
+			// legacy requests are still answered.
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: RuntimeApiMessage::Request(relay_parent, RuntimeApiRequest::Validators(tx)),
+			}).await;
+
+			assert_eq!(rx.await.unwrap().unwrap(), expected_validators);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+}
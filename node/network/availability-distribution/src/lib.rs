@@ -0,0 +1,999 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The Availability Distribution subsystem.
+//!
+//! Erasure chunks are only distributed for candidates which are pending availability on-chain
+//! at one of the relay-parents we are working on. The validators holding the chunks of such a
+//! candidate send chunk `i` directly to the peer of validator `i`, so that each chunk crosses
+//! the network once for every holder. Every chunk is accompanied by the receipt of its candidate
+//! and checked against the erasure root committed to in the receipt. As the candidate hash
+//! covers the erasure root and the hash is checked against the candidates pending availability,
+//! a verified chunk can only ever be stored for the candidate it belongs to.
+
+use std::collections::{HashMap, HashSet};
+
+use parity_scale_codec::{Encode, Decode};
+use futures::channel::oneshot;
+use futures::prelude::*;
+use keystore::KeyStorePtr;
+
+use sc_network::{PeerId, ReputationChange};
+use sp_authority_discovery::AuthorityId as AuthorityDiscoveryId;
+
+use polkadot_subsystem::{
+	Subsystem, SubsystemContext, SpawnedSubsystem, SubsystemResult,
+	FromOverseer, OverseerSignal, ActiveLeavesUpdate,
+};
+use polkadot_subsystem::messages::{
+	AllMessages, AvailabilityDistributionMessage, AvailabilityStoreMessage, NetworkBridgeEvent,
+	NetworkBridgeMessage, RuntimeApiMessage, RuntimeApiRequest, RuntimeApiSender,
+};
+use node_primitives::{ProtocolId, View};
+use polkadot_primitives::{BlakeTwo256, Hash, HashT};
+use polkadot_primitives::parachain::{
+	v2::AbridgedCandidateReceipt, CoreState, ErasureChunk, ValidatorId, ValidatorIndex,
+	ValidatorPair,
+};
+use polkadot_erasure_coding as erasure;
+
+/// The protocol identifier for availability distribution.
+pub const PROTOCOL_V1: ProtocolId = *b"avad";
+
+const COST_MALFORMED_MESSAGE: ReputationChange
+	= ReputationChange::new(-500, "Malformed availability distribution message");
+const COST_NOT_LIVE: ReputationChange
+	= ReputationChange::new(-100, "Chunk message for a candidate which is not live");
+const COST_UNEXPECTED_CHUNK: ReputationChange
+	= ReputationChange::new(-100, "Chunk is neither ours nor requested");
+const COST_INVALID_CHUNK: ReputationChange
+	= ReputationChange::new(-500, "Chunk is not committed to by the erasure root of the candidate");
+const COST_PEER_DUPLICATE_MESSAGE: ReputationChange
+	= ReputationChange::new(-200, "Peer sent the same chunk twice");
+const BENEFIT_VALID_CHUNK_FIRST: ReputationChange
+	= ReputationChange::new(15, "Peer was the first to send a valid chunk");
+
+/// Messages sent over the network by this subsystem.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub enum WireMessage {
+	/// An erasure chunk of the given candidate.
+	#[codec(index = "0")]
+	Chunk(AbridgedCandidateReceipt, ErasureChunk),
+	/// A request for the erasure chunk of the given candidate with the given index.
+	#[codec(index = "1")]
+	RequestChunk(AbridgedCandidateReceipt, u32),
+}
+
+/// The availability distribution subsystem.
+#[derive(Clone)]
+pub struct AvailabilityDistributionSubsystem {
+	keystore: KeyStorePtr,
+}
+
+impl AvailabilityDistributionSubsystem {
+	/// Create a new instance of the `AvailabilityDistributionSubsystem`, which keeps the chunks
+	/// of any validator key found in the given keystore.
+	pub fn new(keystore: KeyStorePtr) -> Self {
+		AvailabilityDistributionSubsystem { keystore }
+	}
+}
+
+impl<C> Subsystem<C> for AvailabilityDistributionSubsystem
+	where C: SubsystemContext<Message = AvailabilityDistributionMessage>
+{
+	fn start(self, ctx: C) -> SpawnedSubsystem {
+		SpawnedSubsystem(run(ctx, self.keystore).map(|_| ()).boxed())
+	}
+}
+
+/// Data kept for each relay-parent we are working on.
+struct PerRelayParent {
+	/// The number of validators, which is also the number of chunks of each candidate.
+	n_validators: usize,
+	/// Our index in the validator set, if we are a validator.
+	our_index: Option<ValidatorIndex>,
+	/// The peers of the validators, by validator index, where they could be resolved.
+	validator_peers: Vec<Option<PeerId>>,
+	/// The hashes of the candidates pending availability in the post-state of the relay-parent.
+	pending_availability: HashSet<Hash>,
+}
+
+/// Data kept for each live candidate.
+struct CandidateState {
+	receipt: AbridgedCandidateReceipt,
+	/// The verified chunks of the candidate we have, by index.
+	chunks: HashMap<u32, ErasureChunk>,
+	/// The chunk indices we have sent to each peer.
+	sent: HashMap<PeerId, HashSet<u32>>,
+	/// The chunk indices each peer has sent to us.
+	received: HashMap<PeerId, HashSet<u32>>,
+	/// The chunk indices we were asked to fetch and have not received yet.
+	fetching: HashSet<u32>,
+}
+
+impl CandidateState {
+	fn new(receipt: AbridgedCandidateReceipt) -> Self {
+		CandidateState {
+			receipt,
+			chunks: HashMap::new(),
+			sent: HashMap::new(),
+			received: HashMap::new(),
+			fetching: HashSet::new(),
+		}
+	}
+}
+
+#[derive(Default)]
+struct State {
+	per_relay_parent: HashMap<Hash, PerRelayParent>,
+	/// Candidates which are pending availability or whose relay-parent we are working on, by
+	/// candidate hash.
+	candidates: HashMap<Hash, CandidateState>,
+	peer_views: HashMap<PeerId, View>,
+}
+
+impl State {
+	/// The relay-parents we are working on at which the candidate is pending availability.
+	fn pending_at<'a>(&'a self, candidate_hash: &Hash)
+		-> impl Iterator<Item = (&'a Hash, &'a PerRelayParent)> + 'a
+	{
+		let candidate_hash = *candidate_hash;
+		self.per_relay_parent.iter()
+			.filter(move |(_, per_relay_parent)| per_relay_parent.pending_availability.contains(&candidate_hash))
+	}
+
+	/// The data of a relay-parent we are working on which the candidate is pending availability
+	/// at, or otherwise of its own relay-parent.
+	fn context_of(&self, candidate_hash: &Hash, relay_parent: &Hash) -> Option<&PerRelayParent> {
+		self.pending_at(candidate_hash).next().map(|(_, per_relay_parent)| per_relay_parent)
+			.or_else(|| self.per_relay_parent.get(relay_parent))
+	}
+
+	/// Whether a candidate is still worth keeping state about.
+	fn is_live(&self, candidate: &CandidateState) -> bool {
+		self.per_relay_parent.contains_key(&candidate.receipt.relay_parent)
+			|| self.per_relay_parent.values().any(|per_relay_parent| {
+				per_relay_parent.pending_availability.contains(&candidate.receipt.hash())
+			})
+	}
+}
+
+fn network_update_message(n: NetworkBridgeEvent) -> AllMessages {
+	AllMessages::AvailabilityDistribution(AvailabilityDistributionMessage::NetworkBridgeUpdate(n))
+}
+
+async fn run(
+	mut ctx: impl SubsystemContext<Message = AvailabilityDistributionMessage>,
+	keystore: KeyStorePtr,
+) -> SubsystemResult<()> {
+	ctx.send_message(AllMessages::NetworkBridge(
+		NetworkBridgeMessage::RegisterEventProducer(PROTOCOL_V1, network_update_message),
+	)).await?;
+
+	let mut state = State::default();
+
+	loop {
+		match ctx.recv().await? {
+			FromOverseer::Signal(OverseerSignal::ActiveLeaves(ActiveLeavesUpdate { activated, deactivated })) => {
+				for relay_parent in activated {
+					let per_relay_parent = match fetch_relay_parent_data(&mut ctx, relay_parent, &keystore).await? {
+						Some(per_relay_parent) => per_relay_parent,
+						None => continue,
+					};
+
+					let pending: Vec<Hash> = per_relay_parent.pending_availability.iter().cloned().collect();
+					state.per_relay_parent.insert(relay_parent, per_relay_parent);
+
+					for candidate_hash in pending {
+						send_chunks(&mut ctx, &mut state, candidate_hash).await?;
+					}
+				}
+
+				for relay_parent in deactivated {
+					state.per_relay_parent.remove(&relay_parent);
+				}
+
+				let live: HashSet<Hash> = state.candidates.iter()
+					.filter(|(_, candidate)| state.is_live(candidate))
+					.map(|(hash, _)| *hash)
+					.collect();
+				state.candidates.retain(|hash, _| live.contains(hash));
+			}
+			FromOverseer::Signal(OverseerSignal::BlockFinalized(..)) => {}
+			FromOverseer::Signal(OverseerSignal::Conclude) => return Ok(()),
+			FromOverseer::Communication { msg } => match msg {
+				AvailabilityDistributionMessage::DistributeChunk(candidate, chunk) => {
+					handle_distribute(&mut ctx, &mut state, candidate, chunk).await?;
+				}
+				AvailabilityDistributionMessage::FetchChunk(candidate, index) => {
+					handle_fetch(&mut ctx, &mut state, candidate, index).await?;
+				}
+				AvailabilityDistributionMessage::NetworkBridgeUpdate(event) => {
+					handle_network_update(&mut ctx, &mut state, event).await?;
+				}
+			},
+		}
+	}
+}
+
+async fn runtime_api_request<T>(
+	ctx: &mut impl SubsystemContext<Message = AvailabilityDistributionMessage>,
+	relay_parent: Hash,
+	make_request: impl FnOnce(RuntimeApiSender<T>) -> RuntimeApiRequest,
+) -> SubsystemResult<Option<T>> {
+	let (tx, rx) = oneshot::channel();
+	ctx.send_message(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+		relay_parent,
+		make_request(tx),
+	))).await?;
+
+	match rx.await? {
+		Ok(res) => Ok(Some(res)),
+		Err(e) => {
+			log::warn!(
+				target: "availability_distribution",
+				"Runtime API request failed at relay-parent {}: {:?}",
+				relay_parent,
+				e,
+			);
+
+			Ok(None)
+		}
+	}
+}
+
+/// Fetch the validators, their peers and the candidates pending availability at the
+/// relay-parent. The authority discovery keys are in the same order as the validators.
+async fn fetch_relay_parent_data(
+	ctx: &mut impl SubsystemContext<Message = AvailabilityDistributionMessage>,
+	relay_parent: Hash,
+	keystore: &KeyStorePtr,
+) -> SubsystemResult<Option<PerRelayParent>> {
+	let validators = match runtime_api_request(ctx, relay_parent, RuntimeApiRequest::Validators).await? {
+		Some(validators) => validators,
+		None => return Ok(None),
+	};
+
+	let authorities: Vec<AuthorityDiscoveryId> = match runtime_api_request(
+		ctx,
+		relay_parent,
+		RuntimeApiRequest::AuthorityDiscoveryKeys,
+	).await? {
+		Some(authorities) => authorities,
+		None => return Ok(None),
+	};
+
+	let cores = match runtime_api_request(ctx, relay_parent, RuntimeApiRequest::AvailabilityCores).await? {
+		Some(cores) => cores,
+		None => return Ok(None),
+	};
+
+	let (tx, rx) = oneshot::channel();
+	ctx.send_message(AllMessages::NetworkBridge(
+		NetworkBridgeMessage::ResolveValidators(authorities, tx),
+	)).await?;
+	let validator_peers = rx.await?;
+
+	let pending_availability = cores.into_iter()
+		.filter_map(|core| match core {
+			CoreState::Occupied(occupied) => Some(occupied.candidate.hash()),
+			_ => None,
+		})
+		.collect();
+
+	Ok(Some(PerRelayParent {
+		n_validators: validators.len(),
+		our_index: our_validator_index(&validators, keystore),
+		validator_peers,
+		pending_availability,
+	}))
+}
+
+fn our_validator_index(validators: &[ValidatorId], keystore: &KeyStorePtr) -> Option<ValidatorIndex> {
+	let keystore = keystore.read();
+	validators.iter()
+		.position(|v| keystore.key_pair::<ValidatorPair>(v).is_ok())
+		.map(|i| i as ValidatorIndex)
+}
+
+/// Check that the chunk is committed to by the erasure root of the candidate.
+fn check_chunk(candidate: &AbridgedCandidateReceipt, chunk: &ErasureChunk) -> bool {
+	match erasure::branch_hash(&candidate.commitments.erasure_root, &chunk.proof, chunk.index as usize) {
+		Ok(hash) => hash == BlakeTwo256::hash(&chunk.chunk),
+		Err(_) => false,
+	}
+}
+
+async fn handle_distribute(
+	ctx: &mut impl SubsystemContext<Message = AvailabilityDistributionMessage>,
+	state: &mut State,
+	candidate: AbridgedCandidateReceipt,
+	chunk: ErasureChunk,
+) -> SubsystemResult<()> {
+	if !state.per_relay_parent.contains_key(&candidate.relay_parent) {
+		return Ok(());
+	}
+
+	// chunks of our own subsystems are trusted and already stored. They are sent once the
+	// candidate is pending availability.
+	let candidate_hash = candidate.hash();
+	state.candidates.entry(candidate_hash)
+		.or_insert_with(|| CandidateState::new(candidate))
+		.chunks.entry(chunk.index)
+		.or_insert(chunk);
+
+	send_chunks(ctx, state, candidate_hash).await
+}
+
+async fn handle_fetch(
+	ctx: &mut impl SubsystemContext<Message = AvailabilityDistributionMessage>,
+	state: &mut State,
+	candidate: AbridgedCandidateReceipt,
+	index: u32,
+) -> SubsystemResult<()> {
+	let candidate_hash = candidate.hash();
+
+	// chunk `i` is requested from validator `i`, which holds it once the candidate is available.
+	let peer = match state.context_of(&candidate_hash, &candidate.relay_parent) {
+		Some(per_relay_parent) if (index as usize) < per_relay_parent.n_validators => {
+			per_relay_parent.validator_peers.get(index as usize).cloned().flatten()
+		}
+		_ => return Ok(()),
+	};
+
+	let candidate_state = state.candidates.entry(candidate_hash)
+		.or_insert_with(|| CandidateState::new(candidate.clone()));
+
+	if let Some(chunk) = candidate_state.chunks.get(&index) {
+		return ctx.send_message(AllMessages::AvailabilityStore(
+			AvailabilityStoreMessage::StoreChunk(candidate_hash, index, chunk.clone()),
+		)).await;
+	}
+
+	if !candidate_state.fetching.insert(index) {
+		return Ok(());
+	}
+
+	match peer {
+		Some(peer) if state.peer_views.contains_key(&peer) => {
+			send_message(ctx, vec![peer], WireMessage::RequestChunk(candidate, index)).await
+		}
+		_ => {
+			log::debug!(
+				target: "availability_distribution",
+				"Validator {} holding chunk of candidate {} is not connected",
+				index,
+				candidate_hash,
+			);
+
+			candidate_state.fetching.remove(&index);
+			Ok(())
+		}
+	}
+}
+
+/// Send the chunks we have of a candidate pending availability to the validators they belong
+/// to, once their peers are interested in a relay-parent the candidate is pending at.
+async fn send_chunks(
+	ctx: &mut impl SubsystemContext<Message = AvailabilityDistributionMessage>,
+	state: &mut State,
+	candidate_hash: Hash,
+) -> SubsystemResult<()> {
+	let candidate_state = match state.candidates.get_mut(&candidate_hash) {
+		Some(candidate_state) => candidate_state,
+		None => return Ok(()),
+	};
+
+	let mut messages = Vec::new();
+	for (relay_parent, per_relay_parent) in state.per_relay_parent.iter() {
+		if !per_relay_parent.pending_availability.contains(&candidate_hash) {
+			continue;
+		}
+
+		for (index, chunk) in candidate_state.chunks.iter() {
+			let peer = match per_relay_parent.validator_peers.get(*index as usize) {
+				Some(Some(peer)) => peer,
+				_ => continue,
+			};
+
+			let interested = state.peer_views.get(peer).map_or(false, |view| view.0.contains(relay_parent));
+			if interested && candidate_state.sent.entry(peer.clone()).or_default().insert(*index) {
+				messages.push((peer.clone(), WireMessage::Chunk(candidate_state.receipt.clone(), chunk.clone())));
+			}
+		}
+	}
+
+	for (peer, message) in messages {
+		send_message(ctx, vec![peer], message).await?;
+	}
+
+	Ok(())
+}
+
+async fn handle_network_update(
+	ctx: &mut impl SubsystemContext<Message = AvailabilityDistributionMessage>,
+	state: &mut State,
+	event: NetworkBridgeEvent,
+) -> SubsystemResult<()> {
+	match event {
+		NetworkBridgeEvent::PeerConnected(peer, _role) => {
+			state.peer_views.insert(peer, View(Vec::new()));
+		}
+		NetworkBridgeEvent::PeerDisconnected(peer) => {
+			state.peer_views.remove(&peer);
+			for candidate_state in state.candidates.values_mut() {
+				candidate_state.sent.remove(&peer);
+				candidate_state.received.remove(&peer);
+			}
+		}
+		NetworkBridgeEvent::PeerViewChange(peer, view) => {
+			if !state.peer_views.contains_key(&peer) {
+				return Ok(());
+			}
+
+			state.peer_views.insert(peer, view);
+
+			// the peer may now accept the chunks of candidates pending availability in its view.
+			let candidates: Vec<Hash> = state.candidates.keys().cloned().collect();
+			for candidate_hash in candidates {
+				send_chunks(ctx, state, candidate_hash).await?;
+			}
+		}
+		NetworkBridgeEvent::OurViewChange(_view) => {}
+		NetworkBridgeEvent::PeerMessage(peer, bytes) => {
+			if !state.peer_views.contains_key(&peer) {
+				return report_peer(ctx, peer, COST_MALFORMED_MESSAGE).await;
+			}
+
+			match WireMessage::decode(&mut &bytes[..]) {
+				Ok(WireMessage::Chunk(candidate, chunk)) => {
+					handle_incoming_chunk(ctx, state, peer, candidate, chunk).await?;
+				}
+				Ok(WireMessage::RequestChunk(candidate, index)) => {
+					handle_chunk_request(ctx, state, peer, candidate, index).await?;
+				}
+				Err(_) => report_peer(ctx, peer, COST_MALFORMED_MESSAGE).await?,
+			}
+		}
+	}
+
+	Ok(())
+}
+
+/// Handle a chunk sent by a peer. We only accept our own chunk of a candidate pending
+/// availability, or chunks we have requested of candidates our own subsystems asked for.
+async fn handle_incoming_chunk(
+	ctx: &mut impl SubsystemContext<Message = AvailabilityDistributionMessage>,
+	state: &mut State,
+	peer: PeerId,
+	candidate: AbridgedCandidateReceipt,
+	chunk: ErasureChunk,
+) -> SubsystemResult<()> {
+	let candidate_hash = candidate.hash();
+	let index = chunk.index;
+
+	let ours = state.pending_at(&candidate_hash)
+		.any(|(_, per_relay_parent)| per_relay_parent.our_index == Some(index));
+	let requested = state.candidates.get(&candidate_hash)
+		.map_or(false, |candidate_state| candidate_state.fetching.contains(&index));
+
+	let n_validators = match state.context_of(&candidate_hash, &candidate.relay_parent) {
+		Some(per_relay_parent) if ours || requested => per_relay_parent.n_validators,
+		Some(_) => return report_peer(ctx, peer, COST_UNEXPECTED_CHUNK).await,
+		None => return report_peer(ctx, peer, COST_NOT_LIVE).await,
+	};
+
+	let sent_by_peer = state.candidates.get(&candidate_hash)
+		.and_then(|candidate_state| candidate_state.received.get(&peer))
+		.map_or(false, |received| received.contains(&index));
+
+	if sent_by_peer {
+		return report_peer(ctx, peer, COST_PEER_DUPLICATE_MESSAGE).await;
+	}
+
+	if index as usize >= n_validators || !check_chunk(&candidate, &chunk) {
+		return report_peer(ctx, peer, COST_INVALID_CHUNK).await;
+	}
+
+	let candidate_state = state.candidates.entry(candidate_hash)
+		.or_insert_with(|| CandidateState::new(candidate));
+
+	candidate_state.received.entry(peer.clone()).or_default().insert(index);
+
+	if candidate_state.chunks.contains_key(&index) {
+		return Ok(());
+	}
+
+	report_peer(ctx, peer, BENEFIT_VALID_CHUNK_FIRST).await?;
+
+	candidate_state.fetching.remove(&index);
+	candidate_state.chunks.insert(index, chunk.clone());
+
+	ctx.send_message(AllMessages::AvailabilityStore(
+		AvailabilityStoreMessage::StoreChunk(candidate_hash, index, chunk),
+	)).await
+}
+
+async fn handle_chunk_request(
+	ctx: &mut impl SubsystemContext<Message = AvailabilityDistributionMessage>,
+	state: &mut State,
+	peer: PeerId,
+	candidate: AbridgedCandidateReceipt,
+	index: u32,
+) -> SubsystemResult<()> {
+	let candidate_hash = candidate.hash();
+	match state.context_of(&candidate_hash, &candidate.relay_parent) {
+		Some(per_relay_parent) if (index as usize) < per_relay_parent.n_validators => {}
+		Some(_) => return report_peer(ctx, peer, COST_MALFORMED_MESSAGE).await,
+		None => return report_peer(ctx, peer, COST_NOT_LIVE).await,
+	}
+
+	let chunk = match state.candidates.get(&candidate_hash).and_then(|c| c.chunks.get(&index)) {
+		Some(chunk) => Some(chunk.clone()),
+		None => {
+			let (tx, rx) = oneshot::channel();
+			ctx.send_message(AllMessages::AvailabilityStore(
+				AvailabilityStoreMessage::QueryChunk(candidate_hash, index, tx),
+			)).await?;
+
+			rx.await?
+		}
+	};
+
+	match chunk {
+		Some(chunk) => send_message(ctx, vec![peer], WireMessage::Chunk(candidate, chunk)).await,
+		None => Ok(()),
+	}
+}
+
+async fn send_message(
+	ctx: &mut impl SubsystemContext<Message = AvailabilityDistributionMessage>,
+	peers: Vec<PeerId>,
+	message: WireMessage,
+) -> SubsystemResult<()> {
+	if peers.is_empty() {
+		return Ok(());
+	}
+
+	ctx.send_message(AllMessages::NetworkBridge(
+		NetworkBridgeMessage::SendMessage(peers, PROTOCOL_V1, message.encode()),
+	)).await
+}
+
+async fn report_peer(
+	ctx: &mut impl SubsystemContext<Message = AvailabilityDistributionMessage>,
+	peer: PeerId,
+	rep: ReputationChange,
+) -> SubsystemResult<()> {
+	ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(peer, rep))).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::{self, ThreadPool};
+	use assert_matches::assert_matches;
+	use sp_keyring::sr25519::Keyring;
+	use sc_network::ObservedRole;
+
+	use polkadot_primitives::parachain::{AvailableData, BlockData, OccupiedCore, PoVBlock};
+
+	type VirtualOverseer = subsystem_test::TestSubsystemContextHandle<AvailabilityDistributionMessage>;
+
+	struct TestHarness {
+		virtual_overseer: VirtualOverseer,
+	}
+
+	fn test_harness<T: Future<Output=()>>(keystore: KeyStorePtr, test: impl FnOnce(TestHarness) -> T) {
+		let pool = ThreadPool::new().unwrap();
+
+		let (context, virtual_overseer) = subsystem_test::make_subsystem_context(pool);
+
+		let subsystem = run(context, keystore)
+			.map_err(|_| panic!("subsystem execution failed"))
+			.map(|_| ());
+
+		let test_fut = test(TestHarness { virtual_overseer });
+
+		futures::pin_mut!(test_fut);
+		futures::pin_mut!(subsystem);
+
+		executor::block_on(future::select(test_fut, subsystem));
+	}
+
+	struct TestState {
+		/// The relay-parent the candidate is built on.
+		relay_parent: Hash,
+		/// The leaf the candidate is pending availability at.
+		leaf: Hash,
+		validators: Vec<ValidatorId>,
+		authorities: Vec<AuthorityDiscoveryId>,
+		/// The peers of Alice and Charlie. We are Bob.
+		validator_peers: Vec<Option<PeerId>>,
+		keystore: KeyStorePtr,
+		candidate: AbridgedCandidateReceipt,
+		chunks: Vec<ErasureChunk>,
+	}
+
+	impl TestState {
+		fn peer(&self, validator_index: usize) -> PeerId {
+			self.validator_peers[validator_index].clone().unwrap()
+		}
+	}
+
+	impl Default for TestState {
+		fn default() -> Self {
+			let relay_parent = Hash::from([1; 32]);
+			let leaf = Hash::from([2; 32]);
+			let keyrings = [Keyring::Alice, Keyring::Bob, Keyring::Charlie];
+			let validators = keyrings.iter().map(|k| ValidatorId::from(k.public())).collect::<Vec<_>>();
+			let authorities = keyrings.iter().map(|k| AuthorityDiscoveryId::from(k.public())).collect();
+			let validator_peers = vec![Some(PeerId::random()), None, Some(PeerId::random())];
+
+			let keystore = keystore::Store::new_in_memory();
+			keystore.write().insert_ephemeral_from_seed::<ValidatorPair>(&Keyring::Bob.to_seed())
+				.expect("Insert key into keystore");
+
+			let available_data = AvailableData {
+				pov_block: PoVBlock { block_data: BlockData(vec![42, 43, 44]) },
+				omitted_validation: Default::default(),
+			};
+
+			let encoded = erasure::obtain_chunks(validators.len(), &available_data).unwrap();
+			let branches = erasure::branches(encoded.as_ref());
+			let erasure_root = branches.root();
+			let chunks = branches.enumerate()
+				.map(|(index, (proof, chunk))| ErasureChunk {
+					chunk: chunk.to_vec(),
+					index: index as u32,
+					proof,
+				})
+				.collect();
+
+			let mut candidate = AbridgedCandidateReceipt {
+				relay_parent,
+				..Default::default()
+			};
+			candidate.commitments.erasure_root = erasure_root;
+
+			TestState {
+				relay_parent,
+				leaf,
+				validators,
+				authorities,
+				validator_peers,
+				keystore,
+				candidate,
+				chunks,
+			}
+		}
+	}
+
+	async fn network_update(virtual_overseer: &mut VirtualOverseer, event: NetworkBridgeEvent) {
+		virtual_overseer.send(FromOverseer::Communication {
+			msg: AvailabilityDistributionMessage::NetworkBridgeUpdate(event),
+		}).await;
+	}
+
+	async fn peer_message(virtual_overseer: &mut VirtualOverseer, peer: &PeerId, message: WireMessage) {
+		network_update(virtual_overseer, NetworkBridgeEvent::PeerMessage(peer.clone(), message.encode())).await;
+	}
+
+	async fn set_view(virtual_overseer: &mut VirtualOverseer, peer: &PeerId, head: Hash) {
+		network_update(virtual_overseer, NetworkBridgeEvent::PeerViewChange(peer.clone(), View(vec![head]))).await;
+	}
+
+	/// Activate the leaf, answering the requests for its data. The candidate is pending
+	/// availability at the leaf if `pending` is set.
+	async fn activate_leaf(
+		virtual_overseer: &mut VirtualOverseer,
+		test_state: &TestState,
+		update: ActiveLeavesUpdate,
+		pending: bool,
+	) {
+		let leaf = update.activated[0];
+		virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(update))).await;
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::Validators(tx),
+			)) if r == leaf => {
+				tx.send(Ok(test_state.validators.clone())).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::AuthorityDiscoveryKeys(tx),
+			)) if r == leaf => {
+				tx.send(Ok(test_state.authorities.clone())).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::AvailabilityCores(tx),
+			)) if r == leaf => {
+				let core = if pending {
+					CoreState::Occupied(OccupiedCore {
+						para_id: Default::default(),
+						group_responsible: Default::default(),
+						occupied_since: 1,
+						availability: Default::default(),
+						candidate: test_state.candidate.clone(),
+					})
+				} else {
+					CoreState::Free
+				};
+
+				tx.send(Ok(vec![core])).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::ResolveValidators(authorities, tx)) => {
+				assert_eq!(authorities, test_state.authorities);
+				tx.send(test_state.validator_peers.clone()).unwrap();
+			}
+		);
+	}
+
+	/// Start work on the relay-parent of the candidate and connect the peers of the other
+	/// validators, interested in it.
+	async fn setup(virtual_overseer: &mut VirtualOverseer, test_state: &TestState) {
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::RegisterEventProducer(p, _))
+				if p == PROTOCOL_V1
+		);
+
+		activate_leaf(
+			virtual_overseer,
+			test_state,
+			ActiveLeavesUpdate::start_work(test_state.relay_parent),
+			false,
+		).await;
+
+		for peer in test_state.validator_peers.iter().flatten() {
+			network_update(virtual_overseer, NetworkBridgeEvent::PeerConnected(peer.clone(), ObservedRole::Authority)).await;
+			set_view(virtual_overseer, peer, test_state.relay_parent).await;
+		}
+	}
+
+	/// Move on to the leaf the candidate is pending availability at.
+	async fn back_candidate(virtual_overseer: &mut VirtualOverseer, test_state: &TestState) {
+		activate_leaf(
+			virtual_overseer,
+			test_state,
+			ActiveLeavesUpdate {
+				activated: vec![test_state.leaf],
+				deactivated: vec![test_state.relay_parent],
+			},
+			true,
+		).await;
+	}
+
+	async fn expect_send(virtual_overseer: &mut VirtualOverseer, expected_peers: Vec<PeerId>, message: WireMessage) {
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::SendMessage(peers, p, m)) => {
+				assert_eq!(peers, expected_peers);
+				assert_eq!(p, PROTOCOL_V1);
+				assert_eq!(m, message.encode());
+			}
+		);
+	}
+
+	async fn expect_report(virtual_overseer: &mut VirtualOverseer, expected_peer: &PeerId, expected_rep: ReputationChange) {
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(peer, rep)) => {
+				assert_eq!(&peer, expected_peer);
+				assert_eq!(rep, expected_rep);
+			}
+		);
+	}
+
+	async fn expect_store(virtual_overseer: &mut VirtualOverseer, test_state: &TestState, index: u32) {
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::AvailabilityStore(AvailabilityStoreMessage::StoreChunk(h, i, c)) => {
+				assert_eq!(h, test_state.candidate.hash());
+				assert_eq!(i, index);
+				assert_eq!(c, test_state.chunks[index as usize]);
+			}
+		);
+	}
+
+	#[test]
+	fn chunks_are_sent_to_their_validators_once_pending_availability() {
+		let test_state = TestState::default();
+
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			setup(&mut virtual_overseer, &test_state).await;
+
+			// the candidate isn't backed yet, so the chunks are kept.
+			for chunk in test_state.chunks.iter() {
+				virtual_overseer.send(FromOverseer::Communication {
+					msg: AvailabilityDistributionMessage::DistributeChunk(test_state.candidate.clone(), chunk.clone()),
+				}).await;
+			}
+
+			back_candidate(&mut virtual_overseer, &test_state).await;
+
+			// each validator receives its own chunk once it is interested in the leaf.
+			set_view(&mut virtual_overseer, &test_state.peer(2), test_state.leaf).await;
+			expect_send(
+				&mut virtual_overseer,
+				vec![test_state.peer(2)],
+				WireMessage::Chunk(test_state.candidate.clone(), test_state.chunks[2].clone()),
+			).await;
+
+			set_view(&mut virtual_overseer, &test_state.peer(0), test_state.leaf).await;
+			expect_send(
+				&mut virtual_overseer,
+				vec![test_state.peer(0)],
+				WireMessage::Chunk(test_state.candidate.clone(), test_state.chunks[0].clone()),
+			).await;
+
+			// chunks aren't sent twice.
+			set_view(&mut virtual_overseer, &test_state.peer(2), test_state.leaf).await;
+			set_view(&mut virtual_overseer, &test_state.peer(0), test_state.relay_parent).await;
+			set_view(&mut virtual_overseer, &test_state.peer(0), test_state.leaf).await;
+
+			peer_message(
+				&mut virtual_overseer,
+				&test_state.peer(0),
+				WireMessage::Chunk(test_state.candidate.clone(), test_state.chunks[0].clone()),
+			).await;
+			expect_report(&mut virtual_overseer, &test_state.peer(0), COST_UNEXPECTED_CHUNK).await;
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn own_chunk_of_pending_candidate_is_stored() {
+		let test_state = TestState::default();
+
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			setup(&mut virtual_overseer, &test_state).await;
+			back_candidate(&mut virtual_overseer, &test_state).await;
+
+			let peer_a = test_state.peer(0);
+			let peer_c = test_state.peer(2);
+
+			// Bob's chunk.
+			let message = WireMessage::Chunk(test_state.candidate.clone(), test_state.chunks[1].clone());
+			peer_message(&mut virtual_overseer, &peer_a, message.clone()).await;
+
+			expect_report(&mut virtual_overseer, &peer_a, BENEFIT_VALID_CHUNK_FIRST).await;
+			expect_store(&mut virtual_overseer, &test_state, 1).await;
+
+			// the same chunk from another holder is fine, but not from the same peer again.
+			peer_message(&mut virtual_overseer, &peer_c, message.clone()).await;
+			peer_message(&mut virtual_overseer, &peer_a, message).await;
+			expect_report(&mut virtual_overseer, &peer_a, COST_PEER_DUPLICATE_MESSAGE).await;
+
+			// chunks of other validators are not accepted.
+			peer_message(
+				&mut virtual_overseer,
+				&peer_a,
+				WireMessage::Chunk(test_state.candidate.clone(), test_state.chunks[2].clone()),
+			).await;
+			expect_report(&mut virtual_overseer, &peer_a, COST_UNEXPECTED_CHUNK).await;
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn chunks_of_candidates_not_pending_availability_are_rejected() {
+		let test_state = TestState::default();
+
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			setup(&mut virtual_overseer, &test_state).await;
+			back_candidate(&mut virtual_overseer, &test_state).await;
+
+			let peer_a = test_state.peer(0);
+			let peer_c = test_state.peer(2);
+
+			let mut chunk = test_state.chunks[1].clone();
+			chunk.chunk.push(0);
+			peer_message(&mut virtual_overseer, &peer_a, WireMessage::Chunk(test_state.candidate.clone(), chunk)).await;
+
+			expect_report(&mut virtual_overseer, &peer_a, COST_INVALID_CHUNK).await;
+
+			// a receipt committing to the erasure root of any chunks isn't enough.
+			let mut candidate = test_state.candidate.clone();
+			candidate.commitments.fees = 1;
+			peer_message(
+				&mut virtual_overseer,
+				&peer_c,
+				WireMessage::Chunk(candidate, test_state.chunks[1].clone()),
+			).await;
+
+			expect_report(&mut virtual_overseer, &peer_c, COST_NOT_LIVE).await;
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn missing_chunk_is_requested_from_its_validator() {
+		let test_state = TestState::default();
+
+		test_harness(test_state.keystore.clone(), |test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			setup(&mut virtual_overseer, &test_state).await;
+			back_candidate(&mut virtual_overseer, &test_state).await;
+
+			let peer_a = test_state.peer(0);
+			let peer_c = test_state.peer(2);
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: AvailabilityDistributionMessage::FetchChunk(test_state.candidate.clone(), 2),
+			}).await;
+
+			expect_send(
+				&mut virtual_overseer,
+				vec![peer_c.clone()],
+				WireMessage::RequestChunk(test_state.candidate.clone(), 2),
+			).await;
+
+			let chunk = test_state.chunks[2].clone();
+			peer_message(&mut virtual_overseer, &peer_c, WireMessage::Chunk(test_state.candidate.clone(), chunk)).await;
+
+			expect_report(&mut virtual_overseer, &peer_c, BENEFIT_VALID_CHUNK_FIRST).await;
+			expect_store(&mut virtual_overseer, &test_state, 2).await;
+
+			// requests for chunks we don't keep in memory are answered from the store.
+			peer_message(&mut virtual_overseer, &peer_a, WireMessage::RequestChunk(test_state.candidate.clone(), 0)).await;
+
+			let chunk = test_state.chunks[0].clone();
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::AvailabilityStore(AvailabilityStoreMessage::QueryChunk(h, index, tx)) => {
+					assert_eq!(h, test_state.candidate.hash());
+					assert_eq!(index, 0);
+					tx.send(Some(chunk.clone())).unwrap();
+				}
+			);
+
+			expect_send(
+				&mut virtual_overseer,
+				vec![peer_a.clone()],
+				WireMessage::Chunk(test_state.candidate.clone(), chunk),
+			).await;
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+}
@@ -0,0 +1,683 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The Bitfield Distribution subsystem.
+//!
+//! Gossips signed availability bitfields among validators. Bitfields are only accepted for
+//! relay-parents in our view and only sent to peers whose view contains the relay-parent.
+//! At most one bitfield per validator per relay-parent is accepted and relayed, and all
+//! accepted bitfields are passed on to the Provisioner.
+
+use parity_scale_codec::{Encode, Decode};
+use futures::channel::oneshot;
+use futures::prelude::*;
+
+use sc_network::{PeerId, ReputationChange};
+
+use polkadot_subsystem::{
+	Subsystem, SubsystemContext, SpawnedSubsystem, SubsystemResult,
+	FromOverseer, OverseerSignal, ActiveLeavesUpdate,
+};
+use polkadot_subsystem::messages::{
+	AllMessages, BitfieldDistributionMessage, NetworkBridgeEvent, NetworkBridgeMessage,
+	ProvisionableData, ProvisionerMessage, RuntimeApiMessage, RuntimeApiRequest,
+};
+use node_primitives::{ProtocolId, View};
+use polkadot_primitives::Hash;
+use polkadot_primitives::parachain::{
+	SignedAvailabilityBitfield, SigningContext, ValidatorId, ValidatorIndex,
+};
+
+use std::collections::{HashMap, HashSet};
+
+/// The protocol identifier for bitfield distribution.
+pub const PROTOCOL_V1: ProtocolId = *b"bitd";
+
+const COST_SIGNATURE_INVALID: ReputationChange
+	= ReputationChange::new(-100, "Bitfield signature invalid");
+const COST_VALIDATOR_INDEX_INVALID: ReputationChange
+	= ReputationChange::new(-100, "Bitfield validator index invalid");
+const COST_NOT_IN_VIEW: ReputationChange
+	= ReputationChange::new(-100, "Bitfield relay-parent not in our view");
+const COST_PEER_DUPLICATE_MESSAGE: ReputationChange
+	= ReputationChange::new(-500, "Peer sent the same bitfield more than once");
+const COST_MESSAGE_NOT_DECODABLE: ReputationChange
+	= ReputationChange::new(-100, "Malformed bitfield message");
+const BENEFIT_VALID_MESSAGE_FIRST: ReputationChange
+	= ReputationChange::new(15, "Valid bitfield with new information");
+
+/// Messages sent over the network by this subsystem.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub enum WireMessage {
+	/// A signed availability bitfield for the given relay-parent.
+	#[codec(index = "0")]
+	Bitfield(Hash, SignedAvailabilityBitfield),
+}
+
+/// The bitfield distribution subsystem.
+#[derive(Clone)]
+pub struct BitfieldDistribution;
+
+impl<C> Subsystem<C> for BitfieldDistribution
+	where C: SubsystemContext<Message = BitfieldDistributionMessage>
+{
+	fn start(self, ctx: C) -> SpawnedSubsystem {
+		SpawnedSubsystem(run(ctx).map(|_| ()).boxed())
+	}
+}
+
+/// Data kept for each relay-parent we are working on.
+struct PerRelayParentData {
+	signing_context: SigningContext,
+	validators: Vec<ValidatorId>,
+	/// The accepted bitfields, at most one per validator.
+	one_per_validator: HashMap<ValidatorIndex, SignedAvailabilityBitfield>,
+	/// The validators whose bitfields each peer is known to have.
+	peer_knowledge: HashMap<PeerId, PeerKnowledge>,
+}
+
+/// The validators whose bitfields a peer knows, kept apart by direction: a peer may
+/// legitimately send us a bitfield we have just sent it, but never the same one twice.
+#[derive(Default)]
+struct PeerKnowledge {
+	/// The bitfields we sent to the peer.
+	sent: HashSet<ValidatorIndex>,
+	/// The bitfields the peer sent to us.
+	received: HashSet<ValidatorIndex>,
+}
+
+impl PeerKnowledge {
+	fn knows(&self, validator_index: &ValidatorIndex) -> bool {
+		self.sent.contains(validator_index) || self.received.contains(validator_index)
+	}
+
+	/// Note that we send the bitfield of the validator to the peer, returning whether
+	/// the peer didn't know it yet.
+	fn send(&mut self, validator_index: ValidatorIndex) -> bool {
+		if self.knows(&validator_index) {
+			return false;
+		}
+
+		self.sent.insert(validator_index)
+	}
+}
+
+#[derive(Default)]
+struct State {
+	per_relay_parent: HashMap<Hash, PerRelayParentData>,
+	peer_views: HashMap<PeerId, View>,
+}
+
+fn network_update_message(n: NetworkBridgeEvent) -> AllMessages {
+	AllMessages::BitfieldDistribution(BitfieldDistributionMessage::NetworkBridgeUpdate(n))
+}
+
+async fn run(mut ctx: impl SubsystemContext<Message = BitfieldDistributionMessage>) -> SubsystemResult<()> {
+	ctx.send_message(AllMessages::NetworkBridge(
+		NetworkBridgeMessage::RegisterEventProducer(PROTOCOL_V1, network_update_message),
+	)).await?;
+
+	let mut state = State::default();
+
+	loop {
+		match ctx.recv().await? {
+			FromOverseer::Signal(OverseerSignal::ActiveLeaves(ActiveLeavesUpdate { activated, deactivated })) => {
+				for relay_parent in activated {
+					let (validators, signing_context) = match query_basics(&mut ctx, relay_parent).await? {
+						Some(basics) => basics,
+						None => continue,
+					};
+
+					state.per_relay_parent.insert(relay_parent, PerRelayParentData {
+						signing_context,
+						validators,
+						one_per_validator: HashMap::new(),
+						peer_knowledge: HashMap::new(),
+					});
+				}
+
+				for relay_parent in deactivated {
+					state.per_relay_parent.remove(&relay_parent);
+				}
+			}
+			FromOverseer::Signal(OverseerSignal::BlockFinalized(..)) => {}
+			FromOverseer::Signal(OverseerSignal::Conclude) => return Ok(()),
+			FromOverseer::Communication { msg } => match msg {
+				BitfieldDistributionMessage::DistributeBitfield(relay_parent, bitfield) => {
+					handle_our_bitfield(&mut ctx, &mut state, relay_parent, bitfield).await?;
+				}
+				BitfieldDistributionMessage::NetworkBridgeUpdate(event) => {
+					handle_network_update(&mut ctx, &mut state, event).await?;
+				}
+			},
+		}
+	}
+}
+
+async fn query_basics(
+	ctx: &mut impl SubsystemContext<Message = BitfieldDistributionMessage>,
+	relay_parent: Hash,
+) -> SubsystemResult<Option<(Vec<ValidatorId>, SigningContext)>> {
+	let (validators_tx, validators_rx) = oneshot::channel();
+	let (signing_context_tx, signing_context_rx) = oneshot::channel();
+
+	ctx.send_messages(vec![
+		AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+			relay_parent,
+			RuntimeApiRequest::Validators(validators_tx),
+		)),
+		AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+			relay_parent,
+			RuntimeApiRequest::SigningContext(signing_context_tx),
+		)),
+	].into_iter()).await?;
+
+	match (validators_rx.await?, signing_context_rx.await?) {
+		(Ok(validators), Ok(signing_context)) => Ok(Some((validators, signing_context))),
+		(Err(e), _) | (_, Err(e)) => {
+			log::warn!(
+				target: "bitfield_distribution",
+				"Failed to query the runtime API at relay-parent {}: {:?}",
+				relay_parent,
+				e,
+			);
+
+			Ok(None)
+		}
+	}
+}
+
+async fn handle_our_bitfield(
+	ctx: &mut impl SubsystemContext<Message = BitfieldDistributionMessage>,
+	state: &mut State,
+	relay_parent: Hash,
+	bitfield: SignedAvailabilityBitfield,
+) -> SubsystemResult<()> {
+	let job_data = match state.per_relay_parent.get_mut(&relay_parent) {
+		Some(job_data) => job_data,
+		None => {
+			log::debug!(
+				target: "bitfield_distribution",
+				"Not distributing our bitfield for relay-parent {} not in our view",
+				relay_parent,
+			);
+
+			return Ok(());
+		}
+	};
+
+	job_data.one_per_validator.insert(bitfield.validator_index(), bitfield.clone());
+
+	send_to_provisioner(ctx, relay_parent, bitfield.clone()).await?;
+	relay_bitfield(ctx, job_data, &state.peer_views, relay_parent, bitfield).await
+}
+
+async fn handle_network_update(
+	ctx: &mut impl SubsystemContext<Message = BitfieldDistributionMessage>,
+	state: &mut State,
+	event: NetworkBridgeEvent,
+) -> SubsystemResult<()> {
+	match event {
+		NetworkBridgeEvent::PeerConnected(peer, _role) => {
+			state.peer_views.insert(peer, View(Vec::new()));
+		}
+		NetworkBridgeEvent::PeerDisconnected(peer) => {
+			state.peer_views.remove(&peer);
+			for job_data in state.per_relay_parent.values_mut() {
+				job_data.peer_knowledge.remove(&peer);
+			}
+		}
+		NetworkBridgeEvent::PeerViewChange(peer, view) => {
+			handle_peer_view_change(ctx, state, peer, view).await?;
+		}
+		NetworkBridgeEvent::OurViewChange(_) => {
+			// the relay-parents we work on are determined by `ActiveLeaves` signals.
+		}
+		NetworkBridgeEvent::PeerMessage(peer, bytes) => {
+			match WireMessage::decode(&mut &bytes[..]) {
+				Ok(WireMessage::Bitfield(relay_parent, bitfield)) => {
+					handle_incoming_bitfield(ctx, state, peer, relay_parent, bitfield).await?;
+				}
+				Err(_) => report_peer(ctx, peer, COST_MESSAGE_NOT_DECODABLE).await?,
+			}
+		}
+	}
+
+	Ok(())
+}
+
+async fn handle_peer_view_change(
+	ctx: &mut impl SubsystemContext<Message = BitfieldDistributionMessage>,
+	state: &mut State,
+	peer: PeerId,
+	view: View,
+) -> SubsystemResult<()> {
+	let old_view = match state.peer_views.insert(peer.clone(), view.clone()) {
+		Some(old_view) => old_view,
+		None => {
+			log::trace!(target: "bitfield_distribution", "View change from unknown peer {}", peer);
+			View(Vec::new())
+		}
+	};
+
+	// forget what the peer knows about relay-parents it is no longer interested in, so
+	// that the bitfields are sent again should they come back into its view.
+	for removed in old_view.0.iter().filter(|h| !view.0.contains(h)) {
+		if let Some(job_data) = state.per_relay_parent.get_mut(removed) {
+			job_data.peer_knowledge.remove(&peer);
+		}
+	}
+
+	for added in view.0.iter().filter(|h| !old_view.0.contains(h)) {
+		let job_data = match state.per_relay_parent.get_mut(added) {
+			Some(job_data) => job_data,
+			None => continue,
+		};
+
+		let knowledge = job_data.peer_knowledge.entry(peer.clone()).or_default();
+		let messages: Vec<_> = job_data.one_per_validator.iter()
+			.filter(|(validator_index, _)| knowledge.send(**validator_index))
+			.map(|(_, bitfield)| WireMessage::Bitfield(*added, bitfield.clone()).encode())
+			.collect();
+
+		for message in messages {
+			ctx.send_message(AllMessages::NetworkBridge(
+				NetworkBridgeMessage::SendMessage(vec![peer.clone()], PROTOCOL_V1, message),
+			)).await?;
+		}
+	}
+
+	Ok(())
+}
+
+async fn handle_incoming_bitfield(
+	ctx: &mut impl SubsystemContext<Message = BitfieldDistributionMessage>,
+	state: &mut State,
+	peer: PeerId,
+	relay_parent: Hash,
+	bitfield: SignedAvailabilityBitfield,
+) -> SubsystemResult<()> {
+	let job_data = match state.per_relay_parent.get_mut(&relay_parent) {
+		Some(job_data) => job_data,
+		None => return report_peer(ctx, peer, COST_NOT_IN_VIEW).await,
+	};
+
+	let validator_index = bitfield.validator_index();
+	let validator = match job_data.validators.get(validator_index as usize) {
+		Some(validator) => validator,
+		None => return report_peer(ctx, peer, COST_VALIDATOR_INDEX_INVALID).await,
+	};
+
+	if bitfield.check_signature(&job_data.signing_context, validator).is_err() {
+		return report_peer(ctx, peer, COST_SIGNATURE_INVALID).await;
+	}
+
+	if !job_data.peer_knowledge.entry(peer.clone()).or_default().received.insert(validator_index) {
+		return report_peer(ctx, peer, COST_PEER_DUPLICATE_MESSAGE).await;
+	}
+
+	// we already have a bitfield by this validator, which was relayed already.
+	if job_data.one_per_validator.contains_key(&validator_index) {
+		return Ok(());
+	}
+
+	job_data.one_per_validator.insert(validator_index, bitfield.clone());
+
+	report_peer(ctx, peer, BENEFIT_VALID_MESSAGE_FIRST).await?;
+	send_to_provisioner(ctx, relay_parent, bitfield.clone()).await?;
+	relay_bitfield(ctx, job_data, &state.peer_views, relay_parent, bitfield).await
+}
+
+/// Send a bitfield to all peers interested in the relay-parent which don't know it yet.
+async fn relay_bitfield(
+	ctx: &mut impl SubsystemContext<Message = BitfieldDistributionMessage>,
+	job_data: &mut PerRelayParentData,
+	peer_views: &HashMap<PeerId, View>,
+	relay_parent: Hash,
+	bitfield: SignedAvailabilityBitfield,
+) -> SubsystemResult<()> {
+	let validator_index = bitfield.validator_index();
+
+	let interested_peers: Vec<PeerId> = peer_views.iter()
+		.filter(|(_, view)| view.0.contains(&relay_parent))
+		.filter_map(|(peer, _)| {
+			let knowledge = job_data.peer_knowledge.entry(peer.clone()).or_default();
+			if knowledge.send(validator_index) {
+				Some(peer.clone())
+			} else {
+				None
+			}
+		})
+		.collect();
+
+	if interested_peers.is_empty() {
+		return Ok(());
+	}
+
+	ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::SendMessage(
+		interested_peers,
+		PROTOCOL_V1,
+		WireMessage::Bitfield(relay_parent, bitfield).encode(),
+	))).await
+}
+
+async fn send_to_provisioner(
+	ctx: &mut impl SubsystemContext<Message = BitfieldDistributionMessage>,
+	relay_parent: Hash,
+	bitfield: SignedAvailabilityBitfield,
+) -> SubsystemResult<()> {
+	ctx.send_message(AllMessages::Provisioner(ProvisionerMessage::ProvisionableData(
+		ProvisionableData::Bitfield(relay_parent, bitfield),
+	))).await
+}
+
+async fn report_peer(
+	ctx: &mut impl SubsystemContext<Message = BitfieldDistributionMessage>,
+	peer: PeerId,
+	rep: ReputationChange,
+) -> SubsystemResult<()> {
+	ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(peer, rep))).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::{self, ThreadPool};
+	use assert_matches::assert_matches;
+	use sp_keyring::sr25519::Keyring;
+	use sc_network::ObservedRole;
+
+	use polkadot_primitives::parachain::{AvailabilityBitfield, ValidatorPair};
+
+	type VirtualOverseer = subsystem_test::TestSubsystemContextHandle<BitfieldDistributionMessage>;
+
+	struct TestHarness {
+		virtual_overseer: VirtualOverseer,
+	}
+
+	fn test_harness<T: Future<Output=()>>(test: impl FnOnce(TestHarness) -> T) {
+		let pool = ThreadPool::new().unwrap();
+
+		let (context, virtual_overseer) = subsystem_test::make_subsystem_context(pool);
+
+		let subsystem = run(context)
+			.map_err(|_| panic!("subsystem execution failed"))
+			.map(|_| ());
+
+		let test_fut = test(TestHarness { virtual_overseer });
+
+		futures::pin_mut!(test_fut);
+		futures::pin_mut!(subsystem);
+
+		executor::block_on(future::select(test_fut, subsystem));
+	}
+
+	fn validators() -> Vec<ValidatorId> {
+		vec![
+			ValidatorId::from(Keyring::Alice.public()),
+			ValidatorId::from(Keyring::Bob.public()),
+		]
+	}
+
+	fn signed_bitfield(keyring: Keyring, validator_index: ValidatorIndex) -> SignedAvailabilityBitfield {
+		let pair = sc_keystore::Store::new_in_memory()
+			.write()
+			.insert_ephemeral_from_seed::<ValidatorPair>(&keyring.to_seed())
+			.expect("Insert key into keystore");
+
+		SignedAvailabilityBitfield::sign(
+			AvailabilityBitfield(bitvec::bitvec![bitvec::order::Lsb0, u8; 1, 0]),
+			&SigningContext::default(),
+			validator_index,
+			&pair,
+		)
+	}
+
+	async fn network_update(virtual_overseer: &mut VirtualOverseer, event: NetworkBridgeEvent) {
+		virtual_overseer.send(FromOverseer::Communication {
+			msg: BitfieldDistributionMessage::NetworkBridgeUpdate(event),
+		}).await;
+	}
+
+	async fn setup(virtual_overseer: &mut VirtualOverseer, relay_parent: Hash) {
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::RegisterEventProducer(p, _))
+				if p == PROTOCOL_V1
+		);
+
+		virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+			ActiveLeavesUpdate::start_work(relay_parent),
+		))).await;
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::Validators(tx),
+			)) if r == relay_parent => {
+				tx.send(Ok(validators())).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::SigningContext(tx),
+			)) if r == relay_parent => {
+				tx.send(Ok(SigningContext::default())).unwrap();
+			}
+		);
+	}
+
+	#[test]
+	fn our_bitfield_is_sent_to_interested_peers() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let peer_a = PeerId::random();
+			let peer_b = PeerId::random();
+
+			setup(&mut virtual_overseer, relay_parent).await;
+
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerConnected(peer_a.clone(), ObservedRole::Full)).await;
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerConnected(peer_b.clone(), ObservedRole::Full)).await;
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerViewChange(peer_a.clone(), View(vec![relay_parent]))).await;
+
+			let bitfield = signed_bitfield(Keyring::Alice, 0);
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: BitfieldDistributionMessage::DistributeBitfield(relay_parent, bitfield.clone()),
+			}).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::Provisioner(ProvisionerMessage::ProvisionableData(
+					ProvisionableData::Bitfield(r, b),
+				)) if r == relay_parent && b == bitfield
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::SendMessage(peers, p, message)) => {
+					assert_eq!(peers, vec![peer_a.clone()]);
+					assert_eq!(p, PROTOCOL_V1);
+					assert_eq!(message, WireMessage::Bitfield(relay_parent, bitfield.clone()).encode());
+				}
+			);
+
+			// once the relay-parent enters the view of peer B, it gets the bitfield as well.
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerViewChange(peer_b.clone(), View(vec![relay_parent]))).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::SendMessage(peers, _, message)) => {
+					assert_eq!(peers, vec![peer_b.clone()]);
+					assert_eq!(message, WireMessage::Bitfield(relay_parent, bitfield).encode());
+				}
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn incoming_bitfield_is_accepted_once() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let peer_a = PeerId::random();
+			let peer_b = PeerId::random();
+
+			setup(&mut virtual_overseer, relay_parent).await;
+
+			for peer in vec![peer_a.clone(), peer_b.clone()] {
+				network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerConnected(peer.clone(), ObservedRole::Full)).await;
+				network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerViewChange(peer, View(vec![relay_parent]))).await;
+			}
+
+			let bitfield = signed_bitfield(Keyring::Bob, 1);
+			let message = WireMessage::Bitfield(relay_parent, bitfield.clone()).encode();
+
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer_a.clone(), message.clone())).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(peer, rep))
+					if peer == peer_a && rep == BENEFIT_VALID_MESSAGE_FIRST
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::Provisioner(ProvisionerMessage::ProvisionableData(
+					ProvisionableData::Bitfield(r, b),
+				)) if r == relay_parent && b == bitfield
+			);
+
+			// relayed only to the peer which doesn't have it yet.
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::SendMessage(peers, _, m)) => {
+					assert_eq!(peers, vec![peer_b.clone()]);
+					assert_eq!(m, message);
+				}
+			);
+
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer_a.clone(), message)).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(peer, rep))
+					if peer == peer_a && rep == COST_PEER_DUPLICATE_MESSAGE
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn bitfield_we_sent_may_be_sent_back_once() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let peer = PeerId::random();
+
+			setup(&mut virtual_overseer, relay_parent).await;
+
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerConnected(peer.clone(), ObservedRole::Full)).await;
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerViewChange(peer.clone(), View(vec![relay_parent]))).await;
+
+			let bitfield = signed_bitfield(Keyring::Alice, 0);
+			let message = WireMessage::Bitfield(relay_parent, bitfield.clone()).encode();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: BitfieldDistributionMessage::DistributeBitfield(relay_parent, bitfield),
+			}).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::Provisioner(ProvisionerMessage::ProvisionableData(..))
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::SendMessage(peers, _, m)) => {
+					assert_eq!(peers, vec![peer.clone()]);
+					assert_eq!(m, message);
+				}
+			);
+
+			// the peer may have sent the bitfield before receiving ours, which is not punished.
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer.clone(), message.clone())).await;
+
+			// but sending it again is.
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer.clone(), message)).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep))
+					if p == peer && rep == COST_PEER_DUPLICATE_MESSAGE
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn invalid_bitfields_are_punished() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let peer = PeerId::random();
+
+			setup(&mut virtual_overseer, relay_parent).await;
+
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerConnected(peer.clone(), ObservedRole::Full)).await;
+
+			// signed by Alice, but claiming to be from Bob.
+			let bad_signature = WireMessage::Bitfield(relay_parent, signed_bitfield(Keyring::Alice, 1)).encode();
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer.clone(), bad_signature)).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep))
+					if p == peer && rep == COST_SIGNATURE_INVALID
+			);
+
+			let unknown_relay_parent = WireMessage::Bitfield(Hash::from([2; 32]), signed_bitfield(Keyring::Alice, 0)).encode();
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer.clone(), unknown_relay_parent)).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep))
+					if p == peer && rep == COST_NOT_IN_VIEW
+			);
+
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer.clone(), vec![0xff, 0xff])).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep))
+					if p == peer && rep == COST_MESSAGE_NOT_DECODABLE
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+}
@@ -18,18 +18,22 @@
 
 use parity_scale_codec::{Encode, Decode};
 use futures::prelude::*;
-use futures::future::BoxFuture;
+use futures::channel::oneshot;
+use futures::future::{BoxFuture, Fuse, FusedFuture};
 use futures::stream::BoxStream;
 
 use sc_network::{
-	ObservedRole, ReputationChange, PeerId,
+	ObservedRole, ReputationChange, PeerId, Multiaddr,
 	Event as NetworkEvent,
 };
+use sc_network::multiaddr::Protocol;
 use sp_runtime::ConsensusEngineId;
+use sp_authority_discovery::AuthorityId as AuthorityDiscoveryId;
+use futures_timer::Delay;
 
 use polkadot_subsystem::{
 	FromOverseer, OverseerSignal, Subsystem, SubsystemContext, SpawnedSubsystem, SubsystemError,
-	SubsystemResult,
+	SubsystemResult, ActiveLeavesUpdate,
 };
 use polkadot_subsystem::messages::{NetworkBridgeEvent, NetworkBridgeMessage, AllMessages};
 use node_primitives::{ProtocolId, View};
@@ -37,18 +41,52 @@ use polkadot_primitives::{Block, Hash};
 
 use std::collections::btree_map::{BTreeMap, Entry as BEntry};
 use std::collections::hash_map::{HashMap, Entry as HEntry};
+use std::collections::{HashSet, VecDeque};
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+mod validator_discovery;
+
+pub use validator_discovery::DhtAuthorityDiscovery;
 
 /// The maximum amount of heads a peer is allowed to have in their view at any time.
 ///
 /// We use the same limit to compute the view sent to peers locally.
 const MAX_VIEW_HEADS: usize = 5;
 
-/// The engine ID of the polkadot network protocol.
-pub const POLKADOT_ENGINE_ID: ConsensusEngineId = *b"dot2";
-/// The protocol name.
-pub const POLKADOT_PROTOCOL_NAME: &[u8] = b"/polkadot/2";
+/// The number of recently activated heads we remember in order to judge the views of peers.
+const RECENT_HEADS: usize = 64;
+
+/// How long we wait for further leaf changes before sending our view to peers, so that blocks
+/// imported back to back result in a single view update.
+const VIEW_UPDATE_DELAY: Duration = Duration::from_millis(200);
+
+/// The window over which view updates received from a peer are rate-limited.
+const VIEW_UPDATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// The maximum number of view updates accepted from a peer within a `VIEW_UPDATE_WINDOW`.
+const MAX_VIEW_UPDATES_PER_WINDOW: usize = 10;
+
+/// The number of consecutive views without any head known to us after which a peer is
+/// penalized for each further such view.
+const MAX_UNKNOWN_VIEWS: usize = 3;
+
+/// The engine ID of the validation notifications protocol.
+pub const VALIDATION_ENGINE_ID: ConsensusEngineId = *b"dotv";
+/// The name of the validation notifications protocol.
+pub const VALIDATION_PROTOCOL_NAME: &[u8] = b"/polkadot/validation/1";
+/// The engine ID of the collation notifications protocol.
+pub const COLLATION_ENGINE_ID: ConsensusEngineId = *b"dotc";
+/// The name of the collation notifications protocol.
+pub const COLLATION_PROTOCOL_NAME: &[u8] = b"/polkadot/collation/1";
+
+/// The version of the wire protocol spoken by a peer.
+pub type ProtocolVersion = u32;
+/// The current version of the wire protocol.
+pub const VERSION: ProtocolVersion = 1;
+/// The minimum version of the wire protocol we accept peers on.
+pub const MIN_SUPPORTED_VERSION: ProtocolVersion = 1;
 
 const MALFORMED_MESSAGE_COST: ReputationChange
 	= ReputationChange::new(-500, "Malformed Network-bridge message");
@@ -56,10 +94,24 @@ const UNKNOWN_PROTO_COST: ReputationChange
 	= ReputationChange::new(-50, "Message sent to unknown protocol");
 const MALFORMED_VIEW_COST: ReputationChange
 	= ReputationChange::new(-500, "Malformed view");
+const VIEW_UPDATE_RATE_COST: ReputationChange
+	= ReputationChange::new(-100, "View updates sent too frequently");
+const UNKNOWN_VIEW_COST: ReputationChange
+	= ReputationChange::new(-50, "View with only unknown heads");
+const UNSUPPORTED_VERSION_COST: ReputationChange
+	= ReputationChange::new(-100, "Unsupported protocol version");
+const UNEXPECTED_STATUS_COST: ReputationChange
+	= ReputationChange::new(-100, "Unexpected status message");
+const NO_STATUS_COST: ReputationChange
+	= ReputationChange::new(-100, "Message sent before status");
 
 /// Messages received on the network.
 #[derive(Debug, Encode, Decode, Clone)]
 pub enum WireMessage {
+	/// The status of a peer, carrying the newest protocol version it speaks. This must be the
+	/// first message sent on a stream and must keep its encoding across all versions.
+	#[codec(index = "0")]
+	Status(ProtocolVersion),
 	/// A message from a peer on a specific protocol.
 	#[codec(index = "1")]
 	ProtocolMessage(ProtocolId, Vec<u8>),
@@ -68,10 +120,109 @@ pub enum WireMessage {
 	ViewUpdate(View),
 }
 
-/// Information about the notifications protocol. Should be used during network configuration
-/// or shortly after startup to register the protocol with the network service.
-pub fn notifications_protocol_info() -> (ConsensusEngineId, std::borrow::Cow<'static, [u8]>) {
-	(POLKADOT_ENGINE_ID, POLKADOT_PROTOCOL_NAME.into())
+impl WireMessage {
+	/// The oldest protocol version the message can be exchanged on.
+	///
+	/// Messages are never sent to peers on older versions, and received from them only at
+	/// the cost of their reputation.
+	fn min_version(&self) -> ProtocolVersion {
+		match self {
+			WireMessage::Status(_) => 1,
+			WireMessage::ProtocolMessage(..) => 1,
+			WireMessage::ViewUpdate(_) => 1,
+		}
+	}
+}
+
+/// Information about the notifications protocols. Should be used during network configuration
+/// or shortly after startup to register the protocols with the network service.
+pub fn notifications_protocol_info() -> Vec<(ConsensusEngineId, std::borrow::Cow<'static, [u8]>)> {
+	vec![
+		(VALIDATION_ENGINE_ID, VALIDATION_PROTOCOL_NAME.into()),
+		(COLLATION_ENGINE_ID, COLLATION_PROTOCOL_NAME.into()),
+	]
+}
+
+/// A set of peers the network bridge manages connections to separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PeerSet {
+	/// Validators, which we need to be connected to for backing, availability and approval.
+	Validation,
+	/// Collators and all other full nodes.
+	Collation,
+}
+
+impl PeerSet {
+	/// The peer set a peer with the given role belongs to, unless reserved for another one.
+	fn of_role(role: &ObservedRole) -> Self {
+		match role {
+			ObservedRole::Authority => PeerSet::Validation,
+			_ => PeerSet::Collation,
+		}
+	}
+
+	/// The peer set a peer belongs to. Reserved peers always belong to the validation peer set.
+	fn of_peer(peer: &PeerId, role: &ObservedRole, reserved_peers: &HashSet<PeerId>) -> Self {
+		if reserved_peers.contains(peer) {
+			PeerSet::Validation
+		} else {
+			PeerSet::of_role(role)
+		}
+	}
+
+	/// The peer set whose notifications protocol has the given engine ID, if any.
+	fn from_engine_id(engine_id: ConsensusEngineId) -> Option<Self> {
+		match engine_id {
+			VALIDATION_ENGINE_ID => Some(PeerSet::Validation),
+			COLLATION_ENGINE_ID => Some(PeerSet::Collation),
+			_ => None,
+		}
+	}
+
+	/// The engine ID of the notifications protocol of the peer set.
+	pub fn engine_id(&self) -> ConsensusEngineId {
+		match self {
+			PeerSet::Validation => VALIDATION_ENGINE_ID,
+			PeerSet::Collation => COLLATION_ENGINE_ID,
+		}
+	}
+
+	/// The name of the priority group under which reserved peers of the set are kept.
+	fn priority_group(&self) -> &'static str {
+		match self {
+			PeerSet::Validation => "polkadot-validation",
+			PeerSet::Collation => "polkadot-collation",
+		}
+	}
+}
+
+/// The slot limits of the peer sets.
+///
+/// Reserved peers, such as the validators we were asked to connect to, don't occupy any slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerSetsConfig {
+	/// The maximum number of non-reserved peers in the validation peer set.
+	pub validation_slots: usize,
+	/// The maximum number of non-reserved peers in the collation peer set.
+	pub collation_slots: usize,
+}
+
+impl PeerSetsConfig {
+	fn slots(&self, peer_set: PeerSet) -> usize {
+		match peer_set {
+			PeerSet::Validation => self.validation_slots,
+			PeerSet::Collation => self.collation_slots,
+		}
+	}
+}
+
+impl Default for PeerSetsConfig {
+	fn default() -> Self {
+		PeerSetsConfig {
+			validation_slots: 25,
+			collation_slots: 25,
+		}
+	}
 }
 
 /// An action to be carried out by the network.
@@ -79,15 +230,19 @@ pub fn notifications_protocol_info() -> (ConsensusEngineId, std::borrow::Cow<'st
 pub enum NetworkAction {
 	/// Note a change in reputation for a peer.
 	ReputationChange(PeerId, ReputationChange),
-	/// Write a notification to a given peer.
-	WriteNotification(PeerId, Vec<u8>),
+	/// Write a notification to a given peer on the protocol of a peer set.
+	WriteNotification(PeerSet, PeerId, Vec<u8>),
+	/// Disconnect a peer.
+	DisconnectPeer(PeerId),
+	/// Replace the reserved peers of a peer set with the given addresses.
+	SetReservedPeers(PeerSet, HashSet<Multiaddr>),
 }
 
 /// An abstraction over networking for the purposes of this subsystem.
 pub trait Network: Send + 'static {
 	/// Get a stream of all events occurring on the network. This may include events unrelated
-	/// to the Polkadot protocol - the user of this function should filter only for events related
-	/// to the [`POLKADOT_ENGINE_ID`](POLKADOT_ENGINE_ID).
+	/// to the Polkadot protocols - the user of this function should filter only for events related
+	/// to the engine IDs of the peer sets.
 	fn event_stream(&mut self) -> BoxStream<'static, NetworkEvent>;
 
 	/// Get access to an underlying sink for all network actions.
@@ -104,16 +259,31 @@ pub trait Network: Send + 'static {
 		}.boxed()
 	}
 
-	/// Write a notification to a peer on the [`POLKADOT_ENGINE_ID`](POLKADOT_ENGINE_ID) topic.
-	fn write_notification(&mut self, who: PeerId, message: Vec<u8>)
+	/// Write a notification to a peer on the protocol of the given peer set.
+	fn write_notification(&mut self, peer_set: PeerSet, who: PeerId, message: Vec<u8>)
 		-> BoxFuture<SubsystemResult<()>>
 	{
 		async move {
-			self.action_sink().send(NetworkAction::WriteNotification(who, message)).await
+			self.action_sink().send(NetworkAction::WriteNotification(peer_set, who, message)).await
+		}.boxed()
+	}
+
+	/// Disconnect the given peer.
+	fn disconnect_peer(&mut self, who: PeerId) -> BoxFuture<SubsystemResult<()>> {
+		async move {
+			self.action_sink().send(NetworkAction::DisconnectPeer(who)).await
 		}.boxed()
 	}
 }
 
+/// An abstraction over the authority discovery service, which resolves validators to the
+/// addresses they can be reached at.
+pub trait AuthorityDiscovery: Send + 'static {
+	/// Get the addresses the given authority is known to be reachable at, if any.
+	fn get_addresses_by_authority_id(&mut self, authority: AuthorityDiscoveryId)
+		-> BoxFuture<Option<Vec<Multiaddr>>>;
+}
+
 impl Network for Arc<sc_network::NetworkService<Block, Hash>> {
 	fn event_stream(&mut self) -> BoxStream<'static, NetworkEvent> {
 		sc_network::NetworkService::event_stream(self, "polkadot-network-bridge").boxed()
@@ -140,11 +310,20 @@ impl Network for Arc<sc_network::NetworkService<Block, Hash>> {
 						peer,
 						cost_benefit,
 					),
-					NetworkAction::WriteNotification(peer, message) => self.0.write_notification(
+					NetworkAction::WriteNotification(peer_set, peer, message) => self.0.write_notification(
 						peer,
-						POLKADOT_ENGINE_ID,
+						peer_set.engine_id(),
 						message,
 					),
+					NetworkAction::DisconnectPeer(peer) => self.0.disconnect_peer(peer),
+					NetworkAction::SetReservedPeers(peer_set, addresses) => {
+						if let Err(e) = self.0.set_priority_group(
+							peer_set.priority_group().to_owned(),
+							addresses,
+						) {
+							log::warn!("Failed to set reserved peers of {:?}: {}", peer_set, e);
+						}
+					}
 				}
 
 				Ok(())
@@ -164,27 +343,42 @@ impl Network for Arc<sc_network::NetworkService<Block, Hash>> {
 }
 
 /// The network bridge subsystem.
-pub struct NetworkBridge<N>(N);
+#[derive(Clone)]
+pub struct NetworkBridge<N, AD> {
+	net_service: N,
+	authority_discovery: AD,
+	peer_sets: PeerSetsConfig,
+}
 
-impl<N> NetworkBridge<N> {
-	/// Create a new network bridge subsystem with underlying network service.
+impl<N, AD> NetworkBridge<N, AD> {
+	/// Create a new network bridge subsystem with underlying network service, the authority
+	/// discovery service used to resolve validators and the slot limits of the peer sets.
 	///
 	/// This assumes that the network service has had the notifications protocol for the network
 	/// bridge already registered. See [`notifications_protocol_info`](notifications_protocol_info).
-	pub fn new(net_service: N) -> Self {
-		NetworkBridge(net_service)
+	pub fn new(net_service: N, authority_discovery: AD, peer_sets: PeerSetsConfig) -> Self {
+		NetworkBridge {
+			net_service,
+			authority_discovery,
+			peer_sets,
+		}
 	}
 }
 
-impl<Net, Context> Subsystem<Context> for NetworkBridge<Net>
+impl<Net, AD, Context> Subsystem<Context> for NetworkBridge<Net, AD>
 	where
 		Net: Network,
+		AD: AuthorityDiscovery,
 		Context: SubsystemContext<Message=NetworkBridgeMessage>,
 {
 	fn start(self, ctx: Context) -> SpawnedSubsystem {
 		// Swallow error because failure is fatal to the node and we log with more precision
 		// within `run_network`.
-		SpawnedSubsystem(run_network(self.0, ctx).map(|_| ()).boxed())
+		SpawnedSubsystem(
+			run_network(self.net_service, self.authority_discovery, self.peer_sets, ctx)
+				.map(|_| ())
+				.boxed()
+		)
 	}
 }
 
@@ -193,6 +387,61 @@ struct PeerData {
 	view: View,
 	/// The role of the peer.
 	role: ObservedRole,
+	/// The peer set the peer was admitted to.
+	peer_set: PeerSet,
+	/// The protocol version negotiated with the peer, once it has sent its status.
+	version: Option<ProtocolVersion>,
+	/// The start of the current rate-limiting window of the peer's view updates.
+	view_window_start: Instant,
+	/// The number of view updates received from the peer within the current window.
+	view_updates_in_window: usize,
+	/// The number of consecutive views of the peer without any head known to us.
+	unknown_views: usize,
+}
+
+impl PeerData {
+	fn new(role: ObservedRole, peer_set: PeerSet) -> Self {
+		PeerData {
+			view: View(Vec::new()),
+			role,
+			peer_set,
+			version: None,
+			view_window_start: Instant::now(),
+			view_updates_in_window: 0,
+			unknown_views: 0,
+		}
+	}
+
+	/// Note a view update received at the given time. Returns `false` if the peer has exceeded
+	/// its rate limit, in which case the update should be dropped.
+	fn note_view_update(&mut self, now: Instant) -> bool {
+		if now.duration_since(self.view_window_start) >= VIEW_UPDATE_WINDOW {
+			self.view_window_start = now;
+			self.view_updates_in_window = 0;
+		}
+
+		self.view_updates_in_window += 1;
+		self.view_updates_in_window <= MAX_VIEW_UPDATES_PER_WINDOW
+	}
+
+	/// Note a view of the peer, judged by the heads recently activated by us. Returns `true` if
+	/// the peer has sent too many views in a row without any head we know of.
+	fn note_view_heads(&mut self, view: &View, recent_heads: &VecDeque<Hash>) -> bool {
+		if view.0.is_empty() || view.0.iter().any(|h| recent_heads.contains(h)) {
+			self.unknown_views = 0;
+			return false;
+		}
+
+		self.unknown_views += 1;
+		self.unknown_views > MAX_UNKNOWN_VIEWS
+	}
+}
+
+/// Whether a view is malformed, having too many or duplicate heads.
+fn is_malformed_view(view: &View) -> bool {
+	if view.0.len() > MAX_VIEW_HEADS { return true }
+
+	view.0.iter().enumerate().any(|(i, h)| view.0[..i].contains(h))
 }
 
 #[derive(Debug)]
@@ -200,35 +449,40 @@ enum Action {
 	RegisterEventProducer(ProtocolId, fn(NetworkBridgeEvent) -> AllMessages),
 	SendMessage(Vec<PeerId>, ProtocolId, Vec<u8>),
 	ReportPeer(PeerId, ReputationChange),
-	StartWork(Hash),
-	StopWork(Hash),
+	ConnectToValidators(Vec<AuthorityDiscoveryId>),
+	ResolveValidators(Vec<AuthorityDiscoveryId>, oneshot::Sender<Vec<Option<PeerId>>>),
+	ActiveLeaves(ActiveLeavesUpdate),
+	FlushView,
 
-	PeerConnected(PeerId, ObservedRole),
-	PeerDisconnected(PeerId),
-	PeerMessages(PeerId, Vec<WireMessage>),
+	PeerConnected(PeerSet, PeerId, ObservedRole),
+	PeerDisconnected(PeerSet, PeerId),
+	PeerMessages(PeerId, Vec<(PeerSet, WireMessage)>),
 
 	Abort,
 }
 
 fn action_from_overseer_message(
 	res: polkadot_subsystem::SubsystemResult<FromOverseer<NetworkBridgeMessage>>,
-) -> Action {
+) -> Option<Action> {
 	match res {
-		Ok(FromOverseer::Signal(OverseerSignal::StartWork(relay_parent)))
-			=> Action::StartWork(relay_parent),
-		Ok(FromOverseer::Signal(OverseerSignal::StopWork(relay_parent)))
-			=> Action::StopWork(relay_parent),
-		Ok(FromOverseer::Signal(OverseerSignal::Conclude)) => Action::Abort,
-		Ok(FromOverseer::Communication { msg }) => match msg {
+		Ok(FromOverseer::Signal(OverseerSignal::ActiveLeaves(active_leaves)))
+			=> Some(Action::ActiveLeaves(active_leaves)),
+		Ok(FromOverseer::Signal(OverseerSignal::BlockFinalized(..))) => None,
+		Ok(FromOverseer::Signal(OverseerSignal::Conclude)) => Some(Action::Abort),
+		Ok(FromOverseer::Communication { msg }) => Some(match msg {
 			NetworkBridgeMessage::RegisterEventProducer(protocol_id, message_producer)
 				=>  Action::RegisterEventProducer(protocol_id, message_producer),
 			NetworkBridgeMessage::ReportPeer(peer, rep) => Action::ReportPeer(peer, rep),
 			NetworkBridgeMessage::SendMessage(peers, protocol, message)
 				=> Action::SendMessage(peers, protocol, message),
-		},
+			NetworkBridgeMessage::ConnectToValidators(validators)
+				=> Action::ConnectToValidators(validators),
+			NetworkBridgeMessage::ResolveValidators(validators, tx)
+				=> Action::ResolveValidators(validators, tx),
+		}),
 		Err(e) => {
 			log::warn!("Shutting down Network Bridge due to error {:?}", e);
-			Action::Abort
+			Some(Action::Abort)
 		}
 	}
 }
@@ -241,23 +495,21 @@ fn action_from_network_message(event: Option<NetworkEvent>) -> Option<Action> {
 		}
 		Some(NetworkEvent::Dht(_)) => None,
 		Some(NetworkEvent::NotificationStreamOpened { remote, engine_id, role }) => {
-			if engine_id == POLKADOT_ENGINE_ID {
-				Some(Action::PeerConnected(remote, role))
-			} else {
-				None
-			}
+			PeerSet::from_engine_id(engine_id)
+				.map(|peer_set| Action::PeerConnected(peer_set, remote, role))
 		}
 		Some(NetworkEvent::NotificationStreamClosed { remote, engine_id }) => {
-			if engine_id == POLKADOT_ENGINE_ID {
-				Some(Action::PeerDisconnected(remote))
-			} else {
-				None
-			}
+			PeerSet::from_engine_id(engine_id)
+				.map(|peer_set| Action::PeerDisconnected(peer_set, remote))
 		}
 		Some(NetworkEvent::NotificationsReceived { remote, messages }) => {
 			let v: Result<Vec<_>, _> = messages.iter()
-				.filter(|(engine_id, _)| engine_id == &POLKADOT_ENGINE_ID)
-				.map(|(_, msg_bytes)| WireMessage::decode(&mut msg_bytes.as_ref()))
+				.filter_map(|(engine_id, msg_bytes)| {
+					PeerSet::from_engine_id(*engine_id).map(|peer_set| (peer_set, msg_bytes))
+				})
+				.map(|(peer_set, msg_bytes)| {
+					WireMessage::decode(&mut msg_bytes.as_ref()).map(|msg| (peer_set, msg))
+				})
 				.collect();
 
 			match v {
@@ -299,27 +551,86 @@ async fn update_view(
 	if *local_view == new_view { return Ok(None) }
 	*local_view = new_view.clone();
 
-	let message = WireMessage::ViewUpdate(new_view.clone()).encode();
-
-	let notifications = peers.keys().cloned()
-		.map(move |peer| Ok(NetworkAction::WriteNotification(peer, message.clone())));
+	let message = WireMessage::ViewUpdate(new_view.clone());
+	let min_version = message.min_version();
+	let message = message.encode();
+
+	let notifications = peers.iter()
+		.filter(|(_, data)| data.version.map_or(false, |v| v >= min_version))
+		.map(|(peer, data)| (data.peer_set, peer.clone()))
+		.collect::<Vec<_>>()
+		.into_iter()
+		.map(move |(peer_set, peer)| {
+			Ok(NetworkAction::WriteNotification(peer_set, peer, message.clone()))
+		});
 
 	net.action_sink().send_all(&mut stream::iter(notifications)).await?;
 
 	Ok(Some(NetworkBridgeEvent::OurViewChange(local_view.clone())))
 }
 
-async fn run_network<N: Network>(
+/// Extract the `PeerId` from the trailing `/p2p/` component of an address, if any.
+fn peer_id_from_multiaddr(addr: &Multiaddr) -> Option<PeerId> {
+	addr.iter().last().and_then(|protocol| match protocol {
+		Protocol::P2p(multihash) => PeerId::from_multihash(multihash).ok(),
+		_ => None,
+	})
+}
+
+/// Resolve the validators to their addresses and make them the reserved peers of the validation
+/// peer set, returning the IDs of the reserved peers.
+async fn connect_to_validators(
+	validators: Vec<AuthorityDiscoveryId>,
+	net: &mut impl Network,
+	authority_discovery: &mut impl AuthorityDiscovery,
+) -> SubsystemResult<HashSet<PeerId>> {
+	let mut addresses = HashSet::new();
+	for validator in validators {
+		match authority_discovery.get_addresses_by_authority_id(validator.clone()).await {
+			Some(addrs) => addresses.extend(addrs),
+			None => log::debug!("No known addresses of validator {:?}", validator),
+		}
+	}
+
+	let reserved = addresses.iter().filter_map(peer_id_from_multiaddr).collect();
+
+	net.action_sink().send(NetworkAction::SetReservedPeers(PeerSet::Validation, addresses)).await?;
+
+	Ok(reserved)
+}
+
+/// Resolve each validator to the peer of its first address carrying a `PeerId`, if any.
+async fn resolve_validators(
+	validators: Vec<AuthorityDiscoveryId>,
+	authority_discovery: &mut impl AuthorityDiscovery,
+) -> Vec<Option<PeerId>> {
+	let mut peers = Vec::with_capacity(validators.len());
+	for validator in validators {
+		let peer = authority_discovery.get_addresses_by_authority_id(validator).await
+			.and_then(|addrs| addrs.iter().find_map(peer_id_from_multiaddr));
+
+		peers.push(peer);
+	}
+
+	peers
+}
+
+async fn run_network<N: Network, AD: AuthorityDiscovery>(
 	mut net: N,
+	mut authority_discovery: AD,
+	peer_sets: PeerSetsConfig,
 	mut ctx: impl SubsystemContext<Message=NetworkBridgeMessage>,
 ) -> SubsystemResult<()> {
 	let mut event_stream = net.event_stream().fuse();
 
 	// Most recent heads are at the back.
 	let mut live_heads = Vec::with_capacity(MAX_VIEW_HEADS);
+	let mut recent_heads = VecDeque::with_capacity(RECENT_HEADS);
 	let mut local_view = View(Vec::new());
+	let mut flush_view = Fuse::terminated();
 
 	let mut peers: HashMap<PeerId, PeerData> = HashMap::new();
+	let mut reserved_peers: HashSet<PeerId> = HashSet::new();
 	let mut event_producers = BTreeMap::new();
 
 	loop {
@@ -329,8 +640,9 @@ async fn run_network<N: Network>(
 			futures::pin_mut!(subsystem_next);
 
 			let action = futures::select! {
-				subsystem_msg = subsystem_next => Some(action_from_overseer_message(subsystem_msg)),
+				subsystem_msg = subsystem_next => action_from_overseer_message(subsystem_msg),
 				net_event = net_event_next => action_from_network_message(net_event),
+				_ = flush_view => Some(Action::FlushView),
 			};
 
 			match action {
@@ -347,7 +659,7 @@ async fn run_network<N: Network>(
 
 					// send the event producer information on all connected peers.
 					let mut messages = Vec::with_capacity(peers.len() * 2);
-					for (peer, data) in &peers {
+					for (peer, data) in peers.iter().filter(|(_, data)| data.version.is_some()) {
 						messages.push(event_producer(
 							NetworkBridgeEvent::PeerConnected(peer.clone(), data.role.clone())
 						));
@@ -360,14 +672,26 @@ async fn run_network<N: Network>(
 					ctx.send_messages(messages).await?;
 				}
 			}
-			Action::SendMessage(peers, protocol, message) => {
+			Action::SendMessage(to, protocol, message) => {
+				let message = WireMessage::ProtocolMessage(protocol, message);
+				let min_version = message.min_version();
+
+				// only peers which have completed the handshake on a recent enough version.
+				let to: Vec<_> = to.into_iter()
+					.filter_map(|peer| match peers.get(&peer) {
+						Some(data) if data.version.map_or(false, |v| v >= min_version)
+							=> Some((data.peer_set, peer)),
+						_ => None,
+					})
+					.collect();
+
+				if to.is_empty() { continue }
+
 				let mut message_producer = stream::iter({
-					let n_peers = peers.len();
-					let mut message = Some(
-						WireMessage::ProtocolMessage(protocol, message).encode()
-					);
+					let n_peers = to.len();
+					let mut message = Some(message.encode());
 
-					peers.iter().cloned().enumerate().map(move |(i, peer)| {
+					to.into_iter().enumerate().map(move |(i, (peer_set, peer))| {
 						// optimization: avoid cloning the message for the last peer in the
 						// list. The message payload can be quite large. If the underlying
 						// network used `Bytes` this would not be necessary.
@@ -380,7 +704,7 @@ async fn run_network<N: Network>(
 								.clone()
 						};
 
-						Ok(NetworkAction::WriteNotification(peer, message))
+						Ok(NetworkAction::WriteNotification(peer_set, peer, message))
 					})
 				});
 
@@ -389,23 +713,58 @@ async fn run_network<N: Network>(
 			Action::ReportPeer(peer, rep) => {
 				net.report_peer(peer, rep).await?;
 			}
-			Action::StartWork(relay_parent) => {
-				live_heads.push(relay_parent);
-				if let Some(view_update)
-					= update_view(&peers, &live_heads, &mut net, &mut local_view).await?
-				{
-					if let Err(e) = dispatch_update_to_all(
-						view_update,
-						event_producers.values(),
-						&mut ctx,
-					).await {
-						log::warn!("Aborting - Failure to dispatch messages to overseer");
-						return Err(e)
+			Action::ConnectToValidators(validators) => {
+				reserved_peers = connect_to_validators(
+					validators,
+					&mut net,
+					&mut authority_discovery,
+				).await?;
+
+				// peers which connected before the change were admitted to the peer set they
+				// belonged to back then. They are disconnected, and admitted to the right one
+				// when reconnecting.
+				let misclassified: Vec<_> = peers.iter()
+					.filter(|(peer, data)| data.peer_set != PeerSet::of_peer(peer, &data.role, &reserved_peers))
+					.map(|(peer, _)| peer.clone())
+					.collect();
+
+				for peer in misclassified {
+					let announced = peers.remove(&peer).map_or(false, |data| data.version.is_some());
+					net.disconnect_peer(peer.clone()).await?;
+
+					if announced {
+						if let Err(e) = dispatch_update_to_all(
+							NetworkBridgeEvent::PeerDisconnected(peer),
+							event_producers.values(),
+							&mut ctx,
+						).await {
+							log::warn!("Aborting - Failure to dispatch messages to overseer");
+							return Err(e)
+						}
 					}
 				}
 			}
-			Action::StopWork(relay_parent) => {
-				live_heads.retain(|h| h != &relay_parent);
+			Action::ResolveValidators(validators, tx) => {
+				let peers = resolve_validators(validators, &mut authority_discovery).await;
+				let _ = tx.send(peers);
+			}
+			Action::ActiveLeaves(ActiveLeavesUpdate { activated, deactivated }) => {
+				for head in &activated {
+					if recent_heads.len() == RECENT_HEADS {
+						recent_heads.pop_front();
+					}
+					recent_heads.push_back(*head);
+				}
+
+				live_heads.extend(activated);
+				live_heads.retain(|h| !deactivated.contains(h));
+
+				// further leaf changes until the timer fires are sent along with this one.
+				if flush_view.is_terminated() {
+					flush_view = Delay::new(VIEW_UPDATE_DELAY).fuse();
+				}
+			}
+			Action::FlushView => {
 				if let Some(view_update)
 					= update_view(&peers, &live_heads, &mut net, &mut local_view).await?
 				{
@@ -420,28 +779,40 @@ async fn run_network<N: Network>(
 				}
 			}
 
-			Action::PeerConnected(peer, role) => {
-				match peers.entry(peer.clone()) {
-					HEntry::Occupied(_) => continue,
-					HEntry::Vacant(vacant) => {
-						vacant.insert(PeerData {
-							view: View(Vec::new()),
-							role: role.clone(),
-						});
+			Action::PeerConnected(stream_peer_set, peer, role) => {
+				if peers.contains_key(&peer) { continue }
 
-						if let Err(e) = dispatch_update_to_all(
-							NetworkBridgeEvent::PeerConnected(peer, role),
-							event_producers.values(),
-							&mut ctx,
-						).await {
-							log::warn!("Aborting - Failure to dispatch messages to overseer");
-							return Err(e)
-						}
+				let reserved = reserved_peers.contains(&peer);
+				let peer_set = PeerSet::of_peer(&peer, &role, &reserved_peers);
+
+				// the peer is only spoken to on the protocol of its own peer set.
+				if stream_peer_set != peer_set { continue }
+
+				if !reserved {
+					let occupied_slots = peers.iter()
+						.filter(|(p, data)| data.peer_set == peer_set && !reserved_peers.contains(p))
+						.count();
+
+					if occupied_slots >= peer_sets.slots(peer_set) {
+						net.disconnect_peer(peer).await?;
+						continue
 					}
 				}
+
+				peers.insert(peer.clone(), PeerData::new(role, peer_set));
+
+				// the peer is announced to event producers once it has sent its status.
+				net.write_notification(peer_set, peer, WireMessage::Status(VERSION).encode()).await?;
 			}
-			Action::PeerDisconnected(peer) => {
-				if peers.remove(&peer).is_some() {
+			Action::PeerDisconnected(peer_set, peer) => {
+				let announced = match peers.entry(peer.clone()) {
+					HEntry::Occupied(entry) if entry.get().peer_set == peer_set => {
+						entry.remove().version.is_some()
+					}
+					_ => false,
+				};
+
+				if announced {
 					if let Err(e) = dispatch_update_to_all(
 						NetworkBridgeEvent::PeerDisconnected(peer),
 						event_producers.values(),
@@ -459,10 +830,48 @@ async fn run_network<N: Network>(
 				};
 
 				let mut outgoing_messages = Vec::with_capacity(messages.len());
-				for message in messages {
+				let mut rejected = false;
+				for (peer_set, message) in messages {
+					if peer_set != peer_data.peer_set { continue }
+
+					if peer_data.version.is_none() && !matches!(message, WireMessage::Status(_)) {
+						net.report_peer(peer.clone(), NO_STATUS_COST).await?;
+						continue
+					}
+
+					if peer_data.version.map_or(false, |v| v < message.min_version()) {
+						net.report_peer(peer.clone(), UNSUPPORTED_VERSION_COST).await?;
+						continue
+					}
+
 					match message {
+						WireMessage::Status(their_version) => {
+							if peer_data.version.is_some() {
+								net.report_peer(peer.clone(), UNEXPECTED_STATUS_COST).await?;
+								continue
+							}
+
+							if their_version < MIN_SUPPORTED_VERSION {
+								net.report_peer(peer.clone(), UNSUPPORTED_VERSION_COST).await?;
+								net.disconnect_peer(peer.clone()).await?;
+								rejected = true;
+								break
+							}
+
+							// peers on newer versions talk to us on ours.
+							peer_data.version = Some(std::cmp::min(their_version, VERSION));
+
+							let update = NetworkBridgeEvent::PeerConnected(
+								peer.clone(),
+								peer_data.role.clone(),
+							);
+
+							outgoing_messages.extend(
+								event_producers.values().map(|producer| producer(update.clone()))
+							);
+						}
 						WireMessage::ViewUpdate(new_view) => {
-							if new_view.0.len() > MAX_VIEW_HEADS {
+							if is_malformed_view(&new_view) {
 								net.report_peer(
 									peer.clone(),
 									MALFORMED_VIEW_COST,
@@ -471,7 +880,23 @@ async fn run_network<N: Network>(
 								continue
 							}
 
+							if !peer_data.note_view_update(Instant::now()) {
+								net.report_peer(
+									peer.clone(),
+									VIEW_UPDATE_RATE_COST,
+								).await?;
+
+								continue
+							}
+
 							if new_view == peer_data.view { continue }
+
+							if peer_data.note_view_heads(&new_view, &recent_heads) {
+								net.report_peer(
+									peer.clone(),
+									UNKNOWN_VIEW_COST,
+								).await?;
+							}
 							peer_data.view = new_view;
 
 							let update = NetworkBridgeEvent::PeerViewChange(
@@ -505,6 +930,11 @@ async fn run_network<N: Network>(
 					}
 				}
 
+				// the peer was never announced, so it is dropped silently.
+				if rejected {
+					peers.remove(&peer);
+				}
+
 				let send_messages = ctx.send_messages(outgoing_messages);
 				if let Err(e) = send_messages.await {
 					log::warn!("Aborting - Failure to dispatch messages to overseer");
@@ -529,6 +959,7 @@ mod tests {
 
 	use polkadot_subsystem::messages::{StatementDistributionMessage, BitfieldDistributionMessage};
 	use subsystem_test::{SingleItemSink, SingleItemStream};
+	use sp_keyring::Sr25519Keyring;
 
 	// The subsystem's view of the network - only supports a single call to `event_stream`.
 	struct TestNetwork {
@@ -593,25 +1024,43 @@ mod tests {
 			v
 		}
 
+		// Open a stream with a peer in the peer set of its role and complete the handshake.
 		async fn connect_peer(&mut self, peer: PeerId, role: ObservedRole) {
+			let peer_set = PeerSet::of_role(&role);
+			self.connect_peer_on(peer_set, peer, role).await;
+		}
+
+		async fn connect_peer_on(&mut self, peer_set: PeerSet, peer: PeerId, role: ObservedRole) {
+			self.open_stream(peer_set, peer.clone(), role).await;
+
+			assert!(self.next_network_action().await == NetworkAction::WriteNotification(
+				peer_set,
+				peer.clone(),
+				WireMessage::Status(VERSION).encode(),
+			));
+
+			self.peer_message(peer_set, peer, WireMessage::Status(VERSION).encode()).await;
+		}
+
+		async fn open_stream(&mut self, peer_set: PeerSet, peer: PeerId, role: ObservedRole) {
 			self.send_network_event(NetworkEvent::NotificationStreamOpened {
 				remote: peer,
-				engine_id: POLKADOT_ENGINE_ID,
+				engine_id: peer_set.engine_id(),
 				role,
 			}).await;
 		}
 
-		async fn disconnect_peer(&mut self, peer: PeerId) {
+		async fn disconnect_peer(&mut self, peer_set: PeerSet, peer: PeerId) {
 			self.send_network_event(NetworkEvent::NotificationStreamClosed {
 				remote: peer,
-				engine_id: POLKADOT_ENGINE_ID,
+				engine_id: peer_set.engine_id(),
 			}).await;
 		}
 
-		async fn peer_message(&mut self, peer: PeerId, message: Vec<u8>) {
+		async fn peer_message(&mut self, peer_set: PeerSet, peer: PeerId, message: Vec<u8>) {
 			self.send_network_event(NetworkEvent::NotificationsReceived {
 				remote: peer,
-				messages: vec![(POLKADOT_ENGINE_ID, message.into())],
+				messages: vec![(peer_set.engine_id(), message.into())],
 			}).await;
 		}
 
@@ -620,6 +1069,24 @@ mod tests {
 		}
 	}
 
+	// The authority discovery service, knowing the addresses of a fixed set of validators.
+	#[derive(Default)]
+	struct TestAuthorityDiscovery {
+		addresses: HashMap<AuthorityDiscoveryId, Vec<Multiaddr>>,
+	}
+
+	impl AuthorityDiscovery for TestAuthorityDiscovery {
+		fn get_addresses_by_authority_id(&mut self, authority: AuthorityDiscoveryId)
+			-> BoxFuture<Option<Vec<Multiaddr>>>
+		{
+			future::ready(self.addresses.get(&authority).cloned()).boxed()
+		}
+	}
+
+	fn peer_address(peer: &PeerId) -> Multiaddr {
+		format!("/ip4/127.0.0.1/tcp/30333/p2p/{}", peer).parse().unwrap()
+	}
+
 	// network actions are sensitive to ordering of `PeerId`s within a `HashMap`, so
 	// we need to use this to prevent fragile reliance on peer ordering.
 	fn network_actions_contains(actions: &[NetworkAction], action: &NetworkAction) -> bool {
@@ -632,6 +1099,14 @@ mod tests {
 	}
 
 	fn test_harness<T: Future<Output=()>>(test: impl FnOnce(TestHarness) -> T) {
+		test_harness_with(TestAuthorityDiscovery::default(), PeerSetsConfig::default(), test)
+	}
+
+	fn test_harness_with<T: Future<Output=()>>(
+		authority_discovery: TestAuthorityDiscovery,
+		peer_sets: PeerSetsConfig,
+		test: impl FnOnce(TestHarness) -> T,
+	) {
 		let pool = ThreadPool::new().unwrap();
 
 		let (network, network_handle) = new_test_network();
@@ -639,6 +1114,8 @@ mod tests {
 
 		let network_bridge = run_network(
 			network,
+			authority_discovery,
+			peer_sets,
 			context,
 		)
 			.map_err(|_| panic!("subsystem execution failed"))
@@ -668,18 +1145,20 @@ mod tests {
 
 			let hash_a = Hash::from([1; 32]);
 
-			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::StartWork(hash_a))).await;
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+				ActiveLeavesUpdate::start_work(hash_a),
+			))).await;
 
 			let actions = network_handle.next_network_actions(2).await;
 			let wire_message = WireMessage::ViewUpdate(View(vec![hash_a])).encode();
 			assert!(network_actions_contains(
 				&actions,
-				&NetworkAction::WriteNotification(peer_a, wire_message.clone()),
+				&NetworkAction::WriteNotification(PeerSet::Collation, peer_a, wire_message.clone()),
 			));
 
 			assert!(network_actions_contains(
 				&actions,
-				&NetworkAction::WriteNotification(peer_b, wire_message.clone()),
+				&NetworkAction::WriteNotification(PeerSet::Collation, peer_b, wire_message.clone()),
 			));
 		});
 	}
@@ -759,6 +1238,7 @@ mod tests {
 			}
 
 			network_handle.peer_message(
+				PeerSet::Collation,
 				peer.clone(),
 				WireMessage::ViewUpdate(view.clone()).encode(),
 			).await;
@@ -867,11 +1347,12 @@ mod tests {
 			let payload = vec![1, 2, 3];
 
 			network_handle.peer_message(
+				PeerSet::Collation,
 				peer.clone(),
 				WireMessage::ProtocolMessage(proto_statement, payload.clone()).encode(),
 			).await;
 
-			network_handle.disconnect_peer(peer.clone()).await;
+			network_handle.disconnect_peer(PeerSet::Collation, peer.clone()).await;
 
 			// statement distribution message comes first because handlers are ordered by
 			// protocol ID, and then a disconnection event comes - indicating that the message
@@ -901,4 +1382,389 @@ mod tests {
 			);
 		});
 	}
+
+	#[test]
+	fn peers_beyond_slot_limit_are_disconnected() {
+		let peer_sets = PeerSetsConfig {
+			validation_slots: 1,
+			collation_slots: 1,
+		};
+
+		test_harness_with(TestAuthorityDiscovery::default(), peer_sets, |test_harness| async move {
+			let TestHarness { mut network_handle, .. } = test_harness;
+
+			let full_a = PeerId::random();
+			let full_b = PeerId::random();
+			let authority_a = PeerId::random();
+			let authority_b = PeerId::random();
+
+			network_handle.connect_peer(full_a.clone(), ObservedRole::Full).await;
+			network_handle.connect_peer(authority_a.clone(), ObservedRole::Authority).await;
+			network_handle.open_stream(PeerSet::Collation, full_b.clone(), ObservedRole::Full).await;
+
+			assert!(network_handle.next_network_action().await == NetworkAction::DisconnectPeer(full_b));
+
+			network_handle.open_stream(
+				PeerSet::Validation,
+				authority_b.clone(),
+				ObservedRole::Authority,
+			).await;
+
+			assert!(
+				network_handle.next_network_action().await == NetworkAction::DisconnectPeer(authority_b)
+			);
+
+			// a freed slot can be taken by another peer.
+			let full_c = PeerId::random();
+			let full_d = PeerId::random();
+
+			network_handle.disconnect_peer(PeerSet::Collation, full_a).await;
+			network_handle.connect_peer(full_c, ObservedRole::Full).await;
+			network_handle.open_stream(PeerSet::Collation, full_d.clone(), ObservedRole::Full).await;
+
+			assert!(network_handle.next_network_action().await == NetworkAction::DisconnectPeer(full_d));
+		});
+	}
+
+	#[test]
+	fn connects_to_validators_as_reserved_peers() {
+		let validator = PeerId::random();
+		let validator_id: AuthorityDiscoveryId = Sr25519Keyring::Alice.public().into();
+		let unknown_validator_id: AuthorityDiscoveryId = Sr25519Keyring::Bob.public().into();
+
+		let mut authority_discovery = TestAuthorityDiscovery::default();
+		authority_discovery.addresses.insert(validator_id.clone(), vec![peer_address(&validator)]);
+
+		let peer_sets = PeerSetsConfig {
+			validation_slots: 0,
+			collation_slots: 0,
+		};
+
+		test_harness_with(authority_discovery, peer_sets, |test_harness| async move {
+			let TestHarness { mut network_handle, mut virtual_overseer } = test_harness;
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: NetworkBridgeMessage::ConnectToValidators(
+					vec![validator_id, unknown_validator_id],
+				),
+			}).await;
+
+			let expected_addresses = vec![peer_address(&validator)].into_iter().collect();
+			assert!(
+				network_handle.next_network_action().await
+					== NetworkAction::SetReservedPeers(PeerSet::Validation, expected_addresses)
+			);
+
+			// the reserved validator is admitted despite there being no free slots, even
+			// though it didn't connect with the authority role.
+			let other = PeerId::random();
+			network_handle.connect_peer_on(PeerSet::Validation, validator, ObservedRole::Full).await;
+			network_handle.open_stream(PeerSet::Validation, other.clone(), ObservedRole::Authority).await;
+
+			assert!(network_handle.next_network_action().await == NetworkAction::DisconnectPeer(other));
+
+			// replacing the reserved validators drops the previous ones.
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: NetworkBridgeMessage::ConnectToValidators(Vec::new()),
+			}).await;
+
+			assert!(
+				network_handle.next_network_action().await
+					== NetworkAction::SetReservedPeers(PeerSet::Validation, HashSet::new())
+			);
+		});
+	}
+
+	#[test]
+	fn resolves_validators_to_their_peers() {
+		let validator = PeerId::random();
+		let validator_id: AuthorityDiscoveryId = Sr25519Keyring::Alice.public().into();
+		let unknown_validator_id: AuthorityDiscoveryId = Sr25519Keyring::Bob.public().into();
+
+		let mut authority_discovery = TestAuthorityDiscovery::default();
+		authority_discovery.addresses.insert(validator_id.clone(), vec![peer_address(&validator)]);
+
+		test_harness_with(authority_discovery, PeerSetsConfig::default(), |test_harness| async move {
+			let TestHarness { network_handle: _network_handle, mut virtual_overseer } = test_harness;
+
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: NetworkBridgeMessage::ResolveValidators(
+					vec![unknown_validator_id, validator_id],
+					tx,
+				),
+			}).await;
+
+			assert_eq!(rx.await.unwrap(), vec![None, Some(validator)]);
+		});
+	}
+
+	#[test]
+	fn peers_are_reclassified_when_reserved_peers_change() {
+		let validator = PeerId::random();
+		let validator_id: AuthorityDiscoveryId = Sr25519Keyring::Alice.public().into();
+
+		let mut authority_discovery = TestAuthorityDiscovery::default();
+		authority_discovery.addresses.insert(validator_id.clone(), vec![peer_address(&validator)]);
+
+		test_harness_with(authority_discovery, PeerSetsConfig::default(), |test_harness| async move {
+			let TestHarness { mut network_handle, mut virtual_overseer } = test_harness;
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: NetworkBridgeMessage::RegisterEventProducer(
+					*b"abcd",
+					|event| AllMessages::StatementDistribution(
+						StatementDistributionMessage::NetworkBridgeUpdate(event)
+					)
+				),
+			}).await;
+
+			// the validator connects as a full node before it is reserved.
+			network_handle.connect_peer(validator.clone(), ObservedRole::Full).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::StatementDistribution(
+					StatementDistributionMessage::NetworkBridgeUpdate(
+						NetworkBridgeEvent::PeerConnected(p, ObservedRole::Full)
+					)
+				) if p == validator
+			);
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: NetworkBridgeMessage::ConnectToValidators(vec![validator_id]),
+			}).await;
+
+			let expected_addresses = vec![peer_address(&validator)].into_iter().collect();
+			assert!(
+				network_handle.next_network_action().await
+					== NetworkAction::SetReservedPeers(PeerSet::Validation, expected_addresses)
+			);
+
+			// it is dropped from the collation peer set, to reconnect as a validation peer.
+			assert!(
+				network_handle.next_network_action().await
+					== NetworkAction::DisconnectPeer(validator.clone())
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::StatementDistribution(
+					StatementDistributionMessage::NetworkBridgeUpdate(
+						NetworkBridgeEvent::PeerDisconnected(p)
+					)
+				) if p == validator
+			);
+
+			network_handle.connect_peer_on(PeerSet::Validation, validator.clone(), ObservedRole::Full).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::StatementDistribution(
+					StatementDistributionMessage::NetworkBridgeUpdate(
+						NetworkBridgeEvent::PeerConnected(p, ObservedRole::Full)
+					)
+				) if p == validator
+			);
+		});
+	}
+
+	#[test]
+	fn peers_are_announced_after_handshake() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut network_handle, mut virtual_overseer } = test_harness;
+
+			let peer = PeerId::random();
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: NetworkBridgeMessage::RegisterEventProducer(
+					*b"abcd",
+					|event| AllMessages::StatementDistribution(
+						StatementDistributionMessage::NetworkBridgeUpdate(event)
+					)
+				),
+			}).await;
+
+			network_handle.open_stream(PeerSet::Collation, peer.clone(), ObservedRole::Full).await;
+
+			assert!(network_handle.next_network_action().await == NetworkAction::WriteNotification(
+				PeerSet::Collation,
+				peer.clone(),
+				WireMessage::Status(VERSION).encode(),
+			));
+
+			// messages other than the status are rejected before the handshake.
+			network_handle.peer_message(
+				PeerSet::Collation,
+				peer.clone(),
+				WireMessage::ViewUpdate(View(vec![Hash::from([1u8; 32])])).encode(),
+			).await;
+
+			assert!(
+				network_handle.next_network_action().await
+					== NetworkAction::ReputationChange(peer.clone(), NO_STATUS_COST)
+			);
+
+			// messages on the protocol of another peer set are ignored.
+			network_handle.peer_message(
+				PeerSet::Validation,
+				peer.clone(),
+				WireMessage::Status(VERSION).encode(),
+			).await;
+
+			// peers on newer versions are accepted.
+			network_handle.peer_message(
+				PeerSet::Collation,
+				peer.clone(),
+				WireMessage::Status(VERSION + 1).encode(),
+			).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::StatementDistribution(
+					StatementDistributionMessage::NetworkBridgeUpdate(
+						NetworkBridgeEvent::PeerConnected(p, ObservedRole::Full)
+					)
+				) if p == peer
+			);
+
+			network_handle.peer_message(
+				PeerSet::Collation,
+				peer.clone(),
+				WireMessage::Status(VERSION).encode(),
+			).await;
+
+			assert!(
+				network_handle.next_network_action().await
+					== NetworkAction::ReputationChange(peer, UNEXPECTED_STATUS_COST)
+			);
+		});
+	}
+
+	#[test]
+	fn peers_on_unsupported_versions_are_disconnected() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut network_handle, .. } = test_harness;
+
+			let peer = PeerId::random();
+
+			network_handle.open_stream(PeerSet::Collation, peer.clone(), ObservedRole::Full).await;
+
+			assert!(network_handle.next_network_action().await == NetworkAction::WriteNotification(
+				PeerSet::Collation,
+				peer.clone(),
+				WireMessage::Status(VERSION).encode(),
+			));
+
+			network_handle.peer_message(
+				PeerSet::Collation,
+				peer.clone(),
+				WireMessage::Status(MIN_SUPPORTED_VERSION - 1).encode(),
+			).await;
+
+			let actions = network_handle.next_network_actions(2).await;
+			assert!(actions[0] == NetworkAction::ReputationChange(peer.clone(), UNSUPPORTED_VERSION_COST));
+			assert!(actions[1] == NetworkAction::DisconnectPeer(peer.clone()));
+
+			// the peer is no longer known, so it may connect again.
+			network_handle.connect_peer(peer, ObservedRole::Full).await;
+		});
+	}
+
+	#[test]
+	fn view_updates_are_coalesced() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut network_handle, mut virtual_overseer } = test_harness;
+
+			let peer_a = PeerId::random();
+			let peer_b = PeerId::random();
+
+			network_handle.connect_peer(peer_a.clone(), ObservedRole::Full).await;
+			network_handle.connect_peer(peer_b.clone(), ObservedRole::Full).await;
+
+			let hash_a = Hash::from([1; 32]);
+			let hash_b = Hash::from([2; 32]);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+				ActiveLeavesUpdate::start_work(hash_a),
+			))).await;
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+				ActiveLeavesUpdate::start_work(hash_b),
+			))).await;
+
+			// a single view update containing both heads is sent to each peer.
+			let actions = network_handle.next_network_actions(2).await;
+			let wire_message = WireMessage::ViewUpdate(View(vec![hash_b, hash_a])).encode();
+			assert!(network_actions_contains(
+				&actions,
+				&NetworkAction::WriteNotification(PeerSet::Collation, peer_a, wire_message.clone()),
+			));
+
+			assert!(network_actions_contains(
+				&actions,
+				&NetworkAction::WriteNotification(PeerSet::Collation, peer_b, wire_message.clone()),
+			));
+		});
+	}
+
+	#[test]
+	fn peer_view_updates_are_rate_limited() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut network_handle, .. } = test_harness;
+
+			let peer = PeerId::random();
+
+			network_handle.connect_peer(peer.clone(), ObservedRole::Full).await;
+
+			for _ in 0..=MAX_VIEW_UPDATES_PER_WINDOW {
+				network_handle.peer_message(
+					PeerSet::Collation,
+					peer.clone(),
+					WireMessage::ViewUpdate(View(Vec::new())).encode(),
+				).await;
+			}
+
+			assert!(
+				network_handle.next_network_action().await
+					== NetworkAction::ReputationChange(peer, VIEW_UPDATE_RATE_COST)
+			);
+		});
+	}
+
+	#[test]
+	fn peers_with_implausible_views_are_penalized() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut network_handle, .. } = test_harness;
+
+			let peer = PeerId::random();
+			let hash_a = Hash::from([1; 32]);
+
+			network_handle.connect_peer(peer.clone(), ObservedRole::Full).await;
+
+			network_handle.peer_message(
+				PeerSet::Collation,
+				peer.clone(),
+				WireMessage::ViewUpdate(View(vec![hash_a, hash_a])).encode(),
+			).await;
+
+			assert!(
+				network_handle.next_network_action().await
+					== NetworkAction::ReputationChange(peer.clone(), MALFORMED_VIEW_COST)
+			);
+
+			for i in 0..=MAX_UNKNOWN_VIEWS {
+				network_handle.peer_message(
+					PeerSet::Collation,
+					peer.clone(),
+					WireMessage::ViewUpdate(View(vec![Hash::repeat_byte(i as u8 + 2)])).encode(),
+				).await;
+			}
+
+			assert!(
+				network_handle.next_network_action().await
+					== NetworkAction::ReputationChange(peer, UNKNOWN_VIEW_COST)
+			);
+		});
+	}
 }
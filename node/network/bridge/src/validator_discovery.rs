@@ -0,0 +1,169 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Resolving validators to the addresses they publish on the DHT.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use futures::prelude::*;
+use futures::future::BoxFuture;
+use parking_lot::Mutex;
+use parity_scale_codec::Decode;
+use prost::Message;
+use sc_network::{DhtEvent, Event as NetworkEvent, Multiaddr, NetworkService};
+use sc_network::multiaddr::multihash::Sha2_256;
+use sp_authority_discovery::{AuthorityId as AuthorityDiscoveryId, AuthoritySignature};
+use sp_runtime::RuntimeAppPublic;
+
+use polkadot_primitives::{Block, Hash};
+
+use crate::AuthorityDiscovery;
+
+/// The addresses of a validator, as published by the authority discovery worker.
+#[derive(Clone, PartialEq, Message)]
+struct AuthorityAddresses {
+	#[prost(bytes, repeated, tag = "1")]
+	addresses: Vec<Vec<u8>>,
+}
+
+/// Encoded `AuthorityAddresses` along with the signature of the validator.
+#[derive(Clone, PartialEq, Message)]
+struct SignedAuthorityAddresses {
+	#[prost(bytes, tag = "1")]
+	addresses: Vec<u8>,
+	#[prost(bytes, tag = "2")]
+	signature: Vec<u8>,
+}
+
+/// The DHT key under which a validator publishes its addresses.
+fn dht_key(authority: &AuthorityDiscoveryId) -> Vec<u8> {
+	Sha2_256::digest(authority.as_ref()).into_bytes()
+}
+
+/// Decode a record found on the DHT, returning the addresses in it if it is signed by the
+/// given validator.
+fn addresses_from_record(authority: &AuthorityDiscoveryId, record: &[u8]) -> Option<Vec<Multiaddr>> {
+	let signed = SignedAuthorityAddresses::decode(record).ok()?;
+	let signature = AuthoritySignature::decode(&mut &signed.signature[..]).ok()?;
+
+	if !authority.verify(&signed.addresses, &signature) {
+		return None;
+	}
+
+	let addresses = AuthorityAddresses::decode(&signed.addresses[..]).ok()?;
+
+	Some(addresses.addresses.into_iter().filter_map(|a| Multiaddr::try_from(a).ok()).collect())
+}
+
+/// Resolves validators to the addresses they publish on the DHT.
+///
+/// This doesn't query the DHT itself. The authority discovery worker regularly looks up the
+/// records of all validators, and what it finds is sent to every listener of network events.
+/// Records are only verified once the addresses of a validator are requested, as the key of a
+/// record doesn't reveal the validator which published it.
+#[derive(Clone)]
+pub struct DhtAuthorityDiscovery {
+	records: Arc<Mutex<HashMap<Vec<u8>, Vec<u8>>>>,
+}
+
+impl DhtAuthorityDiscovery {
+	/// Create a new resolver along with the future collecting the records found on the DHT,
+	/// which has to be spawned for the resolver to learn of any addresses.
+	pub fn new(network: &NetworkService<Block, Hash>) -> (Self, impl Future<Output = ()> + Send) {
+		let records = Arc::new(Mutex::new(HashMap::new()));
+
+		let collect_records = {
+			let records = records.clone();
+			network.event_stream("polkadot-validator-discovery").for_each(move |event| {
+				if let NetworkEvent::Dht(DhtEvent::ValueFound(values)) = event {
+					let mut records = records.lock();
+					for (key, value) in values {
+						records.insert(key.to_vec(), value);
+					}
+				}
+
+				future::ready(())
+			})
+		};
+
+		(DhtAuthorityDiscovery { records }, collect_records)
+	}
+}
+
+impl AuthorityDiscovery for DhtAuthorityDiscovery {
+	fn get_addresses_by_authority_id(&mut self, authority: AuthorityDiscoveryId)
+		-> BoxFuture<Option<Vec<Multiaddr>>>
+	{
+		let record = self.records.lock().get(&dht_key(&authority)).cloned();
+		let addresses = record.and_then(|record| addresses_from_record(&authority, &record));
+
+		future::ready(addresses.filter(|addresses| !addresses.is_empty())).boxed()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_keyring::Sr25519Keyring;
+
+	fn signed_record(keyring: Sr25519Keyring, addresses: &[Multiaddr]) -> Vec<u8> {
+		let mut encoded_addresses = Vec::new();
+		AuthorityAddresses {
+			addresses: addresses.iter().map(|a| a.to_vec()).collect(),
+		}.encode(&mut encoded_addresses).unwrap();
+
+		let signature = AuthoritySignature::from(keyring.sign(&encoded_addresses));
+
+		let mut record = Vec::new();
+		SignedAuthorityAddresses {
+			addresses: encoded_addresses,
+			signature: parity_scale_codec::Encode::encode(&signature),
+		}.encode(&mut record).unwrap();
+
+		record
+	}
+
+	#[test]
+	fn addresses_are_resolved_from_records_signed_by_the_validator() {
+		let validator: AuthorityDiscoveryId = Sr25519Keyring::Alice.public().into();
+		let address: Multiaddr = "/ip4/127.0.0.1/tcp/30333".parse().unwrap();
+
+		let records = Arc::new(Mutex::new(HashMap::new()));
+		let mut discovery = DhtAuthorityDiscovery { records: records.clone() };
+
+		assert_eq!(
+			futures::executor::block_on(discovery.get_addresses_by_authority_id(validator.clone())),
+			None,
+		);
+
+		// records signed by someone else are rejected.
+		records.lock().insert(dht_key(&validator), signed_record(Sr25519Keyring::Bob, &[address.clone()]));
+
+		assert_eq!(
+			futures::executor::block_on(discovery.get_addresses_by_authority_id(validator.clone())),
+			None,
+		);
+
+		records.lock().insert(dht_key(&validator), signed_record(Sr25519Keyring::Alice, &[address.clone()]));
+
+		assert_eq!(
+			futures::executor::block_on(discovery.get_addresses_by_authority_id(validator)),
+			Some(vec![address]),
+		);
+	}
+}
@@ -0,0 +1,894 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The PoV Distribution subsystem.
+//!
+//! PoVs are fetched on demand: when a PoV is requested, we notify our peers that we are
+//! awaiting it, and peers only ever send us PoVs we have notified them about. PoVs are only
+//! exchanged with the validators assigned to the para of the candidate: we only notify them
+//! that we await a PoV, only accept such notifications from validators assigned to a para,
+//! and only serve a PoV to the validators assigned to its para.
+
+use std::collections::{HashMap, HashSet};
+
+use parity_scale_codec::{Encode, Decode};
+use futures::channel::oneshot;
+use futures::prelude::*;
+
+use sc_network::{PeerId, ReputationChange};
+
+use polkadot_subsystem::{
+	Subsystem, SubsystemContext, SpawnedSubsystem, SubsystemResult,
+	FromOverseer, OverseerSignal, ActiveLeavesUpdate,
+};
+use polkadot_subsystem::messages::{
+	AllMessages, NetworkBridgeEvent, NetworkBridgeMessage, PoVDistributionMessage,
+	RuntimeApiMessage, RuntimeApiRequest, RuntimeApiSender,
+};
+use node_primitives::{ProtocolId, View};
+use polkadot_primitives::Hash;
+use polkadot_primitives::parachain::{v2::AbridgedCandidateReceipt, Chain, Id as ParaId, PoVBlock};
+use sp_authority_discovery::AuthorityId as AuthorityDiscoveryId;
+
+/// The protocol identifier for PoV distribution.
+pub const PROTOCOL_V1: ProtocolId = *b"povd";
+
+const COST_MALFORMED_MESSAGE: ReputationChange
+	= ReputationChange::new(-500, "Malformed PoV distribution message");
+const COST_AWAITED_NOT_IN_VIEW: ReputationChange
+	= ReputationChange::new(-100, "Peer awaits PoVs for a relay-parent not in view");
+const COST_APPARENT_FLOOD: ReputationChange
+	= ReputationChange::new(-500, "Peer awaits more PoVs than there are validators");
+const COST_NOT_IN_GROUP: ReputationChange
+	= ReputationChange::new(-100, "Peer awaits PoVs but is not assigned to any para");
+const COST_UNEXPECTED_POV: ReputationChange
+	= ReputationChange::new(-500, "Peer sent a PoV we were not awaiting");
+const COST_WRONG_HASH: ReputationChange
+	= ReputationChange::new(-500, "Peer sent a PoV with the wrong hash");
+const BENEFIT_FRESH_POV: ReputationChange
+	= ReputationChange::new(25, "Peer supplied an awaited PoV");
+
+/// Messages sent over the network by this subsystem.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub enum WireMessage {
+	/// Notification that we are awaiting the given PoVs, by hash, in the context of the
+	/// given relay-parent.
+	#[codec(index = "0")]
+	Awaiting(Hash, Vec<Hash>),
+	/// An awaited PoV in the context of the given relay-parent: `(relay_parent, pov_hash, pov)`.
+	#[codec(index = "1")]
+	SendPoV(Hash, Hash, PoVBlock),
+}
+
+/// The PoV distribution subsystem.
+#[derive(Clone)]
+pub struct PoVDistribution;
+
+impl<C> Subsystem<C> for PoVDistribution
+	where C: SubsystemContext<Message = PoVDistributionMessage>
+{
+	fn start(self, ctx: C) -> SpawnedSubsystem {
+		SpawnedSubsystem(run(ctx).map(|_| ()).boxed())
+	}
+}
+
+/// Data kept for each relay-parent we are working on.
+struct BlockBasedState {
+	/// PoVs we have, by hash.
+	known: HashMap<Hash, PoVBlock>,
+	/// PoVs we are awaiting, by hash, with the channels of those who requested them.
+	fetching: HashMap<Hash, Vec<oneshot::Sender<PoVBlock>>>,
+	/// The para of each PoV we have or are awaiting, by hash.
+	paras: HashMap<Hash, ParaId>,
+	/// The number of validators at the relay-parent, which bounds the number of PoVs
+	/// awaited by each peer.
+	n_validators: usize,
+	/// The peers of the validators assigned to each para.
+	group_peers: HashMap<ParaId, HashSet<PeerId>>,
+}
+
+impl BlockBasedState {
+	/// Whether the peer is a validator assigned to the para of the PoV.
+	fn in_group_of(&self, pov_hash: &Hash, peer: &PeerId) -> bool {
+		self.paras.get(pov_hash)
+			.and_then(|para| self.group_peers.get(para))
+			.map_or(false, |peers| peers.contains(peer))
+	}
+
+	/// Whether the peer is a validator assigned to any para.
+	fn in_any_group(&self, peer: &PeerId) -> bool {
+		self.group_peers.values().any(|peers| peers.contains(peer))
+	}
+}
+
+#[derive(Default)]
+struct PeerState {
+	/// The PoVs awaited by the peer, for each relay-parent in its view.
+	awaited: HashMap<Hash, HashSet<Hash>>,
+}
+
+#[derive(Default)]
+struct State {
+	relay_parent_state: HashMap<Hash, BlockBasedState>,
+	peer_state: HashMap<PeerId, PeerState>,
+	our_view: View,
+}
+
+fn network_update_message(n: NetworkBridgeEvent) -> AllMessages {
+	AllMessages::PoVDistribution(PoVDistributionMessage::NetworkBridgeUpdate(n))
+}
+
+async fn run(mut ctx: impl SubsystemContext<Message = PoVDistributionMessage>) -> SubsystemResult<()> {
+	ctx.send_message(AllMessages::NetworkBridge(
+		NetworkBridgeMessage::RegisterEventProducer(PROTOCOL_V1, network_update_message),
+	)).await?;
+
+	let mut state = State::default();
+
+	loop {
+		match ctx.recv().await? {
+			FromOverseer::Signal(OverseerSignal::ActiveLeaves(ActiveLeavesUpdate { activated, deactivated })) => {
+				for relay_parent in activated {
+					if let Some(relay_parent_state) = fetch_relay_parent_data(&mut ctx, relay_parent).await? {
+						state.relay_parent_state.insert(relay_parent, relay_parent_state);
+					}
+				}
+
+				for relay_parent in deactivated {
+					// this drops the channels of any outstanding fetches.
+					state.relay_parent_state.remove(&relay_parent);
+				}
+			}
+			FromOverseer::Signal(OverseerSignal::BlockFinalized(..)) => {}
+			FromOverseer::Signal(OverseerSignal::Conclude) => return Ok(()),
+			FromOverseer::Communication { msg } => match msg {
+				PoVDistributionMessage::FetchPoV(relay_parent, candidate, response_sender) => {
+					handle_fetch(&mut ctx, &mut state, relay_parent, candidate, response_sender).await?;
+				}
+				PoVDistributionMessage::DistributePoV(relay_parent, candidate, pov) => {
+					handle_distribute(&mut ctx, &mut state, relay_parent, candidate, pov).await?;
+				}
+				PoVDistributionMessage::NetworkBridgeUpdate(event) => {
+					handle_network_update(&mut ctx, &mut state, event).await?;
+				}
+			},
+		}
+	}
+}
+
+async fn runtime_api_request<T>(
+	ctx: &mut impl SubsystemContext<Message = PoVDistributionMessage>,
+	relay_parent: Hash,
+	make_request: impl FnOnce(RuntimeApiSender<T>) -> RuntimeApiRequest,
+) -> SubsystemResult<Option<T>> {
+	let (tx, rx) = oneshot::channel();
+	ctx.send_message(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+		relay_parent,
+		make_request(tx),
+	))).await?;
+
+	match rx.await? {
+		Ok(res) => Ok(Some(res)),
+		Err(e) => {
+			log::warn!(
+				target: "pov_distribution",
+				"Runtime API request failed at relay-parent {}: {:?}",
+				relay_parent,
+				e,
+			);
+
+			Ok(None)
+		}
+	}
+}
+
+/// Fetch the number of validators at the relay-parent and the peers of the validators
+/// assigned to each para. The authority discovery keys are in the same order as the validators.
+async fn fetch_relay_parent_data(
+	ctx: &mut impl SubsystemContext<Message = PoVDistributionMessage>,
+	relay_parent: Hash,
+) -> SubsystemResult<Option<BlockBasedState>> {
+	let validators = match runtime_api_request(ctx, relay_parent, RuntimeApiRequest::Validators).await? {
+		Some(validators) => validators,
+		None => return Ok(None),
+	};
+
+	let roster = match runtime_api_request(ctx, relay_parent, RuntimeApiRequest::DutyRoster).await? {
+		Some(roster) => roster,
+		None => return Ok(None),
+	};
+
+	let authorities: Vec<AuthorityDiscoveryId> = match runtime_api_request(
+		ctx,
+		relay_parent,
+		RuntimeApiRequest::AuthorityDiscoveryKeys,
+	).await? {
+		Some(authorities) => authorities,
+		None => return Ok(None),
+	};
+
+	let (tx, rx) = oneshot::channel();
+	ctx.send_message(AllMessages::NetworkBridge(
+		NetworkBridgeMessage::ResolveValidators(authorities, tx),
+	)).await?;
+	let validator_peers = rx.await?;
+
+	let mut group_peers: HashMap<ParaId, HashSet<PeerId>> = HashMap::new();
+	for (i, duty) in roster.validator_duty.iter().enumerate() {
+		if let (Chain::Parachain(id), Some(Some(peer))) = (duty, validator_peers.get(i)) {
+			group_peers.entry(*id).or_default().insert(peer.clone());
+		}
+	}
+
+	Ok(Some(BlockBasedState {
+		known: HashMap::new(),
+		fetching: HashMap::new(),
+		paras: HashMap::new(),
+		n_validators: validators.len(),
+		group_peers,
+	}))
+}
+
+async fn handle_fetch(
+	ctx: &mut impl SubsystemContext<Message = PoVDistributionMessage>,
+	state: &mut State,
+	relay_parent: Hash,
+	candidate: AbridgedCandidateReceipt,
+	response_sender: oneshot::Sender<PoVBlock>,
+) -> SubsystemResult<()> {
+	let relay_parent_state = match state.relay_parent_state.get_mut(&relay_parent) {
+		Some(relay_parent_state) => relay_parent_state,
+		None => return Ok(()),
+	};
+
+	let pov_hash = candidate.pov_block_hash;
+	if let Some(pov) = relay_parent_state.known.get(&pov_hash) {
+		let _ = response_sender.send(pov.clone());
+		return Ok(());
+	}
+
+	if let Some(response_senders) = relay_parent_state.fetching.get_mut(&pov_hash) {
+		response_senders.push(response_sender);
+		return Ok(());
+	}
+
+	// peers may only await `n_validators` PoVs from us, so we do not await more either.
+	// dropping the response sender fails the fetch.
+	if relay_parent_state.fetching.len() >= relay_parent_state.n_validators {
+		log::warn!(
+			target: "pov_distribution",
+			"Not fetching PoV {} at relay-parent {}: already awaiting {} PoVs",
+			pov_hash,
+			relay_parent,
+			relay_parent_state.fetching.len(),
+		);
+
+		return Ok(());
+	}
+
+	relay_parent_state.fetching.insert(pov_hash, vec![response_sender]);
+	relay_parent_state.paras.insert(pov_hash, candidate.parachain_index);
+
+	// notify the validators assigned to the para which are interested in the relay-parent
+	// that we await the PoV.
+	let peers: Vec<_> = state.peer_state.iter()
+		.filter(|(peer, peer_state)| {
+			peer_state.awaited.contains_key(&relay_parent)
+				&& relay_parent_state.in_group_of(&pov_hash, peer)
+		})
+		.map(|(peer, _)| peer.clone())
+		.collect();
+
+	send_message(ctx, peers, WireMessage::Awaiting(relay_parent, vec![pov_hash])).await
+}
+
+async fn handle_distribute(
+	ctx: &mut impl SubsystemContext<Message = PoVDistributionMessage>,
+	state: &mut State,
+	relay_parent: Hash,
+	candidate: AbridgedCandidateReceipt,
+	pov: PoVBlock,
+) -> SubsystemResult<()> {
+	match state.relay_parent_state.get_mut(&relay_parent) {
+		Some(relay_parent_state) => {
+			relay_parent_state.paras.insert(candidate.pov_block_hash, candidate.parachain_index);
+		}
+		None => return Ok(()),
+	}
+
+	note_pov(ctx, state, relay_parent, candidate.pov_block_hash, pov).await
+}
+
+/// Note a PoV we have obtained: answer outstanding fetches, send it to the peers of its
+/// para's validators awaiting it and keep it for those which await it later.
+async fn note_pov(
+	ctx: &mut impl SubsystemContext<Message = PoVDistributionMessage>,
+	state: &mut State,
+	relay_parent: Hash,
+	pov_hash: Hash,
+	pov: PoVBlock,
+) -> SubsystemResult<()> {
+	let relay_parent_state = match state.relay_parent_state.get_mut(&relay_parent) {
+		Some(relay_parent_state) => relay_parent_state,
+		None => return Ok(()),
+	};
+
+	for response_sender in relay_parent_state.fetching.remove(&pov_hash).into_iter().flatten() {
+		let _ = response_sender.send(pov.clone());
+	}
+
+	let peers: Vec<_> = state.peer_state.iter_mut()
+		.filter_map(|(peer, peer_state)| {
+			let awaited = peer_state.awaited.get_mut(&relay_parent)?;
+			if awaited.remove(&pov_hash) && relay_parent_state.in_group_of(&pov_hash, peer) {
+				Some(peer.clone())
+			} else {
+				None
+			}
+		})
+		.collect();
+
+	relay_parent_state.known.insert(pov_hash, pov.clone());
+
+	send_message(ctx, peers, WireMessage::SendPoV(relay_parent, pov_hash, pov)).await
+}
+
+async fn handle_network_update(
+	ctx: &mut impl SubsystemContext<Message = PoVDistributionMessage>,
+	state: &mut State,
+	event: NetworkBridgeEvent,
+) -> SubsystemResult<()> {
+	match event {
+		NetworkBridgeEvent::PeerConnected(peer, _role) => {
+			state.peer_state.insert(peer, PeerState::default());
+		}
+		NetworkBridgeEvent::PeerDisconnected(peer) => {
+			state.peer_state.remove(&peer);
+		}
+		NetworkBridgeEvent::PeerViewChange(peer, view) => {
+			let peer_state = match state.peer_state.get_mut(&peer) {
+				Some(peer_state) => peer_state,
+				None => return Ok(()),
+			};
+
+			peer_state.awaited.retain(|relay_parent, _| view.0.contains(relay_parent));
+
+			let mut added = Vec::new();
+			for relay_parent in view.0 {
+				if !peer_state.awaited.contains_key(&relay_parent) {
+					peer_state.awaited.insert(relay_parent, HashSet::new());
+					added.push(relay_parent);
+				}
+			}
+
+			// let the peer know which PoVs we await in the context of its new relay-parents.
+			for relay_parent in added {
+				let awaited: Vec<_> = match state.relay_parent_state.get(&relay_parent) {
+					Some(relay_parent_state) => relay_parent_state.fetching.keys()
+						.filter(|pov_hash| relay_parent_state.in_group_of(pov_hash, &peer))
+						.cloned()
+						.collect(),
+					None => continue,
+				};
+
+				if !awaited.is_empty() {
+					send_message(ctx, vec![peer.clone()], WireMessage::Awaiting(relay_parent, awaited)).await?;
+				}
+			}
+		}
+		NetworkBridgeEvent::OurViewChange(view) => {
+			// cancel any outstanding fetches for relay-parents which have left our view.
+			for removed in state.our_view.0.iter().filter(|h| !view.0.contains(h)) {
+				if let Some(relay_parent_state) = state.relay_parent_state.get_mut(removed) {
+					relay_parent_state.fetching.clear();
+				}
+			}
+
+			state.our_view = view;
+		}
+		NetworkBridgeEvent::PeerMessage(peer, bytes) => {
+			if !state.peer_state.contains_key(&peer) {
+				return report_peer(ctx, peer, COST_MALFORMED_MESSAGE).await;
+			}
+
+			match WireMessage::decode(&mut &bytes[..]) {
+				Ok(WireMessage::Awaiting(relay_parent, pov_hashes)) => {
+					handle_awaiting(ctx, state, peer, relay_parent, pov_hashes).await?;
+				}
+				Ok(WireMessage::SendPoV(relay_parent, pov_hash, pov)) => {
+					handle_incoming_pov(ctx, state, peer, relay_parent, pov_hash, pov).await?;
+				}
+				Err(_) => report_peer(ctx, peer, COST_MALFORMED_MESSAGE).await?,
+			}
+		}
+	}
+
+	Ok(())
+}
+
+async fn handle_awaiting(
+	ctx: &mut impl SubsystemContext<Message = PoVDistributionMessage>,
+	state: &mut State,
+	peer: PeerId,
+	relay_parent: Hash,
+	pov_hashes: Vec<Hash>,
+) -> SubsystemResult<()> {
+	let relay_parent_state = match state.relay_parent_state.get(&relay_parent) {
+		Some(relay_parent_state) if state.our_view.0.contains(&relay_parent) => relay_parent_state,
+		_ => return report_peer(ctx, peer, COST_AWAITED_NOT_IN_VIEW).await,
+	};
+
+	if !relay_parent_state.in_any_group(&peer) {
+		return report_peer(ctx, peer, COST_NOT_IN_GROUP).await;
+	}
+
+	let awaited = match state.peer_state.get_mut(&peer).and_then(|p| p.awaited.get_mut(&relay_parent)) {
+		Some(awaited) => awaited,
+		None => return report_peer(ctx, peer, COST_AWAITED_NOT_IN_VIEW).await,
+	};
+
+	if awaited.len() + pov_hashes.len() > relay_parent_state.n_validators {
+		return report_peer(ctx, peer, COST_APPARENT_FLOOD).await;
+	}
+
+	let mut messages = Vec::new();
+	for pov_hash in pov_hashes {
+		match relay_parent_state.known.get(&pov_hash) {
+			Some(pov) => if relay_parent_state.in_group_of(&pov_hash, &peer) {
+				messages.push(WireMessage::SendPoV(relay_parent, pov_hash, pov.clone()));
+			},
+			None => { awaited.insert(pov_hash); }
+		}
+	}
+
+	for message in messages {
+		send_message(ctx, vec![peer.clone()], message).await?;
+	}
+
+	Ok(())
+}
+
+async fn handle_incoming_pov(
+	ctx: &mut impl SubsystemContext<Message = PoVDistributionMessage>,
+	state: &mut State,
+	peer: PeerId,
+	relay_parent: Hash,
+	pov_hash: Hash,
+	pov: PoVBlock,
+) -> SubsystemResult<()> {
+	let awaited = state.relay_parent_state.get(&relay_parent)
+		.map_or(false, |s| s.fetching.contains_key(&pov_hash));
+
+	if !awaited {
+		return report_peer(ctx, peer, COST_UNEXPECTED_POV).await;
+	}
+
+	if pov.hash() != pov_hash {
+		return report_peer(ctx, peer, COST_WRONG_HASH).await;
+	}
+
+	report_peer(ctx, peer, BENEFIT_FRESH_POV).await?;
+	note_pov(ctx, state, relay_parent, pov_hash, pov).await
+}
+
+async fn send_message(
+	ctx: &mut impl SubsystemContext<Message = PoVDistributionMessage>,
+	peers: Vec<PeerId>,
+	message: WireMessage,
+) -> SubsystemResult<()> {
+	if peers.is_empty() {
+		return Ok(());
+	}
+
+	ctx.send_message(AllMessages::NetworkBridge(
+		NetworkBridgeMessage::SendMessage(peers, PROTOCOL_V1, message.encode()),
+	)).await
+}
+
+async fn report_peer(
+	ctx: &mut impl SubsystemContext<Message = PoVDistributionMessage>,
+	peer: PeerId,
+	rep: ReputationChange,
+) -> SubsystemResult<()> {
+	ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(peer, rep))).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::{self, ThreadPool};
+	use assert_matches::assert_matches;
+	use sp_keyring::sr25519::Keyring;
+	use sc_network::ObservedRole;
+
+	use polkadot_primitives::parachain::{BlockData, DutyRoster, ValidatorId};
+
+	type VirtualOverseer = subsystem_test::TestSubsystemContextHandle<PoVDistributionMessage>;
+
+	struct TestHarness {
+		virtual_overseer: VirtualOverseer,
+	}
+
+	fn test_harness<T: Future<Output=()>>(test: impl FnOnce(TestHarness) -> T) {
+		let pool = ThreadPool::new().unwrap();
+
+		let (context, virtual_overseer) = subsystem_test::make_subsystem_context(pool);
+
+		let subsystem = run(context)
+			.map_err(|_| panic!("subsystem execution failed"))
+			.map(|_| ());
+
+		let test_fut = test(TestHarness { virtual_overseer });
+
+		futures::pin_mut!(test_fut);
+		futures::pin_mut!(subsystem);
+
+		executor::block_on(future::select(test_fut, subsystem));
+	}
+
+	fn test_pov() -> PoVBlock {
+		PoVBlock { block_data: BlockData(vec![42, 43, 44]) }
+	}
+
+	fn candidate_for(pov: &PoVBlock) -> AbridgedCandidateReceipt {
+		AbridgedCandidateReceipt {
+			pov_block_hash: pov.hash(),
+			..Default::default()
+		}
+	}
+
+	async fn network_update(virtual_overseer: &mut VirtualOverseer, event: NetworkBridgeEvent) {
+		virtual_overseer.send(FromOverseer::Communication {
+			msg: PoVDistributionMessage::NetworkBridgeUpdate(event),
+		}).await;
+	}
+
+	/// Start work on the relay-parent, with the given peers connected and interested in it.
+	///
+	/// Alice and Bob are assigned to the para of the test candidates and Charlie validates
+	/// the relay chain. The `group` peers are those of Alice and Bob, the first of the
+	/// `others` is that of Charlie.
+	async fn setup(
+		virtual_overseer: &mut VirtualOverseer,
+		relay_parent: Hash,
+		group: &[PeerId],
+		others: &[PeerId],
+	) {
+		let keyrings = [Keyring::Alice, Keyring::Bob, Keyring::Charlie];
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::RegisterEventProducer(p, _))
+				if p == PROTOCOL_V1
+		);
+
+		virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+			ActiveLeavesUpdate::start_work(relay_parent),
+		))).await;
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::Validators(tx),
+			)) if r == relay_parent => {
+				tx.send(Ok(keyrings.iter().map(|k| ValidatorId::from(k.public())).collect())).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::DutyRoster(tx),
+			)) if r == relay_parent => {
+				let para = ParaId::default();
+				tx.send(Ok(DutyRoster {
+					validator_duty: vec![Chain::Parachain(para), Chain::Parachain(para), Chain::Relay],
+				})).unwrap();
+			}
+		);
+
+		let authorities: Vec<_> = keyrings.iter().map(|k| AuthorityDiscoveryId::from(k.public())).collect();
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::AuthorityDiscoveryKeys(tx),
+			)) if r == relay_parent => {
+				tx.send(Ok(authorities.clone())).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::ResolveValidators(a, tx)) => {
+				assert_eq!(a, authorities);
+				tx.send(vec![group.get(0).cloned(), group.get(1).cloned(), others.get(0).cloned()]).unwrap();
+			}
+		);
+
+		network_update(virtual_overseer, NetworkBridgeEvent::OurViewChange(View(vec![relay_parent]))).await;
+
+		for peer in group.iter().chain(others) {
+			network_update(virtual_overseer, NetworkBridgeEvent::PeerConnected(peer.clone(), ObservedRole::Full)).await;
+			network_update(virtual_overseer, NetworkBridgeEvent::PeerViewChange(peer.clone(), View(vec![relay_parent]))).await;
+		}
+	}
+
+	async fn expect_send(virtual_overseer: &mut VirtualOverseer, expected_peers: Vec<PeerId>, message: WireMessage) {
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::SendMessage(peers, p, m)) => {
+				assert_eq!(peers, expected_peers);
+				assert_eq!(p, PROTOCOL_V1);
+				assert_eq!(m, message.encode());
+			}
+		);
+	}
+
+	#[test]
+	fn fetched_pov_is_requested_from_peers() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let peer = PeerId::random();
+			let pov = test_pov();
+			let pov_hash = pov.hash();
+
+			setup(&mut virtual_overseer, relay_parent, &[peer.clone()], &[]).await;
+
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: PoVDistributionMessage::FetchPoV(relay_parent, candidate_for(&pov), tx),
+			}).await;
+
+			expect_send(&mut virtual_overseer, vec![peer.clone()], WireMessage::Awaiting(relay_parent, vec![pov_hash])).await;
+
+			network_update(
+				&mut virtual_overseer,
+				NetworkBridgeEvent::PeerMessage(peer.clone(), WireMessage::SendPoV(relay_parent, pov_hash, pov.clone()).encode()),
+			).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep))
+					if p == peer && rep == BENEFIT_FRESH_POV
+			);
+
+			assert_eq!(rx.await.unwrap(), pov);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn distributed_pov_is_sent_only_to_awaiting_peers() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let peer_a = PeerId::random();
+			let peer_b = PeerId::random();
+			let pov = test_pov();
+			let pov_hash = pov.hash();
+
+			setup(&mut virtual_overseer, relay_parent, &[peer_a.clone(), peer_b.clone()], &[]).await;
+
+			network_update(
+				&mut virtual_overseer,
+				NetworkBridgeEvent::PeerMessage(peer_a.clone(), WireMessage::Awaiting(relay_parent, vec![pov_hash]).encode()),
+			).await;
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: PoVDistributionMessage::DistributePoV(relay_parent, candidate_for(&pov), pov.clone()),
+			}).await;
+
+			expect_send(&mut virtual_overseer, vec![peer_a], WireMessage::SendPoV(relay_parent, pov_hash, pov.clone())).await;
+
+			// a peer awaiting the PoV later gets it right away.
+			network_update(
+				&mut virtual_overseer,
+				NetworkBridgeEvent::PeerMessage(peer_b.clone(), WireMessage::Awaiting(relay_parent, vec![pov_hash]).encode()),
+			).await;
+
+			expect_send(&mut virtual_overseer, vec![peer_b], WireMessage::SendPoV(relay_parent, pov_hash, pov)).await;
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn fetch_is_canceled_when_relay_parent_leaves_view() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+
+			setup(&mut virtual_overseer, relay_parent, &[], &[]).await;
+
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: PoVDistributionMessage::FetchPoV(relay_parent, candidate_for(&test_pov()), tx),
+			}).await;
+
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::OurViewChange(View(Vec::new()))).await;
+
+			assert!(rx.await.is_err());
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn unexpected_or_wrong_povs_are_punished() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let peer = PeerId::random();
+			let pov = test_pov();
+			let pov_hash = pov.hash();
+
+			setup(&mut virtual_overseer, relay_parent, &[peer.clone()], &[]).await;
+
+			network_update(
+				&mut virtual_overseer,
+				NetworkBridgeEvent::PeerMessage(peer.clone(), WireMessage::SendPoV(relay_parent, pov_hash, pov.clone()).encode()),
+			).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep))
+					if p == peer && rep == COST_UNEXPECTED_POV
+			);
+
+			let (tx, _rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: PoVDistributionMessage::FetchPoV(relay_parent, candidate_for(&pov), tx),
+			}).await;
+
+			expect_send(&mut virtual_overseer, vec![peer.clone()], WireMessage::Awaiting(relay_parent, vec![pov_hash])).await;
+
+			let wrong_pov = PoVBlock { block_data: BlockData(vec![1]) };
+			network_update(
+				&mut virtual_overseer,
+				NetworkBridgeEvent::PeerMessage(peer.clone(), WireMessage::SendPoV(relay_parent, pov_hash, wrong_pov).encode()),
+			).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep))
+					if p == peer && rep == COST_WRONG_HASH
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn povs_are_only_exchanged_with_the_validators_of_the_para() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let group_peer = PeerId::random();
+			let other_validator = PeerId::random();
+			let non_validator = PeerId::random();
+			let pov = test_pov();
+			let pov_hash = pov.hash();
+
+			setup(
+				&mut virtual_overseer,
+				relay_parent,
+				&[group_peer.clone()],
+				&[other_validator.clone(), non_validator.clone()],
+			).await;
+
+			// only the validator assigned to the para is notified that we await the PoV.
+			let (tx, rx) = oneshot::channel();
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: PoVDistributionMessage::FetchPoV(relay_parent, candidate_for(&pov), tx),
+			}).await;
+
+			expect_send(&mut virtual_overseer, vec![group_peer.clone()], WireMessage::Awaiting(relay_parent, vec![pov_hash])).await;
+
+			network_update(
+				&mut virtual_overseer,
+				NetworkBridgeEvent::PeerMessage(non_validator.clone(), WireMessage::Awaiting(relay_parent, vec![pov_hash]).encode()),
+			).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep))
+					if p == non_validator && rep == COST_NOT_IN_GROUP
+			);
+
+			// a validator of another para may await the PoV, but is never served it.
+			network_update(
+				&mut virtual_overseer,
+				NetworkBridgeEvent::PeerMessage(other_validator.clone(), WireMessage::Awaiting(relay_parent, vec![pov_hash]).encode()),
+			).await;
+
+			network_update(
+				&mut virtual_overseer,
+				NetworkBridgeEvent::PeerMessage(group_peer.clone(), WireMessage::SendPoV(relay_parent, pov_hash, pov.clone()).encode()),
+			).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep))
+					if p == group_peer && rep == BENEFIT_FRESH_POV
+			);
+
+			assert_eq!(rx.await.unwrap(), pov);
+
+			network_update(
+				&mut virtual_overseer,
+				NetworkBridgeEvent::PeerMessage(other_validator.clone(), WireMessage::Awaiting(relay_parent, vec![pov_hash]).encode()),
+			).await;
+
+			// nothing is sent to the other validator before we react to the next message.
+			network_update(
+				&mut virtual_overseer,
+				NetworkBridgeEvent::PeerMessage(non_validator.clone(), WireMessage::Awaiting(relay_parent, Vec::new()).encode()),
+			).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep))
+					if p == non_validator && rep == COST_NOT_IN_GROUP
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn fetches_beyond_the_number_of_validators_fail() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+
+			setup(&mut virtual_overseer, relay_parent, &[], &[]).await;
+
+			let mut receivers = Vec::new();
+			for i in 0..4u8 {
+				let pov = PoVBlock { block_data: BlockData(vec![i]) };
+				let (tx, rx) = oneshot::channel();
+				virtual_overseer.send(FromOverseer::Communication {
+					msg: PoVDistributionMessage::FetchPoV(relay_parent, candidate_for(&pov), tx),
+				}).await;
+
+				receivers.push(rx);
+			}
+
+			// there are 3 validators, so the last fetch fails while the others are pending.
+			let last = receivers.pop().unwrap();
+			assert!(last.await.is_err());
+
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::OurViewChange(View(Vec::new()))).await;
+
+			for rx in receivers {
+				assert!(rx.await.is_err());
+			}
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+}
@@ -0,0 +1,836 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The Statement Distribution subsystem.
+//!
+//! Gossips signed backing statements among validators, according to the views of our peers.
+//! `Valid` and `Invalid` statements are only accepted and sent after a `Seconded` statement for
+//! the same candidate, and statements received from peers are passed on to Candidate Backing.
+//! The network bridge is asked to keep connections open to the current validators.
+
+use parity_scale_codec::{Encode, Decode};
+use futures::channel::oneshot;
+use futures::prelude::*;
+
+use sc_network::{PeerId, ReputationChange};
+use sp_authority_discovery::AuthorityId as AuthorityDiscoveryId;
+
+use polkadot_subsystem::{
+	Subsystem, SubsystemContext, SpawnedSubsystem, SubsystemResult,
+	FromOverseer, OverseerSignal, ActiveLeavesUpdate,
+};
+use polkadot_subsystem::messages::{
+	AllMessages, CandidateBackingMessage, NetworkBridgeEvent, NetworkBridgeMessage,
+	RuntimeApiMessage, RuntimeApiRequest, StatementDistributionMessage,
+};
+use node_primitives::{ProtocolId, SignedFullStatement, View};
+use polkadot_primitives::Hash;
+use polkadot_primitives::parachain::{
+	CompactStatement, SigningContext, ValidatorId, ValidatorIndex,
+};
+
+use std::collections::{HashMap, HashSet};
+
+/// The protocol identifier for statement distribution.
+pub const PROTOCOL_V1: ProtocolId = *b"stmd";
+
+const COST_UNEXPECTED_STATEMENT: ReputationChange
+	= ReputationChange::new(-100, "Unexpected statement");
+const COST_INVALID_SIGNATURE: ReputationChange
+	= ReputationChange::new(-500, "Invalid statement signature");
+const COST_DUPLICATE_STATEMENT: ReputationChange
+	= ReputationChange::new(-250, "Statement sent more than once by peer");
+const COST_MESSAGE_NOT_DECODABLE: ReputationChange
+	= ReputationChange::new(-100, "Malformed statement message");
+const BENEFIT_VALID_STATEMENT_FIRST: ReputationChange
+	= ReputationChange::new(25, "Peer was the first to send us a valid statement");
+
+/// Messages sent over the network by this subsystem.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub enum WireMessage {
+	/// A signed full statement under the given relay-parent.
+	#[codec(index = "0")]
+	Statement(Hash, SignedFullStatement),
+}
+
+/// The statement distribution subsystem.
+#[derive(Clone)]
+pub struct StatementDistribution;
+
+impl<C> Subsystem<C> for StatementDistribution
+	where C: SubsystemContext<Message = StatementDistributionMessage>
+{
+	fn start(self, ctx: C) -> SpawnedSubsystem {
+		SpawnedSubsystem(run(ctx).map(|_| ()).boxed())
+	}
+}
+
+/// Identifies a statement by its contents and the validator who signed it.
+type StatementFingerprint = (CompactStatement, ValidatorIndex);
+
+fn fingerprint(statement: &SignedFullStatement) -> StatementFingerprint {
+	(statement.payload().to_compact(), statement.validator_index())
+}
+
+fn candidate_hash(compact: &CompactStatement) -> Hash {
+	match *compact {
+		CompactStatement::Candidate(hash)
+			| CompactStatement::Valid(hash)
+			| CompactStatement::Invalid(hash) => hash,
+	}
+}
+
+/// Data kept for each relay-parent we are working on.
+struct ActiveHeadData {
+	signing_context: SigningContext,
+	validators: Vec<ValidatorId>,
+	/// All statements we know about, in the order we learned of them. A `Seconded` statement
+	/// for a candidate always precedes any `Valid` or `Invalid` statements about it.
+	statements: Vec<(StatementFingerprint, SignedFullStatement)>,
+	known_statements: HashSet<StatementFingerprint>,
+	/// Candidates for which we have seen a `Seconded` statement.
+	seconded_candidates: HashSet<Hash>,
+	/// The statements each peer is known to have.
+	peer_knowledge: HashMap<PeerId, PeerKnowledge>,
+}
+
+/// The statements a peer knows, kept apart by direction: a peer may legitimately send us
+/// a statement we have just sent it, but never the same one twice.
+#[derive(Default)]
+struct PeerKnowledge {
+	/// The statements we sent to the peer.
+	sent: HashSet<StatementFingerprint>,
+	/// The statements the peer sent to us.
+	received: HashSet<StatementFingerprint>,
+}
+
+impl PeerKnowledge {
+	fn knows(&self, fingerprint: &StatementFingerprint) -> bool {
+		self.sent.contains(fingerprint) || self.received.contains(fingerprint)
+	}
+
+	/// Note that we send the statement to the peer, returning whether the peer didn't
+	/// know it yet.
+	fn send(&mut self, fingerprint: &StatementFingerprint) -> bool {
+		if self.knows(fingerprint) {
+			return false;
+		}
+
+		self.sent.insert(fingerprint.clone())
+	}
+}
+
+impl ActiveHeadData {
+	fn new(signing_context: SigningContext, validators: Vec<ValidatorId>) -> Self {
+		ActiveHeadData {
+			signing_context,
+			validators,
+			statements: Vec::new(),
+			known_statements: HashSet::new(),
+			seconded_candidates: HashSet::new(),
+			peer_knowledge: HashMap::new(),
+		}
+	}
+
+	/// Note a new statement. Returns `false` if the statement was already known.
+	fn note_statement(&mut self, statement: SignedFullStatement) -> bool {
+		let fingerprint = fingerprint(&statement);
+		if !self.known_statements.insert(fingerprint.clone()) {
+			return false;
+		}
+
+		if let CompactStatement::Candidate(hash) = fingerprint.0 {
+			self.seconded_candidates.insert(hash);
+		}
+
+		self.statements.push((fingerprint, statement));
+		true
+	}
+
+	/// Whether we would accept the statement at this point, according to the data-dependency
+	/// order of statements.
+	fn is_acceptable(&self, compact: &CompactStatement) -> bool {
+		match *compact {
+			CompactStatement::Candidate(_) => true,
+			CompactStatement::Valid(hash) | CompactStatement::Invalid(hash)
+				=> self.seconded_candidates.contains(&hash),
+		}
+	}
+
+	/// Get the encoded messages for all statements matching the filter which the peer doesn't
+	/// know yet, in order, noting them as known by the peer.
+	fn messages_for_peer(
+		&mut self,
+		relay_parent: Hash,
+		peer: &PeerId,
+		filter: impl Fn(&StatementFingerprint) -> bool,
+	) -> Vec<Vec<u8>> {
+		let knowledge = self.peer_knowledge.entry(peer.clone()).or_default();
+
+		self.statements.iter()
+			.filter(|(fingerprint, _)| filter(fingerprint))
+			.filter(|(fingerprint, _)| knowledge.send(fingerprint))
+			.map(|(_, statement)| WireMessage::Statement(relay_parent, statement.clone()).encode())
+			.collect()
+	}
+}
+
+#[derive(Default)]
+struct State {
+	active_heads: HashMap<Hash, ActiveHeadData>,
+	peer_views: HashMap<PeerId, View>,
+	/// The validators we last asked the network bridge to keep connections open to.
+	connected_validators: Vec<AuthorityDiscoveryId>,
+}
+
+fn network_update_message(n: NetworkBridgeEvent) -> AllMessages {
+	AllMessages::StatementDistribution(StatementDistributionMessage::NetworkBridgeUpdate(n))
+}
+
+async fn run(mut ctx: impl SubsystemContext<Message = StatementDistributionMessage>) -> SubsystemResult<()> {
+	ctx.send_message(AllMessages::NetworkBridge(
+		NetworkBridgeMessage::RegisterEventProducer(PROTOCOL_V1, network_update_message),
+	)).await?;
+
+	let mut state = State::default();
+
+	loop {
+		match ctx.recv().await? {
+			FromOverseer::Signal(OverseerSignal::ActiveLeaves(ActiveLeavesUpdate { activated, deactivated })) => {
+				for relay_parent in activated {
+					let (validators, signing_context) = match query_basics(&mut ctx, relay_parent).await? {
+						Some(basics) => basics,
+						None => continue,
+					};
+
+					state.active_heads.insert(relay_parent, ActiveHeadData::new(signing_context, validators));
+
+					connect_to_validators(&mut ctx, &mut state, relay_parent).await?;
+				}
+
+				for relay_parent in deactivated {
+					state.active_heads.remove(&relay_parent);
+				}
+			}
+			FromOverseer::Signal(OverseerSignal::BlockFinalized(..)) => {}
+			FromOverseer::Signal(OverseerSignal::Conclude) => return Ok(()),
+			FromOverseer::Communication { msg } => match msg {
+				StatementDistributionMessage::Share(relay_parent, statement) => {
+					handle_our_statement(&mut ctx, &mut state, relay_parent, statement).await?;
+				}
+				StatementDistributionMessage::NetworkBridgeUpdate(event) => {
+					handle_network_update(&mut ctx, &mut state, event).await?;
+				}
+			},
+		}
+	}
+}
+
+async fn query_basics(
+	ctx: &mut impl SubsystemContext<Message = StatementDistributionMessage>,
+	relay_parent: Hash,
+) -> SubsystemResult<Option<(Vec<ValidatorId>, SigningContext)>> {
+	let (validators_tx, validators_rx) = oneshot::channel();
+	let (signing_context_tx, signing_context_rx) = oneshot::channel();
+
+	ctx.send_messages(vec![
+		AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+			relay_parent,
+			RuntimeApiRequest::Validators(validators_tx),
+		)),
+		AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+			relay_parent,
+			RuntimeApiRequest::SigningContext(signing_context_tx),
+		)),
+	].into_iter()).await?;
+
+	match (validators_rx.await?, signing_context_rx.await?) {
+		(Ok(validators), Ok(signing_context)) => Ok(Some((validators, signing_context))),
+		(Err(e), _) | (_, Err(e)) => {
+			log::warn!(
+				target: "statement_distribution",
+				"Failed to query the runtime API at relay-parent {}: {:?}",
+				relay_parent,
+				e,
+			);
+
+			Ok(None)
+		}
+	}
+}
+
+/// Ask the network bridge to keep connections open to the validators at the given relay-parent,
+/// as those are the peers statements are exchanged with.
+async fn connect_to_validators(
+	ctx: &mut impl SubsystemContext<Message = StatementDistributionMessage>,
+	state: &mut State,
+	relay_parent: Hash,
+) -> SubsystemResult<()> {
+	let (tx, rx) = oneshot::channel();
+	ctx.send_message(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+		relay_parent,
+		RuntimeApiRequest::AuthorityDiscoveryKeys(tx),
+	))).await?;
+
+	let validators = match rx.await? {
+		Ok(validators) => validators,
+		Err(e) => {
+			log::warn!(
+				target: "statement_distribution",
+				"Failed to query the authority discovery keys at relay-parent {}: {:?}",
+				relay_parent,
+				e,
+			);
+
+			return Ok(());
+		}
+	};
+
+	if validators == state.connected_validators {
+		return Ok(());
+	}
+
+	state.connected_validators = validators.clone();
+	ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::ConnectToValidators(validators))).await
+}
+
+async fn handle_our_statement(
+	ctx: &mut impl SubsystemContext<Message = StatementDistributionMessage>,
+	state: &mut State,
+	relay_parent: Hash,
+	statement: SignedFullStatement,
+) -> SubsystemResult<()> {
+	let active_head = match state.active_heads.get_mut(&relay_parent) {
+		Some(active_head) => active_head,
+		None => {
+			log::debug!(
+				target: "statement_distribution",
+				"Not sharing our statement for relay-parent {} not in our view",
+				relay_parent,
+			);
+
+			return Ok(());
+		}
+	};
+
+	let compact = statement.payload().to_compact();
+	if !active_head.note_statement(statement) {
+		return Ok(());
+	}
+
+	circulate_statement(ctx, active_head, &state.peer_views, relay_parent, candidate_hash(&compact)).await
+}
+
+async fn handle_network_update(
+	ctx: &mut impl SubsystemContext<Message = StatementDistributionMessage>,
+	state: &mut State,
+	event: NetworkBridgeEvent,
+) -> SubsystemResult<()> {
+	match event {
+		NetworkBridgeEvent::PeerConnected(peer, _role) => {
+			state.peer_views.insert(peer, View(Vec::new()));
+		}
+		NetworkBridgeEvent::PeerDisconnected(peer) => {
+			state.peer_views.remove(&peer);
+			for active_head in state.active_heads.values_mut() {
+				active_head.peer_knowledge.remove(&peer);
+			}
+		}
+		NetworkBridgeEvent::PeerViewChange(peer, view) => {
+			handle_peer_view_change(ctx, state, peer, view).await?;
+		}
+		NetworkBridgeEvent::OurViewChange(_) => {
+			// the relay-parents we work on are determined by `ActiveLeaves` signals.
+		}
+		NetworkBridgeEvent::PeerMessage(peer, bytes) => {
+			match WireMessage::decode(&mut &bytes[..]) {
+				Ok(WireMessage::Statement(relay_parent, statement)) => {
+					handle_incoming_statement(ctx, state, peer, relay_parent, statement).await?;
+				}
+				Err(_) => report_peer(ctx, peer, COST_MESSAGE_NOT_DECODABLE).await?,
+			}
+		}
+	}
+
+	Ok(())
+}
+
+async fn handle_peer_view_change(
+	ctx: &mut impl SubsystemContext<Message = StatementDistributionMessage>,
+	state: &mut State,
+	peer: PeerId,
+	view: View,
+) -> SubsystemResult<()> {
+	let old_view = match state.peer_views.insert(peer.clone(), view.clone()) {
+		Some(old_view) => old_view,
+		None => {
+			log::trace!(target: "statement_distribution", "View change from unknown peer {}", peer);
+			View(Vec::new())
+		}
+	};
+
+	// forget what the peer knows about relay-parents it is no longer interested in, so
+	// that the statements are sent again should they come back into its view.
+	for removed in old_view.0.iter().filter(|h| !view.0.contains(h)) {
+		if let Some(active_head) = state.active_heads.get_mut(removed) {
+			active_head.peer_knowledge.remove(&peer);
+		}
+	}
+
+	for added in view.0.iter().filter(|h| !old_view.0.contains(h)) {
+		let active_head = match state.active_heads.get_mut(added) {
+			Some(active_head) => active_head,
+			None => continue,
+		};
+
+		let messages = active_head.messages_for_peer(*added, &peer, |_| true);
+		send_to_peer(ctx, &peer, messages).await?;
+	}
+
+	Ok(())
+}
+
+async fn handle_incoming_statement(
+	ctx: &mut impl SubsystemContext<Message = StatementDistributionMessage>,
+	state: &mut State,
+	peer: PeerId,
+	relay_parent: Hash,
+	statement: SignedFullStatement,
+) -> SubsystemResult<()> {
+	let active_head = match state.active_heads.get_mut(&relay_parent) {
+		Some(active_head) => active_head,
+		None => return report_peer(ctx, peer, COST_UNEXPECTED_STATEMENT).await,
+	};
+
+	let fingerprint = fingerprint(&statement);
+	if !active_head.is_acceptable(&fingerprint.0) {
+		return report_peer(ctx, peer, COST_UNEXPECTED_STATEMENT).await;
+	}
+
+	let signature_valid = active_head.validators.get(statement.validator_index() as usize)
+		.map_or(false, |v| statement.check_signature(&active_head.signing_context, v).is_ok());
+
+	if !signature_valid {
+		return report_peer(ctx, peer, COST_INVALID_SIGNATURE).await;
+	}
+
+	if !active_head.peer_knowledge.entry(peer.clone()).or_default().received.insert(fingerprint.clone()) {
+		return report_peer(ctx, peer, COST_DUPLICATE_STATEMENT).await;
+	}
+
+	// we already know the statement and have circulated it.
+	if !active_head.note_statement(statement.clone()) {
+		return Ok(());
+	}
+
+	report_peer(ctx, peer, BENEFIT_VALID_STATEMENT_FIRST).await?;
+
+	ctx.send_message(AllMessages::CandidateBacking(
+		CandidateBackingMessage::Statement(relay_parent, statement),
+	)).await?;
+
+	circulate_statement(ctx, active_head, &state.peer_views, relay_parent, candidate_hash(&fingerprint.0)).await
+}
+
+/// Send all statements about the candidate to each peer interested in the relay-parent which
+/// doesn't know them yet. This ensures that peers learn of the `Seconded` statement before
+/// any `Valid` or `Invalid` statements.
+async fn circulate_statement(
+	ctx: &mut impl SubsystemContext<Message = StatementDistributionMessage>,
+	active_head: &mut ActiveHeadData,
+	peer_views: &HashMap<PeerId, View>,
+	relay_parent: Hash,
+	candidate: Hash,
+) -> SubsystemResult<()> {
+	let interested_peers = peer_views.iter()
+		.filter(|(_, view)| view.0.contains(&relay_parent))
+		.map(|(peer, _)| peer);
+
+	for peer in interested_peers {
+		let messages = active_head.messages_for_peer(
+			relay_parent,
+			peer,
+			|fingerprint| candidate_hash(&fingerprint.0) == candidate,
+		);
+
+		send_to_peer(ctx, peer, messages).await?;
+	}
+
+	Ok(())
+}
+
+async fn send_to_peer(
+	ctx: &mut impl SubsystemContext<Message = StatementDistributionMessage>,
+	peer: &PeerId,
+	messages: Vec<Vec<u8>>,
+) -> SubsystemResult<()> {
+	if messages.is_empty() {
+		return Ok(());
+	}
+
+	ctx.send_messages(messages.into_iter().map(|message| AllMessages::NetworkBridge(
+		NetworkBridgeMessage::SendMessage(vec![peer.clone()], PROTOCOL_V1, message),
+	))).await
+}
+
+async fn report_peer(
+	ctx: &mut impl SubsystemContext<Message = StatementDistributionMessage>,
+	peer: PeerId,
+	rep: ReputationChange,
+) -> SubsystemResult<()> {
+	ctx.send_message(AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(peer, rep))).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::{self, ThreadPool};
+	use assert_matches::assert_matches;
+	use sp_keyring::sr25519::Keyring;
+	use sc_network::ObservedRole;
+
+	use node_primitives::Statement;
+	use polkadot_primitives::parachain::{v2::AbridgedCandidateReceipt, ValidatorPair};
+
+	type VirtualOverseer = subsystem_test::TestSubsystemContextHandle<StatementDistributionMessage>;
+
+	struct TestHarness {
+		virtual_overseer: VirtualOverseer,
+	}
+
+	fn test_harness<T: Future<Output=()>>(test: impl FnOnce(TestHarness) -> T) {
+		let pool = ThreadPool::new().unwrap();
+
+		let (context, virtual_overseer) = subsystem_test::make_subsystem_context(pool);
+
+		let subsystem = run(context)
+			.map_err(|_| panic!("subsystem execution failed"))
+			.map(|_| ());
+
+		let test_fut = test(TestHarness { virtual_overseer });
+
+		futures::pin_mut!(test_fut);
+		futures::pin_mut!(subsystem);
+
+		executor::block_on(future::select(test_fut, subsystem));
+	}
+
+	fn validators() -> Vec<ValidatorId> {
+		vec![
+			ValidatorId::from(Keyring::Alice.public()),
+			ValidatorId::from(Keyring::Bob.public()),
+		]
+	}
+
+	fn sign_statement(
+		keyring: Keyring,
+		validator_index: ValidatorIndex,
+		statement: Statement,
+	) -> SignedFullStatement {
+		let pair = sc_keystore::Store::new_in_memory()
+			.write()
+			.insert_ephemeral_from_seed::<ValidatorPair>(&keyring.to_seed())
+			.expect("Insert key into keystore");
+
+		SignedFullStatement::sign(statement, &SigningContext::default(), validator_index, &pair)
+	}
+
+	fn candidate() -> AbridgedCandidateReceipt {
+		AbridgedCandidateReceipt {
+			relay_parent: Hash::from([1; 32]),
+			..Default::default()
+		}
+	}
+
+	async fn network_update(virtual_overseer: &mut VirtualOverseer, event: NetworkBridgeEvent) {
+		virtual_overseer.send(FromOverseer::Communication {
+			msg: StatementDistributionMessage::NetworkBridgeUpdate(event),
+		}).await;
+	}
+
+	async fn connect_peer(virtual_overseer: &mut VirtualOverseer, peer: PeerId, view: View) {
+		network_update(virtual_overseer, NetworkBridgeEvent::PeerConnected(peer.clone(), ObservedRole::Full)).await;
+		network_update(virtual_overseer, NetworkBridgeEvent::PeerViewChange(peer, view)).await;
+	}
+
+	async fn setup(virtual_overseer: &mut VirtualOverseer, relay_parent: Hash) {
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::RegisterEventProducer(p, _))
+				if p == PROTOCOL_V1
+		);
+
+		virtual_overseer.send(FromOverseer::Signal(OverseerSignal::ActiveLeaves(
+			ActiveLeavesUpdate::start_work(relay_parent),
+		))).await;
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::Validators(tx),
+			)) if r == relay_parent => {
+				tx.send(Ok(validators())).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::SigningContext(tx),
+			)) if r == relay_parent => {
+				tx.send(Ok(SigningContext::default())).unwrap();
+			}
+		);
+
+		let authority_discovery_keys: Vec<AuthorityDiscoveryId> = vec![Keyring::Alice.public().into()];
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				r,
+				RuntimeApiRequest::AuthorityDiscoveryKeys(tx),
+			)) if r == relay_parent => {
+				tx.send(Ok(authority_discovery_keys.clone())).unwrap();
+			}
+		);
+
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::ConnectToValidators(v))
+				if v == authority_discovery_keys
+		);
+	}
+
+	async fn expect_send(virtual_overseer: &mut VirtualOverseer, peer: &PeerId, message: &WireMessage) {
+		assert_matches!(
+			virtual_overseer.recv().await,
+			AllMessages::NetworkBridge(NetworkBridgeMessage::SendMessage(peers, p, m)) => {
+				assert_eq!(peers, vec![peer.clone()]);
+				assert_eq!(p, PROTOCOL_V1);
+				assert_eq!(m, message.encode());
+			}
+		);
+	}
+
+	#[test]
+	fn our_statement_is_sent_to_interested_peers() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let peer_a = PeerId::random();
+			let peer_b = PeerId::random();
+
+			setup(&mut virtual_overseer, relay_parent).await;
+
+			connect_peer(&mut virtual_overseer, peer_a.clone(), View(vec![relay_parent])).await;
+			connect_peer(&mut virtual_overseer, peer_b.clone(), View(Vec::new())).await;
+
+			let seconded = sign_statement(Keyring::Alice, 0, Statement::Seconded(candidate()));
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: StatementDistributionMessage::Share(relay_parent, seconded.clone()),
+			}).await;
+
+			expect_send(&mut virtual_overseer, &peer_a, &WireMessage::Statement(relay_parent, seconded.clone())).await;
+
+			// once the relay-parent enters the view of peer B, it gets the statement as well.
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerViewChange(peer_b.clone(), View(vec![relay_parent]))).await;
+
+			expect_send(&mut virtual_overseer, &peer_b, &WireMessage::Statement(relay_parent, seconded)).await;
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn incoming_statement_is_forwarded_to_backing_and_peers() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let peer_a = PeerId::random();
+			let peer_b = PeerId::random();
+
+			setup(&mut virtual_overseer, relay_parent).await;
+
+			connect_peer(&mut virtual_overseer, peer_a.clone(), View(vec![relay_parent])).await;
+			connect_peer(&mut virtual_overseer, peer_b.clone(), View(vec![relay_parent])).await;
+
+			let seconded = sign_statement(Keyring::Alice, 0, Statement::Seconded(candidate()));
+			let message = WireMessage::Statement(relay_parent, seconded.clone());
+
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer_a.clone(), message.encode())).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep))
+					if p == peer_a && rep == BENEFIT_VALID_STATEMENT_FIRST
+			);
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::CandidateBacking(CandidateBackingMessage::Statement(r, s))
+					if r == relay_parent && s == seconded
+			);
+
+			// relayed only to the peer which doesn't have it yet.
+			expect_send(&mut virtual_overseer, &peer_b, &message).await;
+
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer_a.clone(), message.encode())).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep))
+					if p == peer_a && rep == COST_DUPLICATE_STATEMENT
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn statement_we_sent_may_be_sent_back_once() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let peer = PeerId::random();
+
+			setup(&mut virtual_overseer, relay_parent).await;
+
+			connect_peer(&mut virtual_overseer, peer.clone(), View(vec![relay_parent])).await;
+
+			let seconded = sign_statement(Keyring::Alice, 0, Statement::Seconded(candidate()));
+			let message = WireMessage::Statement(relay_parent, seconded.clone());
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: StatementDistributionMessage::Share(relay_parent, seconded),
+			}).await;
+
+			expect_send(&mut virtual_overseer, &peer, &message).await;
+
+			// the peer may have sent the statement before receiving ours, which is not punished.
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer.clone(), message.encode())).await;
+
+			// but sending it again is.
+			network_update(&mut virtual_overseer, NetworkBridgeEvent::PeerMessage(peer.clone(), message.encode())).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep))
+					if p == peer && rep == COST_DUPLICATE_STATEMENT
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn seconded_statement_is_sent_before_valid() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let peer_a = PeerId::random();
+			let peer_b = PeerId::random();
+
+			setup(&mut virtual_overseer, relay_parent).await;
+
+			connect_peer(&mut virtual_overseer, peer_a.clone(), View(vec![relay_parent])).await;
+
+			let candidate = candidate();
+			let seconded = sign_statement(Keyring::Alice, 0, Statement::Seconded(candidate.clone()));
+			let valid = sign_statement(Keyring::Bob, 1, Statement::Valid(candidate.hash()));
+
+			// a `Valid` statement without a preceding `Seconded` is unexpected.
+			network_update(
+				&mut virtual_overseer,
+				NetworkBridgeEvent::PeerMessage(peer_a.clone(), WireMessage::Statement(relay_parent, valid.clone()).encode()),
+			).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep))
+					if p == peer_a && rep == COST_UNEXPECTED_STATEMENT
+			);
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: StatementDistributionMessage::Share(relay_parent, seconded.clone()),
+			}).await;
+
+			expect_send(&mut virtual_overseer, &peer_a, &WireMessage::Statement(relay_parent, seconded.clone())).await;
+
+			virtual_overseer.send(FromOverseer::Communication {
+				msg: StatementDistributionMessage::Share(relay_parent, valid.clone()),
+			}).await;
+
+			expect_send(&mut virtual_overseer, &peer_a, &WireMessage::Statement(relay_parent, valid.clone())).await;
+
+			// a peer connecting later gets both statements, in order.
+			connect_peer(&mut virtual_overseer, peer_b.clone(), View(vec![relay_parent])).await;
+
+			expect_send(&mut virtual_overseer, &peer_b, &WireMessage::Statement(relay_parent, seconded)).await;
+			expect_send(&mut virtual_overseer, &peer_b, &WireMessage::Statement(relay_parent, valid)).await;
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+
+	#[test]
+	fn invalid_statements_are_punished() {
+		test_harness(|test_harness| async move {
+			let TestHarness { mut virtual_overseer } = test_harness;
+
+			let relay_parent = Hash::from([1; 32]);
+			let peer = PeerId::random();
+
+			setup(&mut virtual_overseer, relay_parent).await;
+
+			connect_peer(&mut virtual_overseer, peer.clone(), View(vec![relay_parent])).await;
+
+			// signed by Alice, but claiming to be from Bob.
+			let bad_signature = sign_statement(Keyring::Alice, 1, Statement::Seconded(candidate()));
+			network_update(
+				&mut virtual_overseer,
+				NetworkBridgeEvent::PeerMessage(peer.clone(), WireMessage::Statement(relay_parent, bad_signature).encode()),
+			).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep))
+					if p == peer && rep == COST_INVALID_SIGNATURE
+			);
+
+			let unknown_relay_parent = sign_statement(Keyring::Alice, 0, Statement::Seconded(candidate()));
+			network_update(
+				&mut virtual_overseer,
+				NetworkBridgeEvent::PeerMessage(
+					peer.clone(),
+					WireMessage::Statement(Hash::from([2; 32]), unknown_relay_parent).encode(),
+				),
+			).await;
+
+			assert_matches!(
+				virtual_overseer.recv().await,
+				AllMessages::NetworkBridge(NetworkBridgeMessage::ReportPeer(p, rep))
+					if p == peer && rep == COST_UNEXPECTED_STATEMENT
+			);
+
+			virtual_overseer.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
+		});
+	}
+}
@@ -28,13 +28,14 @@ use futures_timer::Delay;
 use kv_log_macro as log;
 
 use polkadot_primitives::parachain::{BlockData, PoVBlock};
-use polkadot_overseer::Overseer;
+use polkadot_overseer::{Overseer, AllSubsystems};
 
 use polkadot_subsystem::{Subsystem, SubsystemContext, SpawnedSubsystem, FromOverseer};
 use polkadot_subsystem::messages::{
 	AllMessages, CandidateBackingMessage, CandidateValidationMessage
 };
 
+#[derive(Clone)]
 struct Subsystem1;
 
 impl Subsystem1 {
@@ -81,6 +82,7 @@ impl<C> Subsystem<C> for Subsystem1
 	}
 }
 
+#[derive(Clone)]
 struct Subsystem2;
 
 impl Subsystem2 {
@@ -127,10 +129,13 @@ fn main() {
 			Delay::new(Duration::from_secs(1)).await;
 		});
 
+		let all_subsystems = AllSubsystems::dummy()
+			.replace_candidate_validation(Subsystem2)
+			.replace_candidate_backing(Subsystem1);
 		let (overseer, _handler) = Overseer::new(
 			vec![],
-			Subsystem2,
-			Subsystem1,
+			all_subsystems,
+			None,
 			spawner,
 		).unwrap();
 		let overseer_fut = overseer.run().fuse();
@@ -55,11 +55,13 @@
 //! ```
 
 use std::fmt::Debug;
+use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
 use std::task::Poll;
-use std::time::Duration;
-use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use std::collections::{HashMap, HashSet};
 
 use futures::channel::{mpsc, oneshot};
 use futures::{
@@ -74,14 +76,21 @@ use streamunordered::{StreamYield, StreamUnordered};
 
 use polkadot_primitives::{Block, BlockNumber, Hash};
 use client::{BlockImportNotification, BlockchainEvents, FinalityNotification};
+use prometheus_endpoint::{
+	register, CounterVec, GaugeVec, Histogram, HistogramOpts, Opts, PrometheusError, Registry, U64,
+};
 
 use polkadot_subsystem::messages::{
-	CandidateValidationMessage, CandidateBackingMessage, AllMessages
+	CandidateValidationMessage, CandidateBackingMessage, CandidateSelectionMessage,
+	StatementDistributionMessage, AvailabilityDistributionMessage, PoVDistributionMessage,
+	BitfieldDistributionMessage, BitfieldSigningMessage, ProvisionerMessage, RuntimeApiMessage,
+	AvailabilityStoreMessage, NetworkBridgeMessage, AllMessages,
 };
 pub use polkadot_subsystem::{
 	Subsystem, SubsystemContext, OverseerSignal, FromOverseer, SubsystemError, SubsystemResult,
-	SpawnedSubsystem,
+	SpawnedSubsystem, RequestError, ActiveLeavesUpdate,
 };
+use polkadot_subsystem::await_response;
 
 
 // A capacity of bounded channels inside the overseer.
@@ -150,6 +159,7 @@ enum Event {
 	BlockImported(BlockInfo),
 	BlockFinalized(BlockInfo),
 	MsgToSubsystem(AllMessages),
+	Introspect(oneshot::Sender<Introspection>),
 	Stop,
 }
 
@@ -183,6 +193,14 @@ impl OverseerHandler {
 		Ok(())
 	}
 
+	/// Get a snapshot of the active leaves and of the state of each `Subsystem`.
+	pub async fn introspect(&mut self) -> SubsystemResult<Introspection> {
+		let (tx, rx) = oneshot::channel();
+		self.events_tx.send(Event::Introspect(tx)).await?;
+
+		Ok(rx.await?)
+	}
+
 	/// Tell `Overseer` to shutdown.
 	pub async fn stop(&mut self) -> SubsystemResult<()> {
 		self.events_tx.send(Event::Stop).await?;
@@ -244,6 +262,29 @@ impl Debug for ToOverseer {
 /// [`Subsystem`]: trait.Subsystem.html
 struct SubsystemInstance<M> {
 	tx: mpsc::Sender<FromOverseer<M>>,
+	/// The number of messages and signals sent to the instance it hasn't received yet.
+	queued: Arc<AtomicUsize>,
+	/// Whether the instance is still running.
+	running: Arc<AtomicBool>,
+	/// The number of requests made by the instance which haven't been answered yet.
+	outstanding_requests: Arc<AtomicUsize>,
+}
+
+impl<M> SubsystemInstance<M> {
+	/// Send a message or signal to the instance, returning the resulting queue size.
+	async fn send(&mut self, msg: FromOverseer<M>) -> Result<usize, mpsc::SendError> {
+		// count the message before sending it, so that the instance never receives
+		// a message which isn't counted yet.
+		let queued = self.queued.fetch_add(1, atomic::Ordering::SeqCst) + 1;
+
+		match self.tx.send(msg).await {
+			Ok(()) => Ok(queued),
+			Err(e) => {
+				self.queued.fetch_sub(1, atomic::Ordering::SeqCst);
+				Err(e)
+			}
+		}
+	}
 }
 
 /// A context type that is given to the [`Subsystem`] upon spawning.
@@ -257,6 +298,17 @@ struct SubsystemInstance<M> {
 pub struct OverseerSubsystemContext<M>{
 	rx: mpsc::Receiver<FromOverseer<M>>,
 	tx: mpsc::Sender<ToOverseer>,
+	id: SubsystemId,
+	queued: Arc<AtomicUsize>,
+	outstanding_requests: Arc<AtomicUsize>,
+	metrics: Metrics,
+}
+
+impl<M> OverseerSubsystemContext<M> {
+	fn on_received(&self) {
+		let queued = self.queued.fetch_sub(1, atomic::Ordering::SeqCst).saturating_sub(1);
+		self.metrics.on_queue_size(self.id, queued);
+	}
 }
 
 #[async_trait::async_trait]
@@ -265,14 +317,20 @@ impl<M: Send + 'static> SubsystemContext for OverseerSubsystemContext<M> {
 
 	async fn try_recv(&mut self) -> Result<Option<FromOverseer<M>>, ()> {
 		match poll!(self.rx.next()) {
-			Poll::Ready(Some(msg)) => Ok(Some(msg)),
+			Poll::Ready(Some(msg)) => {
+				self.on_received();
+				Ok(Some(msg))
+			}
 			Poll::Ready(None) => Err(()),
 			Poll::Pending => Ok(None),
 		}
 	}
 
 	async fn recv(&mut self) -> SubsystemResult<FromOverseer<M>> {
-		self.rx.next().await.ok_or(SubsystemError)
+		let msg = self.rx.next().await.ok_or(SubsystemError)?;
+		self.on_received();
+
+		Ok(msg)
 	}
 
 	async fn spawn(&mut self, s: Pin<Box<dyn Future<Output = ()> + Send>>) -> SubsystemResult<()> {
@@ -299,12 +357,280 @@ impl<M: Send + 'static> SubsystemContext for OverseerSubsystemContext<M> {
 
 		Ok(())
 	}
+
+	async fn request<R, F>(&mut self, make_msg: F, timeout: Duration) -> Result<R, RequestError>
+		where R: Send + 'static, F: FnOnce(oneshot::Sender<R>) -> AllMessages + Send
+	{
+		let outstanding = self.outstanding_requests.fetch_add(1, atomic::Ordering::SeqCst) + 1;
+		self.metrics.on_outstanding_requests(self.id, outstanding);
+
+		let (tx, rx) = oneshot::channel();
+		let res = match self.tx.send(ToOverseer::SubsystemMessage(make_msg(tx))).await {
+			Ok(()) => await_response(rx, timeout).await,
+			Err(_) => Err(RequestError::Send),
+		};
+
+		let outstanding = self.outstanding_requests.fetch_sub(1, atomic::Ordering::SeqCst) - 1;
+		self.metrics.on_outstanding_requests(self.id, outstanding);
+
+		if let Err(e) = res {
+			self.metrics.on_request_failed(self.id, e);
+		}
+
+		res
+	}
 }
 
 /// A subsystem compatible with the overseer - one which can be run in the context of the
 /// overseer.
 pub type CompatibleSubsystem<M> = Box<dyn Subsystem<OverseerSubsystemContext<M>> + Send>;
 
+/// Identifies a subsystem slot of the [`Overseer`].
+///
+/// [`Overseer`]: struct.Overseer.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SubsystemId {
+	/// The candidate validation subsystem.
+	CandidateValidation,
+	/// The candidate backing subsystem.
+	CandidateBacking,
+	/// The candidate selection subsystem.
+	CandidateSelection,
+	/// The statement distribution subsystem.
+	StatementDistribution,
+	/// The availability distribution subsystem.
+	AvailabilityDistribution,
+	/// The PoV distribution subsystem.
+	PoVDistribution,
+	/// The bitfield distribution subsystem.
+	BitfieldDistribution,
+	/// The bitfield signing subsystem.
+	BitfieldSigning,
+	/// The provisioner subsystem.
+	Provisioner,
+	/// The runtime API subsystem.
+	RuntimeApi,
+	/// The availability store subsystem.
+	AvailabilityStore,
+	/// The network bridge subsystem.
+	NetworkBridge,
+}
+
+impl SubsystemId {
+	/// The name of the subsystem, as used in metric labels.
+	pub fn name(&self) -> &'static str {
+		match self {
+			SubsystemId::CandidateValidation => "candidate-validation",
+			SubsystemId::CandidateBacking => "candidate-backing",
+			SubsystemId::CandidateSelection => "candidate-selection",
+			SubsystemId::StatementDistribution => "statement-distribution",
+			SubsystemId::AvailabilityDistribution => "availability-distribution",
+			SubsystemId::PoVDistribution => "pov-distribution",
+			SubsystemId::BitfieldDistribution => "bitfield-distribution",
+			SubsystemId::BitfieldSigning => "bitfield-signing",
+			SubsystemId::Provisioner => "provisioner",
+			SubsystemId::RuntimeApi => "runtime-api",
+			SubsystemId::AvailabilityStore => "availability-store",
+			SubsystemId::NetworkBridge => "network-bridge",
+		}
+	}
+}
+
+/// The state of a subsystem, as reported by [`OverseerHandler::introspect`].
+///
+/// [`OverseerHandler::introspect`]: struct.OverseerHandler.html#method.introspect
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubsystemState {
+	/// The subsystem.
+	pub id: SubsystemId,
+	/// Whether the subsystem is running, as opposed to having exited.
+	pub running: bool,
+	/// The number of times the subsystem has been restarted.
+	pub restarts: u32,
+	/// The number of messages and signals queued for the subsystem.
+	pub queued: usize,
+	/// The number of requests made by the subsystem which haven't been answered yet.
+	pub outstanding_requests: usize,
+}
+
+/// A snapshot of the state of the [`Overseer`], as reported by [`OverseerHandler::introspect`].
+///
+/// [`Overseer`]: struct.Overseer.html
+/// [`OverseerHandler::introspect`]: struct.OverseerHandler.html#method.introspect
+#[derive(Debug, Clone, PartialEq)]
+pub struct Introspection {
+	/// The active leaves, with their block numbers.
+	pub active_leaves: Vec<(Hash, BlockNumber)>,
+	/// The state of each subsystem.
+	pub subsystems: Vec<SubsystemState>,
+}
+
+#[derive(Clone)]
+struct MetricsInner {
+	queue_size: GaugeVec<U64>,
+	routed_messages: CounterVec<U64>,
+	dropped_messages: CounterVec<U64>,
+	outstanding_requests: GaugeVec<U64>,
+	failed_requests: CounterVec<U64>,
+	active_leaves_duration: Histogram,
+}
+
+/// Prometheus metrics of the overseer, which are no-ops if no registry was given.
+#[derive(Clone)]
+struct Metrics(Option<MetricsInner>);
+
+impl Metrics {
+	fn register(registry: Option<&Registry>) -> Result<Self, PrometheusError> {
+		let registry = match registry {
+			Some(registry) => registry,
+			None => return Ok(Metrics(None)),
+		};
+
+		Ok(Metrics(Some(MetricsInner {
+			queue_size: register(
+				GaugeVec::new(
+					Opts::new(
+						"parachain_overseer_queue_size",
+						"Number of messages and signals queued for a subsystem",
+					),
+					&["subsystem"],
+				)?,
+				registry,
+			)?,
+			routed_messages: register(
+				CounterVec::new(
+					Opts::new(
+						"parachain_overseer_routed_messages_total",
+						"Number of messages routed to a subsystem",
+					),
+					&["subsystem"],
+				)?,
+				registry,
+			)?,
+			dropped_messages: register(
+				CounterVec::new(
+					Opts::new(
+						"parachain_overseer_dropped_messages_total",
+						"Number of messages dropped because their subsystem was not running",
+					),
+					&["subsystem"],
+				)?,
+				registry,
+			)?,
+			outstanding_requests: register(
+				GaugeVec::new(
+					Opts::new(
+						"parachain_overseer_outstanding_requests",
+						"Number of requests made by a subsystem which haven't been answered yet",
+					),
+					&["subsystem"],
+				)?,
+				registry,
+			)?,
+			failed_requests: register(
+				CounterVec::new(
+					Opts::new(
+						"parachain_overseer_failed_requests_total",
+						"Number of requests made by a subsystem which failed, timed out or were canceled",
+					),
+					&["subsystem", "reason"],
+				)?,
+				registry,
+			)?,
+			active_leaves_duration: register(
+				Histogram::with_opts(HistogramOpts::new(
+					"parachain_overseer_active_leaves_duration",
+					"Time taken to deliver an ActiveLeaves signal to all subsystems, in seconds",
+				))?,
+				registry,
+			)?,
+		})))
+	}
+
+	fn on_queue_size(&self, id: SubsystemId, queued: usize) {
+		if let Some(metrics) = &self.0 {
+			metrics.queue_size.with_label_values(&[id.name()]).set(queued as u64);
+		}
+	}
+
+	fn on_message_routed(&self, id: SubsystemId) {
+		if let Some(metrics) = &self.0 {
+			metrics.routed_messages.with_label_values(&[id.name()]).inc();
+		}
+	}
+
+	fn on_message_dropped(&self, id: SubsystemId) {
+		if let Some(metrics) = &self.0 {
+			metrics.dropped_messages.with_label_values(&[id.name()]).inc();
+		}
+	}
+
+	fn on_outstanding_requests(&self, id: SubsystemId, outstanding: usize) {
+		if let Some(metrics) = &self.0 {
+			metrics.outstanding_requests.with_label_values(&[id.name()]).set(outstanding as u64);
+		}
+	}
+
+	fn on_request_failed(&self, id: SubsystemId, error: RequestError) {
+		if let Some(metrics) = &self.0 {
+			let reason = match error {
+				RequestError::Send => "send",
+				RequestError::Canceled => "canceled",
+				RequestError::Timeout => "timeout",
+			};
+			metrics.failed_requests.with_label_values(&[id.name(), reason]).inc();
+		}
+	}
+
+	fn on_active_leaves_delivered(&self, duration: Duration) {
+		if let Some(metrics) = &self.0 {
+			metrics.active_leaves_duration.observe(duration.as_secs_f64());
+		}
+	}
+}
+
+impl Debug for Metrics {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Metrics {{ enabled: {} }}", self.0.is_some())
+	}
+}
+
+/// What the [`Overseer`] does when a subsystem exits, by returning or by panicking,
+/// while the overseer is running.
+///
+/// A restarted subsystem is started with a fresh context and receives an `ActiveLeaves`
+/// signal activating all of the currently active leaves.
+///
+/// [`Overseer`]: struct.Overseer.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SupervisionPolicy {
+	/// Restart the subsystem every time it exits. The delay before a restart starts at
+	/// `initial` and doubles with each restart, up to `max`.
+	RestartWithBackoff {
+		/// The delay before the first restart.
+		initial: Duration,
+		/// The maximum delay before a restart.
+		max: Duration,
+	},
+	/// Restart the subsystem immediately, at most `max_restarts` times. Any further exit
+	/// is escalated, unless the subsystem ran for at least `reset_after` since its last
+	/// restart, in which case the restarts are counted from zero again.
+	RestartLimited {
+		/// The number of restarts after which exits are escalated.
+		max_restarts: u32,
+		/// How long a restarted subsystem has to run before its restarts are forgotten.
+		reset_after: Duration,
+	},
+	/// Shut down the overseer along with all other subsystems.
+	Escalate,
+}
+
+impl Default for SupervisionPolicy {
+	fn default() -> Self {
+		SupervisionPolicy::Escalate
+	}
+}
+
 /// A subsystem that we oversee.
 ///
 /// Ties together the [`Subsystem`] itself and it's running instance
@@ -312,24 +638,536 @@ pub type CompatibleSubsystem<M> = Box<dyn Subsystem<OverseerSubsystemContext<M>>
 /// for whatever reason).
 ///
 /// [`Subsystem`]: trait.Subsystem.html
-#[allow(dead_code)]
 struct OverseenSubsystem<M> {
+	id: SubsystemId,
 	instance: Option<SubsystemInstance<M>>,
+	/// Starts a fresh instance of the subsystem.
+	start: Box<dyn FnMut(OverseerSubsystemContext<M>) -> SpawnedSubsystem + Send>,
+	metrics: Metrics,
+}
+
+impl<M: Send + 'static> OverseenSubsystem<M> {
+	/// Start a fresh instance of the subsystem, replacing the current one.
+	fn start<S: Spawn>(
+		&mut self,
+		spawner: &mut S,
+		futures: &mut FuturesUnordered<RemoteHandle<SubsystemId>>,
+		streams: &mut StreamUnordered<mpsc::Receiver<ToOverseer>>,
+	) -> SubsystemResult<()> {
+		let (to_tx, to_rx) = mpsc::channel(CHANNEL_CAPACITY);
+		let (from_tx, from_rx) = mpsc::channel(CHANNEL_CAPACITY);
+		let queued = Arc::new(AtomicUsize::new(0));
+		let running = Arc::new(AtomicBool::new(true));
+		let outstanding_requests = Arc::new(AtomicUsize::new(0));
+		let ctx = OverseerSubsystemContext {
+			rx: to_rx,
+			tx: from_tx,
+			id: self.id,
+			queued: queued.clone(),
+			outstanding_requests: outstanding_requests.clone(),
+			metrics: self.metrics.clone(),
+		};
+		let f = (self.start)(ctx);
+
+		// catch panics, so that they are handled by supervision instead of being resumed
+		// when the handle is polled.
+		let id = self.id;
+		let exited = running.clone();
+		let f = AssertUnwindSafe(f.0).catch_unwind().map(move |res| {
+			exited.store(false, atomic::Ordering::SeqCst);
+			if res.is_err() {
+				log::error!("Subsystem {:?} panicked", id);
+			}
+
+			id
+		});
+
+		let handle = spawner.spawn_with_handle(f)?;
+
+		streams.push(from_rx);
+		futures.push(handle);
+
+		self.instance = Some(SubsystemInstance {
+			tx: to_tx,
+			queued,
+			running,
+			outstanding_requests,
+		});
+
+		Ok(())
+	}
+
+	/// Start a fresh instance of the subsystem and let it know about the given leaves.
+	async fn restart<S: Spawn>(
+		&mut self,
+		spawner: &mut S,
+		futures: &mut FuturesUnordered<RemoteHandle<SubsystemId>>,
+		streams: &mut StreamUnordered<mpsc::Receiver<ToOverseer>>,
+		leaves: Vec<Hash>,
+	) -> SubsystemResult<()> {
+		self.start(spawner, futures, streams)?;
+
+		if !leaves.is_empty() {
+			let update = ActiveLeavesUpdate { activated: leaves, deactivated: Vec::new() };
+			self.send_signal(OverseerSignal::ActiveLeaves(update)).await?;
+		}
+
+		Ok(())
+	}
+
+	/// Send a message to the wrapped subsystem.
+	///
+	/// If the inner `instance` is `None`, nothing is happening. If the subsystem has exited,
+	/// the `instance` is dropped and the exit is left to supervision.
+	async fn send_message(&mut self, msg: M) -> SubsystemResult<()> {
+		self.metrics.on_message_routed(self.id);
+
+		let sent = match self.instance {
+			Some(ref mut instance) => instance.send(FromOverseer::Communication { msg }).await.ok(),
+			None => None,
+		};
+
+		match sent {
+			Some(queued) => self.metrics.on_queue_size(self.id, queued),
+			None => {
+				self.instance = None;
+				self.metrics.on_message_dropped(self.id);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Send a signal to the wrapped subsystem.
+	///
+	/// If the inner `instance` is `None`, nothing is happening. If the subsystem has exited,
+	/// the `instance` is dropped and the exit is left to supervision.
+	async fn send_signal(&mut self, signal: OverseerSignal) -> SubsystemResult<()> {
+		if let Some(ref mut instance) = self.instance {
+			match instance.send(FromOverseer::Signal(signal)).await {
+				Ok(queued) => self.metrics.on_queue_size(self.id, queued),
+				Err(_) => self.instance = None,
+			}
+		}
+
+		Ok(())
+	}
+
+	fn state(&self, restarts: &HashMap<SubsystemId, u32>) -> SubsystemState {
+		SubsystemState {
+			id: self.id,
+			running: self.instance.as_ref().map_or(false, |i| i.running.load(atomic::Ordering::SeqCst)),
+			restarts: restarts.get(&self.id).cloned().unwrap_or(0),
+			queued: self.instance.as_ref().map_or(0, |i| i.queued.load(atomic::Ordering::SeqCst)),
+			outstanding_requests: self.instance.as_ref()
+				.map_or(0, |i| i.outstanding_requests.load(atomic::Ordering::SeqCst)),
+		}
+	}
+}
+
+/// A subsystem which does nothing but drain the messages and signals sent to it.
+///
+/// It implements [`Subsystem`] for any message type, which makes it a placeholder for
+/// every slot of the [`Overseer`] that has no real subsystem yet.
+///
+/// [`Subsystem`]: trait.Subsystem.html
+/// [`Overseer`]: struct.Overseer.html
+#[derive(Clone)]
+pub struct DummySubsystem;
+
+impl<C: SubsystemContext> Subsystem<C> for DummySubsystem {
+	fn start(self, mut ctx: C) -> SpawnedSubsystem {
+		SpawnedSubsystem(Box::pin(async move {
+			loop {
+				match ctx.recv().await {
+					Ok(FromOverseer::Signal(OverseerSignal::Conclude)) => return,
+					Err(_) => return,
+					_ => continue,
+				}
+			}
+		}))
+	}
+}
+
+/// The set of subsystems the [`Overseer`] is started with, one for each variant of
+/// [`AllMessages`].
+///
+/// Start from [`AllSubsystems::dummy`], which fills every slot with a [`DummySubsystem`],
+/// and replace the slots of interest one by one:
+///
+/// ```text
+/// let all_subsystems = AllSubsystems::dummy()
+///     .replace_candidate_validation(CandidateValidationSubsystem::new(pool))
+///     .replace_candidate_backing(CandidateBackingSubsystem::new(keystore));
+/// ```
+///
+/// [`Overseer`]: struct.Overseer.html
+/// [`AllMessages`]: enum.AllMessages.html
+/// [`DummySubsystem`]: struct.DummySubsystem.html
+/// [`AllSubsystems::dummy`]: struct.AllSubsystems.html#method.dummy
+pub struct AllSubsystems<CV, CB, CS, SD, AD, PoVD, BD, BS, P, RA, AS, NB> {
+	/// A candidate validation subsystem.
+	pub candidate_validation: CV,
+	/// A candidate backing subsystem.
+	pub candidate_backing: CB,
+	/// A candidate selection subsystem.
+	pub candidate_selection: CS,
+	/// A statement distribution subsystem.
+	pub statement_distribution: SD,
+	/// An availability distribution subsystem.
+	pub availability_distribution: AD,
+	/// A PoV distribution subsystem.
+	pub pov_distribution: PoVD,
+	/// A bitfield distribution subsystem.
+	pub bitfield_distribution: BD,
+	/// A bitfield signing subsystem.
+	pub bitfield_signing: BS,
+	/// A provisioner subsystem.
+	pub provisioner: P,
+	/// A runtime API subsystem.
+	pub runtime_api: RA,
+	/// An availability store subsystem.
+	pub availability_store: AS,
+	/// A network bridge subsystem.
+	pub network_bridge: NB,
+}
+
+impl AllSubsystems<
+	DummySubsystem, DummySubsystem, DummySubsystem, DummySubsystem, DummySubsystem, DummySubsystem,
+	DummySubsystem, DummySubsystem, DummySubsystem, DummySubsystem, DummySubsystem, DummySubsystem,
+> {
+	/// Create a set of subsystems where every slot holds a [`DummySubsystem`].
+	///
+	/// [`DummySubsystem`]: struct.DummySubsystem.html
+	pub fn dummy() -> Self {
+		AllSubsystems {
+			candidate_validation: DummySubsystem,
+			candidate_backing: DummySubsystem,
+			candidate_selection: DummySubsystem,
+			statement_distribution: DummySubsystem,
+			availability_distribution: DummySubsystem,
+			pov_distribution: DummySubsystem,
+			bitfield_distribution: DummySubsystem,
+			bitfield_signing: DummySubsystem,
+			provisioner: DummySubsystem,
+			runtime_api: DummySubsystem,
+			availability_store: DummySubsystem,
+			network_bridge: DummySubsystem,
+		}
+	}
+}
+
+impl<CV, CB, CS, SD, AD, PoVD, BD, BS, P, RA, AS, NB>
+	AllSubsystems<CV, CB, CS, SD, AD, PoVD, BD, BS, P, RA, AS, NB>
+{
+	/// Replace the candidate validation subsystem.
+	pub fn replace_candidate_validation<NEW>(
+		self,
+		candidate_validation: NEW,
+	) -> AllSubsystems<NEW, CB, CS, SD, AD, PoVD, BD, BS, P, RA, AS, NB> {
+		AllSubsystems {
+			candidate_validation,
+			candidate_backing: self.candidate_backing,
+			candidate_selection: self.candidate_selection,
+			statement_distribution: self.statement_distribution,
+			availability_distribution: self.availability_distribution,
+			pov_distribution: self.pov_distribution,
+			bitfield_distribution: self.bitfield_distribution,
+			bitfield_signing: self.bitfield_signing,
+			provisioner: self.provisioner,
+			runtime_api: self.runtime_api,
+			availability_store: self.availability_store,
+			network_bridge: self.network_bridge,
+		}
+	}
+
+	/// Replace the candidate backing subsystem.
+	pub fn replace_candidate_backing<NEW>(
+		self,
+		candidate_backing: NEW,
+	) -> AllSubsystems<CV, NEW, CS, SD, AD, PoVD, BD, BS, P, RA, AS, NB> {
+		AllSubsystems {
+			candidate_validation: self.candidate_validation,
+			candidate_backing,
+			candidate_selection: self.candidate_selection,
+			statement_distribution: self.statement_distribution,
+			availability_distribution: self.availability_distribution,
+			pov_distribution: self.pov_distribution,
+			bitfield_distribution: self.bitfield_distribution,
+			bitfield_signing: self.bitfield_signing,
+			provisioner: self.provisioner,
+			runtime_api: self.runtime_api,
+			availability_store: self.availability_store,
+			network_bridge: self.network_bridge,
+		}
+	}
+
+	/// Replace the candidate selection subsystem.
+	pub fn replace_candidate_selection<NEW>(
+		self,
+		candidate_selection: NEW,
+	) -> AllSubsystems<CV, CB, NEW, SD, AD, PoVD, BD, BS, P, RA, AS, NB> {
+		AllSubsystems {
+			candidate_validation: self.candidate_validation,
+			candidate_backing: self.candidate_backing,
+			candidate_selection,
+			statement_distribution: self.statement_distribution,
+			availability_distribution: self.availability_distribution,
+			pov_distribution: self.pov_distribution,
+			bitfield_distribution: self.bitfield_distribution,
+			bitfield_signing: self.bitfield_signing,
+			provisioner: self.provisioner,
+			runtime_api: self.runtime_api,
+			availability_store: self.availability_store,
+			network_bridge: self.network_bridge,
+		}
+	}
+
+	/// Replace the statement distribution subsystem.
+	pub fn replace_statement_distribution<NEW>(
+		self,
+		statement_distribution: NEW,
+	) -> AllSubsystems<CV, CB, CS, NEW, AD, PoVD, BD, BS, P, RA, AS, NB> {
+		AllSubsystems {
+			candidate_validation: self.candidate_validation,
+			candidate_backing: self.candidate_backing,
+			candidate_selection: self.candidate_selection,
+			statement_distribution,
+			availability_distribution: self.availability_distribution,
+			pov_distribution: self.pov_distribution,
+			bitfield_distribution: self.bitfield_distribution,
+			bitfield_signing: self.bitfield_signing,
+			provisioner: self.provisioner,
+			runtime_api: self.runtime_api,
+			availability_store: self.availability_store,
+			network_bridge: self.network_bridge,
+		}
+	}
+
+	/// Replace the availability distribution subsystem.
+	pub fn replace_availability_distribution<NEW>(
+		self,
+		availability_distribution: NEW,
+	) -> AllSubsystems<CV, CB, CS, SD, NEW, PoVD, BD, BS, P, RA, AS, NB> {
+		AllSubsystems {
+			candidate_validation: self.candidate_validation,
+			candidate_backing: self.candidate_backing,
+			candidate_selection: self.candidate_selection,
+			statement_distribution: self.statement_distribution,
+			availability_distribution,
+			pov_distribution: self.pov_distribution,
+			bitfield_distribution: self.bitfield_distribution,
+			bitfield_signing: self.bitfield_signing,
+			provisioner: self.provisioner,
+			runtime_api: self.runtime_api,
+			availability_store: self.availability_store,
+			network_bridge: self.network_bridge,
+		}
+	}
+
+	/// Replace the PoV distribution subsystem.
+	pub fn replace_pov_distribution<NEW>(
+		self,
+		pov_distribution: NEW,
+	) -> AllSubsystems<CV, CB, CS, SD, AD, NEW, BD, BS, P, RA, AS, NB> {
+		AllSubsystems {
+			candidate_validation: self.candidate_validation,
+			candidate_backing: self.candidate_backing,
+			candidate_selection: self.candidate_selection,
+			statement_distribution: self.statement_distribution,
+			availability_distribution: self.availability_distribution,
+			pov_distribution,
+			bitfield_distribution: self.bitfield_distribution,
+			bitfield_signing: self.bitfield_signing,
+			provisioner: self.provisioner,
+			runtime_api: self.runtime_api,
+			availability_store: self.availability_store,
+			network_bridge: self.network_bridge,
+		}
+	}
+
+	/// Replace the bitfield distribution subsystem.
+	pub fn replace_bitfield_distribution<NEW>(
+		self,
+		bitfield_distribution: NEW,
+	) -> AllSubsystems<CV, CB, CS, SD, AD, PoVD, NEW, BS, P, RA, AS, NB> {
+		AllSubsystems {
+			candidate_validation: self.candidate_validation,
+			candidate_backing: self.candidate_backing,
+			candidate_selection: self.candidate_selection,
+			statement_distribution: self.statement_distribution,
+			availability_distribution: self.availability_distribution,
+			pov_distribution: self.pov_distribution,
+			bitfield_distribution,
+			bitfield_signing: self.bitfield_signing,
+			provisioner: self.provisioner,
+			runtime_api: self.runtime_api,
+			availability_store: self.availability_store,
+			network_bridge: self.network_bridge,
+		}
+	}
+
+	/// Replace the bitfield signing subsystem.
+	pub fn replace_bitfield_signing<NEW>(
+		self,
+		bitfield_signing: NEW,
+	) -> AllSubsystems<CV, CB, CS, SD, AD, PoVD, BD, NEW, P, RA, AS, NB> {
+		AllSubsystems {
+			candidate_validation: self.candidate_validation,
+			candidate_backing: self.candidate_backing,
+			candidate_selection: self.candidate_selection,
+			statement_distribution: self.statement_distribution,
+			availability_distribution: self.availability_distribution,
+			pov_distribution: self.pov_distribution,
+			bitfield_distribution: self.bitfield_distribution,
+			bitfield_signing,
+			provisioner: self.provisioner,
+			runtime_api: self.runtime_api,
+			availability_store: self.availability_store,
+			network_bridge: self.network_bridge,
+		}
+	}
+
+	/// Replace the provisioner subsystem.
+	pub fn replace_provisioner<NEW>(
+		self,
+		provisioner: NEW,
+	) -> AllSubsystems<CV, CB, CS, SD, AD, PoVD, BD, BS, NEW, RA, AS, NB> {
+		AllSubsystems {
+			candidate_validation: self.candidate_validation,
+			candidate_backing: self.candidate_backing,
+			candidate_selection: self.candidate_selection,
+			statement_distribution: self.statement_distribution,
+			availability_distribution: self.availability_distribution,
+			pov_distribution: self.pov_distribution,
+			bitfield_distribution: self.bitfield_distribution,
+			bitfield_signing: self.bitfield_signing,
+			provisioner,
+			runtime_api: self.runtime_api,
+			availability_store: self.availability_store,
+			network_bridge: self.network_bridge,
+		}
+	}
+
+	/// Replace the runtime API subsystem.
+	pub fn replace_runtime_api<NEW>(
+		self,
+		runtime_api: NEW,
+	) -> AllSubsystems<CV, CB, CS, SD, AD, PoVD, BD, BS, P, NEW, AS, NB> {
+		AllSubsystems {
+			candidate_validation: self.candidate_validation,
+			candidate_backing: self.candidate_backing,
+			candidate_selection: self.candidate_selection,
+			statement_distribution: self.statement_distribution,
+			availability_distribution: self.availability_distribution,
+			pov_distribution: self.pov_distribution,
+			bitfield_distribution: self.bitfield_distribution,
+			bitfield_signing: self.bitfield_signing,
+			provisioner: self.provisioner,
+			runtime_api,
+			availability_store: self.availability_store,
+			network_bridge: self.network_bridge,
+		}
+	}
+
+	/// Replace the availability store subsystem.
+	pub fn replace_availability_store<NEW>(
+		self,
+		availability_store: NEW,
+	) -> AllSubsystems<CV, CB, CS, SD, AD, PoVD, BD, BS, P, RA, NEW, NB> {
+		AllSubsystems {
+			candidate_validation: self.candidate_validation,
+			candidate_backing: self.candidate_backing,
+			candidate_selection: self.candidate_selection,
+			statement_distribution: self.statement_distribution,
+			availability_distribution: self.availability_distribution,
+			pov_distribution: self.pov_distribution,
+			bitfield_distribution: self.bitfield_distribution,
+			bitfield_signing: self.bitfield_signing,
+			provisioner: self.provisioner,
+			runtime_api: self.runtime_api,
+			availability_store,
+			network_bridge: self.network_bridge,
+		}
+	}
+
+	/// Replace the network bridge subsystem.
+	pub fn replace_network_bridge<NEW>(
+		self,
+		network_bridge: NEW,
+	) -> AllSubsystems<CV, CB, CS, SD, AD, PoVD, BD, BS, P, RA, AS, NEW> {
+		AllSubsystems {
+			candidate_validation: self.candidate_validation,
+			candidate_backing: self.candidate_backing,
+			candidate_selection: self.candidate_selection,
+			statement_distribution: self.statement_distribution,
+			availability_distribution: self.availability_distribution,
+			pov_distribution: self.pov_distribution,
+			bitfield_distribution: self.bitfield_distribution,
+			bitfield_signing: self.bitfield_signing,
+			provisioner: self.provisioner,
+			runtime_api: self.runtime_api,
+			availability_store: self.availability_store,
+			network_bridge,
+		}
+	}
 }
 
 /// The `Overseer` itself.
 pub struct Overseer<S: Spawn> {
-	/// A validation subsystem
-	validation_subsystem: OverseenSubsystem<CandidateValidationMessage>,
+	/// A candidate validation subsystem.
+	candidate_validation_subsystem: OverseenSubsystem<CandidateValidationMessage>,
 
-	/// A candidate backing subsystem
+	/// A candidate backing subsystem.
 	candidate_backing_subsystem: OverseenSubsystem<CandidateBackingMessage>,
 
+	/// A candidate selection subsystem.
+	candidate_selection_subsystem: OverseenSubsystem<CandidateSelectionMessage>,
+
+	/// A statement distribution subsystem.
+	statement_distribution_subsystem: OverseenSubsystem<StatementDistributionMessage>,
+
+	/// An availability distribution subsystem.
+	availability_distribution_subsystem: OverseenSubsystem<AvailabilityDistributionMessage>,
+
+	/// A PoV distribution subsystem.
+	pov_distribution_subsystem: OverseenSubsystem<PoVDistributionMessage>,
+
+	/// A bitfield distribution subsystem.
+	bitfield_distribution_subsystem: OverseenSubsystem<BitfieldDistributionMessage>,
+
+	/// A bitfield signing subsystem.
+	bitfield_signing_subsystem: OverseenSubsystem<BitfieldSigningMessage>,
+
+	/// A provisioner subsystem.
+	provisioner_subsystem: OverseenSubsystem<ProvisionerMessage>,
+
+	/// A runtime API subsystem.
+	runtime_api_subsystem: OverseenSubsystem<RuntimeApiMessage>,
+
+	/// An availability store subsystem.
+	availability_store_subsystem: OverseenSubsystem<AvailabilityStoreMessage>,
+
+	/// A network bridge subsystem.
+	network_bridge_subsystem: OverseenSubsystem<NetworkBridgeMessage>,
+
 	/// Spawner to spawn tasks to.
 	s: S,
 
 	/// Here we keep handles to spawned subsystems to be notified when they terminate.
-	running_subsystems: FuturesUnordered<RemoteHandle<()>>,
+	running_subsystems: FuturesUnordered<RemoteHandle<SubsystemId>>,
+
+	/// The supervision policies set for subsystems. Subsystems without one escalate.
+	supervision_policies: HashMap<SubsystemId, SupervisionPolicy>,
+
+	/// The number of times each subsystem has been restarted.
+	restarts: HashMap<SubsystemId, u32>,
+
+	/// When each restarted subsystem was last started.
+	last_restarted: HashMap<SubsystemId, Instant>,
+
+	/// Subsystems waiting for their backoff to elapse before being restarted.
+	pending_restarts: FuturesUnordered<BoxFuture<'static, SubsystemId>>,
 
 	/// Gather running subsystms' outbound streams into one.
 	running_subsystems_rx: StreamUnordered<mpsc::Receiver<ToOverseer>>,
@@ -344,6 +1182,15 @@ pub struct Overseer<S: Spawn> {
 
 	/// The set of the "active leaves".
 	active_leaves: HashSet<(Hash, BlockNumber)>,
+
+	/// The parent hash and number of the blocks above the last finalized block we know of.
+	block_ancestry: HashMap<Hash, (Hash, BlockNumber)>,
+
+	/// The hash and number of the last finalized block, if any block was finalized yet.
+	finalized: Option<(Hash, BlockNumber)>,
+
+	/// Various Prometheus metrics.
+	metrics: Metrics,
 }
 
 impl<S> Overseer<S>
@@ -352,12 +1199,9 @@ where
 {
 	/// Create a new intance of the `Overseer` with a fixed set of [`Subsystem`]s.
 	///
-	/// Each [`Subsystem`] is passed to this function as an explicit parameter
-	/// and is supposed to implement some interface that is generic over message type
-	/// that is specific to this [`Subsystem`]. At the moment there are only two
-	/// subsystems:
-	///   * Validation
-	///   * CandidateBacking
+	/// The [`Subsystem`]s are passed to this function as an [`AllSubsystems`], which holds
+	/// one subsystem for each message type of [`AllMessages`]. Each of them is supposed to
+	/// implement some interface that is generic over the message type specific to it.
 	///
 	/// As any entity that satisfies the interface may act as a [`Subsystem`] this allows
 	/// mocking in the test code:
@@ -384,22 +1228,28 @@ where
 	/// ```
 	///
 	/// [`Subsystem`]: trait.Subsystem.html
+	/// [`AllSubsystems`]: struct.AllSubsystems.html
+	/// [`AllMessages`]: enum.AllMessages.html
 	///
 	/// # Example
 	///
 	/// The [`Subsystems`] may be any type as long as they implement an expected interface.
-	/// Here, we create two mock subsystems and start the `Overseer` with them. For the sake
-	/// of simplicity the termination of the example is done with a timeout.
+	/// Here, we create a mock validation subsystem and start the `Overseer` with it, leaving
+	/// every other slot to a [`DummySubsystem`]. For the sake of simplicity the termination
+	/// of the example is done with a timeout.
+	///
+	/// [`DummySubsystem`]: struct.DummySubsystem.html
 	/// ```
 	/// # use std::time::Duration;
 	/// # use futures::{executor, pin_mut, select, FutureExt};
 	/// # use futures_timer::Delay;
-	/// # use polkadot_overseer::Overseer;
+	/// # use polkadot_overseer::{Overseer, AllSubsystems};
 	/// # use polkadot_subsystem::{
 	/// #     Subsystem, SpawnedSubsystem, SubsystemContext,
-	/// #     messages::{CandidateValidationMessage, CandidateBackingMessage},
+	/// #     messages::CandidateValidationMessage,
 	/// # };
 	///
+	/// #[derive(Clone)]
 	/// struct ValidationSubsystem;
 	///
 	/// impl<C> Subsystem<C> for ValidationSubsystem
@@ -417,28 +1267,14 @@ where
 	///     }
 	/// }
 	///
-	/// struct CandidateBackingSubsystem;
-	/// impl<C> Subsystem<C> for CandidateBackingSubsystem
-	/// 	where C: SubsystemContext<Message=CandidateBackingMessage>
-	/// {
-	///     fn start(
-	///         self,
-	///         mut ctx: C,
-	///     ) -> SpawnedSubsystem {
-	///         SpawnedSubsystem(Box::pin(async move {
-	///             loop {
-	///                 Delay::new(Duration::from_secs(1)).await;
-	///             }
-	///         }))
-	///     }
-	/// }
-	///
 	/// # fn main() { executor::block_on(async move {
 	/// let spawner = executor::ThreadPool::new().unwrap();
+	/// let all_subsystems = AllSubsystems::dummy()
+	///     .replace_candidate_validation(ValidationSubsystem);
 	/// let (overseer, _handler) = Overseer::new(
 	///     vec![],
-	///     ValidationSubsystem,
-	///     CandidateBackingSubsystem,
+	///     all_subsystems,
+	///     None,
 	///     spawner,
 	/// ).unwrap();
 	///
@@ -455,65 +1291,212 @@ where
 	/// #
 	/// # }); }
 	/// ```
-	pub fn new(
+	pub fn new<CV, CB, CS, SD, AD, PoVD, BD, BS, P, RA, AS, NB>(
 		leaves: impl IntoIterator<Item = BlockInfo>,
-		validation: impl Subsystem<OverseerSubsystemContext<CandidateValidationMessage>> + Send,
-		candidate_backing: impl Subsystem<OverseerSubsystemContext<CandidateBackingMessage>> + Send,
+		all_subsystems: AllSubsystems<CV, CB, CS, SD, AD, PoVD, BD, BS, P, RA, AS, NB>,
+		prometheus_registry: Option<&Registry>,
 		mut s: S,
-	) -> SubsystemResult<(Self, OverseerHandler)> {
+	) -> SubsystemResult<(Self, OverseerHandler)>
+	where
+		CV: Subsystem<OverseerSubsystemContext<CandidateValidationMessage>> + Clone + Send + 'static,
+		CB: Subsystem<OverseerSubsystemContext<CandidateBackingMessage>> + Clone + Send + 'static,
+		CS: Subsystem<OverseerSubsystemContext<CandidateSelectionMessage>> + Clone + Send + 'static,
+		SD: Subsystem<OverseerSubsystemContext<StatementDistributionMessage>> + Clone + Send + 'static,
+		AD: Subsystem<OverseerSubsystemContext<AvailabilityDistributionMessage>> + Clone + Send + 'static,
+		PoVD: Subsystem<OverseerSubsystemContext<PoVDistributionMessage>> + Clone + Send + 'static,
+		BD: Subsystem<OverseerSubsystemContext<BitfieldDistributionMessage>> + Clone + Send + 'static,
+		BS: Subsystem<OverseerSubsystemContext<BitfieldSigningMessage>> + Clone + Send + 'static,
+		P: Subsystem<OverseerSubsystemContext<ProvisionerMessage>> + Clone + Send + 'static,
+		RA: Subsystem<OverseerSubsystemContext<RuntimeApiMessage>> + Clone + Send + 'static,
+		AS: Subsystem<OverseerSubsystemContext<AvailabilityStoreMessage>> + Clone + Send + 'static,
+		NB: Subsystem<OverseerSubsystemContext<NetworkBridgeMessage>> + Clone + Send + 'static,
+	{
 		let (events_tx, events_rx) = mpsc::channel(CHANNEL_CAPACITY);
 
 		let handler = OverseerHandler {
 			events_tx: events_tx.clone(),
 		};
 
+		let metrics = Metrics::register(prometheus_registry).map_err(|e| {
+			log::error!("Failed to register overseer metrics: {:?}", e);
+			SubsystemError
+		})?;
+
 		let mut running_subsystems_rx = StreamUnordered::new();
 		let mut running_subsystems = FuturesUnordered::new();
 
-		let validation_subsystem = spawn(
+		let candidate_validation_subsystem = spawn(
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
-			validation,
+			&metrics,
+			SubsystemId::CandidateValidation,
+			all_subsystems.candidate_validation,
 		)?;
 
 		let candidate_backing_subsystem = spawn(
 			&mut s,
 			&mut running_subsystems,
 			&mut running_subsystems_rx,
-			candidate_backing,
+			&metrics,
+			SubsystemId::CandidateBacking,
+			all_subsystems.candidate_backing,
 		)?;
 
-		let active_leaves = HashSet::new();
+		let candidate_selection_subsystem = spawn(
+			&mut s,
+			&mut running_subsystems,
+			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemId::CandidateSelection,
+			all_subsystems.candidate_selection,
+		)?;
 
-		let leaves = leaves
-			.into_iter()
-			.map(|BlockInfo { hash, parent_hash: _, number }| (hash, number))
-			.collect();
+		let statement_distribution_subsystem = spawn(
+			&mut s,
+			&mut running_subsystems,
+			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemId::StatementDistribution,
+			all_subsystems.statement_distribution,
+		)?;
 
-		let this = Self {
-			validation_subsystem,
-			candidate_backing_subsystem,
-			s,
-			running_subsystems,
-			running_subsystems_rx,
-			events_rx,
-			leaves,
-			active_leaves,
-		};
+		let availability_distribution_subsystem = spawn(
+			&mut s,
+			&mut running_subsystems,
+			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemId::AvailabilityDistribution,
+			all_subsystems.availability_distribution,
+		)?;
 
-		Ok((this, handler))
-	}
+		let pov_distribution_subsystem = spawn(
+			&mut s,
+			&mut running_subsystems,
+			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemId::PoVDistribution,
+			all_subsystems.pov_distribution,
+		)?;
 
-	// Stop the overseer.
-	async fn stop(mut self) {
-		if let Some(ref mut s) = self.validation_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
-		}
+		let bitfield_distribution_subsystem = spawn(
+			&mut s,
+			&mut running_subsystems,
+			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemId::BitfieldDistribution,
+			all_subsystems.bitfield_distribution,
+		)?;
 
-		if let Some(ref mut s) = self.candidate_backing_subsystem.instance {
-			let _ = s.tx.send(FromOverseer::Signal(OverseerSignal::Conclude)).await;
-		}
+		let bitfield_signing_subsystem = spawn(
+			&mut s,
+			&mut running_subsystems,
+			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemId::BitfieldSigning,
+			all_subsystems.bitfield_signing,
+		)?;
+
+		let provisioner_subsystem = spawn(
+			&mut s,
+			&mut running_subsystems,
+			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemId::Provisioner,
+			all_subsystems.provisioner,
+		)?;
+
+		let runtime_api_subsystem = spawn(
+			&mut s,
+			&mut running_subsystems,
+			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemId::RuntimeApi,
+			all_subsystems.runtime_api,
+		)?;
+
+		let availability_store_subsystem = spawn(
+			&mut s,
+			&mut running_subsystems,
+			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemId::AvailabilityStore,
+			all_subsystems.availability_store,
+		)?;
+
+		let network_bridge_subsystem = spawn(
+			&mut s,
+			&mut running_subsystems,
+			&mut running_subsystems_rx,
+			&metrics,
+			SubsystemId::NetworkBridge,
+			all_subsystems.network_bridge,
+		)?;
+
+		let active_leaves = HashSet::new();
+		let mut block_ancestry = HashMap::new();
+
+		let leaves = leaves
+			.into_iter()
+			.map(|BlockInfo { hash, parent_hash, number }| {
+				block_ancestry.insert(hash, (parent_hash, number));
+				(hash, number)
+			})
+			.collect();
+
+		let this = Self {
+			candidate_validation_subsystem,
+			candidate_backing_subsystem,
+			candidate_selection_subsystem,
+			statement_distribution_subsystem,
+			availability_distribution_subsystem,
+			pov_distribution_subsystem,
+			bitfield_distribution_subsystem,
+			bitfield_signing_subsystem,
+			provisioner_subsystem,
+			runtime_api_subsystem,
+			availability_store_subsystem,
+			network_bridge_subsystem,
+			s,
+			running_subsystems,
+			supervision_policies: HashMap::new(),
+			restarts: HashMap::new(),
+			last_restarted: HashMap::new(),
+			pending_restarts: FuturesUnordered::new(),
+			running_subsystems_rx,
+			events_rx,
+			leaves,
+			active_leaves,
+			block_ancestry,
+			finalized: None,
+			metrics,
+		};
+
+		Ok((this, handler))
+	}
+
+	/// Set the [`SupervisionPolicy`] of a subsystem. By default, the exit of any subsystem
+	/// shuts down the `Overseer`.
+	///
+	/// [`SupervisionPolicy`]: enum.SupervisionPolicy.html
+	pub fn set_supervision_policy(&mut self, subsystem: SubsystemId, policy: SupervisionPolicy) {
+		self.supervision_policies.insert(subsystem, policy);
+	}
+
+	// Stop the overseer.
+	async fn stop(mut self) {
+		let _ = self.candidate_validation_subsystem.send_signal(OverseerSignal::Conclude).await;
+		let _ = self.candidate_backing_subsystem.send_signal(OverseerSignal::Conclude).await;
+		let _ = self.candidate_selection_subsystem.send_signal(OverseerSignal::Conclude).await;
+		let _ = self.statement_distribution_subsystem.send_signal(OverseerSignal::Conclude).await;
+		let _ = self.availability_distribution_subsystem.send_signal(OverseerSignal::Conclude).await;
+		let _ = self.pov_distribution_subsystem.send_signal(OverseerSignal::Conclude).await;
+		let _ = self.bitfield_distribution_subsystem.send_signal(OverseerSignal::Conclude).await;
+		let _ = self.bitfield_signing_subsystem.send_signal(OverseerSignal::Conclude).await;
+		let _ = self.provisioner_subsystem.send_signal(OverseerSignal::Conclude).await;
+		let _ = self.runtime_api_subsystem.send_signal(OverseerSignal::Conclude).await;
+		let _ = self.availability_store_subsystem.send_signal(OverseerSignal::Conclude).await;
+		let _ = self.network_bridge_subsystem.send_signal(OverseerSignal::Conclude).await;
 
 		let mut stop_delay = Delay::new(Duration::from_secs(STOP_DELAY)).fuse();
 
@@ -533,12 +1516,16 @@ where
 	/// Run the `Overseer`.
 	pub async fn run(mut self) -> SubsystemResult<()> {
 		let leaves = std::mem::take(&mut self.leaves);
+		let mut update = ActiveLeavesUpdate::default();
 
 		for leaf in leaves.into_iter() {
-			self.broadcast_signal(OverseerSignal::StartWork(leaf.0)).await?;
-			self.active_leaves.insert(leaf);
+			if self.active_leaves.insert(leaf) {
+				update.activated.push(leaf.0);
+			}
 		}
 
+		self.broadcast_active_leaves(update).await?;
+
 		loop {
 			while let Poll::Ready(Some(msg)) = poll!(&mut self.events_rx.next()) {
 				match msg {
@@ -555,6 +1542,9 @@ where
 					Event::BlockFinalized(block) => {
 						self.block_finalized(block).await?;
 					}
+					Event::Introspect(tx) => {
+						let _ = tx.send(self.introspect());
+					}
 				}
 			}
 
@@ -571,11 +1561,27 @@ where
 				}
 			}
 
-			// Some subsystem exited? It's time to panic.
-			if let Poll::Ready(Some(finished)) = poll!(self.running_subsystems.next()) {
-				log::error!("Subsystem finished unexpectedly {:?}", finished);
-				self.stop().await;
-				return Err(SubsystemError);
+			// Some subsystem exited? Restart it or shut down, as its supervision policy says.
+			while let Poll::Ready(Some(finished)) = poll!(self.running_subsystems.next()) {
+				match self.restart_delay(finished) {
+					Some(delay) => {
+						log::warn!(
+							"Subsystem {:?} finished unexpectedly, restarting in {:?}",
+							finished,
+							delay,
+						);
+						self.pending_restarts.push(Delay::new(delay).map(move |_| finished).boxed());
+					}
+					None => {
+						log::error!("Subsystem {:?} finished unexpectedly", finished);
+						self.stop().await;
+						return Err(SubsystemError);
+					}
+				}
+			}
+
+			while let Poll::Ready(Some(id)) = poll!(self.pending_restarts.next()) {
+				self.restart_subsystem(id).await?;
 			}
 
 			// Looks like nothing is left to be polled, let's take a break.
@@ -583,46 +1589,184 @@ where
 		}
 	}
 
+	/// Note that a subsystem has exited and return the delay after which it should be
+	/// restarted, or `None` if its exit should be escalated.
+	fn restart_delay(&mut self, id: SubsystemId) -> Option<Duration> {
+		let policy = self.supervision_policies.get(&id).cloned().unwrap_or_default();
+		let last_restarted = self.last_restarted.get(&id).cloned();
+		let restarts = self.restarts.entry(id).or_insert(0);
+
+		if let SupervisionPolicy::RestartLimited { reset_after, .. } = policy {
+			if last_restarted.map_or(false, |at| at.elapsed() >= reset_after) {
+				*restarts = 0;
+			}
+		}
+
+		let delay = match policy {
+			SupervisionPolicy::RestartWithBackoff { initial, max } => {
+				initial.checked_mul(2u32.saturating_pow(*restarts)).map_or(max, |delay| delay.min(max))
+			}
+			SupervisionPolicy::RestartLimited { max_restarts, .. } if *restarts < max_restarts => {
+				Duration::from_secs(0)
+			}
+			SupervisionPolicy::RestartLimited { .. } | SupervisionPolicy::Escalate => return None,
+		};
+
+		*restarts += 1;
+		Some(delay)
+	}
+
+	async fn restart_subsystem(&mut self, id: SubsystemId) -> SubsystemResult<()> {
+		self.last_restarted.insert(id, Instant::now());
+
+		let leaves = self.active_leaves.iter().map(|(hash, _)| *hash).collect();
+		let spawner = &mut self.s;
+		let futures = &mut self.running_subsystems;
+		let streams = &mut self.running_subsystems_rx;
+
+		match id {
+			SubsystemId::CandidateValidation => {
+				self.candidate_validation_subsystem.restart(spawner, futures, streams, leaves).await
+			}
+			SubsystemId::CandidateBacking => {
+				self.candidate_backing_subsystem.restart(spawner, futures, streams, leaves).await
+			}
+			SubsystemId::CandidateSelection => {
+				self.candidate_selection_subsystem.restart(spawner, futures, streams, leaves).await
+			}
+			SubsystemId::StatementDistribution => {
+				self.statement_distribution_subsystem.restart(spawner, futures, streams, leaves).await
+			}
+			SubsystemId::AvailabilityDistribution => {
+				self.availability_distribution_subsystem.restart(spawner, futures, streams, leaves).await
+			}
+			SubsystemId::PoVDistribution => {
+				self.pov_distribution_subsystem.restart(spawner, futures, streams, leaves).await
+			}
+			SubsystemId::BitfieldDistribution => {
+				self.bitfield_distribution_subsystem.restart(spawner, futures, streams, leaves).await
+			}
+			SubsystemId::BitfieldSigning => {
+				self.bitfield_signing_subsystem.restart(spawner, futures, streams, leaves).await
+			}
+			SubsystemId::Provisioner => {
+				self.provisioner_subsystem.restart(spawner, futures, streams, leaves).await
+			}
+			SubsystemId::RuntimeApi => {
+				self.runtime_api_subsystem.restart(spawner, futures, streams, leaves).await
+			}
+			SubsystemId::AvailabilityStore => {
+				self.availability_store_subsystem.restart(spawner, futures, streams, leaves).await
+			}
+			SubsystemId::NetworkBridge => {
+				self.network_bridge_subsystem.restart(spawner, futures, streams, leaves).await
+			}
+		}
+	}
+
 	async fn block_imported(&mut self, block: BlockInfo) -> SubsystemResult<()> {
+		if let Some(finalized) = self.finalized {
+			if block.number <= finalized.1 {
+				return Ok(());
+			}
+
+			self.block_ancestry.insert(block.hash, (block.parent_hash, block.number));
+
+			if !is_descendant_of(&self.block_ancestry, block.hash, block.number, finalized) {
+				// the block is on a fork which was abandoned by finality.
+				return Ok(());
+			}
+		} else {
+			self.block_ancestry.insert(block.hash, (block.parent_hash, block.number));
+		}
+
+		let mut update = ActiveLeavesUpdate::default();
+
 		if let Some(parent) = self.active_leaves.take(&(block.parent_hash, block.number - 1)) {
-			self.broadcast_signal(OverseerSignal::StopWork(parent.0)).await?;
+			update.deactivated.push(parent.0);
 		}
 
-		if !self.active_leaves.contains(&(block.hash, block.number)) {
-			self.broadcast_signal(OverseerSignal::StartWork(block.hash)).await?;
-			self.active_leaves.insert((block.hash, block.number));
+		if self.active_leaves.insert((block.hash, block.number)) {
+			update.activated.push(block.hash);
 		}
 
-		Ok(())
+		self.broadcast_active_leaves(update).await
 	}
 
 	async fn block_finalized(&mut self, block: BlockInfo) -> SubsystemResult<()> {
-		let mut stop_these = Vec::new();
-
-		self.active_leaves.retain(|(h, n)| {
-			if *n <= block.number {
-				stop_these.push(*h);
-				false
-			} else {
-				true
+		let mut update = ActiveLeavesUpdate::default();
+		let ancestry = &self.block_ancestry;
+
+		self.active_leaves.retain(|&(hash, number)| {
+			let keep = is_descendant_of(ancestry, hash, number, (block.hash, block.number));
+			if !keep {
+				update.deactivated.push(hash);
 			}
+
+			keep
 		});
+		update.deactivated.sort();
 
-		for hash in stop_these.into_iter() {
-			self.broadcast_signal(OverseerSignal::StopWork(hash)).await?
-		}
+		self.block_ancestry.retain(|_, (_, number)| *number > block.number);
+		self.finalized = Some((block.hash, block.number));
+
+		self.broadcast_active_leaves(update).await?;
+		self.broadcast_signal(OverseerSignal::BlockFinalized(block.hash, block.number)).await?;
 
 		Ok(())
 	}
 
-	async fn broadcast_signal(&mut self, signal: OverseerSignal) -> SubsystemResult<()> {
-		if let Some(ref mut s) = self.validation_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal.clone())).await?;
+	async fn broadcast_active_leaves(&mut self, update: ActiveLeavesUpdate) -> SubsystemResult<()> {
+		if update.is_empty() {
+			return Ok(());
 		}
 
-		if let Some(ref mut s) = self.candidate_backing_subsystem.instance {
-			s.tx.send(FromOverseer::Signal(signal)).await?;
+		let started = Instant::now();
+		self.broadcast_signal(OverseerSignal::ActiveLeaves(update)).await?;
+		self.metrics.on_active_leaves_delivered(started.elapsed());
+
+		Ok(())
+	}
+
+	fn introspect(&self) -> Introspection {
+		let mut active_leaves: Vec<_> = self.active_leaves.iter().cloned().collect();
+		active_leaves.sort_by_key(|(hash, number)| (*number, *hash));
+
+		let restarts = &self.restarts;
+		let subsystems = vec![
+			self.candidate_validation_subsystem.state(restarts),
+			self.candidate_backing_subsystem.state(restarts),
+			self.candidate_selection_subsystem.state(restarts),
+			self.statement_distribution_subsystem.state(restarts),
+			self.availability_distribution_subsystem.state(restarts),
+			self.pov_distribution_subsystem.state(restarts),
+			self.bitfield_distribution_subsystem.state(restarts),
+			self.bitfield_signing_subsystem.state(restarts),
+			self.provisioner_subsystem.state(restarts),
+			self.runtime_api_subsystem.state(restarts),
+			self.availability_store_subsystem.state(restarts),
+			self.network_bridge_subsystem.state(restarts),
+		];
+
+		Introspection {
+			active_leaves,
+			subsystems,
 		}
+	}
+
+	async fn broadcast_signal(&mut self, signal: OverseerSignal) -> SubsystemResult<()> {
+		self.candidate_validation_subsystem.send_signal(signal.clone()).await?;
+		self.candidate_backing_subsystem.send_signal(signal.clone()).await?;
+		self.candidate_selection_subsystem.send_signal(signal.clone()).await?;
+		self.statement_distribution_subsystem.send_signal(signal.clone()).await?;
+		self.availability_distribution_subsystem.send_signal(signal.clone()).await?;
+		self.pov_distribution_subsystem.send_signal(signal.clone()).await?;
+		self.bitfield_distribution_subsystem.send_signal(signal.clone()).await?;
+		self.bitfield_signing_subsystem.send_signal(signal.clone()).await?;
+		self.provisioner_subsystem.send_signal(signal.clone()).await?;
+		self.runtime_api_subsystem.send_signal(signal.clone()).await?;
+		self.availability_store_subsystem.send_signal(signal.clone()).await?;
+		self.network_bridge_subsystem.send_signal(signal).await?;
 
 		Ok(())
 	}
@@ -630,20 +1774,40 @@ where
 	async fn route_message(&mut self, msg: AllMessages) {
 		match msg {
 			AllMessages::CandidateValidation(msg) => {
-				if let Some(ref mut s) = self.validation_subsystem.instance {
-					let _= s.tx.send(FromOverseer::Communication { msg }).await;
-				}
+				let _ = self.candidate_validation_subsystem.send_message(msg).await;
 			}
 			AllMessages::CandidateBacking(msg) => {
-				if let Some(ref mut s) = self.candidate_backing_subsystem.instance {
-					let _ = s.tx.send(FromOverseer::Communication { msg }).await;
-				}
+				let _ = self.candidate_backing_subsystem.send_message(msg).await;
+			}
+			AllMessages::CandidateSelection(msg) => {
+				let _ = self.candidate_selection_subsystem.send_message(msg).await;
+			}
+			AllMessages::StatementDistribution(msg) => {
+				let _ = self.statement_distribution_subsystem.send_message(msg).await;
+			}
+			AllMessages::AvailabilityDistribution(msg) => {
+				let _ = self.availability_distribution_subsystem.send_message(msg).await;
+			}
+			AllMessages::PoVDistribution(msg) => {
+				let _ = self.pov_distribution_subsystem.send_message(msg).await;
 			}
-			_ => {
-				// TODO: temporary catch-all until all subsystems are integrated with overseer.
-				// The overseer is not complete until this is an exhaustive match with all
-				// messages targeting an included subsystem.
-				// https://github.com/paritytech/polkadot/issues/1317
+			AllMessages::BitfieldDistribution(msg) => {
+				let _ = self.bitfield_distribution_subsystem.send_message(msg).await;
+			}
+			AllMessages::BitfieldSigning(msg) => {
+				let _ = self.bitfield_signing_subsystem.send_message(msg).await;
+			}
+			AllMessages::Provisioner(msg) => {
+				let _ = self.provisioner_subsystem.send_message(msg).await;
+			}
+			AllMessages::RuntimeApi(msg) => {
+				let _ = self.runtime_api_subsystem.send_message(msg).await;
+			}
+			AllMessages::AvailabilityStore(msg) => {
+				let _ = self.availability_store_subsystem.send_message(msg).await;
+			}
+			AllMessages::NetworkBridge(msg) => {
+				let _ = self.network_bridge_subsystem.send_message(msg).await;
 			}
 		}
 	}
@@ -653,38 +1817,60 @@ where
 	}
 }
 
+/// Whether the block with the given hash and number is the `finalized` block or one of its
+/// descendants, according to the known `ancestry`. Blocks above the finalized block whose
+/// ancestry isn't known are assumed to be descendants.
+fn is_descendant_of(
+	ancestry: &HashMap<Hash, (Hash, BlockNumber)>,
+	mut hash: Hash,
+	mut number: BlockNumber,
+	(finalized_hash, finalized_number): (Hash, BlockNumber),
+) -> bool {
+	while number > finalized_number {
+		match ancestry.get(&hash) {
+			Some((parent_hash, _)) => {
+				hash = *parent_hash;
+				number -= 1;
+			}
+			None => return true,
+		}
+	}
+
+	number == finalized_number && hash == finalized_hash
+}
+
 fn spawn<S: Spawn, M: Send + 'static>(
 	spawner: &mut S,
-	futures: &mut FuturesUnordered<RemoteHandle<()>>,
+	futures: &mut FuturesUnordered<RemoteHandle<SubsystemId>>,
 	streams: &mut StreamUnordered<mpsc::Receiver<ToOverseer>>,
-	s: impl Subsystem<OverseerSubsystemContext<M>>,
+	metrics: &Metrics,
+	id: SubsystemId,
+	s: impl Subsystem<OverseerSubsystemContext<M>> + Clone + Send + 'static,
 ) -> SubsystemResult<OverseenSubsystem<M>> {
-	let (to_tx, to_rx) = mpsc::channel(CHANNEL_CAPACITY);
-	let (from_tx, from_rx) = mpsc::channel(CHANNEL_CAPACITY);
-	let ctx = OverseerSubsystemContext { rx: to_rx, tx: from_tx };
-	let f = s.start(ctx);
+	let mut subsystem = OverseenSubsystem {
+		id,
+		instance: None,
+		start: Box::new(move |ctx| s.clone().start(ctx)),
+		metrics: metrics.clone(),
+	};
 
-	let handle = spawner.spawn_with_handle(f.0)?;
+	subsystem.start(spawner, futures, streams)?;
 
-	streams.push(from_rx);
-	futures.push(handle);
-
-	let instance = Some(SubsystemInstance {
-		tx: to_tx,
-	});
-
-	Ok(OverseenSubsystem {
-		instance,
-	})
+	Ok(subsystem)
 }
 
 #[cfg(test)]
 mod tests {
 	use futures::{executor, pin_mut, select, channel::mpsc, FutureExt};
 
+	use std::sync::atomic::{self, AtomicBool, AtomicUsize};
+
 	use polkadot_primitives::parachain::{BlockData, PoVBlock};
+	use polkadot_subsystem::messages::{NetworkBridgeEvent, RuntimeApiRequest};
+	use polkadot_node_primitives::View;
 	use super::*;
 
+	#[derive(Clone)]
 	struct TestSubsystem1(mpsc::Sender<usize>);
 
 	impl<C> Subsystem<C> for TestSubsystem1
@@ -710,6 +1896,7 @@ mod tests {
 		}
 	}
 
+	#[derive(Clone)]
 	struct TestSubsystem2(mpsc::Sender<usize>);
 
 	impl<C> Subsystem<C> for TestSubsystem2
@@ -754,6 +1941,7 @@ mod tests {
 		}
 	}
 
+	#[derive(Clone)]
 	struct TestSubsystem4;
 
 	impl<C> Subsystem<C> for TestSubsystem4
@@ -775,10 +1963,13 @@ mod tests {
 			let (s1_tx, mut s1_rx) = mpsc::channel(64);
 			let (s2_tx, mut s2_rx) = mpsc::channel(64);
 
+			let all_subsystems = AllSubsystems::dummy()
+				.replace_candidate_validation(TestSubsystem1(s1_tx))
+				.replace_candidate_backing(TestSubsystem2(s2_tx));
 			let (overseer, mut handler) = Overseer::new(
 				vec![],
-				TestSubsystem1(s1_tx),
-				TestSubsystem2(s2_tx),
+				all_subsystems,
+				None,
 				spawner,
 			).unwrap();
 			let overseer_fut = overseer.run().fuse();
@@ -825,10 +2016,13 @@ mod tests {
 
 		executor::block_on(async move {
 			let (s1_tx, _) = mpsc::channel(64);
+			let all_subsystems = AllSubsystems::dummy()
+				.replace_candidate_validation(TestSubsystem1(s1_tx))
+				.replace_candidate_backing(TestSubsystem4);
 			let (overseer, _handle) = Overseer::new(
 				vec![],
-				TestSubsystem1(s1_tx),
-				TestSubsystem4,
+				all_subsystems,
+				None,
 				spawner,
 			).unwrap();
 			let overseer_fut = overseer.run().fuse();
@@ -841,6 +2035,217 @@ mod tests {
 		})
 	}
 
+	#[derive(Clone)]
+	struct ExitingSubsystem(Arc<AtomicUsize>);
+
+	impl<C: SubsystemContext> Subsystem<C> for ExitingSubsystem {
+		fn start(self, _ctx: C) -> SpawnedSubsystem {
+			self.0.fetch_add(1, atomic::Ordering::SeqCst);
+
+			SpawnedSubsystem(Box::pin(async move {
+				// Do nothing and exit.
+			}))
+		}
+	}
+
+	// Spawn a subsystem that immediately exits, allowing two restarts.
+	//
+	// Should conclude the overseer with an error once the restarts are used up.
+	#[test]
+	fn overseer_escalates_after_limited_restarts() {
+		let spawner = executor::ThreadPool::new().unwrap();
+
+		executor::block_on(async move {
+			let starts = Arc::new(AtomicUsize::new(0));
+			let all_subsystems = AllSubsystems::dummy()
+				.replace_candidate_backing(ExitingSubsystem(starts.clone()));
+			let (mut overseer, _handle) = Overseer::new(
+				vec![],
+				all_subsystems,
+				None,
+				spawner,
+			).unwrap();
+
+			overseer.set_supervision_policy(
+				SubsystemId::CandidateBacking,
+				SupervisionPolicy::RestartLimited {
+					max_restarts: 2,
+					reset_after: Duration::from_secs(60),
+				},
+			);
+
+			let overseer_fut = overseer.run().fuse();
+			pin_mut!(overseer_fut);
+
+			select! {
+				res = overseer_fut => assert!(res.is_err()),
+				complete => (),
+			}
+
+			assert_eq!(starts.load(atomic::Ordering::SeqCst), 3);
+		})
+	}
+
+	#[derive(Clone)]
+	struct SlowlyExitingSubsystem(Arc<AtomicUsize>);
+
+	impl<C: SubsystemContext> Subsystem<C> for SlowlyExitingSubsystem {
+		fn start(self, _ctx: C) -> SpawnedSubsystem {
+			self.0.fetch_add(1, atomic::Ordering::SeqCst);
+
+			SpawnedSubsystem(Box::pin(async move {
+				Delay::new(Duration::from_millis(50)).await;
+			}))
+		}
+	}
+
+	// Spawn a subsystem that exits after running for a while, allowing a single restart
+	// which is forgotten after a shorter healthy period.
+	//
+	// Should keep restarting the subsystem instead of escalating.
+	#[test]
+	fn overseer_resets_limited_restarts_after_healthy_period() {
+		let spawner = executor::ThreadPool::new().unwrap();
+
+		executor::block_on(async move {
+			let starts = Arc::new(AtomicUsize::new(0));
+			let all_subsystems = AllSubsystems::dummy()
+				.replace_candidate_backing(SlowlyExitingSubsystem(starts.clone()));
+			let (mut overseer, mut handler) = Overseer::new(
+				vec![],
+				all_subsystems,
+				None,
+				spawner,
+			).unwrap();
+
+			overseer.set_supervision_policy(
+				SubsystemId::CandidateBacking,
+				SupervisionPolicy::RestartLimited {
+					max_restarts: 1,
+					reset_after: Duration::from_millis(10),
+				},
+			);
+
+			let overseer_fut = overseer.run().fuse();
+			pin_mut!(overseer_fut);
+
+			let mut stopped = false;
+
+			loop {
+				select! {
+					res = overseer_fut => {
+						assert!(res.is_ok());
+						break;
+					},
+					_ = Delay::new(Duration::from_millis(10)).fuse() => {
+						if !stopped && starts.load(atomic::Ordering::SeqCst) >= 4 {
+							handler.stop().await.unwrap();
+							stopped = true;
+						}
+					},
+				}
+			}
+
+			assert!(starts.load(atomic::Ordering::SeqCst) >= 4);
+		})
+	}
+
+	#[derive(Clone)]
+	struct PanickingSubsystem {
+		signals: mpsc::Sender<OverseerSignal>,
+		panicked: Arc<AtomicBool>,
+	}
+
+	impl<C: SubsystemContext> Subsystem<C> for PanickingSubsystem {
+		fn start(self, mut ctx: C) -> SpawnedSubsystem {
+			let mut sender = self.signals;
+			let panicked = self.panicked;
+
+			SpawnedSubsystem(Box::pin(async move {
+				loop {
+					match ctx.recv().await {
+						Ok(FromOverseer::Signal(OverseerSignal::Conclude)) | Err(_) => return,
+						Ok(FromOverseer::Signal(s)) => {
+							sender.send(s).await.unwrap();
+
+							if !panicked.swap(true, atomic::Ordering::SeqCst) {
+								panic!("the first instance panics on its first signal");
+							}
+						}
+						Ok(FromOverseer::Communication { .. }) => {}
+					}
+				}
+			}))
+		}
+	}
+
+	// Tests that a panicking subsystem with a restart policy is restarted without
+	// taking the overseer down, and learns about the active leaves again.
+	#[test]
+	fn overseer_restarts_panicking_subsystem() {
+		let spawner = executor::ThreadPool::new().unwrap();
+
+		executor::block_on(async move {
+			let first_block_hash = [1; 32].into();
+			let first_block = BlockInfo {
+				hash: first_block_hash,
+				parent_hash: [0; 32].into(),
+				number: 1,
+			};
+
+			let (tx, mut rx) = mpsc::channel(64);
+			let all_subsystems = AllSubsystems::dummy()
+				.replace_statement_distribution(PanickingSubsystem {
+					signals: tx,
+					panicked: Arc::new(AtomicBool::new(false)),
+				});
+			let (mut overseer, mut handler) = Overseer::new(
+				vec![first_block],
+				all_subsystems,
+				None,
+				spawner,
+			).unwrap();
+
+			overseer.set_supervision_policy(
+				SubsystemId::StatementDistribution,
+				SupervisionPolicy::RestartWithBackoff {
+					initial: Duration::from_millis(10),
+					max: Duration::from_millis(100),
+				},
+			);
+
+			let overseer_fut = overseer.run().fuse();
+			pin_mut!(overseer_fut);
+
+			let mut results = Vec::new();
+
+			loop {
+				select! {
+					res = overseer_fut => {
+						assert!(res.is_ok());
+						break;
+					},
+					res = rx.next() => {
+						if let Some(res) = res {
+							results.push(res);
+						}
+
+						if results.len() == 2 {
+							handler.stop().await.unwrap();
+						}
+					}
+					complete => break,
+				}
+			}
+
+			assert_eq!(results, vec![
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::start_work(first_block_hash)),
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::start_work(first_block_hash)),
+			]);
+		})
+	}
+
+	#[derive(Clone)]
 	struct TestSubsystem5(mpsc::Sender<OverseerSignal>);
 
 	impl<C> Subsystem<C> for TestSubsystem5
@@ -867,6 +2272,7 @@ mod tests {
 		}
 	}
 
+	#[derive(Clone)]
 	struct TestSubsystem6(mpsc::Sender<OverseerSignal>);
 
 	impl<C> Subsystem<C> for TestSubsystem6
@@ -894,7 +2300,7 @@ mod tests {
 	}
 
 	// Tests that starting with a defined set of leaves and receiving
-	// notifications on imported blocks triggers expected `StartWork` and `StopWork` heartbeats.
+	// notifications on imported blocks triggers expected `ActiveLeaves` heartbeats.
 	#[test]
 	fn overseer_start_stop_works() {
 		let spawner = executor::ThreadPool::new().unwrap();
@@ -923,10 +2329,13 @@ mod tests {
 			let (tx_5, mut rx_5) = mpsc::channel(64);
 			let (tx_6, mut rx_6) = mpsc::channel(64);
 
+			let all_subsystems = AllSubsystems::dummy()
+				.replace_candidate_validation(TestSubsystem5(tx_5))
+				.replace_candidate_backing(TestSubsystem6(tx_6));
 			let (overseer, mut handler) = Overseer::new(
 				vec![first_block],
-				TestSubsystem5(tx_5),
-				TestSubsystem6(tx_6),
+				all_subsystems,
+				None,
 				spawner,
 			).unwrap();
 
@@ -940,11 +2349,15 @@ mod tests {
 			handler.block_imported(third_block).await.unwrap();
 
 			let expected_heartbeats = vec![
-				OverseerSignal::StartWork(first_block_hash),
-				OverseerSignal::StopWork(first_block_hash),
-				OverseerSignal::StartWork(second_block_hash),
-				OverseerSignal::StopWork(second_block_hash),
-				OverseerSignal::StartWork(third_block_hash),
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::start_work(first_block_hash)),
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate {
+					activated: vec![second_block_hash],
+					deactivated: vec![first_block_hash],
+				}),
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate {
+					activated: vec![third_block_hash],
+					deactivated: vec![second_block_hash],
+				}),
 			];
 
 			loop {
@@ -978,7 +2391,7 @@ mod tests {
 	}
 
 	// Tests that starting with a defined set of leaves and receiving
-	// notifications on imported blocks triggers expected `StartWork` and `StopWork` heartbeats.
+	// notifications on imported blocks triggers expected `ActiveLeaves` heartbeats.
 	#[test]
 	fn overseer_finalize_works() {
 		let spawner = executor::ThreadPool::new().unwrap();
@@ -1008,10 +2421,13 @@ mod tests {
 			let (tx_6, mut rx_6) = mpsc::channel(64);
 
 			// start with two forks of different height.
+			let all_subsystems = AllSubsystems::dummy()
+				.replace_candidate_validation(TestSubsystem5(tx_5))
+				.replace_candidate_backing(TestSubsystem6(tx_6));
 			let (overseer, mut handler) = Overseer::new(
 				vec![first_block, second_block],
-				TestSubsystem5(tx_5),
-				TestSubsystem6(tx_6),
+				all_subsystems,
+				None,
 				spawner,
 			).unwrap();
 
@@ -1025,10 +2441,15 @@ mod tests {
 			handler.block_finalized(third_block).await.unwrap();
 
 			let expected_heartbeats = vec![
-				OverseerSignal::StartWork(first_block_hash),
-				OverseerSignal::StartWork(second_block_hash),
-				OverseerSignal::StopWork(first_block_hash),
-				OverseerSignal::StopWork(second_block_hash),
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate {
+					activated: vec![first_block_hash, second_block_hash],
+					deactivated: Vec::new(),
+				}),
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate {
+					activated: Vec::new(),
+					deactivated: vec![first_block_hash, second_block_hash],
+				}),
+				OverseerSignal::BlockFinalized(third_block_hash, 3),
 			];
 
 			loop {
@@ -1067,4 +2488,372 @@ mod tests {
 			}
 		});
 	}
+
+	// Tests that finality deactivates the leaves of abandoned forks, and that blocks imported
+	// on top of them later on are ignored.
+	#[test]
+	fn overseer_prunes_abandoned_forks() {
+		let spawner = executor::ThreadPool::new().unwrap();
+
+		executor::block_on(async move {
+			let a1_hash = [1; 32].into();
+			let a2_hash = [2; 32].into();
+			let a3_hash = [3; 32].into();
+			let b1_hash = [11; 32].into();
+			let b2_hash = [12; 32].into();
+
+			let block = |hash, parent_hash, number| BlockInfo { hash, parent_hash, number };
+
+			let (tx_5, mut rx_5) = mpsc::channel(64);
+			let all_subsystems = AllSubsystems::dummy()
+				.replace_candidate_validation(TestSubsystem5(tx_5));
+			let (overseer, mut handler) = Overseer::new(
+				vec![block(a1_hash, [0; 32].into(), 1)],
+				all_subsystems,
+				None,
+				spawner,
+			).unwrap();
+
+			let overseer_fut = overseer.run().fuse();
+			pin_mut!(overseer_fut);
+
+			handler.block_imported(block(b1_hash, [0; 32].into(), 1)).await.unwrap();
+			handler.block_imported(block(a2_hash, a1_hash, 2)).await.unwrap();
+			handler.block_finalized(block(a1_hash, [0; 32].into(), 1)).await.unwrap();
+			handler.block_imported(block(b2_hash, b1_hash, 2)).await.unwrap();
+			handler.block_imported(block(a3_hash, a2_hash, 3)).await.unwrap();
+
+			let expected_heartbeats = vec![
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::start_work(a1_hash)),
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::start_work(b1_hash)),
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate {
+					activated: vec![a2_hash],
+					deactivated: vec![a1_hash],
+				}),
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate::stop_work(b1_hash)),
+				OverseerSignal::BlockFinalized(a1_hash, 1),
+				OverseerSignal::ActiveLeaves(ActiveLeavesUpdate {
+					activated: vec![a3_hash],
+					deactivated: vec![a2_hash],
+				}),
+			];
+
+			let mut ss5_results = Vec::new();
+			loop {
+				select! {
+					res = overseer_fut => {
+						assert!(res.is_ok());
+						break;
+					},
+					res = rx_5.next() => {
+						if let Some(res) = res {
+							ss5_results.push(res);
+						}
+					}
+					complete => break,
+				}
+
+				if ss5_results.len() == expected_heartbeats.len() {
+					handler.stop().await.unwrap();
+				}
+			}
+
+			assert_eq!(ss5_results, expected_heartbeats);
+		});
+	}
+
+	#[derive(Clone)]
+	struct CounterSubsystem {
+		signals_received: Arc<AtomicUsize>,
+		msgs_received: Arc<AtomicUsize>,
+		stop_signals_received: Arc<AtomicUsize>,
+	}
+
+	impl CounterSubsystem {
+		fn new() -> Self {
+			CounterSubsystem {
+				signals_received: Arc::new(AtomicUsize::new(0)),
+				msgs_received: Arc::new(AtomicUsize::new(0)),
+				stop_signals_received: Arc::new(AtomicUsize::new(0)),
+			}
+		}
+	}
+
+	impl<C: SubsystemContext> Subsystem<C> for CounterSubsystem {
+		fn start(self, mut ctx: C) -> SpawnedSubsystem {
+			SpawnedSubsystem(Box::pin(async move {
+				loop {
+					match ctx.recv().await {
+						Ok(FromOverseer::Signal(OverseerSignal::Conclude)) => {
+							self.stop_signals_received.fetch_add(1, atomic::Ordering::SeqCst);
+							return;
+						}
+						Ok(FromOverseer::Signal(_)) => {
+							self.signals_received.fetch_add(1, atomic::Ordering::SeqCst);
+						}
+						Ok(FromOverseer::Communication { .. }) => {
+							self.msgs_received.fetch_add(1, atomic::Ordering::SeqCst);
+						}
+						Err(_) => return,
+					}
+				}
+			}))
+		}
+	}
+
+	fn test_view_change() -> NetworkBridgeEvent {
+		NetworkBridgeEvent::OurViewChange(View(Vec::new()))
+	}
+
+	// Checks that signals reach every subsystem and that messages of every kind are
+	// routed to the subsystem registered for them.
+	#[test]
+	fn overseer_all_subsystems_receive_signals_and_messages() {
+		let spawner = executor::ThreadPool::new().unwrap();
+
+		executor::block_on(async move {
+			let counter = CounterSubsystem::new();
+
+			let all_subsystems = AllSubsystems {
+				candidate_validation: counter.clone(),
+				candidate_backing: counter.clone(),
+				candidate_selection: counter.clone(),
+				statement_distribution: counter.clone(),
+				availability_distribution: counter.clone(),
+				pov_distribution: counter.clone(),
+				bitfield_distribution: counter.clone(),
+				bitfield_signing: counter.clone(),
+				provisioner: counter.clone(),
+				runtime_api: counter.clone(),
+				availability_store: counter.clone(),
+				network_bridge: counter.clone(),
+			};
+
+			let first_block = BlockInfo {
+				hash: [1; 32].into(),
+				parent_hash: [0; 32].into(),
+				number: 1,
+			};
+
+			let (overseer, mut handler) = Overseer::new(
+				vec![first_block],
+				all_subsystems,
+				None,
+				spawner,
+			).unwrap();
+			let overseer_fut = overseer.run().fuse();
+			pin_mut!(overseer_fut);
+
+			let (tx, _) = oneshot::channel();
+			let (watcher_tx, _) = mpsc::channel(1);
+
+			// `BitfieldSigningMessage` has no variants, so there is no message to route to
+			// the bitfield signing subsystem.
+			let msgs = vec![
+				AllMessages::CandidateValidation(CandidateValidationMessage::Validate(
+					Default::default(),
+					Default::default(),
+					PoVBlock { block_data: BlockData(Vec::new()) },
+					tx,
+				)),
+				AllMessages::CandidateBacking(
+					CandidateBackingMessage::RegisterBackingWatcher(Default::default(), watcher_tx),
+				),
+				AllMessages::CandidateSelection(
+					CandidateSelectionMessage::Invalid(Default::default(), Default::default()),
+				),
+				AllMessages::StatementDistribution(
+					StatementDistributionMessage::NetworkBridgeUpdate(test_view_change()),
+				),
+				AllMessages::AvailabilityDistribution(
+					AvailabilityDistributionMessage::FetchChunk(Default::default(), 0),
+				),
+				AllMessages::PoVDistribution(
+					PoVDistributionMessage::NetworkBridgeUpdate(test_view_change()),
+				),
+				AllMessages::BitfieldDistribution(
+					BitfieldDistributionMessage::NetworkBridgeUpdate(test_view_change()),
+				),
+				AllMessages::Provisioner(
+					ProvisionerMessage::RequestInherentData(Default::default(), oneshot::channel().0),
+				),
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					Default::default(),
+					RuntimeApiRequest::Validators(oneshot::channel().0),
+				)),
+				AllMessages::AvailabilityStore(
+					AvailabilityStoreMessage::QueryChunk(Default::default(), 0, oneshot::channel().0),
+				),
+				AllMessages::NetworkBridge(
+					NetworkBridgeMessage::SendMessage(Vec::new(), *b"test", Vec::new()),
+				),
+			];
+
+			let n_msgs = msgs.len();
+			for msg in msgs {
+				handler.send_msg(msg).await.unwrap();
+			}
+
+			const NUM_SUBSYSTEMS: usize = 12;
+
+			let mut stopped = false;
+			loop {
+				select! {
+					res = overseer_fut => {
+						assert!(res.is_ok());
+						break;
+					},
+					_ = Delay::new(Duration::from_millis(50)).fuse() => {
+						let signals = counter.signals_received.load(atomic::Ordering::SeqCst);
+						let msgs = counter.msgs_received.load(atomic::Ordering::SeqCst);
+
+						if !stopped && signals == NUM_SUBSYSTEMS && msgs == n_msgs {
+							handler.stop().await.unwrap();
+							stopped = true;
+						}
+					}
+				}
+			}
+
+			assert_eq!(counter.signals_received.load(atomic::Ordering::SeqCst), NUM_SUBSYSTEMS);
+			assert_eq!(counter.msgs_received.load(atomic::Ordering::SeqCst), n_msgs);
+			assert_eq!(counter.stop_signals_received.load(atomic::Ordering::SeqCst), NUM_SUBSYSTEMS);
+		});
+	}
+
+	// Tests that introspection reports the active leaves and the state of every subsystem.
+	#[test]
+	fn overseer_introspection_reports_leaves_and_subsystems() {
+		let spawner = executor::ThreadPool::new().unwrap();
+
+		executor::block_on(async move {
+			let first_block_hash = [1; 32].into();
+			let first_block = BlockInfo {
+				hash: first_block_hash,
+				parent_hash: [0; 32].into(),
+				number: 1,
+			};
+
+			let (overseer, mut handler) = Overseer::new(
+				vec![first_block],
+				AllSubsystems::dummy(),
+				None,
+				spawner,
+			).unwrap();
+
+			let overseer_fut = overseer.run().fuse();
+			pin_mut!(overseer_fut);
+
+			let introspection = select! {
+				_ = overseer_fut => panic!("overseer exited before being introspected"),
+				res = handler.introspect().fuse() => res.unwrap(),
+			};
+
+			assert_eq!(introspection.active_leaves, vec![(first_block_hash, 1)]);
+			assert_eq!(introspection.subsystems.len(), 12);
+			assert_eq!(introspection.subsystems[0].id, SubsystemId::CandidateValidation);
+			assert_eq!(introspection.subsystems[11].id, SubsystemId::NetworkBridge);
+			assert!(introspection.subsystems.iter().all(|s| s.running && s.restarts == 0));
+
+			handler.stop().await.unwrap();
+			assert!(overseer_fut.await.is_ok());
+		});
+	}
+
+	// A subsystem which holds on to the responders of runtime API requests without answering.
+	#[derive(Clone)]
+	struct UnresponsiveSubsystem;
+
+	impl<C> Subsystem<C> for UnresponsiveSubsystem
+		where C: SubsystemContext<Message=RuntimeApiMessage>
+	{
+		fn start(self, mut ctx: C) -> SpawnedSubsystem {
+			SpawnedSubsystem(Box::pin(async move {
+				let mut held = Vec::new();
+
+				loop {
+					match ctx.recv().await {
+						Ok(FromOverseer::Signal(OverseerSignal::Conclude)) | Err(_) => return,
+						Ok(FromOverseer::Communication { msg }) => held.push(msg),
+						Ok(FromOverseer::Signal(_)) => {}
+					}
+				}
+			}))
+		}
+	}
+
+	// A subsystem which makes one request nobody answers and one request which gets dropped.
+	#[derive(Clone)]
+	struct RequestingSubsystem(mpsc::Sender<Result<(), RequestError>>);
+
+	impl<C> Subsystem<C> for RequestingSubsystem
+		where C: SubsystemContext<Message=CandidateValidationMessage>
+	{
+		fn start(self, mut ctx: C) -> SpawnedSubsystem {
+			let mut sender = self.0;
+			SpawnedSubsystem(Box::pin(async move {
+				let timed_out = ctx.request(
+					|tx| AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						Default::default(),
+						RuntimeApiRequest::Validators(tx),
+					)),
+					Duration::from_millis(50),
+				).await;
+				sender.send(timed_out.map(|_| ())).await.unwrap();
+
+				let canceled = ctx.request(
+					|tx| AllMessages::AvailabilityStore(
+						AvailabilityStoreMessage::QueryChunk(Default::default(), 0, tx),
+					),
+					Duration::from_secs(5),
+				).await;
+				sender.send(canceled.map(|_| ())).await.unwrap();
+
+				while let Ok(msg) = ctx.recv().await {
+					if let FromOverseer::Signal(OverseerSignal::Conclude) = msg {
+						return;
+					}
+				}
+			}))
+		}
+	}
+
+	// Tests that requests fail with a typed error on timeout and on dropped responders.
+	#[test]
+	fn overseer_request_times_out_or_is_canceled() {
+		let spawner = executor::ThreadPool::new().unwrap();
+
+		executor::block_on(async move {
+			let (tx, mut rx) = mpsc::channel(64);
+			let all_subsystems = AllSubsystems::dummy()
+				.replace_candidate_validation(RequestingSubsystem(tx))
+				.replace_runtime_api(UnresponsiveSubsystem);
+			let (overseer, mut handler) = Overseer::new(
+				vec![],
+				all_subsystems,
+				None,
+				spawner,
+			).unwrap();
+
+			let overseer_fut = overseer.run().fuse();
+			pin_mut!(overseer_fut);
+
+			let mut results = Vec::new();
+			loop {
+				select! {
+					_ = overseer_fut => break,
+					res = rx.next() => {
+						if let Some(res) = res {
+							results.push(res);
+							if results.len() == 2 {
+								handler.stop().await.unwrap();
+							}
+						}
+					}
+				}
+			}
+
+			assert_eq!(results, vec![Err(RequestError::Timeout), Err(RequestError::Canceled)]);
+		});
+	}
 }
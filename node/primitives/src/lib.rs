@@ -23,8 +23,9 @@
 use parity_scale_codec::{Decode, Encode};
 use polkadot_primitives::{Hash,
 	parachain::{
-		AbridgedCandidateReceipt, CandidateReceipt, CompactStatement,
-		EncodeAs, Signed,
+		v2::AbridgedCandidateReceipt, v2::CandidateReceipt, CompactStatement,
+		EncodeAs, Signed, HeadData, v2::CandidateCommitments, OmittedValidationData,
+		Id as ParaId,
 	}
 };
 
@@ -42,16 +43,21 @@ pub enum Statement {
 	Invalid(Hash),
 }
 
+impl Statement {
+	/// Transform this statement into its compact version, which references only the hash
+	/// of the candidate.
+	pub fn to_compact(&self) -> CompactStatement {
+		match *self {
+			Statement::Seconded(ref c) => CompactStatement::Candidate(c.hash()),
+			Statement::Valid(hash) => CompactStatement::Valid(hash),
+			Statement::Invalid(hash) => CompactStatement::Invalid(hash),
+		}
+	}
+}
+
 impl EncodeAs<CompactStatement> for Statement {
 	fn encode_as(&self) -> Vec<u8> {
-		let statement = match *self {
-			Statement::Seconded(ref c) => {
-				polkadot_primitives::parachain::CompactStatement::Candidate(c.hash())
-			}
-			Statement::Valid(hash) => polkadot_primitives::parachain::CompactStatement::Valid(hash),
-			Statement::Invalid(hash) => polkadot_primitives::parachain::CompactStatement::Invalid(hash),
-		};
-		statement.encode()
+		self.to_compact().encode()
 	}
 }
 
@@ -63,8 +69,19 @@ impl EncodeAs<CompactStatement> for Statement {
 /// Only the compact `SignedStatement` is suitable for submission to the chain.
 pub type SignedFullStatement = Signed<Statement, CompactStatement>;
 
+/// The state of an availability core at some relay-parent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CoreState {
+	/// The core is occupied by the candidate with the given hash, pending availability.
+	Occupied(Hash),
+	/// The core is free and the given para is scheduled to occupy it next.
+	Scheduled(ParaId),
+	/// The core is free and nothing is scheduled on it.
+	Free,
+}
+
 /// A misbehaviour report.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum MisbehaviorReport {
 	/// These validator nodes disagree on this candidate's validity, please figure it out
 	///
@@ -81,6 +98,46 @@ pub enum MisbehaviorReport {
 	DoubleVote(CandidateReceipt, SignedFullStatement, SignedFullStatement),
 }
 
+/// The outputs of a successful validation of a candidate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationOutputs {
+	/// The head-data produced by validating the candidate.
+	pub head_data: HeadData,
+	/// The commitments which the candidate receipt is expected to carry, computed by the
+	/// validation itself.
+	pub commitments: CandidateCommitments,
+	/// The global and local validation data the candidate was validated against.
+	pub omitted_validation: OmittedValidationData,
+}
+
+/// Reasons a candidate can be deemed invalid.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvalidCandidate {
+	/// The PoV block does not hash to the PoV hash committed to in the receipt.
+	PoVHashMismatch,
+	/// The collator's signature on the candidate is invalid.
+	BadCollatorSignature,
+	/// The para is not active in the context of the relay-parent.
+	InactivePara,
+	/// The head-data produced is larger than allowed, in bytes.
+	HeadDataTooLarge(u64),
+	/// The validation function failed to execute, with the given description.
+	ExecutionError(String),
+	/// The head-data produced by the validation function doesn't match the head-data in the receipt.
+	HeadDataMismatch,
+	/// The commitments produced by validation don't match the commitments in the receipt.
+	CommitmentsMismatch,
+}
+
+/// The result of validating a candidate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationResult {
+	/// Candidate is valid. The validation outputs are provided.
+	Valid(ValidationOutputs),
+	/// Candidate is invalid, for the given reason.
+	Invalid(InvalidCandidate),
+}
+
 /// A unique identifier for a network protocol.
 pub type ProtocolId = [u8; 4];
 
@@ -29,11 +29,20 @@ use grandpa::{self, FinalityProofProvider as GrandpaFinalityProofProvider};
 use sc_executor::native_executor_instance;
 use log::info;
 use sp_blockchain::HeaderBackend;
-use polkadot_overseer::{self as overseer, BlockInfo, Overseer, OverseerHandler};
-use polkadot_subsystem::{
-	Subsystem, SubsystemContext, SpawnedSubsystem,
-	messages::{CandidateValidationMessage, CandidateBackingMessage},
-};
+use polkadot_overseer::{self as overseer, AllSubsystems, BlockInfo, Overseer, OverseerHandler};
+use polkadot_node_core_candidate_validation::{CandidateValidationSubsystem, ValidationPool};
+use polkadot_node_core_backing::CandidateBackingSubsystem;
+use polkadot_node_core_bitfield_signing::BitfieldSigningSubsystem;
+use polkadot_node_core_proposer::ProposerFactory;
+use polkadot_node_core_provisioner::ProvisioningSubsystem;
+use polkadot_node_core_runtime_api::RuntimeApiSubsystem;
+use polkadot_node_core_av_store::{self as av_store, AvailabilityStoreSubsystem};
+use polkadot_statement_distribution::StatementDistribution;
+use polkadot_availability_distribution::AvailabilityDistributionSubsystem;
+use polkadot_pov_distribution::PoVDistribution;
+use polkadot_bitfield_distribution::BitfieldDistribution;
+use polkadot_network_bridge::{self as network_bridge, DhtAuthorityDiscovery, NetworkBridge, PeerSetsConfig};
+use sc_keystore::KeyStorePtr;
 pub use service::{
 	Role, PruningMode, TransactionPoolOptions, Error, RuntimeGenesis,
 	TFullClient, TLightClient, TFullBackend, TLightBackend, TFullCallExecutor, TLightCallExecutor,
@@ -58,6 +67,9 @@ pub use westend_runtime;
 use prometheus_endpoint::Registry;
 pub use self::client::PolkadotClient;
 
+/// The number of blocks past the last finalized block for which availability data is kept.
+const AVAILABILITY_ACCEPTANCE_PERIOD: polkadot_primitives::BlockNumber = 600;
+
 native_executor_instance!(
 	pub PolkadotExecutor,
 	polkadot_runtime::api::dispatch,
@@ -267,37 +279,64 @@ macro_rules! new_full_start {
 	}}
 }
 
-struct CandidateValidationSubsystem;
-
-impl<C> Subsystem<C> for CandidateValidationSubsystem
-	where C: SubsystemContext<Message = CandidateValidationMessage>
+/// Create the runtime API subsystem for the given client, which answers requests of the
+/// `ParachainHostV2` runtime API if the runtime at the best block implements it.
+pub fn runtime_api_subsystem<Client>(client: Arc<Client>) -> RuntimeApiSubsystem<Client>
+	where
+		Client: ProvideRuntimeApi<Block> + HeaderBackend<Block>,
+		Client::Api: ParachainHost<Block>
+			+ parachain::ParachainHostV2<Block>
+			+ authority_discovery_primitives::AuthorityDiscoveryApi<Block>,
 {
-	fn start(self, mut ctx: C) -> SpawnedSubsystem {
-		SpawnedSubsystem(Box::pin(async move {
-			while let Ok(_) = ctx.recv().await {}
-		}))
-	}
-}
+	use sp_api::ApiExt;
 
-struct CandidateBackingSubsystem;
+	let best_block = BlockId::Hash(client.info().best_hash);
+	let has_v2 = client.runtime_api()
+		.has_api::<dyn parachain::ParachainHostV2<Block, Error = ()>>(&best_block)
+		.unwrap_or(false);
 
-impl<C> Subsystem<C> for CandidateBackingSubsystem
-	where C: SubsystemContext<Message = CandidateBackingMessage>
-{
-	fn start(self, mut ctx: C) -> SpawnedSubsystem {
-		SpawnedSubsystem(Box::pin(async move {
-			while let Ok(_) = ctx.recv().await {}
-		}))
+	if has_v2 {
+		RuntimeApiSubsystem::with_v2(client)
+	} else {
+		RuntimeApiSubsystem::new(client)
 	}
 }
 
-fn real_overseer<S: futures::task::Spawn>(
+fn real_overseer<S, Client, N, AD>(
 	leaves: impl IntoIterator<Item = BlockInfo>,
+	keystore: KeyStorePtr,
+	runtime_api: RuntimeApiSubsystem<Client>,
+	network: N,
+	authority_discovery: AD,
+	availability_store: AvailabilityStoreSubsystem,
+	registry: Option<&Registry>,
 	s: S,
-) -> Result<(Overseer<S>, OverseerHandler), ServiceError> {
-	let validation = CandidateValidationSubsystem;
-	let candidate_backing = CandidateBackingSubsystem;
-	Overseer::new(leaves, validation, candidate_backing, s)
+) -> Result<(Overseer<S>, OverseerHandler), ServiceError>
+	where
+		S: futures::task::Spawn,
+		Client: ProvideRuntimeApi<Block> + Send + Sync + 'static,
+		Client::Api: ParachainHost<Block> + authority_discovery_primitives::AuthorityDiscoveryApi<Block>,
+		N: network_bridge::Network + Clone,
+		AD: network_bridge::AuthorityDiscovery + Clone,
+{
+	let all_subsystems = AllSubsystems::dummy()
+		.replace_candidate_validation(CandidateValidationSubsystem::new(ValidationPool::new()))
+		.replace_candidate_backing(CandidateBackingSubsystem::new(keystore.clone()))
+		.replace_statement_distribution(StatementDistribution)
+		.replace_availability_distribution(AvailabilityDistributionSubsystem::new(keystore.clone()))
+		.replace_pov_distribution(PoVDistribution)
+		.replace_bitfield_distribution(BitfieldDistribution)
+		.replace_bitfield_signing(BitfieldSigningSubsystem::new(keystore))
+		.replace_provisioner(ProvisioningSubsystem)
+		.replace_runtime_api(runtime_api)
+		.replace_availability_store(availability_store)
+		.replace_network_bridge(NetworkBridge::new(
+			network,
+			authority_discovery,
+			PeerSetsConfig::default(),
+		));
+
+	Overseer::new(leaves, all_subsystems, registry, s)
 		.map_err(|e| ServiceError::Other(format!("Failed to create an Overseer: {:?}", e)))
 }
 
@@ -314,13 +353,19 @@ macro_rules! new_full {
 	) => {{
 		use sc_client_api::ExecutorProvider;
 		use sp_core::traits::BareCryptoStorePtr;
+		use futures::stream::StreamExt;
 
 		let is_collator = $collating_for.is_some();
 		let role = $config.role.clone();
 		let is_authority = role.is_authority() && !is_collator;
 		let force_authoring = $config.force_authoring;
+		let db_path = match $config.database.path() {
+			Some(path) => std::path::PathBuf::from(path),
+			None => return Err("Starting a Polkadot service with a custom database isn't supported".to_string().into()),
+		};
 		let disable_grandpa = $config.disable_grandpa;
 		let name = $config.network.node_name.clone();
+		let authority_discovery_enabled = $authority_discovery_enabled;
 
 		let (builder, mut import_setup, inherent_data_providers, mut rpc_setup) =
 			new_full_start!($config, $runtime, $dispatch);
@@ -341,6 +386,26 @@ macro_rules! new_full {
 		let shared_voter_state = rpc_setup.take()
 			.expect("The SharedVoterState is present for Full Services or setup failed before. qed");
 
+		for (engine_id, protocol_name) in network_bridge::notifications_protocol_info() {
+			network.register_notifications_protocol(engine_id, protocol_name);
+		}
+
+		let (validator_discovery, collect_validator_records) = DhtAuthorityDiscovery::new(&network);
+		task_manager.spawn_handle().spawn("validator-discovery", collect_validator_records);
+
+		let availability_store = {
+			let mut path = db_path;
+			path.push("availability");
+
+			AvailabilityStoreSubsystem::open(
+				av_store::Config {
+					cache_size: None,
+					path,
+				},
+				AVAILABILITY_ACCEPTANCE_PERIOD,
+			)?
+		};
+
 		let overseer_client = client.clone();
 		let spawner = task_manager.spawn_handle();
 		let leaves: Vec<_> = select_chain.clone().ok_or(ServiceError::SelectChainRequired)?
@@ -359,7 +424,20 @@ macro_rules! new_full {
 			})
 			.collect();
 
-		let (overseer, handler) = real_overseer(leaves, spawner)?;
+		// None of the served runtimes include the parachains runtime modules, so they don't
+		// implement `ParachainHostV2`.
+		let (overseer, handler) = real_overseer(
+			leaves,
+			keystore.clone(),
+			RuntimeApiSubsystem::new(client.clone()),
+			network.clone(),
+			validator_discovery,
+			availability_store,
+			prometheus_registry.as_ref(),
+			spawner,
+		)?;
+
+		let proposer_handler = handler.clone();
 
 		task_manager.spawn_essential_handle().spawn_blocking("overseer", Box::pin(async move {
 			use futures::{pin_mut, select, FutureExt};
@@ -386,11 +464,11 @@ macro_rules! new_full {
 			let can_author_with =
 				consensus_common::CanAuthorWithNativeVersion::new(client.executor().clone());
 
-			// TODO: custom proposer (https://github.com/paritytech/polkadot/issues/1248)
-			let proposer = sc_basic_authorship::ProposerFactory::new(
+			let proposer = ProposerFactory::new(
 				client.clone(),
 				transaction_pool,
-				None,
+				proposer_handler,
+				prometheus_registry.as_ref(),
 			);
 
 			let babe_config = babe::BabeParams {
@@ -410,6 +488,40 @@ macro_rules! new_full {
 			task_manager.spawn_essential_handle().spawn_blocking("babe", babe);
 		}
 
+		if matches!(role, Role::Authority{..} | Role::Sentry{..}) {
+			if authority_discovery_enabled {
+				let (sentries, authority_discovery_role) = match role {
+					Role::Authority { ref sentry_nodes } => (
+						sentry_nodes.clone(),
+						authority_discovery::Role::Authority (
+							keystore.clone(),
+						),
+					),
+					Role::Sentry {..} => (
+						vec![],
+						authority_discovery::Role::Sentry,
+					),
+					_ => unreachable!("Due to outer matches! constraint; qed."),
+				};
+
+				let network_event_stream = network.event_stream("authority-discovery");
+				let dht_event_stream = network_event_stream.filter_map(|e| async move { match e {
+					sc_network::Event::Dht(e) => Some(e),
+					_ => None,
+				}}).boxed();
+				let authority_discovery = authority_discovery::AuthorityDiscovery::new(
+					client.clone(),
+					network.clone(),
+					sentries,
+					dht_event_stream,
+					authority_discovery_role,
+					prometheus_registry.clone(),
+				);
+
+				task_manager.spawn_handle().spawn("authority-discovery", authority_discovery);
+			}
+		}
+
 		// if the node isn't actively participating in consensus then it doesn't
 		// need a keystore, regardless of which protocol we use below.
 		let keystore = if is_authority {
@@ -596,7 +708,7 @@ pub fn polkadot_new_full(
 	mut config: Configuration,
 	collating_for: Option<(CollatorId, parachain::Id)>,
 	_max_block_data_size: Option<u64>,
-	_authority_discovery_enabled: bool,
+	authority_discovery_enabled: bool,
 	_slot_duration: u64,
 	grandpa_pause: Option<(u32, u32)>,
 )
@@ -628,7 +740,7 @@ pub fn kusama_new_full(
 	mut config: Configuration,
 	collating_for: Option<(CollatorId, parachain::Id)>,
 	_max_block_data_size: Option<u64>,
-	_authority_discovery_enabled: bool,
+	authority_discovery_enabled: bool,
 	_slot_duration: u64,
 	grandpa_pause: Option<(u32, u32)>,
 ) -> Result<(
@@ -660,7 +772,7 @@ pub fn westend_new_full(
 	mut config: Configuration,
 	collating_for: Option<(CollatorId, parachain::Id)>,
 	_max_block_data_size: Option<u64>,
-	_authority_discovery_enabled: bool,
+	authority_discovery_enabled: bool,
 	_slot_duration: u64,
 	grandpa_pause: Option<(u32, u32)>,
 )
@@ -703,3 +815,75 @@ pub fn westend_new_light(mut config: Configuration, ) -> Result<TaskManager, Ser
 {
 	new_light!(config, westend_runtime::RuntimeApi, KusamaExecutor)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::pin::Pin;
+	use futures::{channel::oneshot, executor, future::{self, BoxFuture}, prelude::*, sink, stream::{self, BoxStream}};
+	use polkadot_subsystem::SubsystemError;
+	use polkadot_subsystem::messages::{AllMessages, RuntimeApiMessage, RuntimeApiRequest};
+	use authority_discovery_primitives::AuthorityId as AuthorityDiscoveryId;
+
+	// A network without any peers.
+	#[derive(Clone)]
+	struct NoNetwork;
+
+	impl network_bridge::Network for NoNetwork {
+		fn event_stream(&mut self) -> BoxStream<'static, sc_network::Event> {
+			stream::pending().boxed()
+		}
+
+		fn action_sink<'a>(&'a mut self)
+			-> Pin<Box<dyn Sink<network_bridge::NetworkAction, Error = SubsystemError> + Send + 'a>>
+		{
+			Box::pin(sink::drain().sink_map_err(|e| match e {}))
+		}
+	}
+
+	#[derive(Clone)]
+	struct NoAuthorityDiscovery;
+
+	impl network_bridge::AuthorityDiscovery for NoAuthorityDiscovery {
+		fn get_addresses_by_authority_id(&mut self, _authority: AuthorityDiscoveryId)
+			-> BoxFuture<Option<Vec<sc_network::Multiaddr>>>
+		{
+			future::ready(None).boxed()
+		}
+	}
+
+	#[test]
+	fn real_overseer_answers_availability_cores() {
+		let client = Arc::new(polkadot_test_runtime_client::new_node_client());
+		let genesis_hash = client.info().genesis_hash;
+		let spawner = executor::ThreadPool::new().unwrap();
+
+		let (overseer, mut handler) = real_overseer(
+			Vec::new(),
+			sc_keystore::Store::new_in_memory(),
+			runtime_api_subsystem(client),
+			NoNetwork,
+			NoAuthorityDiscovery,
+			AvailabilityStoreSubsystem::new_in_memory(AVAILABILITY_ACCEPTANCE_PERIOD),
+			None,
+			spawner,
+		).unwrap();
+
+		let test_fut = async move {
+			let (tx, rx) = oneshot::channel();
+			handler.send_msg(AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				genesis_hash,
+				RuntimeApiRequest::AvailabilityCores(tx),
+			))).await.unwrap();
+
+			let cores = rx.await.unwrap().expect("the test runtime implements `ParachainHostV2`");
+			// no paras are registered at genesis.
+			assert!(cores.is_empty());
+
+			handler.stop().await.unwrap();
+		};
+
+		let (overseer_result, ()) = executor::block_on(future::join(overseer.run(), test_fut));
+		overseer_result.unwrap();
+	}
+}
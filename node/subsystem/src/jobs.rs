@@ -0,0 +1,528 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Utilities for subsystems which spawn one job per relay-parent.
+//!
+//! A [`JobManager`] spawns jobs for the leaves the overseer activates, aborts them when the
+//! leaves are deactivated, routes messages to the job of the relay-parent they concern and
+//! forwards the messages jobs want to send to the rest of the system.
+//!
+//! [`JobManager`]: struct.JobManager.html
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::channel::{mpsc, oneshot};
+use futures::future::{self, AbortHandle};
+use futures::prelude::*;
+use futures::stream::FusedStream;
+
+use polkadot_primitives::Hash;
+
+use crate::{await_response, RequestError, SubsystemContext, SubsystemError, SubsystemResult};
+use crate::messages::{
+	AllMessages, BitfieldSigningMessage, CandidateBackingMessage, ProvisionableData,
+	ProvisionerMessage,
+};
+
+/// Capacity of the channel over which jobs send messages to the rest of the system.
+const CHANNEL_CAPACITY: usize = 64;
+
+/// A message which is addressed to the job of a particular relay-parent.
+pub trait JobMessage: Send + 'static {
+	/// The relay-parent of the job the message is addressed to.
+	fn relay_parent(&self) -> Hash;
+}
+
+impl JobMessage for CandidateBackingMessage {
+	fn relay_parent(&self) -> Hash {
+		match self {
+			CandidateBackingMessage::RegisterBackingWatcher(relay_parent, _) => *relay_parent,
+			CandidateBackingMessage::Second(relay_parent, _, _) => *relay_parent,
+			CandidateBackingMessage::Statement(relay_parent, _) => *relay_parent,
+		}
+	}
+}
+
+impl JobMessage for ProvisionerMessage {
+	fn relay_parent(&self) -> Hash {
+		match self {
+			ProvisionerMessage::RequestBlockAuthorshipData(relay_parent, _) => *relay_parent,
+			ProvisionerMessage::RequestInherentData(relay_parent, _) => *relay_parent,
+			ProvisionerMessage::ProvisionableData(data) => match data {
+				ProvisionableData::Bitfield(relay_parent, _) => *relay_parent,
+				ProvisionableData::BackedCandidate(backed) => backed.candidate.relay_parent,
+				ProvisionableData::MisbehaviorReport(relay_parent, _) => *relay_parent,
+				ProvisionableData::Dispute(relay_parent, _) => *relay_parent,
+			},
+		}
+	}
+}
+
+impl JobMessage for BitfieldSigningMessage {
+	fn relay_parent(&self) -> Hash {
+		match *self {}
+	}
+}
+
+/// An event produced by the jobs of a [`JobManager`].
+///
+/// [`JobManager`]: struct.JobManager.html
+#[derive(Debug)]
+pub enum JobEvent {
+	/// A job wants to send a message to some other `Subsystem`.
+	Message(AllMessages),
+	/// A job of the given relay-parent failed. It has been removed, unless it was replaced by a
+	/// newer job for the same relay-parent already.
+	Failed(Hash, SubsystemError),
+}
+
+/// The handle jobs send messages to the rest of the system with.
+///
+/// It mirrors the sending side of [`SubsystemContext`], so that jobs make requests with the same
+/// deadlines and errors as subsystems do.
+///
+/// [`SubsystemContext`]: ../trait.SubsystemContext.html
+#[derive(Clone)]
+pub struct JobSender(mpsc::Sender<AllMessages>);
+
+impl JobSender {
+	/// Create a handle sending on `tx`.
+	///
+	/// Jobs spawned by a [`JobManager`] are handed their sender. This is for tasks which are
+	/// spawned by a subsystem itself, which then forwards what they send to the overseer.
+	///
+	/// [`JobManager`]: struct.JobManager.html
+	pub fn new(tx: mpsc::Sender<AllMessages>) -> Self {
+		JobSender(tx)
+	}
+
+	/// Send a direct message to some other `Subsystem`, routed based on message type.
+	pub async fn send_message(&mut self, msg: AllMessages) -> SubsystemResult<()> {
+		self.0.send(msg).await.map_err(Into::into)
+	}
+
+	/// Send a request to some other `Subsystem` and await its response.
+	///
+	/// This behaves like [`SubsystemContext::request`].
+	///
+	/// [`SubsystemContext::request`]: ../trait.SubsystemContext.html#method.request
+	pub async fn request<R, F>(&mut self, make_msg: F, timeout: Duration) -> Result<R, RequestError>
+		where F: FnOnce(oneshot::Sender<R>) -> AllMessages
+	{
+		let (tx, rx) = oneshot::channel();
+		self.0.send(make_msg(tx)).await.map_err(|_| RequestError::Send)?;
+
+		await_response(rx, timeout).await
+	}
+}
+
+/// A handle to a job running for a single relay-parent.
+struct JobHandle<M> {
+	/// Tells apart the jobs spawned for the same relay-parent over time.
+	generation: u64,
+	abort_handle: AbortHandle,
+	to_job: mpsc::UnboundedSender<M>,
+}
+
+impl<M> JobHandle<M> {
+	fn stop(self) {
+		self.abort_handle.abort();
+	}
+}
+
+/// Manages the jobs of a subsystem, one per relay-parent, receiving messages of type `M`.
+///
+/// The manager is a never-ending stream of [`JobEvent`]s, meant to be polled alongside the
+/// subsystem's context. Messages produced by jobs should be sent on to the overseer and
+/// failures handled as the subsystem sees fit.
+///
+/// [`JobEvent`]: enum.JobEvent.html
+pub struct JobManager<M> {
+	jobs: HashMap<Hash, JobHandle<M>>,
+	next_generation: u64,
+	from_jobs_tx: mpsc::Sender<AllMessages>,
+	from_jobs_rx: mpsc::Receiver<AllMessages>,
+	failures_tx: mpsc::UnboundedSender<(Hash, u64, SubsystemError)>,
+	failures_rx: mpsc::UnboundedReceiver<(Hash, u64, SubsystemError)>,
+}
+
+impl<M: JobMessage> JobManager<M> {
+	/// Create a new job manager without any jobs.
+	pub fn new() -> Self {
+		let (from_jobs_tx, from_jobs_rx) = mpsc::channel(CHANNEL_CAPACITY);
+		let (failures_tx, failures_rx) = mpsc::unbounded();
+
+		JobManager {
+			jobs: HashMap::new(),
+			next_generation: 0,
+			from_jobs_tx,
+			from_jobs_rx,
+			failures_tx,
+			failures_rx,
+		}
+	}
+
+	/// Spawn a job for the given relay-parent, unless one is running already.
+	///
+	/// The job is built by `job` from the receiving end of the messages routed to it and the
+	/// [`JobSender`] of the messages it wants to send to the rest of the system.
+	///
+	/// [`JobSender`]: struct.JobSender.html
+	pub async fn spawn_job<F, Fut>(
+		&mut self,
+		ctx: &mut impl SubsystemContext,
+		relay_parent: Hash,
+		job: F,
+	) -> SubsystemResult<()>
+	where
+		F: FnOnce(mpsc::UnboundedReceiver<M>, JobSender) -> Fut,
+		Fut: Future<Output = SubsystemResult<()>> + Send + 'static,
+	{
+		if self.jobs.contains_key(&relay_parent) {
+			return Ok(());
+		}
+
+		let generation = self.next_generation;
+		self.next_generation += 1;
+
+		let (to_job, rx_to) = mpsc::unbounded();
+		let (job, abort_handle) = future::abortable(job(rx_to, JobSender(self.from_jobs_tx.clone())));
+		let failures_tx = self.failures_tx.clone();
+
+		ctx.spawn(Box::pin(job.map(move |res| {
+			if let Ok(Err(e)) = res {
+				let _ = failures_tx.unbounded_send((relay_parent, generation, e));
+			}
+		}))).await?;
+
+		self.jobs.insert(relay_parent, JobHandle { generation, abort_handle, to_job });
+
+		Ok(())
+	}
+
+	/// Abort the job of the given relay-parent, if any.
+	pub fn stop_job(&mut self, relay_parent: Hash) {
+		if let Some(handle) = self.jobs.remove(&relay_parent) {
+			handle.stop();
+		}
+	}
+
+	/// Abort all jobs.
+	pub fn stop_all(&mut self) {
+		for (_, handle) in self.jobs.drain() {
+			handle.stop();
+		}
+	}
+
+	/// Route a message to the job of its relay-parent.
+	///
+	/// Returns the message back if there is no such job, or if the job has concluded.
+	pub fn send_msg(&mut self, msg: M) -> Result<(), M> {
+		let relay_parent = msg.relay_parent();
+
+		let res = match self.jobs.get(&relay_parent) {
+			Some(handle) => handle.to_job.unbounded_send(msg),
+			None => return Err(msg),
+		};
+
+		res.map_err(|e| {
+			// the job has concluded on its own, most likely because it failed.
+			self.jobs.remove(&relay_parent);
+			e.into_inner()
+		})
+	}
+
+	/// Whether a job is running for the given relay-parent.
+	pub fn contains(&self, relay_parent: &Hash) -> bool {
+		self.jobs.contains_key(relay_parent)
+	}
+}
+
+impl<M: JobMessage> Default for JobManager<M> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<M> Stream for JobManager<M> {
+	type Item = JobEvent;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<JobEvent>> {
+		if let Poll::Ready(Some((relay_parent, generation, e))) = self.failures_rx.poll_next_unpin(cx) {
+			// the relay-parent may have been deactivated and activated again since the job failed,
+			// in which case the newer job must be kept.
+			if self.jobs.get(&relay_parent).map_or(false, |handle| handle.generation == generation) {
+				self.jobs.remove(&relay_parent);
+			}
+
+			return Poll::Ready(Some(JobEvent::Failed(relay_parent, e)));
+		}
+
+		// we hold the senders ourselves, so neither stream ever ends.
+		match self.from_jobs_rx.poll_next_unpin(cx) {
+			Poll::Ready(Some(msg)) => Poll::Ready(Some(JobEvent::Message(msg))),
+			Poll::Ready(None) | Poll::Pending => Poll::Pending,
+		}
+	}
+}
+
+impl<M> FusedStream for JobManager<M> {
+	fn is_terminated(&self) -> bool {
+		false
+	}
+}
+
+impl<M> Drop for JobManager<M> {
+	fn drop(&mut self) {
+		for (_, handle) in self.jobs.drain() {
+			handle.stop();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use async_trait::async_trait;
+	use futures::executor::{self, ThreadPool};
+	use futures::task::SpawnExt;
+	use assert_matches::assert_matches;
+
+	use polkadot_primitives::parachain::v2::AbridgedCandidateReceipt;
+
+	use crate::FromOverseer;
+	use crate::messages::CandidateSelectionMessage;
+
+	#[derive(Debug)]
+	enum TestMessage {
+		// makes the job report its relay-parent.
+		Echo(Hash),
+		// makes the job fail.
+		Fail(Hash),
+	}
+
+	impl JobMessage for TestMessage {
+		fn relay_parent(&self) -> Hash {
+			match self {
+				TestMessage::Echo(relay_parent) | TestMessage::Fail(relay_parent) => *relay_parent,
+			}
+		}
+	}
+
+	// A context which only spawns tasks.
+	struct TestContext(ThreadPool);
+
+	#[async_trait]
+	impl SubsystemContext for TestContext {
+		type Message = ();
+
+		async fn try_recv(&mut self) -> Result<Option<FromOverseer<()>>, ()> {
+			Ok(None)
+		}
+
+		async fn recv(&mut self) -> SubsystemResult<FromOverseer<()>> {
+			future::pending().await
+		}
+
+		async fn spawn(&mut self, s: Pin<Box<dyn Future<Output = ()> + Send>>) -> SubsystemResult<()> {
+			self.0.spawn(s).map_err(Into::into)
+		}
+
+		async fn send_message(&mut self, _: AllMessages) -> SubsystemResult<()> {
+			Ok(())
+		}
+
+		async fn send_messages<T>(&mut self, _: T) -> SubsystemResult<()>
+			where T: IntoIterator<Item = AllMessages> + Send, T::IntoIter: Send
+		{
+			Ok(())
+		}
+	}
+
+	fn echo(relay_parent: Hash) -> AllMessages {
+		AllMessages::CandidateSelection(
+			CandidateSelectionMessage::Invalid(relay_parent, AbridgedCandidateReceipt::default()),
+		)
+	}
+
+	async fn test_job(
+		relay_parent: Hash,
+		_alive: oneshot::Sender<()>,
+		mut rx_to: mpsc::UnboundedReceiver<TestMessage>,
+		mut sender: JobSender,
+	) -> SubsystemResult<()> {
+		while let Some(msg) = rx_to.next().await {
+			match msg {
+				TestMessage::Echo(_) => sender.send_message(echo(relay_parent)).await?,
+				TestMessage::Fail(_) => return Err(SubsystemError),
+			}
+		}
+
+		Ok(())
+	}
+
+	// Spawns a test job, returning a receiver which is canceled once the job has ended.
+	async fn spawn_test_job(
+		jobs: &mut JobManager<TestMessage>,
+		ctx: &mut TestContext,
+		relay_parent: Hash,
+	) -> oneshot::Receiver<()> {
+		let (alive_tx, alive_rx) = oneshot::channel();
+		jobs.spawn_job(ctx, relay_parent, move |rx_to, sender| test_job(relay_parent, alive_tx, rx_to, sender))
+			.await
+			.unwrap();
+
+		alive_rx
+	}
+
+	fn test_harness<T: Future<Output = ()>>(test: impl FnOnce(JobManager<TestMessage>, TestContext) -> T) {
+		let ctx = TestContext(ThreadPool::new().unwrap());
+
+		executor::block_on(test(JobManager::new(), ctx));
+	}
+
+	#[test]
+	fn messages_are_routed_to_the_job_of_their_relay_parent() {
+		let a = Hash::repeat_byte(1);
+		let b = Hash::repeat_byte(2);
+
+		test_harness(|mut jobs, mut ctx| async move {
+			let _a_alive = spawn_test_job(&mut jobs, &mut ctx, a).await;
+			let _b_alive = spawn_test_job(&mut jobs, &mut ctx, b).await;
+
+			assert!(jobs.contains(&a));
+			assert!(jobs.contains(&b));
+
+			assert!(jobs.send_msg(TestMessage::Echo(b)).is_ok());
+			assert_matches!(
+				jobs.next().await,
+				Some(JobEvent::Message(AllMessages::CandidateSelection(
+					CandidateSelectionMessage::Invalid(relay_parent, _),
+				))) => assert_eq!(relay_parent, b)
+			);
+
+			assert!(jobs.send_msg(TestMessage::Echo(a)).is_ok());
+			assert_matches!(
+				jobs.next().await,
+				Some(JobEvent::Message(AllMessages::CandidateSelection(
+					CandidateSelectionMessage::Invalid(relay_parent, _),
+				))) => assert_eq!(relay_parent, a)
+			);
+
+			let c = Hash::repeat_byte(3);
+			assert_matches!(jobs.send_msg(TestMessage::Echo(c)), Err(TestMessage::Echo(relay_parent)) => {
+				assert_eq!(relay_parent, c);
+			});
+		});
+	}
+
+	#[test]
+	fn spawning_a_job_for_a_relay_parent_twice_keeps_the_first_job() {
+		let a = Hash::repeat_byte(1);
+
+		test_harness(|mut jobs, mut ctx| async move {
+			let _first_alive = spawn_test_job(&mut jobs, &mut ctx, a).await;
+			let second_alive = spawn_test_job(&mut jobs, &mut ctx, a).await;
+
+			// the second job was never spawned.
+			assert_eq!(second_alive.await, Err(oneshot::Canceled));
+			assert_eq!(jobs.jobs.get(&a).unwrap().generation, 0);
+		});
+	}
+
+	#[test]
+	fn stopped_jobs_are_aborted() {
+		let a = Hash::repeat_byte(1);
+		let b = Hash::repeat_byte(2);
+
+		test_harness(|mut jobs, mut ctx| async move {
+			let a_alive = spawn_test_job(&mut jobs, &mut ctx, a).await;
+			let b_alive = spawn_test_job(&mut jobs, &mut ctx, b).await;
+
+			jobs.stop_job(a);
+
+			assert_eq!(a_alive.await, Err(oneshot::Canceled));
+			assert!(!jobs.contains(&a));
+			assert!(jobs.send_msg(TestMessage::Echo(a)).is_err());
+			assert!(jobs.contains(&b));
+
+			jobs.stop_all();
+
+			assert_eq!(b_alive.await, Err(oneshot::Canceled));
+			assert!(!jobs.contains(&b));
+		});
+	}
+
+	#[test]
+	fn failed_jobs_are_reported_and_removed() {
+		let a = Hash::repeat_byte(1);
+
+		test_harness(|mut jobs, mut ctx| async move {
+			let _a_alive = spawn_test_job(&mut jobs, &mut ctx, a).await;
+
+			assert!(jobs.send_msg(TestMessage::Fail(a)).is_ok());
+			assert_matches!(
+				jobs.next().await,
+				Some(JobEvent::Failed(relay_parent, SubsystemError)) => assert_eq!(relay_parent, a)
+			);
+
+			assert!(!jobs.contains(&a));
+		});
+	}
+
+	#[test]
+	fn stale_failures_do_not_remove_newer_jobs() {
+		let a = Hash::repeat_byte(1);
+
+		test_harness(|mut jobs, mut ctx| async move {
+			let first_alive = spawn_test_job(&mut jobs, &mut ctx, a).await;
+			jobs.stop_job(a);
+			assert_eq!(first_alive.await, Err(oneshot::Canceled));
+
+			let _second_alive = spawn_test_job(&mut jobs, &mut ctx, a).await;
+
+			// a failure of the first job which is only noticed after the second job was spawned.
+			jobs.failures_tx.unbounded_send((a, 0, SubsystemError)).unwrap();
+			assert_matches!(
+				jobs.next().await,
+				Some(JobEvent::Failed(relay_parent, SubsystemError)) => assert_eq!(relay_parent, a)
+			);
+
+			assert!(jobs.contains(&a));
+			assert!(jobs.send_msg(TestMessage::Echo(a)).is_ok());
+			assert_matches!(jobs.next().await, Some(JobEvent::Message(_)));
+		});
+	}
+
+	#[test]
+	fn dropping_the_manager_aborts_all_jobs() {
+		let a = Hash::repeat_byte(1);
+		let b = Hash::repeat_byte(2);
+
+		test_harness(|mut jobs, mut ctx| async move {
+			let a_alive = spawn_test_job(&mut jobs, &mut ctx, a).await;
+			let b_alive = spawn_test_job(&mut jobs, &mut ctx, b).await;
+
+			drop(jobs);
+
+			assert_eq!(a_alive.await, Err(oneshot::Canceled));
+			assert_eq!(b_alive.await, Err(oneshot::Canceled));
+		});
+	}
+}
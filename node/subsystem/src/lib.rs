@@ -21,25 +21,57 @@
 //! separate crate.
 
 use std::pin::Pin;
+use std::time::Duration;
 
 use futures::prelude::*;
 use futures::channel::{mpsc, oneshot};
-use futures::future::BoxFuture;
+use futures::future::{self, BoxFuture, Either};
+use futures_timer::Delay;
 
-use polkadot_primitives::Hash;
+use polkadot_primitives::{BlockNumber, Hash};
 use async_trait::async_trait;
 
 use crate::messages::AllMessages;
 
+pub mod jobs;
 pub mod messages;
 
+/// Changes in the set of active leaves: the relay-chain heads which subsystems should work on.
+///
+/// The activated and deactivated hashes are deltas, not complete sets.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ActiveLeavesUpdate {
+	/// Relay-chain block hashes which subsystems should start working on.
+	pub activated: Vec<Hash>,
+	/// Relay-chain block hashes which subsystems should stop working on.
+	pub deactivated: Vec<Hash>,
+}
+
+impl ActiveLeavesUpdate {
+	/// Create an update which only activates the given relay-chain block hash.
+	pub fn start_work(hash: Hash) -> Self {
+		ActiveLeavesUpdate { activated: vec![hash], deactivated: Vec::new() }
+	}
+
+	/// Create an update which only deactivates the given relay-chain block hash.
+	pub fn stop_work(hash: Hash) -> Self {
+		ActiveLeavesUpdate { activated: Vec::new(), deactivated: vec![hash] }
+	}
+
+	/// Whether the update neither activates nor deactivates any leaf.
+	pub fn is_empty(&self) -> bool {
+		self.activated.is_empty() && self.deactivated.is_empty()
+	}
+}
+
 /// Signals sent by an overseer to a subsystem.
 #[derive(PartialEq, Clone, Debug)]
 pub enum OverseerSignal {
-	/// `Subsystem` should start working on block-based work, given by the relay-chain block hash.
-	StartWork(Hash),
-	/// `Subsystem` should stop working on block-based work specified by the relay-chain block hash.
-	StopWork(Hash),
+	/// `Subsystem` should start and stop working on block-based work, given by the relay-chain
+	/// block hashes of the leaves which were activated and deactivated at once.
+	ActiveLeaves(ActiveLeavesUpdate),
+	/// The given relay-chain block has been finalized, along with all of its ancestors.
+	BlockFinalized(Hash, BlockNumber),
 	/// Conclude the work of the `Overseer` and all `Subsystem`s.
 	Conclude,
 }
@@ -94,6 +126,44 @@ impl From<std::convert::Infallible> for SubsystemError {
 	}
 }
 
+impl From<messages::RuntimeApiError> for SubsystemError {
+	fn from(_: messages::RuntimeApiError) -> Self {
+		Self
+	}
+}
+
+/// An error returned by [`SubsystemContext::request`].
+///
+/// [`SubsystemContext::request`]: trait.SubsystemContext.html#method.request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestError {
+	/// The request could not be sent to the overseer.
+	Send,
+	/// The receiving subsystem dropped the responder without answering.
+	Canceled,
+	/// No response arrived before the deadline.
+	Timeout,
+}
+
+impl From<RequestError> for SubsystemError {
+	fn from(_: RequestError) -> Self {
+		Self
+	}
+}
+
+/// Await the response to a request, failing if the responder is dropped or if no response
+/// arrives within `timeout`.
+pub async fn await_response<R>(
+	response: oneshot::Receiver<R>,
+	timeout: Duration,
+) -> Result<R, RequestError> {
+	match future::select(response, Delay::new(timeout)).await {
+		Either::Left((Ok(response), _)) => Ok(response),
+		Either::Left((Err(oneshot::Canceled), _)) => Err(RequestError::Canceled),
+		Either::Right(((), _)) => Err(RequestError::Timeout),
+	}
+}
+
 /// An asynchronous subsystem task..
 ///
 /// In essence it's just a newtype wrapping a `BoxFuture`.
@@ -134,6 +204,22 @@ pub trait SubsystemContext: Send + 'static {
 	/// Send multiple direct messages to other `Subsystem`s, routed based on message type.
 	async fn send_messages<T>(&mut self, msgs: T) -> SubsystemResult<()>
 		where T: IntoIterator<Item = AllMessages> + Send, T::IntoIter: Send;
+
+	/// Send a request to some other `Subsystem` and await its response.
+	///
+	/// The message is built by `make_msg` around the responder the other `Subsystem` is expected
+	/// to answer on. Fails with a [`RequestError`] if the responder is dropped without answering
+	/// or if no response arrives within `timeout`.
+	///
+	/// [`RequestError`]: enum.RequestError.html
+	async fn request<R, F>(&mut self, make_msg: F, timeout: Duration) -> Result<R, RequestError>
+		where R: Send + 'static, F: FnOnce(oneshot::Sender<R>) -> AllMessages + Send
+	{
+		let (tx, rx) = oneshot::channel();
+		self.send_message(make_msg(tx)).await.map_err(|_| RequestError::Send)?;
+
+		await_response(rx, timeout).await
+	}
 }
 
 /// A trait that describes the [`Subsystem`]s that can run on the [`Overseer`].
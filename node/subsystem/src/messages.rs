@@ -25,13 +25,17 @@
 use futures::channel::{mpsc, oneshot};
 
 use sc_network::{ObservedRole, ReputationChange, PeerId};
+use sp_authority_discovery::AuthorityId as AuthorityDiscoveryId;
 use polkadot_primitives::{BlockNumber, Hash, Signature};
 use polkadot_primitives::parachain::{
-	AbridgedCandidateReceipt, PoVBlock, ErasureChunk, BackedCandidate, Id as ParaId,
-	SignedAvailabilityBitfield, SigningContext, ValidatorId, ValidationCode, ValidatorIndex,
+	v2::AbridgedCandidateReceipt, PoVBlock, ErasureChunk, BackedCandidate, Id as ParaId,
+	SignedAvailabilityBitfield, SignedAvailabilityBitfields, SigningContext, ValidatorId,
+	ValidationCode, ValidatorIndex, GlobalValidationSchedule, LocalValidationData, DutyRoster,
+	GroupRotationInfo, CoreAssignment, PersistedValidationData, TransientValidationData,
+	HostConfiguration,
 };
 use polkadot_node_primitives::{
-	MisbehaviorReport, SignedFullStatement, View, ProtocolId,
+	CoreState, MisbehaviorReport, SignedFullStatement, View, ProtocolId, ValidationResult,
 };
 
 /// A notification of a new backed candidate.
@@ -53,16 +57,19 @@ pub enum CandidateBackingMessage {
 	/// in a child of the given relay-parent, referenced by its hash.
 	RegisterBackingWatcher(Hash, mpsc::Sender<NewBackedCandidate>),
 	/// Note that the Candidate Backing subsystem should second the given candidate in the context of the
-	/// given relay-parent (ref. by hash). This candidate must be validated.
-	Second(Hash, AbridgedCandidateReceipt),
+	/// given relay-parent (ref. by hash). This candidate must be validated using the provided PoV.
+	Second(Hash, AbridgedCandidateReceipt, PoVBlock),
 	/// Note a validator's statement about a particular candidate. Disagreements about validity must be escalated
 	/// to a broader check by Misbehavior Arbitration. Agreements are simply tallied until a quorum is reached.
 	Statement(Hash, SignedFullStatement),
 }
 
-/// Blanket error for validation failing.
+/// Blanket error for validation failing for internal reasons, with a description.
+///
+/// This does not indicate that the candidate is invalid, only that validation could
+/// not be carried out. Invalidity is expressed by `ValidationResult::Invalid`.
 #[derive(Debug)]
-pub struct ValidationFailed;
+pub struct ValidationFailed(pub String);
 
 /// Messages received by the Validation subsystem
 #[derive(Debug)]
@@ -70,12 +77,13 @@ pub enum CandidateValidationMessage {
 	/// Validate a candidate, sending a side-channel response of valid or invalid.
 	///
 	/// Provide the relay-parent in whose context this should be validated, the full candidate receipt,
-	/// and the PoV.
+	/// and the PoV. The validation code and the validation data are fetched from the state of the
+	/// relay-parent.
 	Validate(
 		Hash,
 		AbridgedCandidateReceipt,
 		PoVBlock,
-		oneshot::Sender<Result<(), ValidationFailed>>,
+		oneshot::Sender<Result<ValidationResult, ValidationFailed>>,
 	),
 }
 
@@ -109,16 +117,24 @@ pub enum NetworkBridgeMessage {
 
 	/// Send a message to multiple peers.
 	SendMessage(Vec<PeerId>, ProtocolId, Vec<u8>),
+
+	/// Keep connections to the given validators open, replacing the set requested previously.
+	ConnectToValidators(Vec<AuthorityDiscoveryId>),
+
+	/// Resolve the given validators to the IDs of their peers, in the same order. Validators
+	/// whose addresses are unknown resolve to `None`.
+	ResolveValidators(Vec<AuthorityDiscoveryId>, oneshot::Sender<Vec<Option<PeerId>>>),
 }
 
 /// Availability Distribution Message.
 #[derive(Debug)]
 pub enum AvailabilityDistributionMessage {
-	/// Distribute an availability chunk to other validators.
-	DistributeChunk(Hash, ErasureChunk),
+	/// Distribute an erasure chunk of the given candidate to other validators.
+	DistributeChunk(AbridgedCandidateReceipt, ErasureChunk),
 
-	/// Fetch an erasure chunk from networking by candidate hash and chunk index.
-	FetchChunk(Hash, u32),
+	/// Fetch the erasure chunk of the given candidate with the given index from the network.
+	/// The chunk is stored in the Availability Store once it has been received and verified.
+	FetchChunk(AbridgedCandidateReceipt, u32),
 
 	/// Event from the network bridge.
 	NetworkBridgeUpdate(NetworkBridgeEvent),
@@ -134,30 +150,73 @@ pub enum BitfieldDistributionMessage {
 	NetworkBridgeUpdate(NetworkBridgeEvent),
 }
 
+/// Bitfield signing message.
+///
+/// Currently non-instantiable: the subsystem acts purely on overseer signals.
+#[derive(Debug)]
+pub enum BitfieldSigningMessage {}
+
 /// Availability store subsystem message.
 #[derive(Debug)]
 pub enum AvailabilityStoreMessage {
 	/// Query a `PoVBlock` from the AV store.
 	QueryPoV(Hash, oneshot::Sender<Option<PoVBlock>>),
 
-	/// Query an `ErasureChunk` from the AV store.
-	QueryChunk(Hash, ValidatorIndex, oneshot::Sender<ErasureChunk>),
+	/// Query an `ErasureChunk` from the AV store by the candidate hash and validator index.
+	/// Responds with `None` if the chunk is not stored.
+	QueryChunk(Hash, ValidatorIndex, oneshot::Sender<Option<ErasureChunk>>),
 
 	/// Store an `ErasureChunk` in the AV store.
 	StoreChunk(Hash, ValidatorIndex, ErasureChunk),
+
+	/// Store the `PoVBlock` of a candidate in the AV store.
+	StorePoV(Hash, PoVBlock),
 }
 
+/// An error answering a request to the Runtime API subsystem, e.g. because the runtime API
+/// call failed or isn't supported by the runtime.
+#[derive(Debug, Clone)]
+pub struct RuntimeApiError(pub String);
+
+/// A sender for the response to a runtime API request.
+pub type RuntimeApiSender<T> = oneshot::Sender<Result<T, RuntimeApiError>>;
+
 /// A request to the Runtime API subsystem.
 #[derive(Debug)]
 pub enum RuntimeApiRequest {
 	/// Get the current validator set.
-	Validators(oneshot::Sender<Vec<ValidatorId>>),
+	Validators(RuntimeApiSender<Vec<ValidatorId>>),
 	/// Get a signing context for bitfields and statements.
-	SigningContext(oneshot::Sender<SigningContext>),
-	/// Get the validation code for a specific para, assuming execution under given block number, and
-	/// an optional block number representing an intermediate parablock executed in the context of
-	/// that block.
-	ValidationCode(ParaId, BlockNumber, Option<BlockNumber>, oneshot::Sender<ValidationCode>),
+	SigningContext(RuntimeApiSender<SigningContext>),
+	/// Get the duty roster, assigning each validator to a parachain or to the relay chain.
+	DutyRoster(RuntimeApiSender<DutyRoster>),
+	/// Get the authority discovery keys of the current validators, which they can be found on
+	/// the network by.
+	AuthorityDiscoveryKeys(RuntimeApiSender<Vec<AuthorityDiscoveryId>>),
+	/// Get the state of each availability core. The index of a core in the returned vector
+	/// corresponds to the bit of the core in availability bitfields.
+	AvailabilityCores(RuntimeApiSender<Vec<CoreState>>),
+	/// Get the validation code of a specific para in the state of the relay-parent, which candidates
+	/// built on the relay-parent are validated with. Responds with `None` if the para has no
+	/// validation code.
+	ValidationCode(ParaId, RuntimeApiSender<Option<ValidationCode>>),
+	/// Get the global validation schedule that all candidates are validated under.
+	GlobalValidationSchedule(RuntimeApiSender<GlobalValidationSchedule>),
+	/// Get the local validation data for a specific para, if the para is active.
+	LocalValidationData(ParaId, RuntimeApiSender<Option<LocalValidationData>>),
+	/// Get the validator groups, one for each core, along with the information needed to
+	/// determine which group is assigned to which core at any block number.
+	ValidatorGroups(RuntimeApiSender<(Vec<Vec<ValidatorIndex>>, GroupRotationInfo)>),
+	/// Get the assignments of the cores scheduled to be occupied in the child of the relay-parent.
+	ScheduledCores(RuntimeApiSender<Vec<CoreAssignment>>),
+	/// Get the persisted validation data for a specific para, if the para is registered.
+	PersistedValidationData(ParaId, RuntimeApiSender<Option<PersistedValidationData>>),
+	/// Get the transient validation data for a specific para, if the para is registered.
+	TransientValidationData(ParaId, RuntimeApiSender<Option<TransientValidationData>>),
+	/// Get the configuration of the parachains runtime modules in the current session.
+	HostConfiguration(RuntimeApiSender<HostConfiguration<BlockNumber>>),
+	/// Get current, pending or past validation code by its hash, if it is still kept on-chain.
+	ValidationCodeByHash(Hash, RuntimeApiSender<Option<ValidationCode>>),
 }
 
 /// A message to the Runtime API subsystem.
@@ -177,8 +236,24 @@ pub enum StatementDistributionMessage {
 	NetworkBridgeUpdate(NetworkBridgeEvent),
 }
 
-/// This data becomes intrinsics or extrinsics which should be included in a future relay chain block.
+/// Message to the PoV Distribution subsystem.
 #[derive(Debug)]
+pub enum PoVDistributionMessage {
+	/// Fetch the PoV of the given candidate from the network, in the context of the given
+	/// relay-parent. The PoV must hash to the `pov_block_hash` of the candidate.
+	///
+	/// The response channel is dropped if the relay-parent leaves our view before the PoV
+	/// has been received.
+	FetchPoV(Hash, AbridgedCandidateReceipt, oneshot::Sender<PoVBlock>),
+	/// Distribute the PoV of the given candidate to the peers awaiting it, in the context of
+	/// the given relay-parent. The PoV must hash to the `pov_block_hash` of the candidate.
+	DistributePoV(Hash, AbridgedCandidateReceipt, PoVBlock),
+	/// Event from the network bridge.
+	NetworkBridgeUpdate(NetworkBridgeEvent),
+}
+
+/// This data becomes intrinsics or extrinsics which should be included in a future relay chain block.
+#[derive(Debug, Clone)]
 pub enum ProvisionableData {
 	/// This bitfield indicates the availability of various candidate blocks.
 	Bitfield(Hash, SignedAvailabilityBitfield),
@@ -190,6 +265,11 @@ pub enum ProvisionableData {
 	Dispute(Hash, Signature),
 }
 
+/// The data of the inclusion inherent, as assembled by the Provisioner: at most one signed
+/// bitfield per validator, ordered by validator index, and at most one backed candidate per
+/// scheduled availability core, ordered by core index.
+pub type ProvisionerInherentData = (SignedAvailabilityBitfields, Vec<BackedCandidate>);
+
 /// Message to the Provisioner.
 ///
 /// In all cases, the Hash is that of the relay parent.
@@ -200,6 +280,9 @@ pub enum ProvisionerMessage {
 	RequestBlockAuthorshipData(Hash, mpsc::Sender<ProvisionableData>),
 	/// This data should become part of a relay chain block
 	ProvisionableData(ProvisionableData),
+	/// Request the signed bitfields and backed candidates to be included in a block built on
+	/// top of the given relay-parent, as expected by the inclusion inherent.
+	RequestInherentData(Hash, oneshot::Sender<ProvisionerInherentData>),
 }
 
 /// A message type tying together all message types that are used across Subsystems.
@@ -215,12 +298,18 @@ pub enum AllMessages {
 	StatementDistribution(StatementDistributionMessage),
 	/// Message for the availability distribution subsystem.
 	AvailabilityDistribution(AvailabilityDistributionMessage),
+	/// Message for the PoV distribution subsystem.
+	PoVDistribution(PoVDistributionMessage),
 	/// Message for the bitfield distribution subsystem.
 	BitfieldDistribution(BitfieldDistributionMessage),
+	/// Message for the bitfield signing subsystem.
+	BitfieldSigning(BitfieldSigningMessage),
 	/// Message for the Provisioner subsystem.
 	Provisioner(ProvisionerMessage),
 	/// Message for the Runtime API subsystem.
 	RuntimeApi(RuntimeApiMessage),
 	/// Message for the availability store subsystem.
 	AvailabilityStore(AvailabilityStoreMessage),
+	/// Message for the network bridge subsystem.
+	NetworkBridge(NetworkBridgeMessage),
 }
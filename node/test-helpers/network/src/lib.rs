@@ -0,0 +1,1005 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A simulated network for testing the networking subsystems of several nodes together.
+//!
+//! The network runs in-process and hands each node a [`NodeNetwork`] implementing the network
+//! bridge's `Network` trait, so that every node can run a full overseer with a network bridge
+//! on top of it. Notifications between nodes can be delayed, lost at random and dropped between
+//! partitioned nodes.
+//!
+//! Delays are measured on a virtual clock which only moves when the test advances it with
+//! [`SimulatedNetwork::advance_time`]. Nodes get the same peer IDs, in the order they are added,
+//! in every run, and losses are decided by a seeded random number generator, so a test is
+//! reproducible as long as it sends its notifications in a deterministic order. Running the
+//! overseers of all nodes on a single-threaded executor, such as `futures::executor::LocalPool`,
+//! takes care of that.
+//!
+//! [`NodeNetwork`]: struct.NodeNetwork.html
+//! [`SimulatedNetwork::advance_time`]: struct.SimulatedNetwork.html#method.advance_time
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::channel::mpsc;
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use futures::stream::BoxStream;
+use parking_lot::Mutex;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
+
+use polkadot_network_bridge::{
+	AuthorityDiscovery, Network, NetworkAction, PeerSet, notifications_protocol_info,
+};
+use polkadot_subsystem::{SubsystemError, SubsystemResult};
+use sc_network::{Event as NetworkEvent, Multiaddr, ObservedRole, PeerId, ReputationChange};
+use sc_network::config::identity::{self, ed25519};
+use sc_network::multiaddr::Protocol;
+use sp_authority_discovery::AuthorityId as AuthorityDiscoveryId;
+
+/// The configuration of a [`SimulatedNetwork`].
+///
+/// [`SimulatedNetwork`]: struct.SimulatedNetwork.html
+#[derive(Debug, Clone)]
+pub struct NetworkConfig {
+	/// The time it takes a notification to arrive at its recipient, on the virtual clock of the
+	/// network.
+	pub latency: Duration,
+	/// The probability of a notification being lost, between 0 and 1.
+	pub loss: f64,
+	/// The seed of the random number generator deciding which notifications are lost.
+	pub seed: u64,
+}
+
+impl Default for NetworkConfig {
+	fn default() -> Self {
+		NetworkConfig {
+			latency: Duration::from_millis(0),
+			loss: 0.0,
+			seed: 0,
+		}
+	}
+}
+
+/// A change in reputation reported by a node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+	/// The node reporting the peer.
+	pub reporter: PeerId,
+	/// The reported peer.
+	pub peer: PeerId,
+	/// The change in reputation.
+	pub change: ReputationChange,
+}
+
+/// The peer ID of the node with the given index, which is the same in every run.
+fn node_peer_id(index: u64) -> PeerId {
+	let mut seed = [0u8; 32];
+	seed[..8].copy_from_slice(&index.to_le_bytes());
+
+	let secret = ed25519::SecretKey::from_bytes(&mut seed)
+		.expect("any 32 bytes are a valid ed25519 secret key; qed");
+
+	PeerId::from_public_key(identity::PublicKey::Ed25519(ed25519::Keypair::from(secret).public()))
+}
+
+/// A notification on its way, together with its sender and recipient.
+struct InFlight {
+	from: PeerId,
+	to: PeerId,
+	event: NetworkEvent,
+}
+
+struct Node {
+	role: ObservedRole,
+	address: Multiaddr,
+	events_tx: mpsc::UnboundedSender<NetworkEvent>,
+	// the receiving end, until the network bridge of the node asks for it.
+	events_rx: Option<mpsc::UnboundedReceiver<NetworkEvent>>,
+	connected: HashSet<PeerId>,
+	reserved: HashMap<PeerSet, HashSet<Multiaddr>>,
+}
+
+struct Inner {
+	config: NetworkConfig,
+	rng: ChaChaRng,
+	nodes: HashMap<PeerId, Node>,
+	// the peers of the nodes, in the order they were added.
+	peers: Vec<PeerId>,
+	// the virtual time of the network, only advanced by the test.
+	now: Duration,
+	// notifications which have yet to arrive, by arrival time and the order they were sent in.
+	in_flight: BTreeMap<(Duration, u64), InFlight>,
+	sent: u64,
+	// pairs of nodes between which notifications are lost, in both directions.
+	partitions: HashSet<(PeerId, PeerId)>,
+	authorities: HashMap<AuthorityDiscoveryId, PeerId>,
+	reports: Vec<Report>,
+}
+
+impl Inner {
+	fn send_event(&self, to: &PeerId, event: NetworkEvent) {
+		if let Some(node) = self.nodes.get(to) {
+			// the network bridge of the node may have concluded.
+			let _ = node.events_tx.unbounded_send(event);
+		}
+	}
+
+	fn is_connected(&self, a: &PeerId, b: &PeerId) -> bool {
+		self.nodes.get(a).map_or(false, |node| node.connected.contains(b))
+	}
+
+	/// Deliver the notifications in flight which are due by now, in the order they were sent.
+	/// Notifications between nodes which have disconnected in the meantime are lost.
+	fn deliver_due(&mut self) {
+		while let Some(key) = self.in_flight.keys().next().cloned() {
+			if key.0 > self.now { break }
+
+			let InFlight { from, to, event } = self.in_flight.remove(&key)
+				.expect("key was just taken from the map; qed");
+
+			if self.is_connected(&from, &to) {
+				self.send_event(&to, event);
+			}
+		}
+	}
+
+	fn connect(&mut self, a: &PeerId, b: &PeerId) {
+		if a == b { return }
+
+		let (role_a, role_b) = match (self.nodes.get(a), self.nodes.get(b)) {
+			(Some(node_a), Some(node_b)) => (node_a.role.clone(), node_b.role.clone()),
+			_ => return,
+		};
+
+		let newly_connected = self.nodes.get_mut(a)
+			.expect("checked above; qed")
+			.connected
+			.insert(b.clone());
+
+		if !newly_connected { return }
+
+		self.nodes.get_mut(b)
+			.expect("checked above; qed")
+			.connected
+			.insert(a.clone());
+
+		for (engine_id, _) in notifications_protocol_info() {
+			self.send_event(a, NetworkEvent::NotificationStreamOpened {
+				remote: b.clone(),
+				engine_id,
+				role: role_b.clone(),
+			});
+
+			self.send_event(b, NetworkEvent::NotificationStreamOpened {
+				remote: a.clone(),
+				engine_id,
+				role: role_a.clone(),
+			});
+		}
+	}
+
+	fn disconnect(&mut self, a: &PeerId, b: &PeerId) {
+		let was_connected = self.nodes.get_mut(a)
+			.map_or(false, |node| node.connected.remove(b));
+
+		if !was_connected { return }
+
+		if let Some(node) = self.nodes.get_mut(b) {
+			node.connected.remove(a);
+		}
+
+		for (engine_id, _) in notifications_protocol_info() {
+			self.send_event(a, NetworkEvent::NotificationStreamClosed {
+				remote: b.clone(),
+				engine_id,
+			});
+
+			self.send_event(b, NetworkEvent::NotificationStreamClosed {
+				remote: a.clone(),
+				engine_id,
+			});
+		}
+	}
+
+	fn write_notification(&mut self, from: &PeerId, to: &PeerId, peer_set: PeerSet, message: Vec<u8>) {
+		if !self.is_connected(from, to) || self.partitions.contains(&(from.clone(), to.clone())) { return }
+
+		if self.config.loss > 0.0 && self.rng.gen_bool(self.config.loss) { return }
+
+		let at = self.now + self.config.latency;
+		self.in_flight.insert((at, self.sent), InFlight {
+			from: from.clone(),
+			to: to.clone(),
+			event: NetworkEvent::NotificationsReceived {
+				remote: from.clone(),
+				messages: vec![(peer_set.engine_id(), message.into())],
+			},
+		});
+		self.sent += 1;
+
+		self.deliver_due();
+	}
+
+	fn set_reserved_peers(&mut self, from: &PeerId, peer_set: PeerSet, addresses: HashSet<Multiaddr>) {
+		// like a priority group, connect to all reserved peers we know how to reach.
+		let reserved: Vec<_> = self.peers.iter()
+			.filter(|peer| addresses.contains(&self.nodes[*peer].address))
+			.cloned()
+			.collect();
+
+		if let Some(node) = self.nodes.get_mut(from) {
+			node.reserved.insert(peer_set, addresses);
+		}
+
+		for peer in reserved {
+			self.connect(from, &peer);
+		}
+	}
+
+	fn handle_action(&mut self, from: &PeerId, action: NetworkAction) {
+		match action {
+			NetworkAction::ReputationChange(peer, change) => self.reports.push(Report {
+				reporter: from.clone(),
+				peer,
+				change,
+			}),
+			NetworkAction::WriteNotification(peer_set, to, message) => {
+				self.write_notification(from, &to, peer_set, message)
+			}
+			NetworkAction::DisconnectPeer(peer) => self.disconnect(from, &peer),
+			NetworkAction::SetReservedPeers(peer_set, addresses) => {
+				self.set_reserved_peers(from, peer_set, addresses)
+			}
+		}
+	}
+}
+
+/// An in-process network of simulated nodes.
+#[derive(Clone)]
+pub struct SimulatedNetwork(Arc<Mutex<Inner>>);
+
+impl SimulatedNetwork {
+	/// Create a new network without any nodes.
+	pub fn new(config: NetworkConfig) -> Self {
+		let rng = ChaChaRng::seed_from_u64(config.seed);
+
+		SimulatedNetwork(Arc::new(Mutex::new(Inner {
+			config,
+			rng,
+			nodes: HashMap::new(),
+			peers: Vec::new(),
+			now: Duration::from_millis(0),
+			in_flight: BTreeMap::new(),
+			sent: 0,
+			partitions: HashSet::new(),
+			authorities: HashMap::new(),
+			reports: Vec::new(),
+		})))
+	}
+
+	/// Add a node with the given role. The returned network is meant to be handed to the
+	/// network bridge of the node.
+	///
+	/// The peer ID of the node only depends on the number of nodes added before it.
+	pub fn add_node(&self, role: ObservedRole) -> NodeNetwork {
+		let mut inner = self.0.lock();
+		let index = inner.peers.len() as u64;
+		let peer_id = node_peer_id(index);
+
+		let address = Multiaddr::empty()
+			.with(Protocol::Memory(index + 1))
+			.with(Protocol::P2p(peer_id.clone().into()));
+
+		let (events_tx, events_rx) = mpsc::unbounded();
+		inner.nodes.insert(peer_id.clone(), Node {
+			role,
+			address,
+			events_tx,
+			events_rx: Some(events_rx),
+			connected: HashSet::new(),
+			reserved: HashMap::new(),
+		});
+		inner.peers.push(peer_id.clone());
+
+		NodeNetwork {
+			peer_id,
+			network: self.clone(),
+		}
+	}
+
+	/// Connect two nodes, opening the notifications streams of all peer sets between them.
+	pub fn connect(&self, a: &PeerId, b: &PeerId) {
+		self.0.lock().connect(a, b)
+	}
+
+	/// Connect every pair of nodes.
+	pub fn connect_all(&self) {
+		let mut inner = self.0.lock();
+		let peers = inner.peers.clone();
+
+		for (i, a) in peers.iter().enumerate() {
+			for b in &peers[i + 1..] {
+				inner.connect(a, b);
+			}
+		}
+	}
+
+	/// Disconnect two nodes, closing the notifications streams of all peer sets between them.
+	pub fn disconnect(&self, a: &PeerId, b: &PeerId) {
+		self.0.lock().disconnect(a, b)
+	}
+
+	/// Partition the network between two groups of nodes.
+	///
+	/// The nodes stay connected, but notifications between a node of one group and a node of
+	/// the other are lost until the partition is healed.
+	pub fn partition(&self, group_a: &[PeerId], group_b: &[PeerId]) {
+		let mut inner = self.0.lock();
+		for a in group_a {
+			for b in group_b {
+				inner.partitions.insert((a.clone(), b.clone()));
+				inner.partitions.insert((b.clone(), a.clone()));
+			}
+		}
+	}
+
+	/// Heal all partitions.
+	pub fn heal(&self) {
+		self.0.lock().partitions.clear()
+	}
+
+	/// Whether two nodes are connected.
+	pub fn is_connected(&self, a: &PeerId, b: &PeerId) -> bool {
+		self.0.lock().is_connected(a, b)
+	}
+
+	/// The virtual time of the network, which starts at zero.
+	pub fn now(&self) -> Duration {
+		self.0.lock().now
+	}
+
+	/// Advance the virtual time of the network, delivering the notifications which are due by
+	/// then.
+	pub fn advance_time(&self, by: Duration) {
+		let mut inner = self.0.lock();
+		inner.now += by;
+		inner.deliver_due();
+	}
+
+	/// The address of a node.
+	pub fn address(&self, peer: &PeerId) -> Option<Multiaddr> {
+		self.0.lock().nodes.get(peer).map(|node| node.address.clone())
+	}
+
+	/// Note that the given authority is reachable at a node, for the authority discovery
+	/// service of the network.
+	pub fn register_authority(&self, authority: AuthorityDiscoveryId, peer: &PeerId) {
+		self.0.lock().authorities.insert(authority, peer.clone());
+	}
+
+	/// An authority discovery service resolving the authorities registered with the network.
+	pub fn authority_discovery(&self) -> NetworkAuthorityDiscovery {
+		NetworkAuthorityDiscovery(self.clone())
+	}
+
+	/// The reserved peers of a peer set, as last set by the given node.
+	pub fn reserved_peers(&self, peer: &PeerId, peer_set: PeerSet) -> HashSet<Multiaddr> {
+		self.0.lock().nodes.get(peer)
+			.and_then(|node| node.reserved.get(&peer_set).cloned())
+			.unwrap_or_default()
+	}
+
+	/// All reputation changes reported by nodes so far, in order.
+	pub fn reports(&self) -> Vec<Report> {
+		self.0.lock().reports.clone()
+	}
+}
+
+/// The view of a single node onto a [`SimulatedNetwork`].
+///
+/// [`SimulatedNetwork`]: struct.SimulatedNetwork.html
+#[derive(Clone)]
+pub struct NodeNetwork {
+	peer_id: PeerId,
+	network: SimulatedNetwork,
+}
+
+impl NodeNetwork {
+	/// The peer ID of the node.
+	pub fn peer_id(&self) -> &PeerId {
+		&self.peer_id
+	}
+}
+
+impl Network for NodeNetwork {
+	fn event_stream(&mut self) -> BoxStream<'static, NetworkEvent> {
+		let mut inner = self.network.0.lock();
+		let node = inner.nodes.get_mut(&self.peer_id)
+			.expect("nodes are never removed from the network; qed");
+
+		let events_rx = match node.events_rx.take() {
+			Some(events_rx) => events_rx,
+			None => {
+				// a restarted network bridge asks again. Only the latest stream gets events.
+				let (events_tx, events_rx) = mpsc::unbounded();
+				node.events_tx = events_tx;
+				events_rx
+			}
+		};
+
+		events_rx.boxed()
+	}
+
+	fn action_sink<'a>(&'a mut self)
+		-> Pin<Box<dyn Sink<NetworkAction, Error = SubsystemError> + Send + 'a>>
+	{
+		Box::pin(ActionSink(self))
+	}
+}
+
+// Carries out the actions of a node on the network as soon as they are sent.
+struct ActionSink<'a>(&'a NodeNetwork);
+
+impl<'a> Sink<NetworkAction> for ActionSink<'a> {
+	type Error = SubsystemError;
+
+	fn poll_ready(self: Pin<&mut Self>, _: &mut Context) -> Poll<SubsystemResult<()>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn start_send(self: Pin<&mut Self>, action: NetworkAction) -> SubsystemResult<()> {
+		let node = self.0;
+		node.network.0.lock().handle_action(&node.peer_id, action);
+
+		Ok(())
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<SubsystemResult<()>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn poll_close(self: Pin<&mut Self>, _: &mut Context) -> Poll<SubsystemResult<()>> {
+		Poll::Ready(Ok(()))
+	}
+}
+
+/// An authority discovery service resolving the authorities registered with a
+/// [`SimulatedNetwork`] to the addresses of their nodes.
+///
+/// [`SimulatedNetwork`]: struct.SimulatedNetwork.html
+#[derive(Clone)]
+pub struct NetworkAuthorityDiscovery(SimulatedNetwork);
+
+impl AuthorityDiscovery for NetworkAuthorityDiscovery {
+	fn get_addresses_by_authority_id(&mut self, authority: AuthorityDiscoveryId)
+		-> BoxFuture<Option<Vec<Multiaddr>>>
+	{
+		let inner = (self.0).0.lock();
+		let addresses = inner.authorities.get(&authority)
+			.and_then(|peer| inner.nodes.get(peer))
+			.map(|node| vec![node.address.clone()]);
+
+		future::ready(addresses).boxed()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::{self, LocalPool, LocalSpawner};
+	use futures::task::LocalSpawnExt;
+	use assert_matches::assert_matches;
+	use sp_keyring::sr25519::Keyring;
+
+	use node_primitives::{SignedFullStatement, Statement};
+	use polkadot_availability_distribution::AvailabilityDistributionSubsystem;
+	use polkadot_bitfield_distribution::BitfieldDistribution;
+	use polkadot_network_bridge::{NetworkBridge, PeerSetsConfig};
+	use polkadot_overseer::{
+		AllSubsystems, BlockInfo, FromOverseer, Overseer, OverseerSignal, Subsystem, SubsystemContext,
+		SpawnedSubsystem, OverseerHandler,
+	};
+	use polkadot_primitives::Hash;
+	use polkadot_primitives::parachain::{
+		v2::AbridgedCandidateReceipt, AvailabilityBitfield, AvailableData, BlockData, CoreState,
+		ErasureChunk, OccupiedCore, PoVBlock, SignedAvailabilityBitfield, SigningContext,
+		ValidatorId, ValidatorPair,
+	};
+	use polkadot_statement_distribution::StatementDistribution;
+	use polkadot_subsystem::messages::{
+		AllMessages, AvailabilityDistributionMessage, AvailabilityStoreMessage,
+		BitfieldDistributionMessage, CandidateBackingMessage, NetworkBridgeEvent,
+		NetworkBridgeMessage, ProvisionableData, ProvisionerMessage, RuntimeApiMessage,
+		RuntimeApiRequest, StatementDistributionMessage,
+	};
+
+	const PROBE_PROTOCOL: [u8; 4] = *b"prob";
+
+	// Registers an event producer with the network bridge and forwards all events it gets.
+	#[derive(Clone)]
+	struct Probe(mpsc::UnboundedSender<NetworkBridgeEvent>);
+
+	impl<C> Subsystem<C> for Probe
+		where C: SubsystemContext<Message = StatementDistributionMessage>
+	{
+		fn start(self, mut ctx: C) -> SpawnedSubsystem {
+			SpawnedSubsystem(Box::pin(async move {
+				let _ = ctx.send_message(AllMessages::NetworkBridge(
+					NetworkBridgeMessage::RegisterEventProducer(
+						PROBE_PROTOCOL,
+						|event| AllMessages::StatementDistribution(
+							StatementDistributionMessage::NetworkBridgeUpdate(event)
+						),
+					)
+				)).await;
+
+				loop {
+					match ctx.recv().await {
+						Ok(FromOverseer::Communication {
+							msg: StatementDistributionMessage::NetworkBridgeUpdate(event),
+						}) => {
+							let _ = self.0.unbounded_send(event);
+						}
+						Ok(FromOverseer::Signal(OverseerSignal::Conclude)) | Err(_) => return,
+						_ => {}
+					}
+				}
+			}))
+		}
+	}
+
+	struct TestNode {
+		peer_id: PeerId,
+		handler: OverseerHandler,
+		events: mpsc::UnboundedReceiver<NetworkBridgeEvent>,
+	}
+
+	impl TestNode {
+		async fn wait_for_peers(&mut self, peers: &[&PeerId]) {
+			let mut pending: HashSet<_> = peers.iter().cloned().cloned().collect();
+
+			while !pending.is_empty() {
+				let event = self.events.next().await.expect("probe concluded early");
+				if let NetworkBridgeEvent::PeerConnected(peer, _) = event {
+					pending.remove(&peer);
+				}
+			}
+		}
+
+		async fn next_message(&mut self) -> (PeerId, Vec<u8>) {
+			loop {
+				let event = self.events.next().await.expect("probe concluded early");
+				if let NetworkBridgeEvent::PeerMessage(peer, message) = event {
+					return (peer, message)
+				}
+			}
+		}
+
+		async fn send_message(&mut self, to: Vec<PeerId>, payload: Vec<u8>) {
+			self.handler.send_msg(AllMessages::NetworkBridge(
+				NetworkBridgeMessage::SendMessage(to, PROBE_PROTOCOL, payload)
+			)).await.unwrap();
+		}
+	}
+
+	fn start_node(network: &SimulatedNetwork, spawner: &LocalSpawner) -> TestNode {
+		let node_network = network.add_node(ObservedRole::Full);
+		let peer_id = node_network.peer_id().clone();
+		let (events_tx, events) = mpsc::unbounded();
+
+		let network_bridge = NetworkBridge::new(
+			node_network,
+			network.authority_discovery(),
+			PeerSetsConfig::default(),
+		);
+
+		let all_subsystems = AllSubsystems::dummy()
+			.replace_network_bridge(network_bridge)
+			.replace_statement_distribution(Probe(events_tx));
+
+		let (overseer, handler) = Overseer::new(
+			std::iter::empty(),
+			all_subsystems,
+			None,
+			spawner.clone(),
+		).unwrap();
+
+		spawner.spawn_local(overseer.run().map(|_| ())).unwrap();
+
+		TestNode { peer_id, handler, events }
+	}
+
+	#[test]
+	fn notifications_are_delayed_lost_and_partitioned() {
+		let network = SimulatedNetwork::new(NetworkConfig {
+			latency: Duration::from_millis(20),
+			..Default::default()
+		});
+
+		let mut a = network.add_node(ObservedRole::Full);
+		let mut b = network.add_node(ObservedRole::Full);
+		let (peer_a, peer_b) = (a.peer_id().clone(), b.peer_id().clone());
+		let mut events_b = b.event_stream();
+
+		network.connect(&peer_a, &peer_b);
+		assert!(network.is_connected(&peer_b, &peer_a));
+
+		executor::block_on(async move {
+			// the streams of all peer sets are opened.
+			for _ in notifications_protocol_info() {
+				assert_matches!(
+					events_b.next().await,
+					Some(NetworkEvent::NotificationStreamOpened { remote, .. }) if remote == peer_a
+				);
+			}
+
+			a.write_notification(PeerSet::Collation, peer_b.clone(), vec![1]).await.unwrap();
+
+			network.partition(&[peer_a.clone()], &[peer_b.clone()]);
+			a.write_notification(PeerSet::Collation, peer_b.clone(), vec![2]).await.unwrap();
+			network.heal();
+
+			a.write_notification(PeerSet::Validation, peer_b.clone(), vec![3]).await.unwrap();
+
+			// nothing arrives before the latency has passed on the virtual clock.
+			network.advance_time(Duration::from_millis(19));
+			assert!(events_b.next().now_or_never().is_none());
+
+			network.advance_time(Duration::from_millis(1));
+			assert_eq!(network.now(), Duration::from_millis(20));
+
+			let expected = vec![(PeerSet::Collation, vec![1]), (PeerSet::Validation, vec![3])];
+			for (peer_set, payload) in expected {
+				assert_matches!(
+					events_b.next().await,
+					Some(NetworkEvent::NotificationsReceived { remote, messages }) => {
+						assert_eq!(remote, peer_a);
+						assert_eq!(messages, vec![(peer_set.engine_id(), payload.into())]);
+					}
+				);
+			}
+		});
+	}
+
+	#[test]
+	fn lost_notifications_never_arrive() {
+		let network = SimulatedNetwork::new(NetworkConfig {
+			loss: 1.0,
+			..Default::default()
+		});
+
+		let mut a = network.add_node(ObservedRole::Full);
+		let mut b = network.add_node(ObservedRole::Full);
+		let (peer_a, peer_b) = (a.peer_id().clone(), b.peer_id().clone());
+		let mut events_b = b.event_stream();
+
+		network.connect(&peer_a, &peer_b);
+
+		executor::block_on(async move {
+			a.write_notification(PeerSet::Collation, peer_b.clone(), vec![1]).await.unwrap();
+			a.disconnect_peer(peer_b.clone()).await.unwrap();
+
+			for _ in notifications_protocol_info() {
+				assert_matches!(
+					events_b.next().await,
+					Some(NetworkEvent::NotificationStreamOpened { .. })
+				);
+			}
+
+			for _ in notifications_protocol_info() {
+				assert_matches!(
+					events_b.next().await,
+					Some(NetworkEvent::NotificationStreamClosed { remote, .. }) if remote == peer_a
+				);
+			}
+		});
+
+		assert!(!network.is_connected(&peer_a, &peer_b));
+	}
+
+	#[test]
+	fn peer_ids_are_the_same_in_every_run() {
+		let peers = || {
+			let network = SimulatedNetwork::new(NetworkConfig::default());
+			(0..3).map(|_| network.add_node(ObservedRole::Full).peer_id().clone()).collect::<Vec<_>>()
+		};
+
+		let first_run = peers();
+		assert_eq!(first_run, peers());
+		assert_ne!(first_run[0], first_run[1]);
+		assert_ne!(first_run[1], first_run[2]);
+	}
+
+	#[test]
+	fn overseers_exchange_messages_over_the_network() {
+		let mut pool = LocalPool::new();
+		let spawner = pool.spawner();
+		let network = SimulatedNetwork::new(NetworkConfig::default());
+
+		let mut a = start_node(&network, &spawner);
+		let mut b = start_node(&network, &spawner);
+		let mut c = start_node(&network, &spawner);
+
+		network.connect_all();
+
+		pool.run_until(async move {
+			a.wait_for_peers(&[&b.peer_id, &c.peer_id]).await;
+			b.wait_for_peers(&[&a.peer_id]).await;
+			c.wait_for_peers(&[&a.peer_id]).await;
+
+			a.send_message(vec![b.peer_id.clone()], vec![1]).await;
+			assert_eq!(b.next_message().await, (a.peer_id.clone(), vec![1]));
+
+			// once `c` has the message, `a` has sent it to `b` as well.
+			network.partition(&[a.peer_id.clone()], &[b.peer_id.clone()]);
+			a.send_message(vec![b.peer_id.clone(), c.peer_id.clone()], vec![2]).await;
+			assert_eq!(c.next_message().await, (a.peer_id.clone(), vec![2]));
+			network.heal();
+
+			a.send_message(vec![b.peer_id.clone()], vec![3]).await;
+			assert_eq!(b.next_message().await, (a.peer_id.clone(), vec![3]));
+
+			for node in &mut [a, b, c] {
+				node.handler.stop().await.unwrap();
+			}
+		});
+	}
+
+	// Forwards all messages it receives to the test, in place of a subsystem.
+	struct Forward<M>(mpsc::UnboundedSender<M>);
+
+	impl<M> Clone for Forward<M> {
+		fn clone(&self) -> Self {
+			Forward(self.0.clone())
+		}
+	}
+
+	impl<M, C> Subsystem<C> for Forward<M>
+		where M: Send + 'static, C: SubsystemContext<Message = M>
+	{
+		fn start(self, mut ctx: C) -> SpawnedSubsystem {
+			SpawnedSubsystem(Box::pin(async move {
+				loop {
+					match ctx.recv().await {
+						Ok(FromOverseer::Communication { msg }) => {
+							let _ = self.0.unbounded_send(msg);
+						}
+						Ok(FromOverseer::Signal(OverseerSignal::Conclude)) | Err(_) => return,
+						Ok(FromOverseer::Signal(_)) => {}
+					}
+				}
+			}))
+		}
+	}
+
+	// Answers the runtime API requests of the distribution subsystems with the same data at
+	// every relay-parent.
+	#[derive(Clone)]
+	struct MockRuntimeApi {
+		validators: Vec<ValidatorId>,
+		authorities: Vec<AuthorityDiscoveryId>,
+		cores: Vec<CoreState>,
+	}
+
+	impl<C> Subsystem<C> for MockRuntimeApi
+		where C: SubsystemContext<Message = RuntimeApiMessage>
+	{
+		fn start(self, mut ctx: C) -> SpawnedSubsystem {
+			SpawnedSubsystem(Box::pin(async move {
+				loop {
+					let request = match ctx.recv().await {
+						Ok(FromOverseer::Communication { msg: RuntimeApiMessage::Request(_, request) }) => request,
+						Ok(FromOverseer::Signal(OverseerSignal::Conclude)) | Err(_) => return,
+						Ok(FromOverseer::Signal(_)) => continue,
+					};
+
+					match request {
+						RuntimeApiRequest::Validators(tx) => {
+							let _ = tx.send(Ok(self.validators.clone()));
+						}
+						RuntimeApiRequest::SigningContext(tx) => {
+							let _ = tx.send(Ok(SigningContext::default()));
+						}
+						RuntimeApiRequest::AuthorityDiscoveryKeys(tx) => {
+							let _ = tx.send(Ok(self.authorities.clone()));
+						}
+						RuntimeApiRequest::AvailabilityCores(tx) => {
+							let _ = tx.send(Ok(self.cores.clone()));
+						}
+						_ => {}
+					}
+				}
+			}))
+		}
+	}
+
+	struct ValidatorNode {
+		handler: OverseerHandler,
+		backing: mpsc::UnboundedReceiver<CandidateBackingMessage>,
+		provisioner: mpsc::UnboundedReceiver<ProvisionerMessage>,
+		availability_store: mpsc::UnboundedReceiver<AvailabilityStoreMessage>,
+	}
+
+	/// Start a validator node running the real statement, bitfield and availability distribution
+	/// subsystems, with the subsystems they talk to replaced by forwarders to the test.
+	fn start_validator_node(
+		network: &SimulatedNetwork,
+		node_network: NodeNetwork,
+		runtime_api: MockRuntimeApi,
+		keystore: sc_keystore::KeyStorePtr,
+		leaf: Hash,
+		spawner: &LocalSpawner,
+	) -> ValidatorNode {
+		let (backing_tx, backing) = mpsc::unbounded();
+		let (provisioner_tx, provisioner) = mpsc::unbounded();
+		let (availability_store_tx, availability_store) = mpsc::unbounded();
+
+		let network_bridge = NetworkBridge::new(
+			node_network,
+			network.authority_discovery(),
+			PeerSetsConfig::default(),
+		);
+
+		let all_subsystems = AllSubsystems::dummy()
+			.replace_network_bridge(network_bridge)
+			.replace_runtime_api(runtime_api)
+			.replace_statement_distribution(StatementDistribution)
+			.replace_bitfield_distribution(BitfieldDistribution)
+			.replace_availability_distribution(AvailabilityDistributionSubsystem::new(keystore))
+			.replace_candidate_backing(Forward(backing_tx))
+			.replace_provisioner(Forward(provisioner_tx))
+			.replace_availability_store(Forward(availability_store_tx));
+
+		let leaves = vec![BlockInfo { hash: leaf, parent_hash: Hash::zero(), number: 1 }];
+		let (overseer, handler) = Overseer::new(
+			leaves,
+			all_subsystems,
+			None,
+			spawner.clone(),
+		).unwrap();
+
+		spawner.spawn_local(overseer.run().map(|_| ())).unwrap();
+
+		ValidatorNode { handler, backing, provisioner, availability_store }
+	}
+
+	#[test]
+	fn distribution_subsystems_of_validators_work_together() {
+		let mut pool = LocalPool::new();
+		let spawner = pool.spawner();
+		let network = SimulatedNetwork::new(NetworkConfig::default());
+
+		let leaf = Hash::from([1; 32]);
+		let keyrings = [Keyring::Alice, Keyring::Bob, Keyring::Charlie];
+		let validators: Vec<_> = keyrings.iter().map(|k| ValidatorId::from(k.public())).collect();
+		let authorities: Vec<_> = keyrings.iter().map(|k| AuthorityDiscoveryId::from(k.public())).collect();
+
+		let available_data = AvailableData {
+			pov_block: PoVBlock { block_data: BlockData(vec![42, 43, 44]) },
+			omitted_validation: Default::default(),
+		};
+		let branches = polkadot_erasure_coding::obtain_chunks(validators.len(), &available_data).unwrap();
+		let branches = polkadot_erasure_coding::branches(branches.as_ref());
+
+		let mut candidate = AbridgedCandidateReceipt { relay_parent: leaf, ..Default::default() };
+		candidate.commitments.erasure_root = branches.root();
+
+		let chunks: Vec<_> = branches.enumerate()
+			.map(|(index, (proof, chunk))| ErasureChunk { chunk: chunk.to_vec(), index: index as u32, proof })
+			.collect();
+
+		// the candidate is pending availability at the leaf.
+		let runtime_api = MockRuntimeApi {
+			validators: validators.clone(),
+			authorities: authorities.clone(),
+			cores: vec![CoreState::Occupied(OccupiedCore {
+				para_id: Default::default(),
+				group_responsible: Default::default(),
+				occupied_since: 1,
+				availability: Default::default(),
+				candidate: candidate.clone(),
+			})],
+		};
+
+		let node_networks: Vec<_> = authorities.iter()
+			.map(|authority| {
+				let node_network = network.add_node(ObservedRole::Authority);
+				network.register_authority(authority.clone(), node_network.peer_id());
+				node_network
+			})
+			.collect();
+
+		let mut pairs = Vec::new();
+		let mut nodes = Vec::new();
+		for (keyring, node_network) in keyrings.iter().zip(node_networks) {
+			let keystore = sc_keystore::Store::new_in_memory();
+			let pair = keystore.write().insert_ephemeral_from_seed::<ValidatorPair>(&keyring.to_seed())
+				.expect("Insert key into keystore");
+
+			pairs.push(pair);
+			nodes.push(start_validator_node(&network, node_network, runtime_api.clone(), keystore, leaf, &spawner));
+		}
+
+		network.connect_all();
+
+		let seconded = SignedFullStatement::sign(
+			Statement::Seconded(candidate.clone()),
+			&SigningContext::default(),
+			0,
+			&pairs[0],
+		);
+		let bitfield = SignedAvailabilityBitfield::sign(
+			AvailabilityBitfield(bitvec::bitvec![bitvec::order::Lsb0, u8; 1, 0, 0]),
+			&SigningContext::default(),
+			0,
+			&pairs[0],
+		);
+
+		pool.run_until(async move {
+			// Alice seconds the candidate, signs her bitfield and distributes the chunks. The
+			// other validators get them once they let her know they work on the leaf.
+			let alice = &mut nodes[0].handler;
+			alice.send_msg(AllMessages::StatementDistribution(
+				StatementDistributionMessage::Share(leaf, seconded.clone()),
+			)).await.unwrap();
+			alice.send_msg(AllMessages::BitfieldDistribution(
+				BitfieldDistributionMessage::DistributeBitfield(leaf, bitfield.clone()),
+			)).await.unwrap();
+			for chunk in &chunks {
+				alice.send_msg(AllMessages::AvailabilityDistribution(
+					AvailabilityDistributionMessage::DistributeChunk(candidate.clone(), chunk.clone()),
+				)).await.unwrap();
+			}
+
+			for (index, node) in nodes.iter_mut().enumerate().skip(1) {
+				assert_matches!(
+					node.backing.next().await,
+					Some(CandidateBackingMessage::Statement(r, s)) => {
+						assert_eq!(r, leaf);
+						assert_eq!(s, seconded);
+					}
+				);
+
+				assert_matches!(
+					node.provisioner.next().await,
+					Some(ProvisionerMessage::ProvisionableData(ProvisionableData::Bitfield(r, b))) => {
+						assert_eq!(r, leaf);
+						assert_eq!(b, bitfield);
+					}
+				);
+
+				// each validator gets its own chunk.
+				assert_matches!(
+					node.availability_store.next().await,
+					Some(AvailabilityStoreMessage::StoreChunk(h, i, c)) => {
+						assert_eq!(h, candidate.hash());
+						assert_eq!(i, index as u32);
+						assert_eq!(c, chunks[index]);
+					}
+				);
+			}
+
+			for node in &mut nodes {
+				node.handler.stop().await.unwrap();
+			}
+		});
+	}
+}
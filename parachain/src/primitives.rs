@@ -193,6 +193,16 @@ pub struct UpwardMessage {
 	pub data: Vec<u8>,
 }
 
+/// A message from a parachain to another parachain, sent over an HRMP channel.
+#[derive(Clone, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct OutboundHrmpMessage {
+	/// The para that will receive this message.
+	pub recipient: Id,
+	/// The message data.
+	pub data: Vec<u8>,
+}
+
 /// Validation parameters for evaluating the parachain validity function.
 // TODO: balance downloads (https://github.com/paritytech/polkadot/issues/220)
 #[derive(PartialEq, Eq, Decode)]
@@ -216,10 +226,26 @@ pub struct ValidationParams {
 	pub code_upgrade_allowed: Option<polkadot_core_primitives::BlockNumber>,
 }
 
+/// The HRMP commitments of a parachain block.
+#[derive(Clone, Default, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct HrmpCommitments {
+	/// Messages sent by the Parachain to other parachains over HRMP channels.
+	pub horizontal_messages: Vec<OutboundHrmpMessage>,
+	/// The relay-chain block number up to which all inbound HRMP messages have been processed
+	/// by the Parachain.
+	pub hrmp_watermark: RelayChainBlockNumber,
+}
+
 /// The result of parachain validation.
+///
+/// Parachains which use HRMP append their `HrmpCommitments` to the encoded result. A result
+/// without them is encoded exactly as before HRMP existed, so the output of existing validation
+/// functions still decodes. As the encoding isn't self-delimiting, a result has to be decoded
+/// from the whole of its encoding.
 // TODO: egress and balance uploads (https://github.com/paritytech/polkadot/issues/220)
-#[derive(PartialEq, Eq, Encode)]
-#[cfg_attr(feature = "std", derive(Debug, Decode))]
+#[derive(PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct ValidationResult {
 	/// New head data that should be included in the relay chain state.
 	pub head_data: HeadData,
@@ -231,4 +257,43 @@ pub struct ValidationResult {
 	///
 	/// It is expected that the Parachain processes them from first to last.
 	pub processed_downward_messages: u32,
+	/// The HRMP commitments of the Parachain, if it uses HRMP.
+	pub hrmp: Option<HrmpCommitments>,
+}
+
+impl Encode for ValidationResult {
+	fn encode_to<T: codec::Output>(&self, dest: &mut T) {
+		self.head_data.encode_to(dest);
+		self.new_validation_code.encode_to(dest);
+		self.upward_messages.encode_to(dest);
+		self.processed_downward_messages.encode_to(dest);
+
+		if let Some(ref hrmp) = self.hrmp {
+			hrmp.encode_to(dest);
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl Decode for ValidationResult {
+	fn decode<I: codec::Input>(input: &mut I) -> Result<Self, codec::Error> {
+		let head_data = Decode::decode(input)?;
+		let new_validation_code = Decode::decode(input)?;
+		let upward_messages = Decode::decode(input)?;
+		let processed_downward_messages = Decode::decode(input)?;
+
+		let hrmp = match input.remaining_len()? {
+			Some(0) => None,
+			Some(_) => Some(Decode::decode(input)?),
+			None => return Err("A `ValidationResult` can only be decoded from input of known length".into()),
+		};
+
+		Ok(ValidationResult {
+			head_data,
+			new_validation_code,
+			upward_messages,
+			processed_downward_messages,
+			hrmp,
+		})
+	}
 }
@@ -143,7 +143,7 @@ pub fn run_worker(mem_id: &str) -> Result<(), String> {
 				debug!("{} Candidate validated: {:?}", process::id(), result);
 
 				match result {
-					Ok(r) => ValidationResultHeader::Ok(r),
+					Ok(r) => ValidationResultHeader::Ok(r.encode()),
 					Err(e) => ValidationResultHeader::Error(e.to_string()),
 				}
 			};
@@ -166,7 +166,8 @@ struct ValidationHeader {
 
 #[derive(Encode, Decode, Debug)]
 pub enum ValidationResultHeader {
-	Ok(ValidationResult),
+	/// The encoded `ValidationResult`, which isn't self-delimiting.
+	Ok(Vec<u8>),
 	Error(String),
 }
 
@@ -288,7 +289,8 @@ impl ValidationHost {
 			let mut header_buf: &[u8] = header_buf;
 			let header = ValidationResultHeader::decode(&mut header_buf).unwrap();
 			match header {
-				ValidationResultHeader::Ok(result) => Ok(result),
+				ValidationResultHeader::Ok(result) => ValidationResult::decode(&mut &result[..])
+					.map_err(|_| Error::BadReturn),
 				ValidationResultHeader::Error(message) => {
 					debug!("{} Validation error: {}", self.id, message);
 					Err(Error::External(message).into())
@@ -39,6 +39,7 @@ pub extern fn validate_block(params: *const u8, len: usize) -> u64 {
 				new_validation_code: None,
 				upward_messages: sp_std::vec::Vec::new(),
 				processed_downward_messages: 0,
+				hrmp: None,
 			}
 		),
 		Err(_) => panic!("execution failure"),
@@ -50,6 +50,7 @@ pub extern fn validate_block(params: *const u8, len: usize) -> u64 {
 				new_validation_code: output.new_validation_code,
 				upward_messages: sp_std::vec::Vec::new(),
 				processed_downward_messages: 0,
+				hrmp: None,
 			}
 		),
 		Err(_) => panic!("execution failure"),
@@ -29,14 +29,14 @@ use serde::{Serialize, Deserialize};
 #[cfg(feature = "std")]
 use primitives::{bytes, crypto::Pair};
 use primitives::RuntimeDebug;
-use runtime_primitives::traits::{AppVerify, Block as BlockT};
+use runtime_primitives::{Perbill, traits::{AppVerify, Block as BlockT}};
 use inherents::InherentIdentifier;
 use application_crypto::KeyTypeId;
 use polkadot_core_primitives::DownwardMessage;
 
 pub use polkadot_parachain::primitives::{
-	Id, ParachainDispatchOrigin, LOWEST_USER_ID, UpwardMessage, HeadData, BlockData,
-	ValidationCode,
+	Id, ParachainDispatchOrigin, LOWEST_USER_ID, UpwardMessage, OutboundHrmpMessage, HrmpCommitments,
+	HeadData, BlockData, ValidationCode,
 };
 
 /// The key type ID for a collator key.
@@ -212,7 +212,7 @@ pub struct LocalValidationData<N = BlockNumber> {
 /// Commitments made in a `CandidateReceipt`. Many of these are outputs of validation.
 #[derive(PartialEq, Eq, Clone, Encode, Decode)]
 #[cfg_attr(feature = "std", derive(Debug, Default))]
-pub struct CandidateCommitments<H = Hash> {
+pub struct CandidateCommitments<H = Hash, Hrmp = ()> {
 	/// Fees paid from the chain to the relay chain validators.
 	pub fees: Balance,
 	/// Messages destined to be interpreted by the Relay chain itself.
@@ -225,6 +225,11 @@ pub struct CandidateCommitments<H = Hash> {
 	///
 	/// It is expected that the Parachain processes them from first to last.
 	pub processed_downward_messages: u32,
+	/// HRMP commitments, which only candidates of the parachains runtime make.
+	///
+	/// This is `()` for candidates of the legacy parachains module, so that their encoding is
+	/// unchanged. The candidate types of the parachains runtime are found in [`v2`](v2/index.html).
+	pub hrmp: Hrmp,
 }
 
 /// Get a collator signature payload on a relay-parent, block-data combo.
@@ -261,7 +266,7 @@ fn check_collator_signature<H: AsRef<[u8]>>(
 /// All data pertaining to the execution of a parachain candidate.
 #[derive(PartialEq, Eq, Clone, Encode, Decode)]
 #[cfg_attr(feature = "std", derive(Debug, Default))]
-pub struct CandidateReceipt<H = Hash, N = BlockNumber> {
+pub struct CandidateReceipt<H = Hash, N = BlockNumber, Hrmp = ()> {
 	/// The ID of the parachain this is a candidate for.
 	pub parachain_index: Id,
 	/// The hash of the relay-chain block this should be executed in
@@ -280,10 +285,10 @@ pub struct CandidateReceipt<H = Hash, N = BlockNumber> {
 	/// The local validation data.
 	pub local_validation: LocalValidationData<N>,
 	/// Commitments made as a result of validation.
-	pub commitments: CandidateCommitments<H>,
+	pub commitments: CandidateCommitments<H, Hrmp>,
 }
 
-impl<H: AsRef<[u8]>, N> CandidateReceipt<H, N> {
+impl<H: AsRef<[u8]>, N, Hrmp> CandidateReceipt<H, N, Hrmp> {
 	/// Check integrity vs. provided block data.
 	pub fn check_signature(&self) -> Result<(), ()> {
 		check_collator_signature(
@@ -297,7 +302,7 @@ impl<H: AsRef<[u8]>, N> CandidateReceipt<H, N> {
 
 	/// Abridge this `CandidateReceipt`, splitting it into an `AbridgedCandidateReceipt`
 	/// and its omitted component.
-	pub fn abridge(self) -> (AbridgedCandidateReceipt<H>, OmittedValidationData<N>) {
+	pub fn abridge(self) -> (AbridgedCandidateReceipt<H, Hrmp>, OmittedValidationData<N>) {
 		let CandidateReceipt {
 			parachain_index,
 			relay_parent,
@@ -329,13 +334,13 @@ impl<H: AsRef<[u8]>, N> CandidateReceipt<H, N> {
 	}
 }
 
-impl PartialOrd for CandidateReceipt {
+impl<Hrmp: Eq> PartialOrd for CandidateReceipt<Hash, BlockNumber, Hrmp> {
 	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
 		Some(self.cmp(other))
 	}
 }
 
-impl Ord for CandidateReceipt {
+impl<Hrmp: Eq> Ord for CandidateReceipt<Hash, BlockNumber, Hrmp> {
 	fn cmp(&self, other: &Self) -> Ordering {
 		// TODO: compare signatures or something more sane
 		// https://github.com/paritytech/polkadot/issues/222
@@ -362,7 +367,7 @@ pub struct OmittedValidationData<N = BlockNumber> {
 /// be re-generated from relay-chain state.
 #[derive(PartialEq, Eq, Clone, Encode, Decode)]
 #[cfg_attr(feature = "std", derive(Debug, Default))]
-pub struct AbridgedCandidateReceipt<H = Hash> {
+pub struct AbridgedCandidateReceipt<H = Hash, Hrmp = ()> {
 	/// The ID of the parachain this is a candidate for.
 	pub parachain_index: Id,
 	/// The hash of the relay-chain block this should be executed in
@@ -379,10 +384,10 @@ pub struct AbridgedCandidateReceipt<H = Hash> {
 	/// The hash of the pov-block.
 	pub pov_block_hash: H,
 	/// Commitments made as a result of validation.
-	pub commitments: CandidateCommitments<H>,
+	pub commitments: CandidateCommitments<H, Hrmp>,
 }
 
-impl<H: AsRef<[u8]> + Encode> AbridgedCandidateReceipt<H> {
+impl<H: AsRef<[u8]> + Encode, Hrmp: Encode> AbridgedCandidateReceipt<H, Hrmp> {
 	/// Check integrity vs. provided block data.
 	pub fn check_signature(&self) -> Result<(), ()> {
 		check_collator_signature(
@@ -407,10 +412,13 @@ impl<H: AsRef<[u8]> + Encode> AbridgedCandidateReceipt<H> {
 	}
 }
 
-impl AbridgedCandidateReceipt {
+impl<Hrmp> AbridgedCandidateReceipt<Hash, Hrmp> {
 	/// Combine the abridged candidate receipt with the omitted data,
 	/// forming a full `CandidateReceipt`.
-	pub fn complete(self, omitted: OmittedValidationData) -> CandidateReceipt {
+	pub fn complete(
+		self,
+		omitted: OmittedValidationData,
+	) -> CandidateReceipt<Hash, BlockNumber, Hrmp> {
 		let AbridgedCandidateReceipt {
 			parachain_index,
 			relay_parent,
@@ -463,13 +471,13 @@ impl AbridgedCandidateReceipt {
 }
 
 
-impl PartialOrd for AbridgedCandidateReceipt {
+impl<Hrmp: Eq> PartialOrd for AbridgedCandidateReceipt<Hash, Hrmp> {
 	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
 		Some(self.cmp(other))
 	}
 }
 
-impl Ord for AbridgedCandidateReceipt {
+impl<Hrmp: Eq> Ord for AbridgedCandidateReceipt<Hash, Hrmp> {
 	fn cmp(&self, other: &Self) -> Ordering {
 		// TODO: compare signatures or something more sane
 		// https://github.com/paritytech/polkadot/issues/222
@@ -510,7 +518,10 @@ impl CollationInfo {
 	}
 
 	/// Turn this into an `AbridgedCandidateReceipt` by supplying a set of commitments.
-	pub fn into_receipt(self, commitments: CandidateCommitments) -> AbridgedCandidateReceipt {
+	pub fn into_receipt<Hrmp>(
+		self,
+		commitments: CandidateCommitments<Hash, Hrmp>,
+	) -> AbridgedCandidateReceipt<Hash, Hrmp> {
 		let CollationInfo {
 			parachain_index,
 			relay_parent,
@@ -595,8 +606,7 @@ pub struct Activity(#[cfg_attr(feature = "std", serde(with="bytes"))] pub Vec<u8
 
 /// Statements that can be made about parachain candidates. These are the
 /// actual values that are signed.
-#[derive(Clone, PartialEq, Eq, Encode, Decode)]
-#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, PartialEq, Eq, Hash, Encode, Decode, RuntimeDebug)]
 pub enum CompactStatement {
 	/// Proposal of a parachain candidate.
 	#[codec(index = "1")]
@@ -733,6 +743,24 @@ impl From<Vec<SignedAvailabilityBitfield>> for SignedAvailabilityBitfields {
 	}
 }
 
+/// The candidate types of the parachains runtime, whose commitments include HRMP.
+pub mod v2 {
+	use super::{BlockNumber, Hash, HrmpCommitments};
+
+	/// Commitments made in a candidate receipt of the parachains runtime.
+	///
+	/// The HRMP commitments are `None` if the para doesn't use HRMP.
+	pub type CandidateCommitments<H = Hash> = super::CandidateCommitments<H, Option<HrmpCommitments>>;
+
+	/// A candidate receipt of the parachains runtime.
+	pub type CandidateReceipt<H = Hash, N = BlockNumber> =
+		super::CandidateReceipt<H, N, Option<HrmpCommitments>>;
+
+	/// An abridged candidate receipt of the parachains runtime.
+	pub type AbridgedCandidateReceipt<H = Hash> =
+		super::AbridgedCandidateReceipt<H, Option<HrmpCommitments>>;
+}
+
 /// A backed (or backable, depending on context) candidate.
 // TODO: yes, this is roughly the same as AttestedCandidate.
 // After https://github.com/paritytech/polkadot/issues/1250
@@ -740,7 +768,7 @@ impl From<Vec<SignedAvailabilityBitfield>> for SignedAvailabilityBitfields {
 #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
 pub struct BackedCandidate<H = Hash> {
 	/// The candidate referred to.
-	pub candidate: AbridgedCandidateReceipt<H>,
+	pub candidate: v2::AbridgedCandidateReceipt<H>,
 	/// The validity votes themselves, expressed as signatures.
 	pub validity_votes: Vec<ValidityAttestation>,
 	/// The indices of the validators within the group, expressed as a bitfield.
@@ -797,6 +825,219 @@ pub fn check_candidate_backing<H: AsRef<[u8]> + Encode>(
 	Ok(signed)
 }
 
+/// The unique (during session) index of a core.
+#[derive(Encode, Decode, Default, PartialOrd, Ord, Eq, PartialEq, Clone, Copy, RuntimeDebug)]
+pub struct CoreIndex(pub u32);
+
+impl From<u32> for CoreIndex {
+	fn from(i: u32) -> CoreIndex {
+		CoreIndex(i)
+	}
+}
+
+/// The unique (during session) index of a validator group.
+#[derive(Encode, Decode, Default, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub struct GroupIndex(pub u32);
+
+impl From<u32> for GroupIndex {
+	fn from(i: u32) -> GroupIndex {
+		GroupIndex(i)
+	}
+}
+
+/// The assignment type.
+#[derive(Clone, Encode, Decode, PartialEq, RuntimeDebug)]
+pub enum AssignmentKind {
+	/// A parachain.
+	Parachain,
+	/// A parathread, with the collator required to author the block and the number of retries
+	/// used so far.
+	Parathread(CollatorId, u32),
+}
+
+/// How a free core is scheduled to be assigned.
+#[derive(Clone, Encode, Decode, PartialEq, RuntimeDebug)]
+pub struct CoreAssignment {
+	/// The core that is assigned.
+	pub core: CoreIndex,
+	/// The unique ID of the para that is assigned to the core.
+	pub para_id: Id,
+	/// The kind of the assignment.
+	pub kind: AssignmentKind,
+	/// The index of the validator group assigned to the core.
+	pub group_idx: GroupIndex,
+}
+
+impl CoreAssignment {
+	/// Get the ID of a collator who is required to collate this block.
+	pub fn required_collator(&self) -> Option<&CollatorId> {
+		match self.kind {
+			AssignmentKind::Parachain => None,
+			AssignmentKind::Parathread(ref id, _) => Some(id),
+		}
+	}
+}
+
+/// All configuration of the runtime with respect to parachains and parathreads.
+#[derive(Clone, Encode, Decode, PartialEq, Default)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct HostConfiguration<BlockNumber> {
+	/// The minimum frequency at which parachains can update their validation code.
+	pub validation_upgrade_frequency: BlockNumber,
+	/// The delay, in blocks, before a validation upgrade is applied.
+	pub validation_upgrade_delay: BlockNumber,
+	/// The acceptance period, in blocks. This is the amount of blocks after availability that validators
+	/// and fishermen have to perform secondary checks or issue reports.
+	pub acceptance_period: BlockNumber,
+	/// The maximum validation code size, in bytes.
+	pub max_code_size: u32,
+	/// The maximum head-data size, in bytes.
+	pub max_head_data_size: u32,
+	/// The amount of execution cores to dedicate to parathread execution.
+	pub parathread_cores: u32,
+	/// The number of retries that a parathread author has to submit their block.
+	pub parathread_retries: u32,
+	/// The fee for claiming a parathread slot when the claim queue is empty.
+	pub parathread_base_fee: Balance,
+	/// The factor by which the fee for claiming a parathread slot grows with each claim already
+	/// in the queue.
+	pub parathread_fee_growth: Perbill,
+	/// How often parachain groups should be rotated across parachains. Must be non-zero.
+	pub parachain_rotation_frequency: BlockNumber,
+	/// The availability period, in blocks, for parachains. This is the amount of blocks
+	/// after inclusion that validators have to make the block available and signal its availability to
+	/// the chain. Must be at least 1.
+	pub chain_availability_period: BlockNumber,
+	/// The availability period, in blocks, for parathreads. Same as the `chain_availability_period`,
+	/// but a differing timeout due to differing requirements. Must be at least 1.
+	pub thread_availability_period: BlockNumber,
+	/// The amount of blocks ahead to schedule parachains and parathreads.
+	pub scheduling_lookahead: u32,
+	/// Total number of individual messages allowed in the parachain -> relay-chain message queue.
+	pub max_upward_queue_count: u32,
+	/// Total size of messages allowed in the parachain -> relay-chain message queue before which
+	/// no further messages may be added to it. If it exceeds this then the queue may contain only
+	/// a single message.
+	pub watermark_upward_queue_size: u32,
+	/// The maximum total weight of the upward messages dispatched in a single block.
+	pub max_upward_dispatch_weight: u64,
+	/// Total number of individual messages allowed in the relay-chain -> parachain message queue.
+	pub max_downward_queue_count: u32,
+	/// The deposit that the sender should provide for opening an HRMP channel.
+	pub hrmp_sender_deposit: Balance,
+	/// The deposit that the recipient should provide for accepting opening an HRMP channel.
+	pub hrmp_recipient_deposit: Balance,
+	/// The maximum number of messages allowed in an HRMP channel at once.
+	pub hrmp_channel_max_capacity: u32,
+	/// The maximum size of a message that could ever be put into an HRMP channel.
+	pub hrmp_channel_max_message_size: u32,
+	/// The maximum number of inbound HRMP channels a parachain is allowed to accept.
+	pub hrmp_max_parachain_inbound_channels: u32,
+	/// The maximum number of outbound HRMP channels a parachain is allowed to open.
+	pub hrmp_max_parachain_outbound_channels: u32,
+	/// The maximum number of outbound HRMP messages that can be sent by a candidate.
+	pub hrmp_max_message_num_per_candidate: u32,
+	/// The deposit that the reporter of a dispute should provide for starting it.
+	pub dispute_deposit: Balance,
+}
+
+/// Information about how validator groups rotate across the availability cores.
+#[derive(Clone, Encode, Decode, PartialEq, Default, RuntimeDebug)]
+pub struct GroupRotationInfo<N = BlockNumber> {
+	/// The block number where the session started.
+	pub session_start_block: N,
+	/// How often groups rotate. 0 means never.
+	pub group_rotation_frequency: N,
+	/// The current block number.
+	pub now: N,
+}
+
+impl GroupRotationInfo {
+	/// Returns the index of the group assigned to the given core at `now`, given the total
+	/// number of cores.
+	///
+	/// `core_index` should be less than `cores`, which is capped at u32::max().
+	pub fn group_for_core(&self, core_index: CoreIndex, cores: usize) -> GroupIndex {
+		if self.group_rotation_frequency == 0 || cores == 0 {
+			return GroupIndex(core_index.0);
+		}
+
+		let cores = sp_std::cmp::min(cores, u32::max_value() as usize);
+		let rotations = self.now.saturating_sub(self.session_start_block) / self.group_rotation_frequency;
+		let rotations = rotations % cores as u32;
+
+		GroupIndex((core_index.0 + rotations) % cores as u32)
+	}
+}
+
+/// Information about a core which is currently occupied by a candidate pending availability.
+#[derive(Clone, Encode, Decode, PartialEq, RuntimeDebug)]
+pub struct OccupiedCore<H = Hash, N = BlockNumber> {
+	/// The ID of the para occupying the core.
+	pub para_id: Id,
+	/// The group assigned to distribute availability pieces of this candidate.
+	pub group_responsible: GroupIndex,
+	/// The relay-chain block number this core began to be occupied at.
+	pub occupied_since: N,
+	/// A bitfield with one bit for each validator in the set. `1` bits mean that the corresponding
+	/// validator has attested to availability on-chain.
+	pub availability: BitVec<bitvec::order::Lsb0, u8>,
+	/// The candidate pending availability on the core.
+	pub candidate: v2::AbridgedCandidateReceipt<H>,
+}
+
+/// Information about a core which is free and scheduled to be occupied by a para.
+#[derive(Clone, Encode, Decode, PartialEq, RuntimeDebug)]
+pub struct ScheduledCore {
+	/// The ID of the para scheduled on the core.
+	pub para_id: Id,
+	/// The collator required to author the block, if any.
+	pub collator: Option<CollatorId>,
+}
+
+/// The state of an availability core.
+#[derive(Clone, Encode, Decode, PartialEq, RuntimeDebug)]
+pub enum CoreState<H = Hash, N = BlockNumber> {
+	/// The core is occupied by a candidate pending availability.
+	#[codec(index = "0")]
+	Occupied(OccupiedCore<H, N>),
+	/// The core is free and a para is scheduled to occupy it.
+	#[codec(index = "1")]
+	Scheduled(ScheduledCore),
+	/// The core is free and nothing is scheduled on it.
+	#[codec(index = "2")]
+	Free,
+}
+
+/// Validation data which is persisted in the context of each relay-chain block, and which a
+/// candidate of a para is validated against when built on that block.
+#[derive(PartialEq, Eq, Clone, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug, Default))]
+pub struct PersistedValidationData<N = BlockNumber> {
+	/// The parent head-data.
+	pub parent_head: HeadData,
+	/// The relay-chain block number this is in the context of.
+	pub block_number: N,
+}
+
+/// Validation data which is only needed to check a candidate of a para and is not persisted.
+#[derive(PartialEq, Eq, Clone, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug, Default))]
+pub struct TransientValidationData<N = BlockNumber> {
+	/// The maximum code size permitted, in bytes.
+	pub max_code_size: u32,
+	/// The maximum head-data size permitted, in bytes.
+	pub max_head_data_size: u32,
+	/// Whether the para is allowed to upgrade its validation code.
+	///
+	/// This is `Some` if so, and contains the number of the minimum relay-chain
+	/// height at which the upgrade will be applied, if an upgrade is signaled
+	/// now.
+	pub code_upgrade_allowed: Option<N>,
+	/// The number of messages pending in the downward message queue of the para.
+	pub dmq_length: u32,
+}
+
 sp_api::decl_runtime_apis! {
 	/// The API for querying the state of parachains on-chain.
 	#[api_version(3)]
@@ -822,6 +1063,35 @@ sp_api::decl_runtime_apis! {
 		/// Get the `DownwardMessage`'s for the given parachain.
 		fn downward_messages(id: Id) -> Vec<DownwardMessage>;
 	}
+
+	/// The API for querying the state of the parachains runtime modules.
+	///
+	/// Unlike `ParachainHost`, which exposes the legacy parachains module, this exposes the
+	/// scheduler, inclusion and paras modules.
+	pub trait ParachainHostV2 {
+		/// Get the current validators.
+		fn validators() -> Vec<ValidatorId>;
+		/// Get the validator groups, one for each core, along with the information needed to
+		/// determine which group is assigned to which core at any block number.
+		fn validator_groups() -> (Vec<Vec<ValidatorIndex>>, GroupRotationInfo);
+		/// Get the state of each availability core. The index of a core in the returned vector
+		/// corresponds to the bit of the core in availability bitfields.
+		fn availability_cores() -> Vec<CoreState>;
+		/// Get the assignments of the cores scheduled to be occupied in the next block, sorted
+		/// ascending by core index.
+		fn scheduled_cores() -> Vec<CoreAssignment>;
+		/// Get the persisted validation data of the given para, if it is registered.
+		fn persisted_validation_data(para_id: Id) -> Option<PersistedValidationData>;
+		/// Get the transient validation data of the given para, if it is registered.
+		fn transient_validation_data(para_id: Id) -> Option<TransientValidationData>;
+		/// Get the configuration of the current session.
+		fn host_configuration() -> HostConfiguration<BlockNumber>;
+		/// Get the current validation code of the given para, if it is registered.
+		fn validation_code(para_id: Id) -> Option<ValidationCode>;
+		/// Get current, pending or past validation code by its hash, if it is still kept
+		/// on-chain.
+		fn validation_code_by_hash(hash: Hash) -> Option<ValidationCode>;
+	}
 }
 
 /// Runtime ID module.
@@ -963,4 +1233,48 @@ mod tests {
 			&Hash::from([2; 32]),
 		);
 	}
+
+	#[test]
+	fn legacy_commitments_encoding_is_unchanged() {
+		let commitments = CandidateCommitments {
+			fees: 1,
+			upward_messages: Vec::new(),
+			erasure_root: Hash::from([2; 32]),
+			new_validation_code: None,
+			processed_downward_messages: 3,
+			hrmp: (),
+		};
+
+		assert_eq!(
+			commitments.encode(),
+			(1 as Balance, Vec::<UpwardMessage>::new(), Hash::from([2; 32]), None::<ValidationCode>, 3u32)
+				.encode(),
+		);
+	}
+
+	#[test]
+	fn validation_result_hrmp_commitments_are_optional() {
+		use polkadot_parachain::primitives::ValidationResult;
+
+		let mut result = ValidationResult {
+			head_data: HeadData(vec![1, 2, 3]),
+			new_validation_code: None,
+			upward_messages: Vec::new(),
+			processed_downward_messages: 4,
+			hrmp: None,
+		};
+
+		let legacy = (HeadData(vec![1, 2, 3]), None::<ValidationCode>, Vec::<UpwardMessage>::new(), 4u32)
+			.encode();
+		assert_eq!(result.encode(), legacy);
+		assert_eq!(ValidationResult::decode(&mut &legacy[..]).unwrap(), result);
+
+		result.hrmp = Some(HrmpCommitments {
+			horizontal_messages: vec![OutboundHrmpMessage { recipient: 5.into(), data: vec![6] }],
+			hrmp_watermark: 7,
+		});
+
+		let encoded = result.encode();
+		assert_eq!(ValidationResult::decode(&mut &encoded[..]).unwrap(), result);
+	}
 }
@@ -1075,6 +1075,7 @@ mod tests {
 				erasure_root: [1; 32].into(),
 				new_validation_code: None,
 				processed_downward_messages: 0,
+				hrmp: (),
 			},
 		};
 		let (candidate, _) = candidate.abridge();
@@ -19,48 +19,19 @@
 //! Configuration can change only at session boundaries and is buffered until then.
 
 use sp_std::prelude::*;
+use sp_runtime::Perbill;
 use primitives::{
-	parachain::{ValidatorId},
+	Balance,
+	parachain::ValidatorId,
 };
 use frame_support::{
 	decl_storage, decl_module, decl_error,
 	dispatch::DispatchResult,
 	weights::{DispatchClass, Weight},
 };
-use codec::{Encode, Decode};
 use system::ensure_root;
 
-/// All configuration of the runtime with respect to parachains and parathreads.
-#[derive(Clone, Encode, Decode, PartialEq, Default)]
-#[cfg_attr(test, derive(Debug))]
-pub struct HostConfiguration<BlockNumber> {
-	/// The minimum frequency at which parachains can update their validation code.
-	pub validation_upgrade_frequency: BlockNumber,
-	/// The delay, in blocks, before a validation upgrade is applied.
-	pub validation_upgrade_delay: BlockNumber,
-	/// The acceptance period, in blocks. This is the amount of blocks after availability that validators
-	/// and fishermen have to perform secondary checks or issue reports.
-	pub acceptance_period: BlockNumber,
-	/// The maximum validation code size, in bytes.
-	pub max_code_size: u32,
-	/// The maximum head-data size, in bytes.
-	pub max_head_data_size: u32,
-	/// The amount of execution cores to dedicate to parathread execution.
-	pub parathread_cores: u32,
-	/// The number of retries that a parathread author has to submit their block.
-	pub parathread_retries: u32,
-	/// How often parachain groups should be rotated across parachains. Must be non-zero.
-	pub parachain_rotation_frequency: BlockNumber,
-	/// The availability period, in blocks, for parachains. This is the amount of blocks
-	/// after inclusion that validators have to make the block available and signal its availability to
-	/// the chain. Must be at least 1.
-	pub chain_availability_period: BlockNumber,
-	/// The availability period, in blocks, for parathreads. Same as the `chain_availability_period`,
-	/// but a differing timeout due to differing requirements. Must be at least 1.
-	pub thread_availability_period: BlockNumber,
-	/// The amount of blocks ahead to schedule parachains and parathreads.
-	pub scheduling_lookahead: u32,
-}
+pub use primitives::parachain::HostConfiguration;
 
 pub trait Trait: system::Trait { }
 
@@ -152,6 +123,25 @@ decl_module! {
 			Ok(())
 		}
 
+		/// Set the fee for claiming a parathread slot when the claim queue is empty.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_parathread_base_fee(origin, new: Balance) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.parathread_base_fee, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the factor by which the parathread claim fee grows with each queued claim.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_parathread_fee_growth(origin, new: Perbill) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.parathread_fee_growth, new) != new
+			});
+			Ok(())
+		}
 
 		/// Set the parachain validator-group rotation frequency
 		#[weight = (1_000, DispatchClass::Operational)]
@@ -192,6 +182,127 @@ decl_module! {
 			});
 			Ok(())
 		}
+
+		/// Set the maximum number of messages in the upward message queue of a para.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_max_upward_queue_count(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.max_upward_queue_count, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the size of the upward message queue of a para beyond which no further messages
+		/// may be added.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_watermark_upward_queue_size(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.watermark_upward_queue_size, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the maximum total weight of the upward messages dispatched in a single block.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_max_upward_dispatch_weight(origin, new: Weight) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.max_upward_dispatch_weight, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the maximum number of messages in the downward message queue of a para.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_max_downward_queue_count(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.max_downward_queue_count, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the deposit the sender of an HRMP channel has to reserve.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_sender_deposit(origin, new: Balance) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_sender_deposit, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the deposit the recipient of an HRMP channel has to reserve.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_recipient_deposit(origin, new: Balance) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_recipient_deposit, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the maximum number of messages an HRMP channel may hold.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_channel_max_capacity(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_channel_max_capacity, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the maximum size of a message sent over an HRMP channel.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_channel_max_message_size(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_channel_max_message_size, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the maximum number of inbound HRMP channels of a parachain.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_max_parachain_inbound_channels(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_max_parachain_inbound_channels, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the maximum number of outbound HRMP channels of a parachain.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_max_parachain_outbound_channels(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_max_parachain_outbound_channels, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the maximum number of HRMP messages a candidate may send.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_hrmp_max_message_num_per_candidate(origin, new: u32) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.hrmp_max_message_num_per_candidate, new) != new
+			});
+			Ok(())
+		}
+
+		/// Set the deposit the reporter of a dispute has to reserve.
+		#[weight = (1_000, DispatchClass::Operational)]
+		pub fn set_dispute_deposit(origin, new: Balance) -> DispatchResult {
+			ensure_root(origin)?;
+			Self::update_config_member(|config| {
+				sp_std::mem::replace(&mut config.dispute_deposit, new) != new
+			});
+			Ok(())
+		}
 	}
 }
 
@@ -266,10 +377,24 @@ mod tests {
 				max_head_data_size: 1_000,
 				parathread_cores: 2,
 				parathread_retries: 5,
+				parathread_base_fee: 1_000,
+				parathread_fee_growth: Perbill::from_percent(10),
 				parachain_rotation_frequency: 20,
 				chain_availability_period: 10,
 				thread_availability_period: 8,
 				scheduling_lookahead: 3,
+				max_upward_queue_count: 10,
+				watermark_upward_queue_size: 2_048,
+				max_upward_dispatch_weight: 1_000_000,
+				max_downward_queue_count: 10,
+				hrmp_sender_deposit: 100,
+				hrmp_recipient_deposit: 50,
+				hrmp_channel_max_capacity: 8,
+				hrmp_channel_max_message_size: 1_024,
+				hrmp_max_parachain_inbound_channels: 4,
+				hrmp_max_parachain_outbound_channels: 4,
+				hrmp_max_message_num_per_candidate: 5,
+				dispute_deposit: 200,
 			};
 
 			assert!(<Configuration as Store>::PendingConfig::get().is_none());
@@ -295,6 +420,12 @@ mod tests {
 			Configuration::set_parathread_retries(
 				Origin::root(), new_config.parathread_retries,
 			).unwrap();
+			Configuration::set_parathread_base_fee(
+				Origin::root(), new_config.parathread_base_fee,
+			).unwrap();
+			Configuration::set_parathread_fee_growth(
+				Origin::root(), new_config.parathread_fee_growth,
+			).unwrap();
 			Configuration::set_parachain_rotation_frequency(
 				Origin::root(), new_config.parachain_rotation_frequency,
 			).unwrap();
@@ -307,6 +438,42 @@ mod tests {
 			Configuration::set_scheduling_lookahead(
 				Origin::root(), new_config.scheduling_lookahead,
 			).unwrap();
+			Configuration::set_max_upward_queue_count(
+				Origin::root(), new_config.max_upward_queue_count,
+			).unwrap();
+			Configuration::set_watermark_upward_queue_size(
+				Origin::root(), new_config.watermark_upward_queue_size,
+			).unwrap();
+			Configuration::set_max_upward_dispatch_weight(
+				Origin::root(), new_config.max_upward_dispatch_weight,
+			).unwrap();
+			Configuration::set_max_downward_queue_count(
+				Origin::root(), new_config.max_downward_queue_count,
+			).unwrap();
+			Configuration::set_hrmp_sender_deposit(
+				Origin::root(), new_config.hrmp_sender_deposit,
+			).unwrap();
+			Configuration::set_hrmp_recipient_deposit(
+				Origin::root(), new_config.hrmp_recipient_deposit,
+			).unwrap();
+			Configuration::set_hrmp_channel_max_capacity(
+				Origin::root(), new_config.hrmp_channel_max_capacity,
+			).unwrap();
+			Configuration::set_hrmp_channel_max_message_size(
+				Origin::root(), new_config.hrmp_channel_max_message_size,
+			).unwrap();
+			Configuration::set_hrmp_max_parachain_inbound_channels(
+				Origin::root(), new_config.hrmp_max_parachain_inbound_channels,
+			).unwrap();
+			Configuration::set_hrmp_max_parachain_outbound_channels(
+				Origin::root(), new_config.hrmp_max_parachain_outbound_channels,
+			).unwrap();
+			Configuration::set_hrmp_max_message_num_per_candidate(
+				Origin::root(), new_config.hrmp_max_message_num_per_candidate,
+			).unwrap();
+			Configuration::set_dispute_deposit(
+				Origin::root(), new_config.dispute_deposit,
+			).unwrap();
 
 			assert_eq!(<Configuration as Store>::PendingConfig::get(), Some(new_config));
 		})
@@ -0,0 +1,966 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The HRMP module is responsible for horizontal (para-to-para) message passing.
+//!
+//! Channels are opened by a request of the sender, which has to be accepted by the recipient.
+//! Both sides reserve a deposit for the lifetime of the channel. Opening and closing channels
+//! takes effect at the next session change.
+//!
+//! Messages are committed to by candidates of the sender and placed into the channel when the
+//! candidate is enacted. They stay there until the recipient advances its watermark past the
+//! block they were sent at.
+
+use sp_std::prelude::*;
+use sp_runtime::{
+	RuntimeDebug,
+	traits::{AccountIdConversion, UniqueSaturatedInto},
+};
+use primitives::{
+	Balance,
+	parachain::{Id as ParaId, OutboundHrmpMessage},
+};
+use frame_support::{
+	decl_storage, decl_module, decl_error, ensure, IterableStorageMap,
+	dispatch::DispatchResult,
+	weights::Weight,
+	traits::{Get, ReservableCurrency},
+};
+use codec::{Encode, Decode};
+use crate::{
+	configuration::{self, HostConfiguration},
+	initializer::SessionChangeNotification,
+	paras,
+	router::{self, ensure_parachain},
+};
+
+/// A unique identifier of an HRMP channel.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Encode, Decode, RuntimeDebug)]
+pub struct HrmpChannelId {
+	/// The para sending messages over the channel.
+	pub sender: ParaId,
+	/// The para receiving messages over the channel.
+	pub recipient: ParaId,
+}
+
+/// A request to open an HRMP channel.
+#[derive(Encode, Decode)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct HrmpOpenChannelRequest {
+	/// Whether the request was accepted by the recipient.
+	pub confirmed: bool,
+	/// The deposit reserved by the sender.
+	pub sender_deposit: Balance,
+	/// The deposit reserved by the recipient. Zero until the request is confirmed.
+	pub recipient_deposit: Balance,
+	/// The maximum number of messages that can be pending in the channel at once.
+	pub max_capacity: u32,
+	/// The maximum size of a message sent over the channel.
+	pub max_message_size: u32,
+}
+
+/// An open HRMP channel.
+#[derive(Encode, Decode)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct HrmpChannel {
+	/// The deposit reserved by the sender.
+	pub sender_deposit: Balance,
+	/// The deposit reserved by the recipient.
+	pub recipient_deposit: Balance,
+	/// The maximum number of messages that can be pending in the channel at once.
+	pub max_capacity: u32,
+	/// The maximum size of a message sent over the channel.
+	pub max_message_size: u32,
+	/// The number of messages currently pending in the channel.
+	pub msg_count: u32,
+}
+
+/// A message sent over an HRMP channel, as seen by the recipient.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct InboundHrmpMessage<BlockNumber> {
+	/// The relay-chain block number at which the message was put into the channel.
+	pub sent_at: BlockNumber,
+	/// The message data.
+	pub data: Vec<u8>,
+}
+
+pub trait Trait: system::Trait + configuration::Trait + paras::Trait {
+	/// The outer origin type, which channel management calls are checked against.
+	type Origin: From<<Self as system::Trait>::Origin>
+		+ Into<Result<router::Origin, <Self as Trait>::Origin>>;
+
+	/// The currency that channel deposits are reserved in.
+	type Currency: ReservableCurrency<Self::AccountId>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Hrmp {
+		/// Pending requests to open channels, which take effect at the next session change once
+		/// confirmed by the recipient.
+		HrmpOpenChannelRequests: map hasher(twox_64_concat) HrmpChannelId => Option<HrmpOpenChannelRequest>;
+		/// The channel IDs of `HrmpOpenChannelRequests`, in the order they were requested.
+		HrmpOpenChannelRequestsList: Vec<HrmpChannelId>;
+		/// The number of pending open requests sent by each para.
+		HrmpOpenChannelRequestCount: map hasher(twox_64_concat) ParaId => u32;
+		/// The number of pending open requests accepted by each para.
+		HrmpAcceptedChannelRequestCount: map hasher(twox_64_concat) ParaId => u32;
+		/// Pending requests to close channels, which take effect at the next session change.
+		HrmpCloseChannelRequests: map hasher(twox_64_concat) HrmpChannelId => Option<()>;
+		/// The channel IDs of `HrmpCloseChannelRequests`, in the order they were requested.
+		HrmpCloseChannelRequestsList: Vec<HrmpChannelId>;
+		/// The open channels.
+		HrmpChannels: map hasher(twox_64_concat) HrmpChannelId => Option<HrmpChannel>;
+		/// The sorted list of senders of the open channels of each recipient.
+		HrmpIngressChannelsIndex: map hasher(twox_64_concat) ParaId => Vec<ParaId>;
+		/// The sorted list of recipients of the open channels of each sender.
+		HrmpEgressChannelsIndex: map hasher(twox_64_concat) ParaId => Vec<ParaId>;
+		/// The messages pending in each channel, ordered by `sent_at`.
+		HrmpChannelContents: map hasher(twox_64_concat) HrmpChannelId => Vec<InboundHrmpMessage<T::BlockNumber>>;
+		/// The relay-chain block number up to which each para has processed its inbound messages.
+		HrmpWatermarks: map hasher(twox_64_concat) ParaId => Option<T::BlockNumber>;
+	}
+}
+
+decl_error! {
+	pub enum Error for Module<T: Trait> {
+		/// The sender tried to open a channel to itself.
+		OpenHrmpChannelToSelf,
+		/// The recipient is not a valid parachain.
+		OpenHrmpChannelInvalidRecipient,
+		/// The requested capacity is zero or exceeds the configured limit.
+		OpenHrmpChannelInvalidCapacity,
+		/// The requested maximum message size is zero or exceeds the configured limit.
+		OpenHrmpChannelInvalidMessageSize,
+		/// The channel already exists.
+		OpenHrmpChannelAlreadyExists,
+		/// There is already a request to open the channel.
+		OpenHrmpChannelAlreadyRequested,
+		/// The sender has reached its limit of outbound channels.
+		OpenHrmpChannelLimitExceeded,
+		/// There is no request to open the channel.
+		AcceptHrmpChannelDoesntExist,
+		/// The request to open the channel was already accepted.
+		AcceptHrmpChannelAlreadyConfirmed,
+		/// The origin is neither the sender nor the recipient of the requested channel.
+		CancelHrmpOpenChannelUnauthorized,
+		/// There is no request to open the channel.
+		CancelHrmpOpenChannelDoesntExist,
+		/// The request to open the channel was already accepted, so it opens at the next session
+		/// change.
+		CancelHrmpOpenChannelAlreadyConfirmed,
+		/// The recipient has reached its limit of inbound channels.
+		AcceptHrmpChannelLimitExceeded,
+		/// The origin is neither the sender nor the recipient of the channel.
+		CloseHrmpChannelUnauthorized,
+		/// The channel doesn't exist.
+		CloseHrmpChannelDoesntExist,
+		/// There is already a request to close the channel.
+		CloseHrmpChannelAlreadyUnderway,
+		/// The candidate moved the watermark backwards.
+		HrmpWatermarkMovedBackwards,
+		/// The candidate moved the watermark beyond its relay-parent.
+		HrmpWatermarkAheadOfRelayParent,
+		/// The candidate sent more messages than allowed.
+		TooManyHorizontalMessages,
+		/// The messages of the candidate are not sorted by strictly ascending recipient.
+		HorizontalMessagesNotSorted,
+		/// The candidate sent a message over a channel that doesn't exist.
+		HorizontalMessageNoChannel,
+		/// The candidate sent a message exceeding the maximum message size of the channel.
+		HorizontalMessageTooBig,
+		/// The candidate sent a message to a channel that is at capacity.
+		HrmpChannelCapacityExceeded,
+	}
+}
+
+decl_module! {
+	/// The HRMP module.
+	pub struct Module<T: Trait> for enum Call where origin: <T as system::Trait>::Origin {
+		type Error = Error<T>;
+
+		/// Request to open a channel from the origin para to the given recipient, reserving the
+		/// sender deposit. The channel is opened at the session change after the recipient accepts.
+		#[weight = 100_000]
+		pub fn hrmp_init_open_channel(
+			origin,
+			recipient: ParaId,
+			proposed_max_capacity: u32,
+			proposed_max_message_size: u32,
+		) -> DispatchResult {
+			let sender = ensure_parachain(<T as Trait>::Origin::from(origin))?;
+			Self::init_open_channel(sender, recipient, proposed_max_capacity, proposed_max_message_size)
+		}
+
+		/// Accept a request to open a channel from the given sender to the origin para, reserving
+		/// the recipient deposit.
+		#[weight = 100_000]
+		pub fn hrmp_accept_open_channel(origin, sender: ParaId) -> DispatchResult {
+			let recipient = ensure_parachain(<T as Trait>::Origin::from(origin))?;
+			Self::accept_open_channel(recipient, sender)
+		}
+
+		/// Cancel a request to open a channel that the origin para is either the sender or the
+		/// recipient of, returning the sender deposit. Only requests which weren't accepted yet
+		/// can be cancelled.
+		#[weight = 100_000]
+		pub fn hrmp_cancel_open_request(origin, channel_id: HrmpChannelId) -> DispatchResult {
+			let origin = ensure_parachain(<T as Trait>::Origin::from(origin))?;
+			Self::cancel_open_request(origin, channel_id)
+		}
+
+		/// Request to close a channel that the origin para is either the sender or the recipient of.
+		/// The channel is closed and the deposits returned at the next session change.
+		#[weight = 100_000]
+		pub fn hrmp_close_channel(origin, channel_id: HrmpChannelId) -> DispatchResult {
+			let origin = ensure_parachain(<T as Trait>::Origin::from(origin))?;
+			Self::close_channel(origin, channel_id)
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Block initialization logic, called by initializer.
+	pub(crate) fn initializer_initialize(_now: T::BlockNumber) -> Weight { 0 }
+
+	/// Block finalization logic, called by initializer.
+	pub(crate) fn initializer_finalize() { }
+
+	/// Called by the initializer to note that a new session has started.
+	pub(crate) fn initializer_on_new_session(_notification: &SessionChangeNotification<T::BlockNumber>) {
+		Self::clean_up_offboarded();
+		Self::process_open_channel_requests();
+		Self::process_close_channel_requests();
+	}
+
+	fn init_open_channel(
+		sender: ParaId,
+		recipient: ParaId,
+		max_capacity: u32,
+		max_message_size: u32,
+	) -> DispatchResult {
+		let config = <configuration::Module<T>>::config();
+		let channel_id = HrmpChannelId { sender, recipient };
+
+		ensure!(sender != recipient, Error::<T>::OpenHrmpChannelToSelf);
+		ensure!(
+			<paras::Module<T>>::is_parachain(recipient),
+			Error::<T>::OpenHrmpChannelInvalidRecipient,
+		);
+		ensure!(
+			max_capacity > 0 && max_capacity <= config.hrmp_channel_max_capacity,
+			Error::<T>::OpenHrmpChannelInvalidCapacity,
+		);
+		ensure!(
+			max_message_size > 0 && max_message_size <= config.hrmp_channel_max_message_size,
+			Error::<T>::OpenHrmpChannelInvalidMessageSize,
+		);
+		ensure!(
+			!<Self as Store>::HrmpChannels::contains_key(&channel_id),
+			Error::<T>::OpenHrmpChannelAlreadyExists,
+		);
+		ensure!(
+			!<Self as Store>::HrmpOpenChannelRequests::contains_key(&channel_id),
+			Error::<T>::OpenHrmpChannelAlreadyRequested,
+		);
+
+		let egress_count = <Self as Store>::HrmpEgressChannelsIndex::decode_len(&sender).unwrap_or(0)
+			+ <Self as Store>::HrmpOpenChannelRequestCount::get(&sender) as usize;
+		ensure!(
+			egress_count < config.hrmp_max_parachain_outbound_channels as usize,
+			Error::<T>::OpenHrmpChannelLimitExceeded,
+		);
+
+		Self::reserve_deposit(sender, config.hrmp_sender_deposit)?;
+
+		<Self as Store>::HrmpOpenChannelRequests::insert(&channel_id, HrmpOpenChannelRequest {
+			confirmed: false,
+			sender_deposit: config.hrmp_sender_deposit,
+			recipient_deposit: 0,
+			max_capacity,
+			max_message_size,
+		});
+		<Self as Store>::HrmpOpenChannelRequestsList::append(channel_id);
+		<Self as Store>::HrmpOpenChannelRequestCount::mutate(&sender, |count| *count += 1);
+
+		Ok(())
+	}
+
+	fn accept_open_channel(recipient: ParaId, sender: ParaId) -> DispatchResult {
+		let config = <configuration::Module<T>>::config();
+		let channel_id = HrmpChannelId { sender, recipient };
+
+		let mut request = <Self as Store>::HrmpOpenChannelRequests::get(&channel_id)
+			.ok_or(Error::<T>::AcceptHrmpChannelDoesntExist)?;
+		ensure!(!request.confirmed, Error::<T>::AcceptHrmpChannelAlreadyConfirmed);
+
+		let ingress_count = <Self as Store>::HrmpIngressChannelsIndex::decode_len(&recipient).unwrap_or(0)
+			+ <Self as Store>::HrmpAcceptedChannelRequestCount::get(&recipient) as usize;
+		ensure!(
+			ingress_count < config.hrmp_max_parachain_inbound_channels as usize,
+			Error::<T>::AcceptHrmpChannelLimitExceeded,
+		);
+
+		Self::reserve_deposit(recipient, config.hrmp_recipient_deposit)?;
+
+		request.confirmed = true;
+		request.recipient_deposit = config.hrmp_recipient_deposit;
+		<Self as Store>::HrmpOpenChannelRequests::insert(&channel_id, request);
+		<Self as Store>::HrmpAcceptedChannelRequestCount::mutate(&recipient, |count| *count += 1);
+
+		Ok(())
+	}
+
+	fn cancel_open_request(origin: ParaId, channel_id: HrmpChannelId) -> DispatchResult {
+		ensure!(
+			origin == channel_id.sender || origin == channel_id.recipient,
+			Error::<T>::CancelHrmpOpenChannelUnauthorized,
+		);
+
+		let request = <Self as Store>::HrmpOpenChannelRequests::get(&channel_id)
+			.ok_or(Error::<T>::CancelHrmpOpenChannelDoesntExist)?;
+		ensure!(!request.confirmed, Error::<T>::CancelHrmpOpenChannelAlreadyConfirmed);
+
+		<Self as Store>::HrmpOpenChannelRequests::remove(&channel_id);
+		<Self as Store>::HrmpOpenChannelRequestsList::mutate(|requests| {
+			requests.retain(|id| id != &channel_id)
+		});
+		Self::remove_open_request_counts(&channel_id, false);
+		Self::unreserve_deposit(channel_id.sender, request.sender_deposit);
+
+		Ok(())
+	}
+
+	fn close_channel(origin: ParaId, channel_id: HrmpChannelId) -> DispatchResult {
+		ensure!(
+			origin == channel_id.sender || origin == channel_id.recipient,
+			Error::<T>::CloseHrmpChannelUnauthorized,
+		);
+		ensure!(
+			<Self as Store>::HrmpChannels::contains_key(&channel_id),
+			Error::<T>::CloseHrmpChannelDoesntExist,
+		);
+		ensure!(
+			!<Self as Store>::HrmpCloseChannelRequests::contains_key(&channel_id),
+			Error::<T>::CloseHrmpChannelAlreadyUnderway,
+		);
+
+		<Self as Store>::HrmpCloseChannelRequests::insert(&channel_id, ());
+		<Self as Store>::HrmpCloseChannelRequestsList::append(channel_id);
+
+		Ok(())
+	}
+
+	/// Check the HRMP watermark committed to by a candidate of the given recipient.
+	pub(crate) fn check_hrmp_watermark(
+		recipient: ParaId,
+		relay_parent_number: T::BlockNumber,
+		new_hrmp_watermark: T::BlockNumber,
+	) -> DispatchResult {
+		if let Some(last_watermark) = <Self as Store>::HrmpWatermarks::get(&recipient) {
+			ensure!(new_hrmp_watermark >= last_watermark, Error::<T>::HrmpWatermarkMovedBackwards);
+		}
+
+		ensure!(
+			new_hrmp_watermark <= relay_parent_number,
+			Error::<T>::HrmpWatermarkAheadOfRelayParent,
+		);
+
+		Ok(())
+	}
+
+	/// Check the horizontal messages committed to by a candidate of the given sender.
+	pub(crate) fn check_outbound_hrmp(
+		config: &HostConfiguration<T::BlockNumber>,
+		sender: ParaId,
+		horizontal_messages: &[OutboundHrmpMessage],
+	) -> DispatchResult {
+		ensure!(
+			horizontal_messages.len() <= config.hrmp_max_message_num_per_candidate as usize,
+			Error::<T>::TooManyHorizontalMessages,
+		);
+
+		let mut last_recipient = None;
+		for message in horizontal_messages {
+			ensure!(
+				last_recipient.map_or(true, |last| message.recipient > last),
+				Error::<T>::HorizontalMessagesNotSorted,
+			);
+			last_recipient = Some(message.recipient);
+
+			let channel_id = HrmpChannelId { sender, recipient: message.recipient };
+			let channel = <Self as Store>::HrmpChannels::get(&channel_id)
+				.ok_or(Error::<T>::HorizontalMessageNoChannel)?;
+
+			ensure!(
+				message.data.len() <= channel.max_message_size as usize,
+				Error::<T>::HorizontalMessageTooBig,
+			);
+			ensure!(
+				channel.msg_count < channel.max_capacity,
+				Error::<T>::HrmpChannelCapacityExceeded,
+			);
+		}
+
+		Ok(())
+	}
+
+	/// Put the horizontal messages of an enacted candidate into their channels. These should have
+	/// been checked with `check_outbound_hrmp` beforehand.
+	pub(crate) fn queue_outbound_hrmp(sender: ParaId, horizontal_messages: Vec<OutboundHrmpMessage>) -> Weight {
+		let now = <system::Module<T>>::block_number();
+		let mut weight = 0;
+
+		for OutboundHrmpMessage { recipient, data } in horizontal_messages {
+			let channel_id = HrmpChannelId { sender, recipient };
+
+			<Self as Store>::HrmpChannels::mutate(&channel_id, |channel| {
+				if let Some(channel) = channel {
+					channel.msg_count += 1;
+				}
+			});
+			<Self as Store>::HrmpChannelContents::append(
+				&channel_id,
+				InboundHrmpMessage { sent_at: now, data },
+			);
+
+			weight += T::DbWeight::get().reads_writes(1, 2);
+		}
+
+		weight
+	}
+
+	/// Remove all messages sent at or before the new watermark from the inbound channels of the
+	/// given recipient, and note the new watermark.
+	pub(crate) fn prune_hrmp(recipient: ParaId, new_hrmp_watermark: T::BlockNumber) -> Weight {
+		let senders = <Self as Store>::HrmpIngressChannelsIndex::get(&recipient);
+		let mut weight = T::DbWeight::get().reads_writes(1, 1);
+
+		for sender in senders {
+			let channel_id = HrmpChannelId { sender, recipient };
+
+			let pruned = <Self as Store>::HrmpChannelContents::mutate(&channel_id, |contents| {
+				let pruned = contents.iter().take_while(|m| m.sent_at <= new_hrmp_watermark).count();
+				contents.drain(..pruned);
+				pruned
+			});
+
+			if pruned != 0 {
+				<Self as Store>::HrmpChannels::mutate(&channel_id, |channel| {
+					if let Some(channel) = channel {
+						channel.msg_count = channel.msg_count.saturating_sub(pruned as u32);
+					}
+				});
+			}
+
+			weight += T::DbWeight::get().reads_writes(2, 2);
+		}
+
+		<Self as Store>::HrmpWatermarks::insert(&recipient, new_hrmp_watermark);
+
+		weight
+	}
+
+	/// The messages pending in the inbound channels of the given recipient, by sender.
+	pub fn inbound_hrmp_messages(recipient: ParaId) -> Vec<(ParaId, Vec<InboundHrmpMessage<T::BlockNumber>>)> {
+		<Self as Store>::HrmpIngressChannelsIndex::get(&recipient)
+			.into_iter()
+			.map(|sender| {
+				let channel_id = HrmpChannelId { sender, recipient };
+				(sender, <Self as Store>::HrmpChannelContents::get(&channel_id))
+			})
+			.collect()
+	}
+
+	/// Drop the open requests and close the channels that involve paras which are no longer
+	/// parachains, returning the deposits, and remove the remaining HRMP state of these paras.
+	fn clean_up_offboarded() {
+		let is_live = |channel_id: &HrmpChannelId| {
+			<paras::Module<T>>::is_parachain(channel_id.sender)
+				&& <paras::Module<T>>::is_parachain(channel_id.recipient)
+		};
+
+		let mut requests = <Self as Store>::HrmpOpenChannelRequestsList::get();
+		requests.retain(|channel_id| {
+			if is_live(channel_id) {
+				return true;
+			}
+
+			if let Some(request) = <Self as Store>::HrmpOpenChannelRequests::take(channel_id) {
+				Self::remove_open_request_counts(channel_id, request.confirmed);
+				Self::unreserve_deposit(channel_id.sender, request.sender_deposit);
+				Self::unreserve_deposit(channel_id.recipient, request.recipient_deposit);
+			}
+
+			false
+		});
+		<Self as Store>::HrmpOpenChannelRequestsList::put(requests);
+
+		let offboarded: Vec<_> = <Self as Store>::HrmpChannels::iter()
+			.map(|(channel_id, _)| channel_id)
+			.filter(|channel_id| !is_live(channel_id))
+			.collect();
+
+		let mut offboarded_paras: Vec<_> = <Self as Store>::HrmpWatermarks::iter()
+			.map(|(para, _)| para)
+			.filter(|para| !<paras::Module<T>>::is_parachain(*para))
+			.collect();
+
+		for channel_id in offboarded {
+			Self::close_hrmp_channel(&channel_id);
+
+			for para in &[channel_id.sender, channel_id.recipient] {
+				if !<paras::Module<T>>::is_parachain(*para) {
+					offboarded_paras.push(*para);
+				}
+			}
+		}
+
+		offboarded_paras.sort();
+		offboarded_paras.dedup();
+
+		for para in offboarded_paras {
+			<Self as Store>::HrmpWatermarks::remove(&para);
+			<Self as Store>::HrmpEgressChannelsIndex::remove(&para);
+			<Self as Store>::HrmpIngressChannelsIndex::remove(&para);
+		}
+	}
+
+	/// Open the channels of all confirmed requests.
+	fn process_open_channel_requests() {
+		let mut requests = <Self as Store>::HrmpOpenChannelRequestsList::get();
+		requests.retain(|channel_id| {
+			let request = match <Self as Store>::HrmpOpenChannelRequests::get(channel_id) {
+				Some(request) => request,
+				None => return false,
+			};
+
+			if !request.confirmed {
+				return true;
+			}
+
+			<Self as Store>::HrmpOpenChannelRequests::remove(channel_id);
+			Self::remove_open_request_counts(channel_id, true);
+
+			<Self as Store>::HrmpChannels::insert(channel_id, HrmpChannel {
+				sender_deposit: request.sender_deposit,
+				recipient_deposit: request.recipient_deposit,
+				max_capacity: request.max_capacity,
+				max_message_size: request.max_message_size,
+				msg_count: 0,
+			});
+			<Self as Store>::HrmpEgressChannelsIndex::mutate(&channel_id.sender, |recipients| {
+				if let Err(i) = recipients.binary_search(&channel_id.recipient) {
+					recipients.insert(i, channel_id.recipient);
+				}
+			});
+			<Self as Store>::HrmpIngressChannelsIndex::mutate(&channel_id.recipient, |senders| {
+				if let Err(i) = senders.binary_search(&channel_id.sender) {
+					senders.insert(i, channel_id.sender);
+				}
+			});
+
+			false
+		});
+		<Self as Store>::HrmpOpenChannelRequestsList::put(requests);
+	}
+
+	/// Close the channels of all close requests.
+	fn process_close_channel_requests() {
+		for channel_id in <Self as Store>::HrmpCloseChannelRequestsList::take() {
+			<Self as Store>::HrmpCloseChannelRequests::remove(&channel_id);
+			Self::close_hrmp_channel(&channel_id);
+		}
+	}
+
+	/// Remove a channel along with its pending messages and return the deposits.
+	fn close_hrmp_channel(channel_id: &HrmpChannelId) {
+		if let Some(channel) = <Self as Store>::HrmpChannels::take(channel_id) {
+			Self::unreserve_deposit(channel_id.sender, channel.sender_deposit);
+			Self::unreserve_deposit(channel_id.recipient, channel.recipient_deposit);
+		}
+
+		<Self as Store>::HrmpChannelContents::remove(channel_id);
+		<Self as Store>::HrmpEgressChannelsIndex::mutate(&channel_id.sender, |recipients| {
+			if let Ok(i) = recipients.binary_search(&channel_id.recipient) {
+				recipients.remove(i);
+			}
+		});
+		<Self as Store>::HrmpIngressChannelsIndex::mutate(&channel_id.recipient, |senders| {
+			if let Ok(i) = senders.binary_search(&channel_id.sender) {
+				senders.remove(i);
+			}
+		});
+	}
+
+	fn remove_open_request_counts(channel_id: &HrmpChannelId, confirmed: bool) {
+		<Self as Store>::HrmpOpenChannelRequestCount::mutate(&channel_id.sender, |count| {
+			*count = count.saturating_sub(1)
+		});
+
+		if confirmed {
+			<Self as Store>::HrmpAcceptedChannelRequestCount::mutate(&channel_id.recipient, |count| {
+				*count = count.saturating_sub(1)
+			});
+		}
+	}
+
+	fn reserve_deposit(para: ParaId, amount: Balance) -> DispatchResult {
+		T::Currency::reserve(&para.into_account(), amount.unique_saturated_into())
+	}
+
+	fn unreserve_deposit(para: ParaId, amount: Balance) {
+		let _ = T::Currency::unreserve(&para.into_account(), amount.unique_saturated_into());
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use primitives::BlockNumber;
+	use frame_support::{assert_noop, assert_ok, traits::Currency};
+
+	use crate::mock::{
+		new_test_ext, Balances, Configuration, Hrmp, Origin, Paras, System, Test,
+		GenesisConfig as MockGenesisConfig,
+	};
+	use crate::paras::ParaGenesisArgs;
+
+	const PARA_A: u32 = 1000;
+	const PARA_B: u32 = 1001;
+	const PARA_C: u32 = 1002;
+
+	fn default_config() -> HostConfiguration<BlockNumber> {
+		HostConfiguration {
+			hrmp_sender_deposit: 100,
+			hrmp_recipient_deposit: 50,
+			hrmp_channel_max_capacity: 2,
+			hrmp_channel_max_message_size: 8,
+			hrmp_max_parachain_inbound_channels: 1,
+			hrmp_max_parachain_outbound_channels: 1,
+			hrmp_max_message_num_per_candidate: 2,
+			..Default::default()
+		}
+	}
+
+	fn genesis_config() -> MockGenesisConfig {
+		MockGenesisConfig {
+			paras: paras::GenesisConfig {
+				paras: vec![PARA_A, PARA_B, PARA_C].into_iter().map(|id| (id.into(), ParaGenesisArgs {
+					genesis_head: Vec::new().into(),
+					validation_code: Vec::new().into(),
+					parachain: true,
+				})).collect(),
+				..Default::default()
+			},
+			configuration: configuration::GenesisConfig {
+				config: default_config(),
+				..Default::default()
+			},
+			..Default::default()
+		}
+	}
+
+	fn para_origin(id: u32) -> Origin {
+		router::Origin::Parachain(id.into()).into()
+	}
+
+	fn fund(id: u32) {
+		Balances::make_free_balance_be(&ParaId::from(id).into_account(), 1_000);
+	}
+
+	fn reserved(id: u32) -> u128 {
+		Balances::reserved_balance(&ParaId::from(id).into_account())
+	}
+
+	fn new_session() {
+		Paras::initializer_on_new_session(&Default::default());
+		Hrmp::initializer_on_new_session(&Default::default());
+	}
+
+	fn channel_id(sender: u32, recipient: u32) -> HrmpChannelId {
+		HrmpChannelId { sender: sender.into(), recipient: recipient.into() }
+	}
+
+	fn open_channel(sender: u32, recipient: u32) {
+		assert_ok!(Hrmp::hrmp_init_open_channel(para_origin(sender), recipient.into(), 2, 8));
+		assert_ok!(Hrmp::hrmp_accept_open_channel(para_origin(recipient), sender.into()));
+		new_session();
+	}
+
+	fn message(recipient: u32, len: usize) -> OutboundHrmpMessage {
+		OutboundHrmpMessage { recipient: recipient.into(), data: vec![0; len] }
+	}
+
+	#[test]
+	fn channel_opens_at_session_change_after_acceptance() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			fund(PARA_A);
+			fund(PARA_B);
+
+			assert_ok!(Hrmp::hrmp_init_open_channel(para_origin(PARA_A), PARA_B.into(), 2, 8));
+			assert_eq!(reserved(PARA_A), 100);
+
+			// unconfirmed requests stay pending across session changes.
+			new_session();
+			assert!(<Hrmp as Store>::HrmpChannels::get(&channel_id(PARA_A, PARA_B)).is_none());
+
+			assert_ok!(Hrmp::hrmp_accept_open_channel(para_origin(PARA_B), PARA_A.into()));
+			assert_eq!(reserved(PARA_B), 50);
+			assert!(<Hrmp as Store>::HrmpChannels::get(&channel_id(PARA_A, PARA_B)).is_none());
+
+			new_session();
+			assert_eq!(
+				<Hrmp as Store>::HrmpChannels::get(&channel_id(PARA_A, PARA_B)),
+				Some(HrmpChannel {
+					sender_deposit: 100,
+					recipient_deposit: 50,
+					max_capacity: 2,
+					max_message_size: 8,
+					msg_count: 0,
+				}),
+			);
+			assert_eq!(<Hrmp as Store>::HrmpEgressChannelsIndex::get(&ParaId::from(PARA_A)), vec![PARA_B.into()]);
+			assert_eq!(<Hrmp as Store>::HrmpIngressChannelsIndex::get(&ParaId::from(PARA_B)), vec![PARA_A.into()]);
+			assert!(<Hrmp as Store>::HrmpOpenChannelRequestsList::get().is_empty());
+		});
+	}
+
+	#[test]
+	fn open_channel_requests_are_checked() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			fund(PARA_A);
+			fund(PARA_B);
+
+			assert!(Hrmp::hrmp_init_open_channel(Origin::signed(1), PARA_B.into(), 2, 8).is_err());
+			assert_noop!(
+				Hrmp::hrmp_init_open_channel(para_origin(PARA_A), PARA_A.into(), 2, 8),
+				Error::<Test>::OpenHrmpChannelToSelf,
+			);
+			assert_noop!(
+				Hrmp::hrmp_init_open_channel(para_origin(PARA_A), 2000.into(), 2, 8),
+				Error::<Test>::OpenHrmpChannelInvalidRecipient,
+			);
+			assert_noop!(
+				Hrmp::hrmp_init_open_channel(para_origin(PARA_A), PARA_B.into(), 3, 8),
+				Error::<Test>::OpenHrmpChannelInvalidCapacity,
+			);
+			assert_noop!(
+				Hrmp::hrmp_init_open_channel(para_origin(PARA_A), PARA_B.into(), 2, 0),
+				Error::<Test>::OpenHrmpChannelInvalidMessageSize,
+			);
+
+			assert_ok!(Hrmp::hrmp_init_open_channel(para_origin(PARA_A), PARA_B.into(), 2, 8));
+			assert_noop!(
+				Hrmp::hrmp_init_open_channel(para_origin(PARA_A), PARA_B.into(), 2, 8),
+				Error::<Test>::OpenHrmpChannelAlreadyRequested,
+			);
+			assert_noop!(
+				Hrmp::hrmp_init_open_channel(para_origin(PARA_A), PARA_C.into(), 2, 8),
+				Error::<Test>::OpenHrmpChannelLimitExceeded,
+			);
+
+			// without funds for the deposit, the request fails.
+			assert!(Hrmp::hrmp_init_open_channel(para_origin(PARA_C), PARA_B.into(), 2, 8).is_err());
+
+			assert_ok!(Hrmp::hrmp_accept_open_channel(para_origin(PARA_B), PARA_A.into()));
+			assert_noop!(
+				Hrmp::hrmp_accept_open_channel(para_origin(PARA_B), PARA_A.into()),
+				Error::<Test>::AcceptHrmpChannelAlreadyConfirmed,
+			);
+			assert_noop!(
+				Hrmp::hrmp_accept_open_channel(para_origin(PARA_B), PARA_C.into()),
+				Error::<Test>::AcceptHrmpChannelDoesntExist,
+			);
+
+			new_session();
+			assert_noop!(
+				Hrmp::hrmp_init_open_channel(para_origin(PARA_A), PARA_B.into(), 2, 8),
+				Error::<Test>::OpenHrmpChannelAlreadyExists,
+			);
+		});
+	}
+
+	#[test]
+	fn cancelling_open_request_returns_deposit() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			fund(PARA_A);
+			fund(PARA_B);
+
+			assert_ok!(Hrmp::hrmp_init_open_channel(para_origin(PARA_A), PARA_B.into(), 2, 8));
+			assert_eq!(reserved(PARA_A), 100);
+
+			assert_noop!(
+				Hrmp::hrmp_cancel_open_request(para_origin(PARA_C), channel_id(PARA_A, PARA_B)),
+				Error::<Test>::CancelHrmpOpenChannelUnauthorized,
+			);
+			assert_noop!(
+				Hrmp::hrmp_cancel_open_request(para_origin(PARA_B), channel_id(PARA_B, PARA_A)),
+				Error::<Test>::CancelHrmpOpenChannelDoesntExist,
+			);
+
+			assert_ok!(Hrmp::hrmp_cancel_open_request(para_origin(PARA_A), channel_id(PARA_A, PARA_B)));
+			assert_eq!(reserved(PARA_A), 0);
+			assert!(<Hrmp as Store>::HrmpOpenChannelRequests::get(&channel_id(PARA_A, PARA_B)).is_none());
+			assert!(<Hrmp as Store>::HrmpOpenChannelRequestsList::get().is_empty());
+			assert_eq!(<Hrmp as Store>::HrmpOpenChannelRequestCount::get(&ParaId::from(PARA_A)), 0);
+
+			// accepted requests can't be cancelled.
+			assert_ok!(Hrmp::hrmp_init_open_channel(para_origin(PARA_A), PARA_B.into(), 2, 8));
+			assert_ok!(Hrmp::hrmp_accept_open_channel(para_origin(PARA_B), PARA_A.into()));
+			assert_noop!(
+				Hrmp::hrmp_cancel_open_request(para_origin(PARA_B), channel_id(PARA_A, PARA_B)),
+				Error::<Test>::CancelHrmpOpenChannelAlreadyConfirmed,
+			);
+		});
+	}
+
+	#[test]
+	fn closing_channel_returns_deposits() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			fund(PARA_A);
+			fund(PARA_B);
+			open_channel(PARA_A, PARA_B);
+
+			assert_noop!(
+				Hrmp::hrmp_close_channel(para_origin(PARA_C), channel_id(PARA_A, PARA_B)),
+				Error::<Test>::CloseHrmpChannelUnauthorized,
+			);
+			assert_noop!(
+				Hrmp::hrmp_close_channel(para_origin(PARA_B), channel_id(PARA_B, PARA_A)),
+				Error::<Test>::CloseHrmpChannelDoesntExist,
+			);
+
+			assert_ok!(Hrmp::hrmp_close_channel(para_origin(PARA_B), channel_id(PARA_A, PARA_B)));
+			assert_noop!(
+				Hrmp::hrmp_close_channel(para_origin(PARA_A), channel_id(PARA_A, PARA_B)),
+				Error::<Test>::CloseHrmpChannelAlreadyUnderway,
+			);
+
+			new_session();
+			assert!(<Hrmp as Store>::HrmpChannels::get(&channel_id(PARA_A, PARA_B)).is_none());
+			assert!(<Hrmp as Store>::HrmpEgressChannelsIndex::get(&ParaId::from(PARA_A)).is_empty());
+			assert!(<Hrmp as Store>::HrmpIngressChannelsIndex::get(&ParaId::from(PARA_B)).is_empty());
+			assert_eq!(reserved(PARA_A), 0);
+			assert_eq!(reserved(PARA_B), 0);
+		});
+	}
+
+	#[test]
+	fn channels_of_offboarded_paras_are_closed() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			fund(PARA_A);
+			fund(PARA_B);
+			fund(PARA_C);
+			open_channel(PARA_A, PARA_B);
+			assert_ok!(Hrmp::hrmp_init_open_channel(para_origin(PARA_C), PARA_B.into(), 2, 8));
+			Hrmp::prune_hrmp(PARA_B.into(), 0);
+
+			Paras::schedule_para_cleanup(PARA_B.into());
+			new_session();
+
+			assert!(<Hrmp as Store>::HrmpChannels::get(&channel_id(PARA_A, PARA_B)).is_none());
+			assert!(<Hrmp as Store>::HrmpOpenChannelRequests::get(&channel_id(PARA_C, PARA_B)).is_none());
+			assert_eq!(<Hrmp as Store>::HrmpOpenChannelRequestCount::get(&ParaId::from(PARA_C)), 0);
+			assert!(<Hrmp as Store>::HrmpWatermarks::get(&ParaId::from(PARA_B)).is_none());
+			assert!(!<Hrmp as Store>::HrmpIngressChannelsIndex::contains_key(&ParaId::from(PARA_B)));
+			assert_eq!(reserved(PARA_A), 0);
+			assert_eq!(reserved(PARA_B), 0);
+			assert_eq!(reserved(PARA_C), 0);
+		});
+	}
+
+	#[test]
+	fn outbound_messages_are_checked() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			let config = Configuration::config();
+			fund(PARA_A);
+			fund(PARA_B);
+			open_channel(PARA_A, PARA_B);
+
+			let check = |messages: &[OutboundHrmpMessage]| {
+				Hrmp::check_outbound_hrmp(&config, PARA_A.into(), messages)
+			};
+
+			assert_ok!(check(&[message(PARA_B, 8)]));
+			assert_eq!(
+				check(&[message(PARA_B, 1), message(PARA_B, 1), message(PARA_B, 1)]),
+				Err(Error::<Test>::TooManyHorizontalMessages.into()),
+			);
+			assert_eq!(
+				check(&[message(PARA_B, 1), message(PARA_B, 1)]),
+				Err(Error::<Test>::HorizontalMessagesNotSorted.into()),
+			);
+			assert_eq!(
+				check(&[message(PARA_C, 1)]),
+				Err(Error::<Test>::HorizontalMessageNoChannel.into()),
+			);
+			assert_eq!(
+				check(&[message(PARA_B, 9)]),
+				Err(Error::<Test>::HorizontalMessageTooBig.into()),
+			);
+
+			Hrmp::queue_outbound_hrmp(PARA_A.into(), vec![message(PARA_B, 1)]);
+			Hrmp::queue_outbound_hrmp(PARA_A.into(), vec![message(PARA_B, 1)]);
+			assert_eq!(
+				check(&[message(PARA_B, 1)]),
+				Err(Error::<Test>::HrmpChannelCapacityExceeded.into()),
+			);
+		});
+	}
+
+	#[test]
+	fn watermarks_are_checked_and_prune_messages() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			fund(PARA_A);
+			fund(PARA_B);
+			open_channel(PARA_A, PARA_B);
+
+			System::set_block_number(1);
+			Hrmp::queue_outbound_hrmp(PARA_A.into(), vec![message(PARA_B, 1)]);
+			System::set_block_number(2);
+			Hrmp::queue_outbound_hrmp(PARA_A.into(), vec![message(PARA_B, 2)]);
+
+			assert_eq!(
+				Hrmp::inbound_hrmp_messages(PARA_B.into()),
+				vec![(PARA_A.into(), vec![
+					InboundHrmpMessage { sent_at: 1, data: vec![0; 1] },
+					InboundHrmpMessage { sent_at: 2, data: vec![0; 2] },
+				])],
+			);
+
+			assert_eq!(
+				Hrmp::check_hrmp_watermark(PARA_B.into(), 1, 2),
+				Err(Error::<Test>::HrmpWatermarkAheadOfRelayParent.into()),
+			);
+			assert_ok!(Hrmp::check_hrmp_watermark(PARA_B.into(), 1, 1));
+
+			Hrmp::prune_hrmp(PARA_B.into(), 1);
+			assert_eq!(
+				Hrmp::inbound_hrmp_messages(PARA_B.into()),
+				vec![(PARA_A.into(), vec![InboundHrmpMessage { sent_at: 2, data: vec![0; 2] }])],
+			);
+			assert_eq!(
+				<Hrmp as Store>::HrmpChannels::get(&channel_id(PARA_A, PARA_B)).map(|c| c.msg_count),
+				Some(1),
+			);
+
+			assert_eq!(
+				Hrmp::check_hrmp_watermark(PARA_B.into(), 2, 0),
+				Err(Error::<Test>::HrmpWatermarkMovedBackwards.into()),
+			);
+		});
+	}
+}
@@ -23,7 +23,7 @@
 use sp_std::prelude::*;
 use primitives::{
 	parachain::{
-		ValidatorId, AbridgedCandidateReceipt, ValidatorIndex, Id as ParaId,
+		ValidatorId, v2::AbridgedCandidateReceipt, ValidatorIndex, Id as ParaId,
 		AvailabilityBitfield as AvailabilityBitfield, SignedAvailabilityBitfields, SigningContext,
 		BackedCandidate,
 	},
@@ -38,7 +38,10 @@ use bitvec::{order::Lsb0 as BitOrderLsb0, vec::BitVec};
 use sp_staking::SessionIndex;
 use sp_runtime::{DispatchError, traits::{One, Saturating}};
 
-use crate::{configuration, paras, scheduler::{CoreIndex, GroupIndex, CoreAssignment}};
+use crate::{
+	configuration, paras, router, hrmp, validity,
+	scheduler::{CoreIndex, GroupIndex, CoreAssignment},
+};
 
 /// A bitfield signed by a validator indicating that it is keeping its piece of the erasure-coding
 /// for any backed candidates referred to by a `1` bit available.
@@ -62,13 +65,40 @@ pub struct CandidatePendingAvailability<H, N> {
 	receipt: AbridgedCandidateReceipt<H>,
 	/// The received availability votes. One bit per validator.
 	availability_votes: BitVec<BitOrderLsb0, u8>,
+	/// The indices of the validators that backed the candidate.
+	backers: Vec<ValidatorIndex>,
 	/// The block number of the relay-parent of the receipt.
 	relay_parent_number: N,
 	/// The block number of the relay-chain block this was backed in.
 	backed_in_number: N,
 }
 
-pub trait Trait: system::Trait + paras::Trait + configuration::Trait { }
+impl<H, N: Copy> CandidatePendingAvailability<H, N> {
+	/// Get the availability core this is assigned to.
+	pub(crate) fn core_occupied(&self) -> CoreIndex {
+		self.core
+	}
+
+	/// Get the candidate receipt.
+	pub(crate) fn candidate(&self) -> &AbridgedCandidateReceipt<H> {
+		&self.receipt
+	}
+
+	/// Get the received availability votes.
+	pub(crate) fn availability_votes(&self) -> &BitVec<BitOrderLsb0, u8> {
+		&self.availability_votes
+	}
+
+	/// Get the block number of the relay-chain block this was backed in.
+	pub(crate) fn backed_in_number(&self) -> N {
+		self.backed_in_number
+	}
+}
+
+pub trait Trait:
+	system::Trait + paras::Trait + configuration::Trait + router::Trait + hrmp::Trait
+	+ validity::Trait
+{ }
 
 decl_storage! {
 	trait Store for Module<T: Trait> as ParaInclusion {
@@ -261,6 +291,7 @@ impl<T: Trait> Module<T> {
 				Self::enact_candidate(
 					pending_availability.relay_parent_number,
 					pending_availability.receipt,
+					pending_availability.backers,
 				);
 
 				freed_cores.push(pending_availability.core);
@@ -269,9 +300,6 @@ impl<T: Trait> Module<T> {
 			}
 		}
 
-		// TODO: pass available candidates onwards to validity module once implemented.
-		// https://github.com/paritytech/polkadot/issues/1251
-
 		Ok(freed_cores)
 	}
 
@@ -299,9 +327,10 @@ impl<T: Trait> Module<T> {
 		let relay_parent_number = now - One::one();
 
 		// do all checks before writing storage.
-		let core_indices = {
+		let (core_indices, candidate_backers) = {
 			let mut skip = 0;
 			let mut core_indices = Vec::with_capacity(candidates.len());
+			let mut candidate_backers = Vec::with_capacity(candidates.len());
 			let mut last_core = None;
 
 			let mut check_assignment_in_order = |assignment: &CoreAssignment| -> DispatchResult {
@@ -351,6 +380,27 @@ impl<T: Trait> Module<T> {
 					candidate.candidate.check_signature().is_ok(),
 					Error::<T>::NotCollatorSigned,
 				);
+				<router::Module<T>>::check_upward_messages(
+					&config,
+					para_id,
+					&candidate.candidate.commitments.upward_messages,
+				)?;
+				<router::Module<T>>::check_processed_downward_messages(
+					para_id,
+					candidate.candidate.commitments.processed_downward_messages,
+				)?;
+				if let Some(ref hrmp) = candidate.candidate.commitments.hrmp {
+					<hrmp::Module<T>>::check_outbound_hrmp(
+						&config,
+						para_id,
+						&hrmp.horizontal_messages,
+					)?;
+					<hrmp::Module<T>>::check_hrmp_watermark(
+						para_id,
+						relay_parent_number,
+						hrmp.hrmp_watermark.into(),
+					)?;
+				}
 
 				for (i, assignment) in scheduled[skip..].iter().enumerate() {
 					check_assignment_in_order(assignment)?;
@@ -396,6 +446,13 @@ impl<T: Trait> Module<T> {
 						}
 
 						core_indices.push(assignment.core);
+						candidate_backers.push(
+							group_vals.iter()
+								.zip(candidate.validator_indices.iter())
+								.filter(|(_, backed)| **backed)
+								.map(|(val_idx, _)| *val_idx)
+								.collect::<Vec<_>>()
+						);
 						continue 'a;
 					}
 				}
@@ -414,11 +471,15 @@ impl<T: Trait> Module<T> {
 				check_assignment_in_order(assignment)?;
 			}
 
-			core_indices
+			(core_indices, candidate_backers)
 		};
 
 		// one more sweep for actually writing to storage.
-		for (candidate, core) in candidates.into_iter().zip(core_indices.iter().cloned()) {
+		let enumerated = candidates.into_iter()
+			.zip(core_indices.iter().cloned())
+			.zip(candidate_backers);
+
+		for ((candidate, core), backers) in enumerated {
 			let para_id = candidate.candidate.parachain_index;
 
 			// initialize all availability votes to 0.
@@ -428,6 +489,7 @@ impl<T: Trait> Module<T> {
 				core,
 				receipt: candidate.candidate,
 				availability_votes,
+				backers,
 				relay_parent_number,
 				backed_in_number: now,
 			});
@@ -439,7 +501,9 @@ impl<T: Trait> Module<T> {
 	fn enact_candidate(
 		relay_parent_number: T::BlockNumber,
 		receipt: AbridgedCandidateReceipt<T::Hash>,
+		backers: Vec<ValidatorIndex>,
 	) -> Weight {
+		let candidate_hash = receipt.hash();
 		let commitments = receipt.commitments;
 		let config = <configuration::Module<T>>::config();
 
@@ -453,6 +517,33 @@ impl<T: Trait> Module<T> {
 			);
 		}
 
+		weight += <router::Module<T>>::queue_upward_messages(
+			receipt.parachain_index,
+			commitments.upward_messages,
+		);
+		weight += <router::Module<T>>::prune_downward_messages(
+			receipt.parachain_index,
+			commitments.processed_downward_messages,
+		);
+		if let Some(hrmp) = commitments.hrmp {
+			weight += <hrmp::Module<T>>::prune_hrmp(
+				receipt.parachain_index,
+				hrmp.hrmp_watermark.into(),
+			);
+			weight += <hrmp::Module<T>>::queue_outbound_hrmp(
+				receipt.parachain_index,
+				hrmp.horizontal_messages,
+			);
+		}
+
+		weight += <validity::Module<T>>::note_included(
+			candidate_hash,
+			receipt.parachain_index,
+			receipt.relay_parent,
+			CurrentSessionIndex::get(),
+			backers,
+		);
+
 		weight + <paras::Module<T>>::note_new_head(
 			receipt.parachain_index,
 			receipt.head_data,
@@ -483,6 +574,19 @@ impl<T: Trait> Module<T> {
 
 		cleaned_up_cores
 	}
+
+	/// Drops the candidate pending availability for the given para, if any, returning the core
+	/// it occupied.
+	pub(crate) fn drop_pending_availability(para: ParaId) -> Option<CoreIndex> {
+		<PendingAvailability<T>>::take(&para).map(|pending| pending.core)
+	}
+
+	/// Returns the candidate pending availability for the given para, if any.
+	pub(crate) fn pending_availability(para: ParaId)
+		-> Option<CandidatePendingAvailability<T::Hash, T::BlockNumber>>
+	{
+		<PendingAvailability<T>>::get(&para)
+	}
 }
 
 const fn availability_threshold(n_validators: usize) -> usize {
@@ -498,7 +602,7 @@ mod tests {
 	use primitives::{BlockNumber, Hash};
 	use primitives::parachain::{
 		SignedAvailabilityBitfield, CompactStatement as Statement, ValidityAttestation, CollatorId,
-		CandidateCommitments, SignedStatement,
+		v2::CandidateCommitments, SignedStatement,
 	};
 	use frame_support::traits::{OnFinalize, OnInitialize};
 	use keyring::Sr25519Keyring;
@@ -687,6 +791,7 @@ mod tests {
 				core: CoreIndex::from(0),
 				receipt: Default::default(),
 				availability_votes: default_availability_votes(),
+				backers: Vec::new(),
 				relay_parent_number: 0,
 				backed_in_number: 0,
 			});
@@ -695,6 +800,7 @@ mod tests {
 				core: CoreIndex::from(1),
 				receipt: Default::default(),
 				availability_votes: default_availability_votes(),
+				backers: Vec::new(),
 				relay_parent_number: 0,
 				backed_in_number: 0,
 			});
@@ -842,6 +948,7 @@ mod tests {
 					core: CoreIndex::from(0),
 					receipt: Default::default(),
 					availability_votes: default_availability_votes(),
+					backers: Vec::new(),
 					relay_parent_number: 0,
 					backed_in_number: 0,
 				});
@@ -902,6 +1009,7 @@ mod tests {
 					..Default::default()
 				},
 				availability_votes: default_availability_votes(),
+				backers: Vec::new(),
 				relay_parent_number: 0,
 				backed_in_number: 0,
 			});
@@ -914,6 +1022,7 @@ mod tests {
 					..Default::default()
 				},
 				availability_votes: default_availability_votes(),
+				backers: Vec::new(),
 				relay_parent_number: 0,
 				backed_in_number: 0,
 			});
@@ -1269,6 +1378,7 @@ mod tests {
 					core: CoreIndex::from(0),
 					receipt: Default::default(),
 					availability_votes: default_availability_votes(),
+					backers: Vec::new(),
 					relay_parent_number: 3,
 					backed_in_number: 4,
 				});
@@ -1282,6 +1392,42 @@ mod tests {
 				<PendingAvailability<Test>>::remove(&chain_a);
 			}
 
+			// processed more downward messages than are queued - reject
+			{
+				let mut candidate = AbridgedCandidateReceipt {
+					parachain_index: chain_a,
+					relay_parent: System::parent_hash(),
+					pov_block_hash: Hash::from([1; 32]),
+					commitments: CandidateCommitments {
+						processed_downward_messages: 1,
+						..Default::default()
+					},
+					..Default::default()
+				};
+
+				collator_sign_candidate(
+					Sr25519Keyring::One,
+					&mut candidate,
+				);
+
+				let backed = back_candidate(
+					candidate,
+					&validators,
+					group_validators(GroupIndex::from(0)).unwrap().as_ref(),
+					&signing_context,
+					BackingKind::Threshold,
+				);
+
+				assert_eq!(
+					Inclusion::process_candidates(
+						vec![backed],
+						vec![chain_a_assignment.clone()],
+						&group_validators,
+					),
+					Err(router::Error::<Test>::IncorrectProcessedDownwardMessages.into()),
+				);
+			}
+
 			// interfering code upgrade - reject
 			{
 				let mut candidate = AbridgedCandidateReceipt {
@@ -1457,6 +1603,7 @@ mod tests {
 					core: CoreIndex::from(0),
 					receipt: candidate_a,
 					availability_votes: default_availability_votes(),
+					backers: vec![0, 1],
 					relay_parent_number: System::block_number() - 1,
 					backed_in_number: System::block_number(),
 				})
@@ -1468,6 +1615,7 @@ mod tests {
 					core: CoreIndex::from(1),
 					receipt: candidate_b,
 					availability_votes: default_availability_votes(),
+					backers: vec![2, 3],
 					relay_parent_number: System::block_number() - 1,
 					backed_in_number: System::block_number(),
 				})
@@ -1479,6 +1627,7 @@ mod tests {
 					core: CoreIndex::from(2),
 					receipt: candidate_c,
 					availability_votes: default_availability_votes(),
+					backers: vec![4],
 					relay_parent_number: System::block_number() - 1,
 					backed_in_number: System::block_number(),
 				})
@@ -1544,6 +1693,7 @@ mod tests {
 				core: CoreIndex::from(0),
 				receipt: Default::default(),
 				availability_votes: default_availability_votes(),
+				backers: Vec::new(),
 				relay_parent_number: 5,
 				backed_in_number: 6,
 			});
@@ -1552,6 +1702,7 @@ mod tests {
 				core: CoreIndex::from(1),
 				receipt: Default::default(),
 				availability_votes: default_availability_votes(),
+				backers: Vec::new(),
 				relay_parent_number: 6,
 				backed_in_number: 7,
 			});
@@ -24,7 +24,7 @@
 use sp_std::prelude::*;
 use primitives::{
 	inclusion_inherent,
-	parachain::{BackedCandidate, SignedAvailabilityBitfields},
+	parachain::{BackedCandidate, SignedAvailabilityBitfields, Id as ParaId},
 };
 use frame_support::{
 	decl_error, decl_module, decl_storage, ensure,
@@ -34,7 +34,7 @@ use frame_support::{
 };
 use system::ensure_none;
 use crate::{
-	inclusion,
+	inclusion, validity,
 	scheduler::{self, FreedReason},
 };
 use inherents::{InherentIdentifier, InherentData, MakeFatalError, ProvideInherent};
@@ -124,6 +124,24 @@ decl_module! {
 	}
 }
 
+impl<T: Trait> validity::OnParaReverted for Module<T> {
+	fn on_para_reverted(para_id: ParaId) -> Weight {
+		// The candidate pending availability builds on the reverted head, so it is dropped and
+		// its core freed. It was not the fault of the parathread, so it is not charged.
+		match <inclusion::Module<T>>::drop_pending_availability(para_id) {
+			Some(core) => {
+				<scheduler::Module<T>>::schedule(vec![(core, FreedReason::TimedOut)]);
+				T::DbWeight::get().reads_writes(3, 3)
+			}
+			None => T::DbWeight::get().reads_writes(1, 0),
+		}
+	}
+
+	fn max_weight() -> Weight {
+		T::DbWeight::get().reads_writes(3, 3)
+	}
+}
+
 impl<T: Trait> ProvideInherent for Module<T> {
 	type Call = Call<T>;
 	type Error = MakeFatalError<()>;
@@ -27,7 +27,7 @@ use primitives::{
 use frame_support::{
 	decl_storage, decl_module, decl_error, traits::Randomness,
 };
-use crate::{configuration::{self, HostConfiguration}, paras, scheduler, inclusion};
+use crate::{configuration::{self, HostConfiguration}, paras, scheduler, inclusion, validity, router, hrmp};
 
 /// Information about a session change that has just occurred.
 #[derive(Default, Clone)]
@@ -48,6 +48,7 @@ pub struct SessionChangeNotification<BlockNumber> {
 
 pub trait Trait:
 	system::Trait + configuration::Trait + paras::Trait + scheduler::Trait + inclusion::Trait
+	+ validity::Trait + router::Trait + hrmp::Trait
 {
 	/// A randomness beacon.
 	type Randomness: Randomness<Self::Hash>;
@@ -83,10 +84,15 @@ decl_module! {
 			// - Scheduler
 			// - Inclusion
 			// - Validity
+			// - Router
+			// - HRMP
 			let total_weight = configuration::Module::<T>::initializer_initialize(now) +
 				paras::Module::<T>::initializer_initialize(now) +
 				scheduler::Module::<T>::initializer_initialize(now) +
-				inclusion::Module::<T>::initializer_initialize(now);
+				inclusion::Module::<T>::initializer_initialize(now) +
+				validity::Module::<T>::initializer_initialize(now) +
+				router::Module::<T>::initializer_initialize(now) +
+				hrmp::Module::<T>::initializer_initialize(now);
 
 			HasInitialized::set(Some(()));
 
@@ -96,6 +102,9 @@ decl_module! {
 		fn on_finalize() {
 			// reverse initialization order.
 
+			hrmp::Module::<T>::initializer_finalize();
+			router::Module::<T>::initializer_finalize();
+			validity::Module::<T>::initializer_finalize();
 			inclusion::Module::<T>::initializer_finalize();
 			scheduler::Module::<T>::initializer_finalize();
 			paras::Module::<T>::initializer_finalize();
@@ -155,6 +164,9 @@ impl<T: Trait> Module<T> {
 		paras::Module::<T>::initializer_on_new_session(&notification);
 		scheduler::Module::<T>::initializer_on_new_session(&notification);
 		inclusion::Module::<T>::initializer_on_new_session(&notification);
+		validity::Module::<T>::initializer_on_new_session(&notification);
+		router::Module::<T>::initializer_on_new_session(&notification);
+		hrmp::Module::<T>::initializer_on_new_session(&notification);
 	}
 }
 
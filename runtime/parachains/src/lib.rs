@@ -20,13 +20,17 @@
 //! particular the `Initializer` module, as it is responsible for initializing the state
 //! of the other modules.
 
-mod configuration;
-mod inclusion;
-mod inclusion_inherent;
-mod initializer;
-mod paras;
-mod scheduler;
-mod validity;
+pub mod configuration;
+pub mod hrmp;
+pub mod inclusion;
+pub mod inclusion_inherent;
+pub mod initializer;
+pub mod paras;
+pub mod router;
+pub mod scheduler;
+pub mod validity;
+
+pub mod runtime_api_impl;
 
 #[cfg(test)]
 mod mock;
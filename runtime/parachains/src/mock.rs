@@ -21,29 +21,39 @@ use sp_core::{H256};
 use sp_runtime::{
 	Perbill,
 	traits::{
-		BlakeTwo256, IdentityLookup,
+		BlakeTwo256, IdentityLookup, ConvertInto,
 	},
 };
+use sp_staking::{
+	SessionIndex,
+	offence::{ReportOffence, OffenceError},
+};
 use primitives::{
 	BlockNumber,
 	Header,
+	parachain::{Id as ParaId, ValidatorId},
 };
 use frame_support::{
 	impl_outer_origin, impl_outer_dispatch, parameter_types,
 	weights::Weight, traits::Randomness as RandomnessT,
 };
+use std::cell::RefCell;
+use crate::{configuration, router, validity::{DisputeOffence, OnParaReverted}};
 
 /// A test runtime struct.
 #[derive(Clone, Eq, PartialEq)]
 pub struct Test;
 
 impl_outer_origin! {
-	pub enum Origin for Test { }
+	pub enum Origin for Test {
+		router,
+	}
 }
 
 impl_outer_dispatch! {
 	pub enum Call for Test where origin: Origin {
 		initializer::Initializer,
+		configuration::Configuration,
 	}
 }
 
@@ -60,6 +70,7 @@ parameter_types! {
 	pub const MaximumBlockWeight: Weight = 4 * 1024 * 1024;
 	pub const MaximumBlockLength: u32 = 4 * 1024 * 1024;
 	pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+	pub const ExistentialDeposit: u128 = 1;
 }
 
 impl system::Trait for Test {
@@ -89,6 +100,14 @@ impl system::Trait for Test {
 	type OnKilledAccount = ();
 }
 
+impl balances::Trait for Test {
+	type Balance = u128;
+	type DustRemoval = ();
+	type Event = ();
+	type ExistentialDeposit = ExistentialDeposit;
+	type AccountStore = System;
+}
+
 impl crate::initializer::Trait for Test {
 	type Randomness = TestRandomness;
 }
@@ -97,12 +116,83 @@ impl crate::configuration::Trait for Test { }
 
 impl crate::paras::Trait for Test { }
 
-impl crate::scheduler::Trait for Test { }
+impl crate::scheduler::Trait for Test {
+	type Currency = Balances;
+}
 
 impl crate::inclusion::Trait for Test { }
 
+impl crate::router::Trait for Test {
+	type Origin = Origin;
+	type Call = Call;
+}
+
+impl crate::hrmp::Trait for Test {
+	type Origin = Origin;
+	type Currency = Balances;
+}
+
+impl crate::validity::Trait for Test {
+	type Currency = Balances;
+	type OnParaReverted = TestOnParaReverted;
+	type IdentificationTuple = ValidatorId;
+	type IdentifyValidator = ConvertInto;
+	type ReportOffence = TestOffenceReporter;
+}
+
+thread_local! {
+	static OFFENCES: RefCell<Vec<(Vec<u64>, DisputeOffence<ValidatorId>)>> = RefCell::new(Vec::new());
+}
+
+/// The offences reported in the current test.
+pub fn offences() -> Vec<(Vec<u64>, DisputeOffence<ValidatorId>)> {
+	OFFENCES.with(|o| o.borrow().clone())
+}
+
+/// An offence reporter that records the reported offences.
+pub struct TestOffenceReporter;
+
+impl ReportOffence<u64, ValidatorId, DisputeOffence<ValidatorId>> for TestOffenceReporter {
+	fn report_offence(
+		reporters: Vec<u64>,
+		offence: DisputeOffence<ValidatorId>,
+	) -> Result<(), OffenceError> {
+		OFFENCES.with(|o| o.borrow_mut().push((reporters, offence)));
+		Ok(())
+	}
+
+	fn is_known_offence(_offenders: &[ValidatorId], _time_slot: &SessionIndex) -> bool {
+		false
+	}
+}
+
+thread_local! {
+	static REVERTED_PARAS: RefCell<Vec<ParaId>> = RefCell::new(Vec::new());
+}
+
+/// The paras whose heads were reverted in the current test.
+pub fn reverted_paras() -> Vec<ParaId> {
+	REVERTED_PARAS.with(|r| r.borrow().clone())
+}
+
+/// A handler of para reversions that records the reverted paras.
+pub struct TestOnParaReverted;
+
+impl OnParaReverted for TestOnParaReverted {
+	fn on_para_reverted(para_id: ParaId) -> Weight {
+		REVERTED_PARAS.with(|r| r.borrow_mut().push(para_id));
+		0
+	}
+
+	fn max_weight() -> Weight {
+		0
+	}
+}
+
 pub type System = system::Module<Test>;
 
+pub type Balances = balances::Module<Test>;
+
 /// Mocked initializer.
 pub type Initializer = crate::initializer::Module<Test>;
 
@@ -118,6 +208,15 @@ pub type Scheduler = crate::scheduler::Module<Test>;
 /// Mocked inclusion module.
 pub type Inclusion = crate::inclusion::Module<Test>;
 
+/// Mocked router.
+pub type Router = crate::router::Module<Test>;
+
+/// Mocked HRMP module.
+pub type Hrmp = crate::hrmp::Module<Test>;
+
+/// Mocked validity module.
+pub type Validity = crate::validity::Module<Test>;
+
 /// Create a new set of test externalities.
 pub fn new_test_ext(state: GenesisConfig) -> TestExternalities {
 	let mut t = state.system.build_storage::<Test>().unwrap();
@@ -25,8 +25,9 @@
 
 use sp_std::prelude::*;
 use sp_std::marker::PhantomData;
-use sp_runtime::traits::One;
+use sp_runtime::traits::{One, BlakeTwo256, Hash as HashT};
 use primitives::{
+	Hash,
 	parachain::{Id as ParaId, ValidationCode, HeadData},
 };
 use frame_support::{
@@ -197,6 +198,11 @@ decl_storage! {
 		FutureCodeUpgrades get(fn future_code_upgrade_at): map hasher(twox_64_concat) ParaId => Option<T::BlockNumber>;
 		/// The actual future code of a para.
 		FutureCode: map hasher(twox_64_concat) ParaId => Option<ValidationCode>;
+		/// All current, future and past code which is still kept on-chain, by hash.
+		CodeByHash get(fn code_by_hash): map hasher(identity) Hash => Option<ValidationCode>;
+		/// The number of entries of `CurrentCode`, `FutureCode` and `PastCode` holding the code
+		/// with a given hash.
+		CodeByHashRefs: map hasher(identity) Hash => u32;
 
 		/// Upcoming paras (chains and threads). These are only updated on session change. Corresponds to an
 		/// entry in the upcoming-genesis map.
@@ -229,6 +235,7 @@ fn build<T: Trait>(config: &GenesisConfig<T>) {
 	Parachains::put(&parachains);
 
 	for (id, genesis_args) in &config.paras {
+		<Module<T>>::increase_code_ref(&genesis_args.validation_code);
 		<Module<T> as Store>::CurrentCode::insert(&id, &genesis_args.validation_code);
 		<Module<T> as Store>::Heads::insert(&id, &genesis_args.genesis_head);
 	}
@@ -276,7 +283,9 @@ impl<T: Trait> Module<T> {
 
 			<Self as Store>::Heads::remove(&outgoing_para);
 			<Self as Store>::FutureCodeUpgrades::remove(&outgoing_para);
-			<Self as Store>::FutureCode::remove(&outgoing_para);
+			if let Some(future_code) = <Self as Store>::FutureCode::take(&outgoing_para) {
+				Self::decrease_code_ref(&future_code);
+			}
 
 			let removed_code = <Self as Store>::CurrentCode::take(&outgoing_para);
 			if let Some(removed_code) = removed_code {
@@ -305,6 +314,7 @@ impl<T: Trait> Module<T> {
 			}
 
 			<Self as Store>::Heads::insert(&upcoming_para, genesis_data.genesis_head);
+			Self::increase_code_ref(&genesis_data.validation_code);
 			<Self as Store>::CurrentCode::insert(&upcoming_para, genesis_data.validation_code);
 		}
 	}
@@ -365,7 +375,9 @@ impl<T: Trait> Module<T> {
 				for (para_id, _) in pruning_tasks_to_do {
 					let full_deactivate = <Self as Store>::PastCodeMeta::mutate(&para_id, |meta| {
 						for pruned_repl_at in meta.prune_up_to(pruning_height) {
-							<Self as Store>::PastCode::remove(&(para_id, pruned_repl_at));
+							if let Some(code) = <Self as Store>::PastCode::take(&(para_id, pruned_repl_at)) {
+								Self::decrease_code_ref(&code);
+							}
 						}
 
 						meta.most_recent_change().is_none() && Self::para_head(&para_id).is_none()
@@ -382,8 +394,8 @@ impl<T: Trait> Module<T> {
 			});
 
 		// 1 read for the meta for each pruning task, 1 read for the config
-		// 2 writes: updating the meta and pruning the code
-		T::DbWeight::get().reads_writes(1 + pruning_tasks_done, 2 * pruning_tasks_done)
+		// 4 writes: updating the meta, pruning the code and updating the code by hash.
+		T::DbWeight::get().reads_writes(1 + pruning_tasks_done, 4 * pruning_tasks_done)
 	}
 
 	/// Schedule a para to be initialized at the start of the next session.
@@ -438,12 +450,20 @@ impl<T: Trait> Module<T> {
 				T::DbWeight::get().reads_writes(1, 0)
 			} else {
 				*up = Some(expected_at);
+				Self::increase_code_ref(&new_code);
 				FutureCode::insert(&id, new_code);
-				T::DbWeight::get().reads_writes(1, 2)
+				T::DbWeight::get().reads_writes(2, 4)
 			}
 		})
 	}
 
+	/// Revert a para to a previous head, e.g. after a candidate which progressed from it has been
+	/// found invalid.
+	pub(crate) fn revert_head(id: ParaId, head: HeadData) -> Weight {
+		Heads::insert(&id, head);
+		T::DbWeight::get().writes(1)
+	}
+
 	/// Note that a para has progressed to a new head, where the new head was executed in the context
 	/// of a relay-chain block with given number. This will apply pending code upgrades based
 	/// on the block number provided.
@@ -520,11 +540,40 @@ impl<T: Trait> Module<T> {
 		}
 	}
 
+	// note that an entry of `CurrentCode`, `FutureCode` or `PastCode` now holds the given code.
+	fn increase_code_ref(code: &ValidationCode) {
+		let hash = BlakeTwo256::hash(&code.0);
+		<Self as Store>::CodeByHashRefs::mutate(&hash, |refs| {
+			if *refs == 0 {
+				<Self as Store>::CodeByHash::insert(&hash, code);
+			}
+			*refs += 1;
+		});
+	}
+
+	// note that an entry of `CurrentCode`, `FutureCode` or `PastCode` holding the given code
+	// has been removed. The code is removed once no entries hold it anymore.
+	fn decrease_code_ref(code: &ValidationCode) {
+		let hash = BlakeTwo256::hash(&code.0);
+		let refs = <Self as Store>::CodeByHashRefs::get(&hash).saturating_sub(1);
+		if refs == 0 {
+			<Self as Store>::CodeByHashRefs::remove(&hash);
+			<Self as Store>::CodeByHash::remove(&hash);
+		} else {
+			<Self as Store>::CodeByHashRefs::insert(&hash, refs);
+		}
+	}
+
 	/// Whether a para ID corresponds to any live parathread.
 	pub(crate) fn is_parathread(id: ParaId) -> bool {
 		Parathreads::get(&id).is_some()
 	}
 
+	/// Whether a para ID corresponds to any live parachain.
+	pub(crate) fn is_parachain(id: ParaId) -> bool {
+		Parachains::get().binary_search(&id).is_ok()
+	}
+
 	/// The block number of the last scheduled upgrade of the requested para. Includes future upgrades
 	/// if the flag is set. This is the `expected_at` number, not the `activated_at` number.
 	pub(crate) fn last_code_upgrade(id: ParaId, include_future: bool) -> Option<T::BlockNumber> {
@@ -931,6 +980,64 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn code_by_hash_tracks_code_until_pruned() {
+		let acceptance_period = 10;
+		let validation_upgrade_delay = 5;
+
+		let paras = vec![
+			(0u32.into(), ParaGenesisArgs {
+				parachain: true,
+				genesis_head: Default::default(),
+				validation_code: vec![1, 2, 3].into(),
+			}),
+		];
+
+		let genesis_config = MockGenesisConfig {
+			paras: GenesisConfig { paras, ..Default::default() },
+			configuration: crate::configuration::GenesisConfig {
+				config: HostConfiguration {
+					acceptance_period,
+					validation_upgrade_delay,
+					..Default::default()
+				},
+				..Default::default()
+			},
+			..Default::default()
+		};
+
+		new_test_ext(genesis_config).execute_with(|| {
+			let para_id = ParaId::from(0);
+			let old_code = ValidationCode(vec![1, 2, 3]);
+			let new_code = ValidationCode(vec![4, 5, 6]);
+			let old_hash = BlakeTwo256::hash(&old_code.0);
+			let new_hash = BlakeTwo256::hash(&new_code.0);
+
+			run_to_block(2, None);
+			assert_eq!(Paras::code_by_hash(&old_hash), Some(old_code.clone()));
+			assert!(Paras::code_by_hash(&new_hash).is_none());
+
+			let expected_at = 1 + validation_upgrade_delay;
+			Paras::schedule_code_upgrade(para_id, new_code.clone(), expected_at);
+			assert_eq!(Paras::code_by_hash(&new_hash), Some(new_code.clone()));
+
+			run_to_block(expected_at + 1, None);
+			Paras::note_new_head(para_id, Default::default(), expected_at);
+
+			// the replaced code is kept as past code until the end of the acceptance period.
+			assert_eq!(Paras::current_code(&para_id), Some(new_code.clone()));
+			assert_eq!(Paras::code_by_hash(&old_hash), Some(old_code.clone()));
+			assert_eq!(Paras::code_by_hash(&new_hash), Some(new_code.clone()));
+
+			run_to_block(expected_at + 1 + acceptance_period + 1, None);
+
+			assert!(<Paras as Store>::PastCode::get(&(para_id, expected_at)).is_none());
+			assert!(Paras::code_by_hash(&old_hash).is_none());
+			assert!(!<Paras as Store>::CodeByHashRefs::contains_key(&old_hash));
+			assert_eq!(Paras::code_by_hash(&new_hash), Some(new_code));
+		});
+	}
+
 	#[test]
 	fn submit_code_change_when_not_allowed_is_err() {
 		let acceptance_period = 10;
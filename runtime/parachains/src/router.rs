@@ -0,0 +1,799 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The router module is responsible for storing and dispatching upward messages from parachains
+//! to the relay chain, and for queueing downward messages from the relay chain to parachains.
+//!
+//! Upward messages are queued when the candidate carrying them is enacted and dispatched at the
+//! start of the next block, subject to the limits of the `HostConfiguration`. The messages of a
+//! para are dispatched in the order they were queued, while the paras are served round-robin.
+//!
+//! Downward messages are queued by root with `send_downward_message`, or by other modules of the
+//! runtime with `queue_downward_message`. They are removed from the queue of a para once one of
+//! its candidates is enacted which processed them.
+
+use sp_std::prelude::*;
+use sp_runtime::traits::{AccountIdConversion, BadOrigin, Dispatchable};
+use primitives::{
+	DownwardMessage,
+	parachain::{Id as ParaId, ParachainDispatchOrigin, UpwardMessage},
+};
+use frame_support::{
+	decl_storage, decl_module, decl_error, ensure, Parameter,
+	dispatch::DispatchResult,
+	weights::{DispatchClass, GetDispatchInfo, Weight},
+	traits::Get,
+};
+use system::ensure_root;
+use codec::{Encode, Decode};
+use crate::{
+	configuration::{self, HostConfiguration},
+	initializer::SessionChangeNotification,
+	paras,
+};
+
+/// Origin for the router module.
+#[derive(PartialEq, Eq, Clone, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Origin {
+	/// It comes from a parachain.
+	Parachain(ParaId),
+}
+
+/// Ensure that the origin `o` represents a parachain.
+/// Returns `Ok` with the parachain ID that effected the extrinsic or an `Err` otherwise.
+pub fn ensure_parachain<OuterOrigin>(o: OuterOrigin) -> Result<ParaId, BadOrigin>
+	where OuterOrigin: Into<Result<Origin, OuterOrigin>>
+{
+	match o.into() {
+		Ok(Origin::Parachain(id)) => Ok(id),
+		_ => Err(BadOrigin),
+	}
+}
+
+pub trait Trait: system::Trait + configuration::Trait + paras::Trait {
+	/// The outer origin type, which upward messages are dispatched with.
+	type Origin: From<Origin> + From<system::RawOrigin<Self::AccountId>>;
+
+	/// The outer call type, which the payloads of upward messages are decoded into.
+	type Call: Parameter + Dispatchable<Origin=<Self as Trait>::Origin> + GetDispatchInfo;
+}
+
+/// The outcome of dispatching a single upward message.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+enum DispatchOutcome {
+	/// The message was dispatched, or dropped, using the given weight.
+	Dispatched(Weight),
+	/// The message weighs more than the weight remaining in the block and stays queued.
+	OutOfWeight,
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as Router {
+		/// Messages ready to be dispatched onto the relay chain. It is subject to
+		/// `max_upward_queue_count` and `watermark_upward_queue_size` from `HostConfiguration`.
+		RelayDispatchQueues: map hasher(twox_64_concat) ParaId => Vec<UpwardMessage>;
+		/// Size of the dispatch queues. Caches sizes of the queues in `RelayDispatchQueues`.
+		/// First item in the tuple is the count of messages and second is the total length
+		/// (in bytes) of the message payloads.
+		RelayDispatchQueueSize: map hasher(twox_64_concat) ParaId => (u32, u32);
+		/// The ordered list of `ParaId`s that have a `RelayDispatchQueues` entry.
+		NeedsDispatch: Vec<ParaId>;
+		/// The para the next round of dispatching upward messages starts with, or the first para
+		/// after it in `NeedsDispatch` if it no longer has queued messages. `None` starts with the
+		/// first para in `NeedsDispatch`.
+		NextDispatchRoundStartWith: Option<ParaId>;
+		/// Messages waiting to be delivered from the relay chain into each para. It is subject to
+		/// `max_downward_queue_count` from `HostConfiguration`.
+		DownwardMessageQueues: map hasher(twox_64_concat) ParaId => Vec<DownwardMessage<T::AccountId>>;
+	}
+}
+
+decl_error! {
+	pub enum Error for Module<T: Trait> {
+		/// The upward message queue of the para is full.
+		UpwardQueueFull,
+		/// The downward message queue of the para is full.
+		DownwardQueueFull,
+		/// Upward message with the root origin sent by a non-system para.
+		InvalidMessageOrigin,
+		/// The candidate claims to have processed more downward messages than are queued.
+		IncorrectProcessedDownwardMessages,
+		/// Downward message for a para which is not registered.
+		UnknownPara,
+	}
+}
+
+decl_module! {
+	/// The router module.
+	pub struct Module<T: Trait> for enum Call where origin: <T as system::Trait>::Origin {
+		type Error = Error<T>;
+
+		/// Queue a downward message for the given para, to be processed by a later candidate of
+		/// the para.
+		///
+		/// This is the entry point for downward messages from governance; other modules of the
+		/// runtime queue them with `queue_downward_message` directly.
+		#[weight = (10_000_000 + T::DbWeight::get().reads_writes(4, 1), DispatchClass::Operational)]
+		pub fn send_downward_message(
+			origin,
+			para: ParaId,
+			message: DownwardMessage<T::AccountId>,
+		) -> DispatchResult {
+			ensure_root(origin)?;
+			ensure!(
+				<paras::Module<T>>::is_parachain(para) || <paras::Module<T>>::is_parathread(para),
+				Error::<T>::UnknownPara,
+			);
+
+			Self::queue_downward_message(&<configuration::Module<T>>::config(), para, message)
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Block initialization logic, called by initializer.
+	///
+	/// Dispatches the upward messages queued in previous blocks, returning the weight used.
+	pub(crate) fn initializer_initialize(_now: T::BlockNumber) -> Weight {
+		let config = <configuration::Module<T>>::config();
+		let dispatch_weight = Self::dispatch_upward_messages(
+			&config,
+			|para, origin, data, remaining_weight| Self::dispatch_upward_message(
+				&config,
+				para,
+				origin,
+				data,
+				remaining_weight,
+			),
+		);
+
+		dispatch_weight + T::DbWeight::get().reads(1)
+	}
+
+	/// Block finalization logic, called by initializer.
+	pub(crate) fn initializer_finalize() { }
+
+	/// Called by the initializer to note that a new session has started.
+	pub(crate) fn initializer_on_new_session(_notification: &SessionChangeNotification<T::BlockNumber>) {
+		Self::clean_up_offboarded();
+	}
+
+	/// Drop the queued upward and downward messages of paras which are no longer registered.
+	fn clean_up_offboarded() {
+		let is_live = |para: ParaId| {
+			<paras::Module<T>>::is_parachain(para) || <paras::Module<T>>::is_parathread(para)
+		};
+
+		<Self as Store>::NeedsDispatch::mutate(|needs_dispatch| needs_dispatch.retain(|para| {
+			if is_live(*para) {
+				return true;
+			}
+
+			<Self as Store>::RelayDispatchQueues::remove(para);
+			<Self as Store>::RelayDispatchQueueSize::remove(para);
+			false
+		}));
+
+		let offboarded: Vec<_> = <Self as Store>::DownwardMessageQueues::iter()
+			.map(|(para, _)| para)
+			.filter(|para| !is_live(*para))
+			.collect();
+
+		for para in offboarded {
+			<Self as Store>::DownwardMessageQueues::remove(&para);
+		}
+	}
+
+	/// Check that the upward messages of a candidate of the given para can be queued.
+	pub(crate) fn check_upward_messages(
+		config: &HostConfiguration<T::BlockNumber>,
+		para: ParaId,
+		upward_messages: &[UpwardMessage],
+	) -> DispatchResult {
+		if upward_messages.is_empty() {
+			return Ok(());
+		}
+
+		let (count, size) = <Self as Store>::RelayDispatchQueueSize::get(&para);
+		let new_count = count as usize + upward_messages.len();
+		let new_size = upward_messages.iter().fold(size as usize, |a, m| a + m.data.len());
+
+		// a single message may always be queued onto an empty queue, regardless of its size.
+		ensure!(
+			new_count == 1 || (
+				new_count <= config.max_upward_queue_count as usize &&
+					new_size <= config.watermark_upward_queue_size as usize
+			),
+			Error::<T>::UpwardQueueFull,
+		);
+
+		if !para.is_system() {
+			ensure!(
+				upward_messages.iter().all(|m| m.origin != ParachainDispatchOrigin::Root),
+				Error::<T>::InvalidMessageOrigin,
+			);
+		}
+
+		Ok(())
+	}
+
+	/// Queue the upward messages of an enacted candidate for dispatch. These should have been
+	/// checked with `check_upward_messages` beforehand.
+	pub(crate) fn queue_upward_messages(para: ParaId, upward_messages: Vec<UpwardMessage>) -> Weight {
+		if upward_messages.is_empty() {
+			return 0;
+		}
+
+		<Self as Store>::RelayDispatchQueueSize::mutate(&para, |(count, size)| {
+			*count += upward_messages.len() as u32;
+			*size += upward_messages.iter().fold(0, |a, m| a + m.data.len()) as u32;
+		});
+
+		<Self as Store>::RelayDispatchQueues::mutate(&para, |queue| queue.extend(upward_messages));
+
+		<Self as Store>::NeedsDispatch::mutate(|needs_dispatch| {
+			if let Err(i) = needs_dispatch.binary_search(&para) {
+				needs_dispatch.insert(i, para);
+			}
+		});
+
+		T::DbWeight::get().reads_writes(3, 3)
+	}
+
+	/// Queue a downward message for the given para.
+	pub fn queue_downward_message(
+		config: &HostConfiguration<T::BlockNumber>,
+		para: ParaId,
+		message: DownwardMessage<T::AccountId>,
+	) -> DispatchResult {
+		let queue_count = <Self as Store>::DownwardMessageQueues::decode_len(&para).unwrap_or(0);
+		ensure!(
+			queue_count < config.max_downward_queue_count as usize,
+			Error::<T>::DownwardQueueFull,
+		);
+
+		<Self as Store>::DownwardMessageQueues::append(&para, message);
+
+		Ok(())
+	}
+
+	/// Check that a candidate of the given para processed no more downward messages than are
+	/// queued for the para.
+	pub(crate) fn check_processed_downward_messages(para: ParaId, processed: u32) -> DispatchResult {
+		if processed == 0 {
+			return Ok(());
+		}
+
+		let queue_count = <Self as Store>::DownwardMessageQueues::decode_len(&para).unwrap_or(0);
+		ensure!(
+			processed as usize <= queue_count,
+			Error::<T>::IncorrectProcessedDownwardMessages,
+		);
+
+		Ok(())
+	}
+
+	/// Remove the downward messages a para has processed from the front of its queue. These
+	/// should have been checked with `check_processed_downward_messages` beforehand.
+	pub(crate) fn prune_downward_messages(para: ParaId, processed: u32) -> Weight {
+		if processed == 0 {
+			return 0;
+		}
+
+		<Self as Store>::DownwardMessageQueues::mutate(&para, |queue| {
+			// the count was checked, this only keeps a bad count from panicking.
+			let processed = sp_std::cmp::min(processed as usize, queue.len());
+			queue.drain(..processed);
+		});
+
+		T::DbWeight::get().reads_writes(1, 1)
+	}
+
+	/// The downward messages waiting to be processed by the given para.
+	pub fn downward_messages(para: ParaId) -> Vec<DownwardMessage<T::AccountId>> {
+		<Self as Store>::DownwardMessageQueues::get(&para)
+	}
+
+	/// Dispatch queued upward messages round-robin across paras, until the limits of the
+	/// configuration are reached, and return the weight used. Each round starts with the para the
+	/// previous round stopped at, so that no para is starved by the ones ordered before it.
+	///
+	/// At least the queue of one para is dispatched per call as far as the count and size limits
+	/// are concerned, but messages are only dispatched as long as they fit within
+	/// `max_upward_dispatch_weight`. The rest of a queue stays queued for the next round.
+	fn dispatch_upward_messages(
+		config: &HostConfiguration<T::BlockNumber>,
+		mut dispatch_message: impl FnMut(ParaId, ParachainDispatchOrigin, &[u8], Weight) -> DispatchOutcome,
+	) -> Weight {
+		let max_queue_count = config.max_upward_queue_count as usize;
+		let watermark_queue_size = config.watermark_upward_queue_size as usize;
+
+		let mut needs_dispatch = <Self as Store>::NeedsDispatch::get();
+		let start = <Self as Store>::NextDispatchRoundStartWith::get()
+			.map_or(0, |para| needs_dispatch.binary_search(&para).unwrap_or_else(|i| i));
+		let round: Vec<ParaId> = needs_dispatch[start..].iter()
+			.chain(&needs_dispatch[..start])
+			.cloned()
+			.collect();
+
+		let mut drained = Vec::new();
+		let mut next_round_start = None;
+		let mut visited_count = 0usize;
+		let mut dispatched_count = 0usize;
+		let mut dispatched_size = 0usize;
+		let mut remaining_weight = config.max_upward_dispatch_weight;
+
+		for (i, para) in round.iter().enumerate() {
+			let (count, size) = <Self as Store>::RelayDispatchQueueSize::get(para);
+			let (count, size) = (count as usize, size as usize);
+
+			if dispatched_count != 0 && (
+				dispatched_count + count > max_queue_count ||
+					dispatched_size + size > watermark_queue_size
+			) {
+				next_round_start = Some(*para);
+				break
+			}
+
+			visited_count += 1;
+
+			let mut queue = <Self as Store>::RelayDispatchQueues::get(para);
+			let mut queue_dispatched_count = 0usize;
+			let mut queue_dispatched_size = 0usize;
+			for UpwardMessage { origin, data } in &queue {
+				match dispatch_message(*para, origin.clone(), data, remaining_weight) {
+					DispatchOutcome::Dispatched(weight) => {
+						remaining_weight = remaining_weight.saturating_sub(weight);
+						queue_dispatched_count += 1;
+						queue_dispatched_size += data.len();
+					}
+					DispatchOutcome::OutOfWeight => break,
+				}
+			}
+
+			dispatched_count += queue_dispatched_count;
+			dispatched_size += queue_dispatched_size;
+
+			if queue_dispatched_count < queue.len() {
+				queue.drain(..queue_dispatched_count);
+				<Self as Store>::RelayDispatchQueues::insert(para, queue);
+				<Self as Store>::RelayDispatchQueueSize::insert(
+					para,
+					((count - queue_dispatched_count) as u32, (size - queue_dispatched_size) as u32),
+				);
+
+				next_round_start = Some(*para);
+				break
+			}
+
+			drained.push(*para);
+			<Self as Store>::RelayDispatchQueueSize::remove(para);
+			<Self as Store>::RelayDispatchQueues::remove(para);
+
+			if dispatched_count >= max_queue_count || dispatched_size >= watermark_queue_size {
+				// if the round ends here, all of its paras were drained.
+				next_round_start = round.get(i + 1).cloned();
+				break
+			}
+		}
+
+		for para in drained {
+			if let Ok(i) = needs_dispatch.binary_search(&para) {
+				needs_dispatch.remove(i);
+			}
+		}
+
+		<Self as Store>::NeedsDispatch::put(needs_dispatch);
+		match next_round_start {
+			Some(para) => <Self as Store>::NextDispatchRoundStartWith::put(para),
+			None => <Self as Store>::NextDispatchRoundStartWith::kill(),
+		}
+
+		let visited_count = visited_count as Weight;
+		(config.max_upward_dispatch_weight - remaining_weight)
+			+ T::DbWeight::get().reads_writes(2 + 2 * visited_count, 2 + 2 * visited_count)
+	}
+
+	/// Dispatch a single upward message of a para with the origin it requested, if its weight
+	/// doesn't exceed `remaining_weight`.
+	///
+	/// Messages which don't decode into a call, or which weigh more than
+	/// `max_upward_dispatch_weight` and so could never be dispatched, are dropped.
+	fn dispatch_upward_message(
+		config: &HostConfiguration<T::BlockNumber>,
+		para: ParaId,
+		origin: ParachainDispatchOrigin,
+		data: &[u8],
+		remaining_weight: Weight,
+	) -> DispatchOutcome {
+		let message_call = match <T as Trait>::Call::decode(&mut &data[..]) {
+			Ok(message_call) => message_call,
+			Err(_) => return DispatchOutcome::Dispatched(0),
+		};
+
+		let weight = message_call.get_dispatch_info().weight;
+		if weight > config.max_upward_dispatch_weight {
+			return DispatchOutcome::Dispatched(0);
+		}
+
+		if weight > remaining_weight {
+			return DispatchOutcome::OutOfWeight;
+		}
+
+		let origin: <T as Trait>::Origin = match origin {
+			ParachainDispatchOrigin::Signed =>
+				system::RawOrigin::Signed(para.into_account()).into(),
+			ParachainDispatchOrigin::Parachain =>
+				Origin::Parachain(para).into(),
+			ParachainDispatchOrigin::Root =>
+				system::RawOrigin::Root.into(),
+		};
+
+		// Not much to do with the result. It's up to the para to ensure that the message
+		// makes sense.
+		let _ = message_call.dispatch(origin);
+
+		DispatchOutcome::Dispatched(weight)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use primitives::BlockNumber;
+	use frame_support::traits::{OnFinalize, OnInitialize};
+
+	use crate::mock::{
+		new_test_ext, Configuration, Initializer, Paras, Router, Call as TestCall,
+		GenesisConfig as MockGenesisConfig,
+	};
+	use crate::paras::ParaGenesisArgs;
+
+	fn default_config() -> HostConfiguration<BlockNumber> {
+		HostConfiguration {
+			max_upward_queue_count: 3,
+			watermark_upward_queue_size: 8,
+			max_upward_dispatch_weight: 10_000,
+			max_downward_queue_count: 2,
+			..Default::default()
+		}
+	}
+
+	fn genesis_config() -> MockGenesisConfig {
+		MockGenesisConfig {
+			configuration: configuration::GenesisConfig {
+				config: default_config(),
+				..Default::default()
+			},
+			..Default::default()
+		}
+	}
+
+	fn message(origin: ParachainDispatchOrigin, data: Vec<u8>) -> UpwardMessage {
+		UpwardMessage { origin, data }
+	}
+
+	fn parachain_message(len: usize) -> UpwardMessage {
+		message(ParachainDispatchOrigin::Parachain, vec![0; len])
+	}
+
+	fn queue(para: ParaId, messages: Vec<UpwardMessage>) {
+		let config = Configuration::config();
+		assert!(Router::check_upward_messages(&config, para, &messages).is_ok());
+		Router::queue_upward_messages(para, messages);
+	}
+
+	fn set_max_upward_dispatch_weight(weight: Weight) {
+		<Configuration as configuration::Store>::Config::mutate(|config| {
+			config.max_upward_dispatch_weight = weight;
+		});
+	}
+
+	fn dispatched() -> Vec<(ParaId, Vec<u8>)> {
+		let mut dispatched = Vec::new();
+		Router::dispatch_upward_messages(
+			&Configuration::config(),
+			|para, _, data, remaining_weight| {
+				// messages weigh as much as their length.
+				let weight = data.len() as Weight;
+				if weight > remaining_weight {
+					return DispatchOutcome::OutOfWeight;
+				}
+
+				dispatched.push((para, data.to_vec()));
+				DispatchOutcome::Dispatched(weight)
+			},
+		);
+		dispatched
+	}
+
+	#[test]
+	fn upward_queue_limits_are_enforced() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			let config = Configuration::config();
+			let para = ParaId::from(1000);
+
+			// a single message is accepted onto an empty queue regardless of its size.
+			assert!(Router::check_upward_messages(&config, para, &[parachain_message(16)]).is_ok());
+
+			assert!(Router::check_upward_messages(
+				&config,
+				para,
+				&[parachain_message(1), parachain_message(1), parachain_message(1), parachain_message(1)],
+			).is_err());
+
+			assert!(Router::check_upward_messages(
+				&config,
+				para,
+				&[parachain_message(5), parachain_message(5)],
+			).is_err());
+
+			queue(para, vec![parachain_message(4), parachain_message(4)]);
+
+			assert!(Router::check_upward_messages(&config, para, &[parachain_message(1)]).is_err());
+		});
+	}
+
+	#[test]
+	fn root_messages_only_accepted_from_system_paras() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			let config = Configuration::config();
+			let root_message = message(ParachainDispatchOrigin::Root, vec![1]);
+
+			assert!(Router::check_upward_messages(
+				&config,
+				ParaId::from(1000),
+				&[root_message.clone()],
+			).is_err());
+
+			assert!(Router::check_upward_messages(&config, ParaId::from(1), &[root_message]).is_ok());
+		});
+	}
+
+	#[test]
+	fn upward_messages_dispatched_in_order_within_limits() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			let (para_a, para_b, para_c) = (ParaId::from(1), ParaId::from(2), ParaId::from(3));
+
+			queue(para_b, vec![parachain_message(3)]);
+			queue(para_a, vec![parachain_message(3), parachain_message(3)]);
+			queue(para_c, vec![parachain_message(1)]);
+
+			// `para_b` doesn't fit within the size watermark after `para_a`.
+			assert_eq!(dispatched(), vec![(para_a, vec![0; 3]), (para_a, vec![0; 3])]);
+			assert_eq!(dispatched(), vec![(para_b, vec![0; 3]), (para_c, vec![0; 1])]);
+			assert!(dispatched().is_empty());
+
+			// the queue of one para is always dispatched, even beyond the limits.
+			queue(para_a, vec![parachain_message(16)]);
+			assert_eq!(dispatched(), vec![(para_a, vec![0; 16])]);
+		});
+	}
+
+	#[test]
+	fn upward_dispatch_rounds_rotate_across_paras() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			let (para_a, para_b, para_c) = (ParaId::from(1), ParaId::from(2), ParaId::from(3));
+
+			queue(para_a, vec![parachain_message(1), parachain_message(1)]);
+			queue(para_b, vec![parachain_message(1), parachain_message(1)]);
+			queue(para_c, vec![parachain_message(1)]);
+
+			assert_eq!(dispatched(), vec![(para_a, vec![0; 1]), (para_a, vec![0; 1])]);
+			assert_eq!(<Router as Store>::NextDispatchRoundStartWith::get(), Some(para_b));
+
+			// `para_a` queues again, but has to wait for the paras after it.
+			queue(para_a, vec![parachain_message(1), parachain_message(1)]);
+			assert_eq!(
+				dispatched(),
+				vec![(para_b, vec![0; 1]), (para_b, vec![0; 1]), (para_c, vec![0; 1])],
+			);
+			assert_eq!(<Router as Store>::NextDispatchRoundStartWith::get(), Some(para_a));
+
+			assert_eq!(dispatched(), vec![(para_a, vec![0; 1]), (para_a, vec![0; 1])]);
+			assert!(<Router as Store>::NeedsDispatch::get().is_empty());
+		});
+	}
+
+	#[test]
+	fn upward_messages_dispatched_within_weight_limit() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			let (para_a, para_b) = (ParaId::from(1), ParaId::from(2));
+			set_max_upward_dispatch_weight(5);
+
+			queue(para_a, vec![parachain_message(3), parachain_message(3)]);
+			queue(para_b, vec![parachain_message(1)]);
+
+			// the second message of `para_a` stays queued for the next block.
+			assert_eq!(dispatched(), vec![(para_a, vec![0; 3])]);
+			assert_eq!(<Router as Store>::RelayDispatchQueueSize::get(&para_a), (1, 3));
+			assert_eq!(<Router as Store>::NeedsDispatch::get(), vec![para_a, para_b]);
+
+			assert_eq!(dispatched(), vec![(para_a, vec![0; 3]), (para_b, vec![0; 1])]);
+			assert!(<Router as Store>::NeedsDispatch::get().is_empty());
+		});
+	}
+
+	#[test]
+	fn upward_messages_are_weighed_before_dispatch() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			let system_para = ParaId::from(1);
+			let pending_max_code_size = ||
+				<Configuration as configuration::Store>::PendingConfig::get().map(|c| c.max_code_size);
+			let set_max_code_size = TestCall::Configuration(
+				configuration::Call::set_max_code_size(1)
+			).encode();
+			let weight = TestCall::Configuration(
+				configuration::Call::set_max_code_size(1)
+			).get_dispatch_info().weight;
+
+			let dispatch = |remaining_weight| Router::dispatch_upward_message(
+				&Configuration::config(),
+				system_para,
+				ParachainDispatchOrigin::Root,
+				&set_max_code_size,
+				remaining_weight,
+			);
+
+			assert_eq!(dispatch(weight - 1), DispatchOutcome::OutOfWeight);
+			assert!(pending_max_code_size().is_none());
+
+			// a message which could never be dispatched is dropped.
+			set_max_upward_dispatch_weight(weight - 1);
+			assert_eq!(dispatch(weight - 1), DispatchOutcome::Dispatched(0));
+			assert!(pending_max_code_size().is_none());
+
+			set_max_upward_dispatch_weight(weight);
+			assert_eq!(dispatch(weight), DispatchOutcome::Dispatched(weight));
+			assert_eq!(pending_max_code_size(), Some(1));
+		});
+	}
+
+	#[test]
+	fn upward_messages_dispatched_with_requested_origin() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			let system_para = ParaId::from(1);
+			let set_max_code_size = |size| TestCall::Configuration(
+				configuration::Call::set_max_code_size(size)
+			).encode();
+
+			queue(system_para, vec![
+				message(ParachainDispatchOrigin::Signed, set_max_code_size(1)),
+				message(ParachainDispatchOrigin::Root, set_max_code_size(2)),
+			]);
+
+			Initializer::on_initialize(1);
+			Initializer::on_finalize(1);
+
+			// only the call dispatched as root succeeded.
+			assert_eq!(
+				<Configuration as configuration::Store>::PendingConfig::get().map(|c| c.max_code_size),
+				Some(2),
+			);
+		});
+	}
+
+	#[test]
+	fn downward_queue_limits_are_enforced_and_pruned() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			let config = Configuration::config();
+			let para = ParaId::from(1000);
+
+			for i in 0..2u8 {
+				assert!(Router::queue_downward_message(
+					&config,
+					para,
+					DownwardMessage::Opaque(vec![i]),
+				).is_ok());
+			}
+
+			assert!(Router::queue_downward_message(
+				&config,
+				para,
+				DownwardMessage::Opaque(vec![2]),
+			).is_err());
+
+			assert!(Router::check_processed_downward_messages(para, 2).is_ok());
+			assert!(Router::check_processed_downward_messages(para, 3).is_err());
+
+			Router::prune_downward_messages(para, 1);
+			assert_eq!(Router::downward_messages(para), vec![DownwardMessage::Opaque(vec![1])]);
+
+			assert!(Router::check_processed_downward_messages(para, 2).is_err());
+		});
+	}
+
+	#[test]
+	fn downward_messages_are_sent_by_root_to_registered_paras() {
+		let (para, unknown_para) = (ParaId::from(1000), ParaId::from(1001));
+		let genesis_config = MockGenesisConfig {
+			paras: paras::GenesisConfig {
+				paras: vec![(para, ParaGenesisArgs {
+					genesis_head: Vec::new().into(),
+					validation_code: Vec::new().into(),
+					parachain: true,
+				})],
+				..Default::default()
+			},
+			..genesis_config()
+		};
+
+		new_test_ext(genesis_config).execute_with(|| {
+			let message = DownwardMessage::Opaque(vec![1]);
+
+			assert!(Router::send_downward_message(
+				system::RawOrigin::Signed(1).into(),
+				para,
+				message.clone(),
+			).is_err());
+			assert!(Router::send_downward_message(
+				system::RawOrigin::Root.into(),
+				unknown_para,
+				message.clone(),
+			).is_err());
+			assert!(Router::send_downward_message(
+				system::RawOrigin::Root.into(),
+				para,
+				message.clone(),
+			).is_ok());
+
+			assert_eq!(Router::downward_messages(para), vec![message]);
+			assert!(Router::downward_messages(unknown_para).is_empty());
+		});
+	}
+
+	#[test]
+	fn messages_of_offboarded_paras_are_dropped() {
+		let (para_a, para_b) = (ParaId::from(1000), ParaId::from(1001));
+		let genesis_config = MockGenesisConfig {
+			paras: paras::GenesisConfig {
+				paras: vec![para_a, para_b].into_iter().map(|id| (id, ParaGenesisArgs {
+					genesis_head: Vec::new().into(),
+					validation_code: Vec::new().into(),
+					parachain: true,
+				})).collect(),
+				..Default::default()
+			},
+			..genesis_config()
+		};
+
+		new_test_ext(genesis_config).execute_with(|| {
+			let config = Configuration::config();
+
+			for para in &[para_a, para_b] {
+				queue(*para, vec![parachain_message(1)]);
+				assert!(Router::queue_downward_message(
+					&config,
+					*para,
+					DownwardMessage::Opaque(vec![1]),
+				).is_ok());
+			}
+
+			Paras::schedule_para_cleanup(para_a);
+			Paras::initializer_on_new_session(&Default::default());
+			Router::initializer_on_new_session(&Default::default());
+
+			assert_eq!(<Router as Store>::NeedsDispatch::get(), vec![para_b]);
+			assert!(<Router as Store>::RelayDispatchQueues::get(&para_a).is_empty());
+			assert_eq!(<Router as Store>::RelayDispatchQueueSize::get(&para_a), (0, 0));
+			assert!(Router::downward_messages(para_a).is_empty());
+
+			assert_eq!(<Router as Store>::RelayDispatchQueueSize::get(&para_b), (1, 1));
+			assert_eq!(Router::downward_messages(para_b), vec![DownwardMessage::Opaque(vec![1])]);
+		});
+	}
+}
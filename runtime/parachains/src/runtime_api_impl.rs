@@ -0,0 +1,282 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implementations of the `ParachainHostV2` runtime API methods, on top of the parachains
+//! runtime modules.
+//!
+//! A runtime including the parachains modules can implement each of the runtime API methods by
+//! calling the function of the same name.
+
+use sp_std::prelude::*;
+use sp_runtime::traits::{One, Saturating};
+use primitives::{
+	Hash,
+	parachain::{
+		Id as ParaId, ValidatorId, ValidatorIndex, ValidationCode, CoreAssignment, CoreIndex,
+		CoreState, OccupiedCore, ScheduledCore, GroupRotationInfo, HostConfiguration,
+		PersistedValidationData, TransientValidationData,
+	},
+};
+use crate::{initializer, configuration, paras, scheduler, inclusion, router};
+
+/// Implementation for the `validators` function of the runtime API.
+pub fn validators<T: initializer::Trait>() -> Vec<ValidatorId> {
+	<inclusion::Module<T>>::validators()
+}
+
+/// Implementation for the `validator_groups` function of the runtime API.
+pub fn validator_groups<T: initializer::Trait>()
+	-> (Vec<Vec<ValidatorIndex>>, GroupRotationInfo<T::BlockNumber>)
+{
+	let next_block = <system::Module<T>>::block_number() + One::one();
+
+	(
+		<scheduler::Module<T>>::validator_groups(),
+		<scheduler::Module<T>>::group_rotation_info(next_block),
+	)
+}
+
+/// Implementation for the `availability_cores` function of the runtime API.
+pub fn availability_cores<T: initializer::Trait>() -> Vec<CoreState<T::Hash, T::BlockNumber>> {
+	let now = schedule_next_block::<T>();
+	let scheduled = <scheduler::Module<T>>::scheduled();
+
+	<scheduler::Module<T>>::availability_core_paras().into_iter().enumerate().map(|(i, para)| {
+		let core = CoreIndex(i as u32);
+
+		let pending = para.and_then(|para_id| {
+			<inclusion::Module<T>>::pending_availability(para_id).map(|pending| (para_id, pending))
+		});
+
+		if let Some((para_id, pending)) = pending {
+			let group_responsible = <scheduler::Module<T>>::group_assigned_to_core(
+				pending.core_occupied(),
+				now,
+			).unwrap_or_default();
+
+			return CoreState::Occupied(OccupiedCore {
+				para_id,
+				group_responsible,
+				occupied_since: pending.backed_in_number(),
+				availability: pending.availability_votes().clone(),
+				candidate: pending.candidate().clone(),
+			});
+		}
+
+		match scheduled.binary_search_by_key(&core, |assignment| assignment.core) {
+			Ok(pos) => CoreState::Scheduled(ScheduledCore {
+				para_id: scheduled[pos].para_id,
+				collator: scheduled[pos].required_collator().cloned(),
+			}),
+			Err(_) => CoreState::Free,
+		}
+	}).collect()
+}
+
+/// Implementation for the `scheduled_cores` function of the runtime API.
+pub fn scheduled_cores<T: initializer::Trait>() -> Vec<CoreAssignment> {
+	schedule_next_block::<T>();
+	<scheduler::Module<T>>::scheduled()
+}
+
+// The scheduled cores are wiped on finalization of each block, so this schedules the free cores
+// as the initialization of the next block would, and returns the number of the next block.
+// The state changes made while answering a runtime API call are discarded.
+fn schedule_next_block<T: initializer::Trait>() -> T::BlockNumber {
+	let next_block = <system::Module<T>>::block_number() + One::one();
+	<system::Module<T>>::set_block_number(next_block);
+	<scheduler::Module<T>>::schedule(Vec::new());
+
+	next_block
+}
+
+/// Implementation for the `persisted_validation_data` function of the runtime API.
+pub fn persisted_validation_data<T: initializer::Trait>(
+	para_id: ParaId,
+) -> Option<PersistedValidationData<T::BlockNumber>> {
+	<paras::Module<T>>::para_head(&para_id).map(|parent_head| PersistedValidationData {
+		parent_head,
+		block_number: <system::Module<T>>::block_number(),
+	})
+}
+
+/// Implementation for the `transient_validation_data` function of the runtime API.
+pub fn transient_validation_data<T: initializer::Trait>(
+	para_id: ParaId,
+) -> Option<TransientValidationData<T::BlockNumber>> {
+	if <paras::Module<T>>::para_head(&para_id).is_none() {
+		return None;
+	}
+
+	let config = <configuration::Module<T>>::config();
+
+	// candidates built on this block have it as their relay-parent.
+	let relay_parent_number = <system::Module<T>>::block_number();
+	let code_upgrade_allowed = <paras::Module<T>>::last_code_upgrade(para_id, true).map_or(
+		true,
+		|last| last <= relay_parent_number &&
+			relay_parent_number.saturating_sub(last) >= config.validation_upgrade_frequency,
+	);
+
+	Some(TransientValidationData {
+		max_code_size: config.max_code_size,
+		max_head_data_size: config.max_head_data_size,
+		code_upgrade_allowed: if code_upgrade_allowed {
+			Some(relay_parent_number + config.validation_upgrade_delay)
+		} else {
+			None
+		},
+		dmq_length: <router::Module<T>>::downward_messages(para_id).len() as u32,
+	})
+}
+
+/// Implementation for the `host_configuration` function of the runtime API.
+pub fn host_configuration<T: initializer::Trait>() -> HostConfiguration<T::BlockNumber> {
+	<configuration::Module<T>>::config()
+}
+
+/// Implementation for the `validation_code` function of the runtime API.
+pub fn validation_code<T: initializer::Trait>(para_id: ParaId) -> Option<ValidationCode> {
+	<paras::Module<T>>::current_code(&para_id)
+}
+
+/// Implementation for the `validation_code_by_hash` function of the runtime API.
+pub fn validation_code_by_hash<T: initializer::Trait>(hash: Hash) -> Option<ValidationCode> {
+	<paras::Module<T>>::code_by_hash(&hash)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use primitives::parachain::{AssignmentKind, GroupIndex};
+	use keyring::Sr25519Keyring;
+
+	use crate::initializer::SessionChangeNotification;
+	use crate::paras::ParaGenesisArgs;
+	use crate::mock::{
+		new_test_ext, Configuration, Scheduler, System, Test,
+		GenesisConfig as MockGenesisConfig,
+	};
+
+	fn genesis_config() -> MockGenesisConfig {
+		MockGenesisConfig {
+			paras: paras::GenesisConfig {
+				paras: vec![
+					(ParaId::from(1), ParaGenesisArgs {
+						genesis_head: vec![1].into(),
+						validation_code: vec![1, 2, 3].into(),
+						parachain: true,
+					}),
+					(ParaId::from(2), ParaGenesisArgs {
+						genesis_head: vec![2].into(),
+						validation_code: vec![4, 5, 6].into(),
+						parachain: true,
+					}),
+				],
+				..Default::default()
+			},
+			configuration: configuration::GenesisConfig {
+				config: HostConfiguration {
+					parachain_rotation_frequency: 10,
+					validation_upgrade_delay: 5,
+					max_code_size: 100,
+					max_head_data_size: 10,
+					..Default::default()
+				},
+				..Default::default()
+			},
+			..Default::default()
+		}
+	}
+
+	fn start_session() {
+		let validators: Vec<ValidatorId> = [
+			Sr25519Keyring::Alice,
+			Sr25519Keyring::Bob,
+			Sr25519Keyring::Charlie,
+			Sr25519Keyring::Dave,
+		].iter().map(|v| v.public().into()).collect();
+
+		System::set_block_number(1);
+		Scheduler::initializer_on_new_session(&SessionChangeNotification {
+			validators,
+			new_config: Configuration::config(),
+			..Default::default()
+		});
+	}
+
+	#[test]
+	fn free_cores_are_scheduled_for_the_next_block() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			start_session();
+
+			let (groups, rotation_info) = validator_groups::<Test>();
+			assert_eq!(groups.len(), 2);
+			assert_eq!(rotation_info.session_start_block, 1);
+			assert_eq!(rotation_info.now, 2);
+			assert_eq!(rotation_info.group_for_core(CoreIndex(1), groups.len()), GroupIndex(1));
+
+			assert_eq!(availability_cores::<Test>(), vec![
+				CoreState::Scheduled(ScheduledCore { para_id: ParaId::from(1), collator: None }),
+				CoreState::Scheduled(ScheduledCore { para_id: ParaId::from(2), collator: None }),
+			]);
+
+			let scheduled = scheduled_cores::<Test>();
+			assert_eq!(scheduled.len(), 2);
+			assert_eq!(scheduled[0].para_id, ParaId::from(1));
+			assert_eq!(scheduled[0].kind, AssignmentKind::Parachain);
+			assert_eq!(scheduled[1].core, CoreIndex(1));
+		});
+	}
+
+	#[test]
+	fn validation_data_is_exposed_for_registered_paras() {
+		new_test_ext(genesis_config()).execute_with(|| {
+			System::set_block_number(7);
+
+			assert_eq!(
+				persisted_validation_data::<Test>(ParaId::from(1)),
+				Some(PersistedValidationData { parent_head: vec![1].into(), block_number: 7 }),
+			);
+			assert_eq!(
+				transient_validation_data::<Test>(ParaId::from(2)),
+				Some(TransientValidationData {
+					max_code_size: 100,
+					max_head_data_size: 10,
+					code_upgrade_allowed: Some(12),
+					dmq_length: 0,
+				}),
+			);
+
+			assert!(persisted_validation_data::<Test>(ParaId::from(3)).is_none());
+			assert!(transient_validation_data::<Test>(ParaId::from(3)).is_none());
+		});
+	}
+
+	#[test]
+	fn validation_code_is_exposed_by_para_and_hash() {
+		use sp_runtime::traits::{BlakeTwo256, Hash as HashT};
+
+		new_test_ext(genesis_config()).execute_with(|| {
+			let code = ValidationCode(vec![4, 5, 6]);
+
+			assert_eq!(validation_code::<Test>(ParaId::from(2)), Some(code.clone()));
+			assert_eq!(validation_code_by_hash::<Test>(BlakeTwo256::hash(&code.0)), Some(code));
+			assert!(validation_code::<Test>(ParaId::from(3)).is_none());
+			assert!(validation_code_by_hash::<Test>(Hash::default()).is_none());
+		});
+	}
+}
@@ -38,41 +38,27 @@
 use sp_std::prelude::*;
 use sp_std::convert::TryInto;
 use primitives::{
+	Balance,
 	parachain::{Id as ParaId, CollatorId, ValidatorIndex},
 };
 use frame_support::{
-	decl_storage, decl_module, decl_error,
+	decl_storage, decl_module, decl_error, ensure,
+	dispatch::DispatchResult,
 	weights::Weight,
+	traits::ReservableCurrency,
 };
 use codec::{Encode, Decode};
-use sp_runtime::traits::{Saturating, Zero};
+use sp_runtime::traits::{Saturating, UniqueSaturatedInto, Zero};
+use system::ensure_signed;
 
 use rand::{SeedableRng, seq::SliceRandom};
 use rand_chacha::ChaCha20Rng;
 
 use crate::{configuration, paras, initializer::SessionChangeNotification};
 
-/// The unique (during session) index of a core.
-#[derive(Encode, Decode, Default, PartialOrd, Ord, Eq, PartialEq, Clone, Copy)]
-#[cfg_attr(test, derive(Debug))]
-pub struct CoreIndex(u32);
-
-impl From<u32> for CoreIndex {
-	fn from(i: u32) -> CoreIndex {
-		CoreIndex(i)
-	}
-}
-
-/// The unique (during session) index of a validator group.
-#[derive(Encode, Decode, Default, Clone, Copy)]
-#[cfg_attr(test, derive(PartialEq, Debug))]
-pub struct GroupIndex(u32);
-
-impl From<u32> for GroupIndex {
-	fn from(i: u32) -> GroupIndex {
-		GroupIndex(i)
-	}
-}
+pub use primitives::parachain::{
+	CoreIndex, GroupIndex, AssignmentKind, CoreAssignment, GroupRotationInfo,
+};
 
 /// A claim on authoring the next block for a given parathread.
 #[derive(Clone, Encode, Decode, Default)]
@@ -133,43 +119,13 @@ pub(crate) enum CoreOccupied {
 	Parachain,
 }
 
-/// The assignment type.
-#[derive(Clone, Encode, Decode)]
-#[cfg_attr(test, derive(PartialEq, Debug))]
-pub enum AssignmentKind {
-	Parachain,
-	Parathread(CollatorId, u32),
-}
-
-/// How a free core is scheduled to be assigned.
-#[derive(Clone, Encode, Decode)]
-#[cfg_attr(test, derive(PartialEq, Debug))]
-pub struct CoreAssignment {
-	/// The core that is assigned.
-	pub core: CoreIndex,
-	/// The unique ID of the para that is assigned to the core.
-	pub para_id: ParaId,
-	/// The kind of the assignment.
-	pub kind: AssignmentKind,
-	/// The index of the validator group assigned to the core.
-	pub group_idx: GroupIndex,
-}
-
-impl CoreAssignment {
-	/// Get the ID of a collator who is required to collate this block.
-	pub(crate) fn required_collator(&self) -> Option<&CollatorId> {
-		match self.kind {
-			AssignmentKind::Parachain => None,
-			AssignmentKind::Parathread(ref id, _) => Some(id),
-		}
-	}
-
-	fn to_core_occupied(&self) -> CoreOccupied {
-		match self.kind {
+impl CoreOccupied {
+	fn from_assignment(assignment: &CoreAssignment) -> Self {
+		match assignment.kind {
 			AssignmentKind::Parachain => CoreOccupied::Parachain,
 			AssignmentKind::Parathread(ref collator, retries) => CoreOccupied::Parathread(
 				ParathreadEntry {
-					claim: ParathreadClaim(self.para_id, collator.clone()),
+					claim: ParathreadClaim(assignment.para_id, collator.clone()),
 					retries,
 				}
 			),
@@ -185,7 +141,10 @@ pub enum FreedReason {
 	TimedOut,
 }
 
-pub trait Trait: system::Trait + configuration::Trait + paras::Trait { }
+pub trait Trait: system::Trait + configuration::Trait + paras::Trait {
+	/// The currency that parathread claim fees are paid in.
+	type Currency: ReservableCurrency<Self::AccountId>;
+}
 
 decl_storage! {
 	trait Store for Module<T: Trait> as ParaScheduler {
@@ -193,7 +152,7 @@ decl_storage! {
 		///
 		/// Bound: The number of cores is the sum of the numbers of parachains and parathread multiplexers.
 		/// Reasonably, 100-1000. The dominant factor is the number of validators: safe upper bound at 10k.
-		ValidatorGroups: Vec<Vec<ValidatorIndex>>;
+		ValidatorGroups get(fn validator_groups): Vec<Vec<ValidatorIndex>>;
 
 		/// A queue of upcoming claims and which core they should be mapped onto.
 		///
@@ -212,8 +171,11 @@ decl_storage! {
 		///
 		/// Bounded by the number of parathread cores and scheduling lookahead. Reasonably, 10 * 50 = 500.
 		ParathreadClaimIndex: Vec<ParaId>;
+		/// The account which paid for the claim on each parathread in `ParathreadClaimIndex`, if
+		/// the claim was bought with `claim_parathread`, along with the fee reserved from it.
+		ParathreadClaimFees: map hasher(twox_64_concat) ParaId => Option<(T::AccountId, Balance)>;
 		/// The block number where the session start occurred. Used to track how many group rotations have occurred.
-		SessionStartBlock: T::BlockNumber;
+		SessionStartBlock get(fn session_start_block): T::BlockNumber;
 		/// Currently scheduled cores - free but up to be occupied. Ephemeral storage item that's wiped on finalization.
 		///
 		/// Bounded by the number of cores: one for each parachain and parathread multiplexer.
@@ -222,13 +184,51 @@ decl_storage! {
 }
 
 decl_error! {
-	pub enum Error for Module<T: Trait> { }
+	pub enum Error for Module<T: Trait> {
+		/// The para is not a live parathread.
+		ClaimNotParathread,
+		/// There are no parathread cores.
+		NoParathreadCores,
+		/// The parathread claim queue is full.
+		ParathreadQueueFull,
+		/// There is already a claim on the parathread in the queue or occupying a core.
+		ParathreadAlreadyClaimed,
+		/// The current fee for a parathread claim exceeds the maximum fee of the bid.
+		ParathreadClaimFeeTooHigh,
+	}
 }
 
 decl_module! {
 	/// The scheduler module.
 	pub struct Module<T: Trait> for enum Call where origin: <T as system::Trait>::Origin {
 		type Error = Error<T>;
+
+		/// Claim the next block of a parathread for the given collator, bidding up to `max_fee`.
+		///
+		/// The current fee of a claim is reserved from the origin. It is charged once a candidate
+		/// of the claim is included, and refunded if the claim is dropped without one.
+		#[weight = 100_000]
+		pub fn claim_parathread(
+			origin,
+			para_id: ParaId,
+			collator: CollatorId,
+			max_fee: Balance,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+
+			let fee = Self::parathread_claim_fee();
+			ensure!(fee <= max_fee, Error::<T>::ParathreadClaimFeeTooHigh);
+
+			T::Currency::reserve(&who, fee.unique_saturated_into())?;
+			if let Err(e) = Self::try_add_parathread_claim(ParathreadClaim(para_id, collator)) {
+				let _ = T::Currency::unreserve(&who, fee.unique_saturated_into());
+				return Err(e.into());
+			}
+
+			<ParathreadClaimFees<T>>::insert(&para_id, (who, fee));
+
+			Ok(())
+		}
 	}
 }
 
@@ -243,6 +243,7 @@ impl<T: Trait> Module<T> {
 	/// Called by the initializer to finalize the scheduler module.
 	pub(crate) fn initializer_finalize() {
 		// Free all scheduled cores and return parathread claims to queue, with retries incremented.
+		// Claims beyond retries are dropped and their fees refunded.
 		let config = <configuration::Module<T>>::config();
 		ParathreadQueue::mutate(|queue| {
 			for core_assignment in Scheduled::take() {
@@ -254,11 +255,17 @@ impl<T: Trait> Module<T> {
 
 					if entry.retries <= config.parathread_retries {
 						queue.enqueue_entry(entry, config.parathread_cores);
+					} else {
+						ParathreadClaimIndex::mutate(|index| {
+							if let Ok(i) = index.binary_search(&core_assignment.para_id) {
+								index.remove(i);
+							}
+						});
+						Self::refund_claim_fee(core_assignment.para_id);
 					}
 				}
 			}
 		})
-
 	}
 
 	/// Called by the initializer to note that a new session has started.
@@ -329,7 +336,9 @@ impl<T: Trait> Module<T> {
 					queue: Vec::new(),
 					next_core_offset: 0,
 				};
-				claim_index.clear();
+				for para_id in claim_index.drain(..) {
+					Self::refund_claim_fee(para_id);
+				}
 				return;
 			}
 
@@ -345,6 +354,8 @@ impl<T: Trait> Module<T> {
 					if let Ok(i) = claim_index.binary_search(&claim_para) {
 						claim_index.remove(i);
 					}
+
+					Self::refund_claim_fee(claim_para);
 				}
 
 				will_keep
@@ -367,35 +378,70 @@ impl<T: Trait> Module<T> {
 	/// assigned to a core, this call will fail. This call will also fail if the queue is full.
 	///
 	/// Fails if the claim does not correspond to any live parathread.
-	#[allow(unused)]
 	pub fn add_parathread_claim(claim: ParathreadClaim) {
-		if !<paras::Module<T>>::is_parathread(claim.0) { return }
+		let _ = Self::try_add_parathread_claim(claim);
+	}
+
+	fn try_add_parathread_claim(claim: ParathreadClaim) -> Result<(), Error<T>> {
+		ensure!(<paras::Module<T>>::is_parathread(claim.0), Error::<T>::ClaimNotParathread);
 
 		let config = <configuration::Module<T>>::config();
+		ensure!(config.parathread_cores > 0, Error::<T>::NoParathreadCores);
+
 		let queue_max_size = config.parathread_cores * config.scheduling_lookahead;
 
-		ParathreadQueue::mutate(|queue| {
-			if queue.queue.len() >= queue_max_size as usize { return }
+		ensure!(
+			ParathreadQueue::get().queue.len() < queue_max_size as usize,
+			Error::<T>::ParathreadQueueFull,
+		);
 
-			let para_id = claim.0;
+		let para_id = claim.0;
 
-			let competes_with_another = ParathreadClaimIndex::mutate(|index| {
-				match index.binary_search(&para_id) {
-					Ok(_) => true,
-					Err(i) => {
-						index.insert(i, para_id);
-						false
-					}
+		let competes_with_another = ParathreadClaimIndex::mutate(|index| {
+			match index.binary_search(&para_id) {
+				Ok(_) => true,
+				Err(i) => {
+					index.insert(i, para_id);
+					false
 				}
-			});
+			}
+		});
+
+		ensure!(!competes_with_another, Error::<T>::ParathreadAlreadyClaimed);
+
+		let entry = ParathreadEntry { claim, retries: 0 };
+		ParathreadQueue::mutate(|queue| queue.enqueue_entry(entry, config.parathread_cores));
+
+		Ok(())
+	}
 
-			if competes_with_another { return }
+	/// The current fee of a parathread claim.
+	///
+	/// This is `config.parathread_base_fee` when the claim queue is empty, and grows by
+	/// `config.parathread_fee_growth` compounded for each claim already in the queue.
+	pub fn parathread_claim_fee() -> Balance {
+		let config = <configuration::Module<T>>::config();
+		let queue_len = ParathreadQueue::get().queue.len();
 
-			let entry = ParathreadEntry { claim, retries: 0 };
-			queue.enqueue_entry(entry, config.parathread_cores);
+		(0..queue_len).fold(config.parathread_base_fee, |fee, _| {
+			fee.saturating_add(config.parathread_fee_growth * fee)
 		})
 	}
 
+	// Charge the fee reserved for the claim on the given parathread, if any.
+	fn charge_claim_fee(para_id: ParaId) {
+		if let Some((who, fee)) = <ParathreadClaimFees<T>>::take(&para_id) {
+			let _ = T::Currency::slash_reserved(&who, fee.unique_saturated_into());
+		}
+	}
+
+	// Refund the fee reserved for the claim on the given parathread, if any.
+	fn refund_claim_fee(para_id: ParaId) {
+		if let Some((who, fee)) = <ParathreadClaimFees<T>>::take(&para_id) {
+			let _ = T::Currency::unreserve(&who, fee.unique_saturated_into());
+		}
+	}
+
 	/// Schedule all unassigned cores, where possible. Provide a list of cores that should be considered
 	/// newly-freed along with the reason for them being freed. The list is assumed to be sorted in
 	/// ascending order by core index.
@@ -417,7 +463,8 @@ impl<T: Trait> Module<T> {
 									if let Ok(i) = index.binary_search(&entry.claim.0) {
 										index.remove(i);
 									}
-								})
+								});
+								Self::charge_claim_fee(entry.claim.0);
 							}
 							FreedReason::TimedOut => {
 								// If a parathread candidate times out, it's not the collator's fault,
@@ -553,7 +600,7 @@ impl<T: Trait> Module<T> {
 					// of the occupied iterator.
 					let _ = occupied_iter.next();
 
-					availability_cores[assignment.core.0 as usize] = Some(assignment.to_core_occupied());
+					availability_cores[assignment.core.0 as usize] = Some(CoreOccupied::from_assignment(assignment));
 				}
 
 				retain
@@ -577,6 +624,27 @@ impl<T: Trait> Module<T> {
 		}
 	}
 
+	/// Get the para (chain or thread) ID occupying each availability core, or `None` for free cores.
+	pub(crate) fn availability_core_paras() -> Vec<Option<ParaId>> {
+		let parachains = <paras::Module<T>>::parachains();
+
+		AvailabilityCores::get().into_iter().enumerate().map(|(i, core)| match core {
+			None => None,
+			Some(CoreOccupied::Parachain) => parachains.get(i).cloned(),
+			Some(CoreOccupied::Parathread(entry)) => Some(entry.claim.0),
+		}).collect()
+	}
+
+	/// Get the information needed to determine the group assigned to each core at the given
+	/// block number.
+	pub(crate) fn group_rotation_info(now: T::BlockNumber) -> GroupRotationInfo<T::BlockNumber> {
+		GroupRotationInfo {
+			session_start_block: <SessionStartBlock<T>>::get(),
+			group_rotation_frequency: <configuration::Module<T>>::config().parachain_rotation_frequency,
+			now,
+		}
+	}
+
 	/// Get the validators in the given group, if the group index is valid for this session.
 	pub(crate) fn group_validators(group_index: GroupIndex) -> Option<Vec<ValidatorIndex>> {
 		ValidatorGroups::get().get(group_index.0 as usize).map(|g| g.clone())
@@ -671,10 +739,14 @@ mod tests {
 	use super::*;
 
 	use primitives::{BlockNumber, parachain::ValidatorId};
-	use frame_support::traits::{OnFinalize, OnInitialize};
+	use frame_support::{assert_noop, assert_ok, traits::{OnFinalize, OnInitialize}};
+	use sp_runtime::Perbill;
 	use keyring::Sr25519Keyring;
 
-	use crate::mock::{new_test_ext, Configuration, Paras, System, Scheduler, GenesisConfig as MockGenesisConfig};
+	use crate::mock::{
+		new_test_ext, Balances, Configuration, Origin, Paras, System, Scheduler, Test,
+		GenesisConfig as MockGenesisConfig,
+	};
 	use crate::initializer::SessionChangeNotification;
 	use crate::configuration::HostConfiguration;
 	use crate::paras::ParaGenesisArgs;
@@ -1526,4 +1598,146 @@ mod tests {
 			assert!(Scheduler::availability_timeout_predicate().is_none());
 		});
 	}
+
+	fn fee_config() -> HostConfiguration<BlockNumber> {
+		HostConfiguration {
+			parathread_base_fee: 100,
+			parathread_fee_growth: Perbill::from_percent(10),
+			..default_config()
+		}
+	}
+
+	fn start_fee_session(threads: &[ParaId]) {
+		for &id in threads {
+			Paras::schedule_para_initialize(id, ParaGenesisArgs {
+				genesis_head: Vec::new().into(),
+				validation_code: Vec::new().into(),
+				parachain: false,
+			});
+		}
+
+		run_to_block(1, |number| match number {
+			1 => Some(SessionChangeNotification {
+				new_config: fee_config(),
+				validators: vec![
+					ValidatorId::from(Sr25519Keyring::Alice.public()),
+					ValidatorId::from(Sr25519Keyring::Bob.public()),
+					ValidatorId::from(Sr25519Keyring::Charlie.public()),
+				],
+				..Default::default()
+			}),
+			_ => None,
+		});
+
+		for account in 1..=3 {
+			Balances::make_free_balance_be(&account, 1_000);
+		}
+	}
+
+	#[test]
+	fn claim_fee_grows_with_queue_length() {
+		let genesis_config = MockGenesisConfig {
+			configuration: crate::configuration::GenesisConfig {
+				config: fee_config(),
+				..Default::default()
+			},
+			..Default::default()
+		};
+
+		let thread_a = ParaId::from(1);
+		let thread_b = ParaId::from(2);
+		let thread_c = ParaId::from(3);
+		let collator = CollatorId::from(Sr25519Keyring::Alice.public());
+
+		new_test_ext(genesis_config).execute_with(|| {
+			start_fee_session(&[thread_a, thread_b, thread_c]);
+
+			assert_eq!(Scheduler::parathread_claim_fee(), 100);
+			assert_ok!(Scheduler::claim_parathread(Origin::signed(1), thread_a, collator.clone(), 100));
+			assert_eq!(Balances::reserved_balance(&1), 100);
+
+			assert_eq!(Scheduler::parathread_claim_fee(), 110);
+			assert_noop!(
+				Scheduler::claim_parathread(Origin::signed(2), thread_b, collator.clone(), 109),
+				Error::<Test>::ParathreadClaimFeeTooHigh,
+			);
+			assert_ok!(Scheduler::claim_parathread(Origin::signed(2), thread_b, collator.clone(), 110));
+			assert_eq!(Balances::reserved_balance(&2), 110);
+
+			assert_eq!(Scheduler::parathread_claim_fee(), 121);
+
+			// a competing claim is rejected and leaves nothing reserved.
+			assert_eq!(
+				Scheduler::claim_parathread(Origin::signed(3), thread_a, collator.clone(), 1_000),
+				Err(Error::<Test>::ParathreadAlreadyClaimed.into()),
+			);
+			assert_eq!(Balances::reserved_balance(&3), 0);
+		});
+	}
+
+	#[test]
+	fn claim_fee_is_charged_on_conclusion() {
+		let genesis_config = MockGenesisConfig {
+			configuration: crate::configuration::GenesisConfig {
+				config: fee_config(),
+				..Default::default()
+			},
+			..Default::default()
+		};
+
+		let thread_a = ParaId::from(1);
+		let collator = CollatorId::from(Sr25519Keyring::Alice.public());
+
+		new_test_ext(genesis_config).execute_with(|| {
+			start_fee_session(&[thread_a]);
+
+			assert_ok!(Scheduler::claim_parathread(Origin::signed(1), thread_a, collator.clone(), 100));
+
+			run_to_block(2, |_| None);
+			assert_eq!(Scheduler::scheduled().len(), 1);
+			let core = Scheduler::scheduled()[0].core;
+
+			Scheduler::occupied(&[core]);
+			Scheduler::schedule(vec![(core, FreedReason::Concluded)]);
+
+			assert_eq!(Balances::reserved_balance(&1), 0);
+			assert_eq!(Balances::free_balance(&1), 900);
+			assert!(<ParathreadClaimFees<Test>>::get(&thread_a).is_none());
+		});
+	}
+
+	#[test]
+	fn claim_fee_is_refunded_after_retries() {
+		let max_retries = fee_config().parathread_retries;
+
+		let genesis_config = MockGenesisConfig {
+			configuration: crate::configuration::GenesisConfig {
+				config: fee_config(),
+				..Default::default()
+			},
+			..Default::default()
+		};
+
+		let thread_a = ParaId::from(1);
+		let collator = CollatorId::from(Sr25519Keyring::Alice.public());
+
+		new_test_ext(genesis_config).execute_with(|| {
+			start_fee_session(&[thread_a]);
+
+			assert_ok!(Scheduler::claim_parathread(Origin::signed(1), thread_a, collator.clone(), 100));
+
+			run_to_block(2 + max_retries, |_| None);
+			assert_eq!(Scheduler::scheduled().len(), 1);
+			assert_eq!(Balances::reserved_balance(&1), 100);
+
+			run_to_block(2 + max_retries + 1, |_| None);
+			assert!(Scheduler::scheduled().is_empty());
+			assert_eq!(Balances::reserved_balance(&1), 0);
+			assert_eq!(Balances::free_balance(&1), 1_000);
+
+			// the parathread is open for claims again.
+			assert!(ParathreadClaimIndex::get().is_empty());
+			assert_ok!(Scheduler::claim_parathread(Origin::signed(1), thread_a, collator.clone(), 100));
+		});
+	}
 }
@@ -13,3 +13,799 @@
 
 // You should have received a copy of the GNU General Public License
 // along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The validity module is responsible for local disputes on the validity of included candidates.
+//!
+//! Candidates are tracked from their inclusion until the end of the acceptance period. Within
+//! that period, validators of the session the candidate was included in may dispute it. Once
+//! a supermajority of the validator set agrees either way, the dispute concludes, the losing
+//! side is reported for an offence and, if the candidate was invalid, the para head is reverted.
+//! Disputes which haven't concluded by the time the validators of the session of the candidate
+//! are forgotten expire, and the deposit of the reporter who started them is returned.
+
+use sp_std::prelude::*;
+use sp_runtime::{Perbill, RuntimeDebug, traits::{Convert, UniqueSaturatedInto}};
+use sp_staking::{
+	SessionIndex,
+	offence::{Kind, Offence, ReportOffence},
+};
+use primitives::{
+	Balance, Hash,
+	parachain::{
+		Id as ParaId, ValidatorId, ValidatorIndex, HeadData, SigningContext, SignedStatement,
+		CompactStatement,
+	},
+};
+use frame_support::{
+	decl_storage, decl_module, decl_error, ensure, Parameter,
+	dispatch::DispatchResult,
+	weights::Weight,
+	traits::{Get, ReservableCurrency},
+};
+use codec::{Encode, Decode};
+use system::ensure_signed;
+use crate::{configuration, paras, initializer::SessionChangeNotification};
+
+/// A candidate which has been included and may be disputed.
+#[derive(Encode, Decode)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct IncludedCandidate<H, N> {
+	/// The para the candidate was included for.
+	para_id: ParaId,
+	/// The relay-parent of the candidate, which dispute statements are signed in the context of.
+	relay_parent: H,
+	/// The block number the candidate was included at.
+	included_at: N,
+	/// The session the candidate was included in. Only validators of that session may vote.
+	session_index: SessionIndex,
+	/// The head of the para before the candidate was included.
+	prev_head: HeadData,
+	/// The indices of the validators that backed the candidate.
+	backers: Vec<ValidatorIndex>,
+}
+
+/// The votes cast in a dispute, as sorted validator indices.
+#[derive(Encode, Decode, Default)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct DisputeVotes {
+	/// Validators that consider the candidate valid, including the backers.
+	valid: Vec<ValidatorIndex>,
+	/// Validators that consider the candidate invalid.
+	invalid: Vec<ValidatorIndex>,
+}
+
+impl DisputeVotes {
+	fn has_voted(&self, validator_index: ValidatorIndex) -> bool {
+		self.valid.binary_search(&validator_index).is_ok()
+			|| self.invalid.binary_search(&validator_index).is_ok()
+	}
+
+	fn insert_vote(votes: &mut Vec<ValidatorIndex>, validator_index: ValidatorIndex) {
+		if let Err(i) = votes.binary_search(&validator_index) {
+			votes.insert(i, validator_index);
+		}
+	}
+}
+
+/// An offence that is filed against the validators on the losing side of a dispute.
+#[derive(RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Clone, PartialEq, Eq))]
+pub struct DisputeOffence<Offender> {
+	/// The session index in which the disputed candidate was included.
+	pub session_index: SessionIndex,
+	/// The size of the validator set in that session.
+	pub validator_set_count: u32,
+	/// The validators on the losing side of the dispute.
+	pub offenders: Vec<Offender>,
+}
+
+impl<Offender: Clone> Offence<Offender> for DisputeOffence<Offender> {
+	const ID: Kind = *b"para:bad-dispute";
+	type TimeSlot = SessionIndex;
+
+	fn offenders(&self) -> Vec<Offender> {
+		self.offenders.clone()
+	}
+
+	fn session_index(&self) -> SessionIndex {
+		self.session_index
+	}
+
+	fn validator_set_count(&self) -> u32 {
+		self.validator_set_count
+	}
+
+	fn time_slot(&self) -> Self::TimeSlot {
+		self.session_index
+	}
+
+	fn slash_fraction(_offenders_count: u32, _validator_set_count: u32) -> Perbill {
+		// Slash 100%.
+		Perbill::from_percent(100)
+	}
+}
+
+/// Handles the reversion of the head of a para by a concluded dispute.
+pub trait OnParaReverted {
+	/// Called after the head of the para has been reverted to the head before an invalid
+	/// candidate.
+	fn on_para_reverted(para_id: ParaId) -> Weight;
+
+	/// An upper bound on the weight of `on_para_reverted`, charged up-front by disputes.
+	fn max_weight() -> Weight;
+}
+
+impl OnParaReverted for () {
+	fn on_para_reverted(_para_id: ParaId) -> Weight { 0 }
+
+	fn max_weight() -> Weight { 0 }
+}
+
+pub trait Trait: system::Trait + configuration::Trait + paras::Trait {
+	/// The currency that dispute deposits are reserved in.
+	type Currency: ReservableCurrency<Self::AccountId>;
+
+	/// Cleans up the work of a para built on top of a reverted head.
+	type OnParaReverted: OnParaReverted;
+
+	/// The identification of a validator, as used in offence reports.
+	type IdentificationTuple: Parameter;
+
+	/// Identifies the validator behind a parachain validator key, if it can be found.
+	type IdentifyValidator: Convert<ValidatorId, Option<Self::IdentificationTuple>>;
+
+	/// Where the offences of validators on the losing side of a dispute are reported to.
+	type ReportOffence: ReportOffence<
+		Self::AccountId,
+		Self::IdentificationTuple,
+		DisputeOffence<Self::IdentificationTuple>,
+	>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as ParaValidity {
+		/// Candidates that are within their acceptance period or under dispute.
+		IncludedCandidates: map hasher(twox_64_concat) Hash => Option<IncludedCandidate<T::Hash, T::BlockNumber>>;
+		/// The hashes of included candidates along with the block number they were included at,
+		/// in order of inclusion.
+		IncludedCandidatesList: Vec<(T::BlockNumber, Hash)>;
+		/// The disputes in progress.
+		Disputes: map hasher(twox_64_concat) Hash => Option<DisputeVotes>;
+		/// The reporters who started the disputes in progress and the deposits they reserved.
+		DisputeDeposits: map hasher(twox_64_concat) Hash => Option<(T::AccountId, Balance)>;
+		/// The validators of the current and previous session.
+		SessionValidators: map hasher(twox_64_concat) SessionIndex => Option<Vec<ValidatorId>>;
+	}
+}
+
+decl_error! {
+	pub enum Error for Module<T: Trait> {
+		/// The candidate is not included or has left its acceptance period.
+		CandidateNotIncluded,
+		/// The acceptance period of the candidate has elapsed.
+		AcceptancePeriodElapsed,
+		/// The validators of the session the candidate was included in are no longer known.
+		SessionValidatorsUnknown,
+		/// A statement is not a validity vote on the disputed candidate.
+		StatementForWrongCandidate,
+		/// A statement is signed by a validator index out of bounds.
+		ValidatorIndexOutOfBounds,
+		/// A statement has an invalid signature.
+		InvalidStatementSignature,
+		/// A validator has already voted in the dispute.
+		DuplicateVote,
+		/// A dispute can only be started by a vote against the validity of the candidate.
+		DisputeRequiresInvalidVote,
+		/// There are more statements than validators in the session of the candidate.
+		TooManyStatements,
+		/// The witness of the number of included candidates is lower than the actual number.
+		IncludedCandidatesWitnessTooLow,
+	}
+}
+
+decl_module! {
+	/// The validity module.
+	pub struct Module<T: Trait> for enum Call where origin: <T as system::Trait>::Origin {
+		type Error = Error<T>;
+
+		/// Submit signed validity votes on an included candidate, starting a dispute if there is
+		/// none in progress. Starting a dispute reserves `config.dispute_deposit` from the
+		/// reporter, which is returned if the candidate turns out to be invalid.
+		///
+		/// `included_candidates` must be at least the length of `IncludedCandidatesList`, which
+		/// is scanned if the candidate is found invalid and reverted.
+		#[weight = 100_000
+			+ 50_000 * statements.len() as Weight
+			+ Module::<T>::revert_candidate_weight(*included_candidates)]
+		pub fn report_dispute(
+			origin,
+			candidate_hash: Hash,
+			statements: Vec<SignedStatement>,
+			included_candidates: u32,
+		) -> DispatchResult {
+			let reporter = ensure_signed(origin)?;
+			ensure!(
+				<Self as Store>::IncludedCandidatesList::get().len() <= included_candidates as usize,
+				Error::<T>::IncludedCandidatesWitnessTooLow,
+			);
+			Self::process_dispute_statements(reporter, candidate_hash, statements)
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Block initialization logic, called by initializer.
+	pub(crate) fn initializer_initialize(now: T::BlockNumber) -> Weight {
+		Self::prune_accepted(now)
+	}
+
+	/// Block finalization logic, called by initializer.
+	pub(crate) fn initializer_finalize() { }
+
+	/// Called by the initializer to note that a new session has started.
+	pub(crate) fn initializer_on_new_session(notification: &SessionChangeNotification<T::BlockNumber>) {
+		let session_index = notification.session_index;
+
+		<Self as Store>::SessionValidators::insert(&session_index, &notification.validators);
+		if let Some(outdated) = session_index.checked_sub(2) {
+			<Self as Store>::SessionValidators::remove(&outdated);
+			Self::expire_disputes(outdated);
+		}
+	}
+
+	/// Note that a candidate has been included. The candidate may be disputed until the end of
+	/// the acceptance period. This must be called before the new head of the para is noted.
+	pub(crate) fn note_included(
+		candidate_hash: Hash,
+		para_id: ParaId,
+		relay_parent: T::Hash,
+		session_index: SessionIndex,
+		backers: Vec<ValidatorIndex>,
+	) -> Weight {
+		let now = <system::Module<T>>::block_number();
+		let prev_head = <paras::Module<T>>::para_head(&para_id).unwrap_or_default();
+
+		<Self as Store>::IncludedCandidates::insert(&candidate_hash, IncludedCandidate {
+			para_id,
+			relay_parent,
+			included_at: now,
+			session_index,
+			prev_head,
+			backers,
+		});
+		<Self as Store>::IncludedCandidatesList::append((now, candidate_hash));
+
+		T::DbWeight::get().reads_writes(1, 2)
+	}
+
+	/// Stop tracking the candidates whose acceptance period has elapsed, unless they are
+	/// under dispute. Disputed candidates are tracked until the dispute concludes or expires.
+	fn prune_accepted(now: T::BlockNumber) -> Weight {
+		let acceptance_period = <configuration::Module<T>>::config().acceptance_period;
+		let mut included = <Self as Store>::IncludedCandidatesList::get();
+
+		let accepted = included.iter()
+			.take_while(|(included_at, _)| *included_at + acceptance_period < now)
+			.count();
+
+		for (_, candidate_hash) in included.drain(..accepted) {
+			if !<Self as Store>::Disputes::contains_key(&candidate_hash) {
+				<Self as Store>::IncludedCandidates::remove(&candidate_hash);
+			}
+		}
+
+		if accepted != 0 {
+			<Self as Store>::IncludedCandidatesList::put(included);
+		}
+
+		T::DbWeight::get().reads_writes(2 + accepted as u64, 1 + accepted as u64)
+	}
+
+	fn process_dispute_statements(
+		reporter: T::AccountId,
+		candidate_hash: Hash,
+		statements: Vec<SignedStatement>,
+	) -> DispatchResult {
+		let candidate = <Self as Store>::IncludedCandidates::get(&candidate_hash)
+			.ok_or(Error::<T>::CandidateNotIncluded)?;
+		let validators = <Self as Store>::SessionValidators::get(&candidate.session_index)
+			.ok_or(Error::<T>::SessionValidatorsUnknown)?;
+
+		ensure!(statements.len() <= validators.len(), Error::<T>::TooManyStatements);
+
+		let existing_votes = <Self as Store>::Disputes::get(&candidate_hash);
+		let is_new_dispute = existing_votes.is_none();
+		let mut votes = match existing_votes {
+			Some(votes) => votes,
+			None => {
+				let acceptance_period = <configuration::Module<T>>::config().acceptance_period;
+				let now = <system::Module<T>>::block_number();
+				ensure!(
+					now <= candidate.included_at + acceptance_period,
+					Error::<T>::AcceptancePeriodElapsed
+				);
+				ensure!(
+					statements.iter().any(|s| s.payload() == &CompactStatement::Invalid(candidate_hash)),
+					Error::<T>::DisputeRequiresInvalidVote
+				);
+
+				let mut valid = candidate.backers.clone();
+				valid.sort_unstable();
+				valid.dedup();
+				DisputeVotes { valid, invalid: Vec::new() }
+			}
+		};
+
+		let signing_context = SigningContext {
+			session_index: candidate.session_index,
+			parent_hash: candidate.relay_parent,
+		};
+
+		for statement in &statements {
+			let is_valid = match statement.payload() {
+				CompactStatement::Valid(h) if h == &candidate_hash => true,
+				CompactStatement::Invalid(h) if h == &candidate_hash => false,
+				_ => return Err(Error::<T>::StatementForWrongCandidate.into()),
+			};
+
+			let validator_index = statement.validator_index();
+			let validator = validators.get(validator_index as usize)
+				.ok_or(Error::<T>::ValidatorIndexOutOfBounds)?;
+
+			statement.check_signature(&signing_context, validator)
+				.map_err(|_| Error::<T>::InvalidStatementSignature)?;
+
+			ensure!(!votes.has_voted(validator_index), Error::<T>::DuplicateVote);
+
+			if is_valid {
+				DisputeVotes::insert_vote(&mut votes.valid, validator_index);
+			} else {
+				DisputeVotes::insert_vote(&mut votes.invalid, validator_index);
+			}
+		}
+
+		if is_new_dispute {
+			let deposit = <configuration::Module<T>>::config().dispute_deposit;
+			T::Currency::reserve(&reporter, deposit.unique_saturated_into())?;
+			<Self as Store>::DisputeDeposits::insert(&candidate_hash, (reporter, deposit));
+		}
+
+		let threshold = supermajority_threshold(validators.len());
+		if votes.valid.len() >= threshold {
+			let reporter = Self::release_deposit(&candidate_hash, false);
+			Self::conclude_dispute(reporter, candidate_hash, candidate, votes.invalid, &validators);
+		} else if votes.invalid.len() >= threshold {
+			let reporter = Self::release_deposit(&candidate_hash, true);
+			Self::revert_candidate(&candidate);
+			Self::conclude_dispute(reporter, candidate_hash, candidate, votes.valid, &validators);
+		} else {
+			<Self as Store>::Disputes::insert(&candidate_hash, votes);
+		}
+
+		Ok(())
+	}
+
+	/// Stop tracking a concluded dispute and report the validators on the losing side, with the
+	/// reporter who started the dispute as the reporter of the offence.
+	fn conclude_dispute(
+		reporter: Option<T::AccountId>,
+		candidate_hash: Hash,
+		candidate: IncludedCandidate<T::Hash, T::BlockNumber>,
+		losers: Vec<ValidatorIndex>,
+		validators: &[ValidatorId],
+	) {
+		<Self as Store>::Disputes::remove(&candidate_hash);
+		<Self as Store>::IncludedCandidates::remove(&candidate_hash);
+
+		let offenders: Vec<_> = losers.into_iter()
+			.filter_map(|i| validators.get(i as usize))
+			.filter_map(|v| T::IdentifyValidator::convert(v.clone()))
+			.collect();
+
+		if offenders.is_empty() {
+			return;
+		}
+
+		let offence = DisputeOffence {
+			session_index: candidate.session_index,
+			validator_set_count: validators.len() as u32,
+			offenders,
+		};
+
+		// The offence may already be known if the validators were reported on another fork.
+		let _ = T::ReportOffence::report_offence(reporter.into_iter().collect(), offence);
+	}
+
+	/// Expire the disputes on candidates of the given session, which can't conclude anymore as
+	/// the validators of the session are no longer known. The reporters who started them are
+	/// not at fault, so their deposits are returned.
+	fn expire_disputes(session_index: SessionIndex) {
+		let expired: Vec<Hash> = <Self as Store>::Disputes::iter()
+			.map(|(candidate_hash, _)| candidate_hash)
+			.filter(|candidate_hash| <Self as Store>::IncludedCandidates::get(candidate_hash)
+				.map_or(true, |c| c.session_index <= session_index)
+			)
+			.collect();
+
+		for candidate_hash in expired {
+			<Self as Store>::Disputes::remove(&candidate_hash);
+			<Self as Store>::IncludedCandidates::remove(&candidate_hash);
+			Self::release_deposit(&candidate_hash, true);
+		}
+	}
+
+	/// Return the deposit of the reporter who started the dispute on the candidate, or slash it
+	/// if the dispute was frivolous. Returns the reporter, if the dispute had one.
+	fn release_deposit(candidate_hash: &Hash, refund: bool) -> Option<T::AccountId> {
+		let (reporter, deposit) = <Self as Store>::DisputeDeposits::take(candidate_hash)?;
+		if refund {
+			let _ = T::Currency::unreserve(&reporter, deposit.unique_saturated_into());
+		} else {
+			let _ = T::Currency::slash_reserved(&reporter, deposit.unique_saturated_into());
+		}
+
+		Some(reporter)
+	}
+
+	/// The worst-case weight of `revert_candidate` with the given number of candidates in
+	/// `IncludedCandidatesList`, all of which are descendants of the reverted one.
+	fn revert_candidate_weight(included_candidates: u32) -> Weight {
+		let n = included_candidates as u64;
+		// Reading the list and reverting the head. Each descendant is read and removed along
+		// with its dispute and deposit, and the deposit is unreserved.
+		T::DbWeight::get().reads_writes(1 + 3 * n, 1 + 4 * n)
+			+ T::OnParaReverted::max_weight()
+	}
+
+	/// Revert the para of an invalid candidate to the head before its inclusion, drop the work
+	/// built on top of it and stop tracking the candidates of the para which were included
+	/// after it. Disputes on those candidates are moot, so their deposits are returned.
+	fn revert_candidate(candidate: &IncludedCandidate<T::Hash, T::BlockNumber>) {
+		<paras::Module<T>>::revert_head(candidate.para_id, candidate.prev_head.clone());
+		T::OnParaReverted::on_para_reverted(candidate.para_id);
+
+		for (included_at, candidate_hash) in <Self as Store>::IncludedCandidatesList::get() {
+			if included_at <= candidate.included_at {
+				continue;
+			}
+
+			let is_descendant = <Self as Store>::IncludedCandidates::get(&candidate_hash)
+				.map_or(false, |c| c.para_id == candidate.para_id);
+
+			if is_descendant {
+				<Self as Store>::IncludedCandidates::remove(&candidate_hash);
+				<Self as Store>::Disputes::remove(&candidate_hash);
+				Self::release_deposit(&candidate_hash, true);
+			}
+		}
+	}
+}
+
+/// The number of votes needed for a supermajority (more than 2/3) of the given number of
+/// validators.
+fn supermajority_threshold(n_validators: usize) -> usize {
+	n_validators - n_validators.saturating_sub(1) / 3
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use primitives::BlockNumber;
+	use keyring::Sr25519Keyring;
+	use frame_support::traits::Currency;
+
+	use crate::configuration::HostConfiguration;
+	use crate::paras::ParaGenesisArgs;
+	use crate::mock::{
+		new_test_ext, offences, reverted_paras, Balances, Paras, System, Validity, Origin, Test,
+		GenesisConfig as MockGenesisConfig,
+	};
+
+	const SESSION: SessionIndex = 1;
+
+	fn validators() -> Vec<Sr25519Keyring> {
+		vec![
+			Sr25519Keyring::Alice,
+			Sr25519Keyring::Bob,
+			Sr25519Keyring::Charlie,
+			Sr25519Keyring::Dave,
+		]
+	}
+
+	fn genesis_config(para_id: ParaId) -> MockGenesisConfig {
+		MockGenesisConfig {
+			paras: paras::GenesisConfig {
+				paras: vec![(para_id, ParaGenesisArgs {
+					genesis_head: vec![1].into(),
+					validation_code: Vec::new().into(),
+					parachain: true,
+				})],
+				..Default::default()
+			},
+			configuration: configuration::GenesisConfig {
+				config: HostConfiguration {
+					acceptance_period: 5,
+					dispute_deposit: 10,
+					..Default::default()
+				},
+				..Default::default()
+			},
+			..Default::default()
+		}
+	}
+
+	fn start_session() {
+		new_session(SESSION);
+		Balances::make_free_balance_be(&1, 100);
+	}
+
+	fn new_session(session_index: SessionIndex) {
+		Validity::initializer_on_new_session(&SessionChangeNotification {
+			validators: validators().iter().map(|v| v.public().into()).collect(),
+			session_index,
+			..Default::default()
+		});
+	}
+
+	fn include_candidate(para_id: ParaId, candidate_hash: Hash, backers: Vec<ValidatorIndex>) {
+		Validity::note_included(candidate_hash, para_id, Default::default(), SESSION, backers);
+		Paras::note_new_head(para_id, vec![2].into(), System::block_number());
+	}
+
+	fn statement(statement: CompactStatement, validator_index: ValidatorIndex) -> SignedStatement {
+		let context = SigningContext { session_index: SESSION, parent_hash: Hash::default() };
+		let key = validators()[validator_index as usize];
+		SignedStatement::sign(statement, &context, validator_index, &key.pair().into())
+	}
+
+	fn dispute(candidate_hash: Hash, statements: Vec<SignedStatement>) -> DispatchResult {
+		dispute_by(1, candidate_hash, statements)
+	}
+
+	fn dispute_by(
+		reporter: u64,
+		candidate_hash: Hash,
+		statements: Vec<SignedStatement>,
+	) -> DispatchResult {
+		let included_candidates = <Validity as Store>::IncludedCandidatesList::get().len() as u32;
+		Validity::report_dispute(
+			Origin::signed(reporter),
+			candidate_hash,
+			statements,
+			included_candidates,
+		)
+	}
+
+	fn public(validator_index: usize) -> ValidatorId {
+		validators()[validator_index].public().into()
+	}
+
+	#[test]
+	fn supermajority_threshold_is_more_than_two_thirds() {
+		assert_eq!(supermajority_threshold(1), 1);
+		assert_eq!(supermajority_threshold(3), 3);
+		assert_eq!(supermajority_threshold(4), 3);
+		assert_eq!(supermajority_threshold(5), 4);
+		assert_eq!(supermajority_threshold(10), 7);
+	}
+
+	#[test]
+	fn invalid_candidate_reverts_head_and_reports_backers() {
+		let para_id = ParaId::from(1);
+		let candidate_hash = Hash::from([1; 32]);
+
+		new_test_ext(genesis_config(para_id)).execute_with(|| {
+			start_session();
+			include_candidate(para_id, candidate_hash, vec![0]);
+
+			assert!(dispute(candidate_hash, vec![
+				statement(CompactStatement::Invalid(candidate_hash), 1),
+				statement(CompactStatement::Invalid(candidate_hash), 2),
+			]).is_ok());
+
+			// no supermajority yet.
+			assert!(<Validity as Store>::Disputes::get(&candidate_hash).is_some());
+			assert_eq!(Paras::para_head(&para_id), Some(vec![2].into()));
+
+			assert!(dispute(candidate_hash, vec![
+				statement(CompactStatement::Invalid(candidate_hash), 3),
+			]).is_ok());
+
+			assert!(<Validity as Store>::Disputes::get(&candidate_hash).is_none());
+			assert!(<Validity as Store>::IncludedCandidates::get(&candidate_hash).is_none());
+			assert_eq!(Paras::para_head(&para_id), Some(vec![1].into()));
+			assert_eq!(reverted_paras(), vec![para_id]);
+			assert_eq!(Balances::reserved_balance(&1), 0);
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert_eq!(offences(), vec![(vec![1], DisputeOffence {
+				session_index: SESSION,
+				validator_set_count: 4,
+				offenders: vec![public(0)],
+			})]);
+		});
+	}
+
+	#[test]
+	fn valid_candidate_reports_disputers() {
+		let para_id = ParaId::from(1);
+		let candidate_hash = Hash::from([1; 32]);
+
+		new_test_ext(genesis_config(para_id)).execute_with(|| {
+			start_session();
+			include_candidate(para_id, candidate_hash, vec![0]);
+
+			assert!(dispute(candidate_hash, vec![
+				statement(CompactStatement::Invalid(candidate_hash), 1),
+				statement(CompactStatement::Valid(candidate_hash), 2),
+				statement(CompactStatement::Valid(candidate_hash), 3),
+			]).is_ok());
+
+			assert!(<Validity as Store>::Disputes::get(&candidate_hash).is_none());
+			assert_eq!(Paras::para_head(&para_id), Some(vec![2].into()));
+			assert!(reverted_paras().is_empty());
+			assert_eq!(Balances::reserved_balance(&1), 0);
+			assert_eq!(Balances::free_balance(&1), 90);
+			assert_eq!(offences(), vec![(vec![1], DisputeOffence {
+				session_index: SESSION,
+				validator_set_count: 4,
+				offenders: vec![public(1)],
+			})]);
+		});
+	}
+
+	#[test]
+	fn offences_are_reported_by_the_reporter_who_started_the_dispute() {
+		let para_id = ParaId::from(1);
+		let candidate_hash = Hash::from([1; 32]);
+
+		new_test_ext(genesis_config(para_id)).execute_with(|| {
+			start_session();
+			include_candidate(para_id, candidate_hash, vec![0]);
+
+			assert!(dispute(candidate_hash, vec![
+				statement(CompactStatement::Invalid(candidate_hash), 1),
+			]).is_ok());
+			assert!(dispute_by(2, candidate_hash, vec![
+				statement(CompactStatement::Valid(candidate_hash), 2),
+				statement(CompactStatement::Valid(candidate_hash), 3),
+			]).is_ok());
+
+			assert!(<Validity as Store>::Disputes::get(&candidate_hash).is_none());
+			assert_eq!(Balances::free_balance(&1), 90);
+			assert_eq!(offences(), vec![(vec![1], DisputeOffence {
+				session_index: SESSION,
+				validator_set_count: 4,
+				offenders: vec![public(1)],
+			})]);
+		});
+	}
+
+	#[test]
+	fn dispute_statements_are_checked() {
+		let para_id = ParaId::from(1);
+		let candidate_hash = Hash::from([1; 32]);
+		let other_hash = Hash::from([2; 32]);
+
+		new_test_ext(genesis_config(para_id)).execute_with(|| {
+			start_session();
+			include_candidate(para_id, candidate_hash, vec![0]);
+
+			assert_eq!(
+				dispute(other_hash, vec![statement(CompactStatement::Invalid(other_hash), 1)]),
+				Err(Error::<Test>::CandidateNotIncluded.into()),
+			);
+			assert_eq!(
+				dispute(candidate_hash, vec![statement(CompactStatement::Valid(candidate_hash), 1)]),
+				Err(Error::<Test>::DisputeRequiresInvalidVote.into()),
+			);
+			assert_eq!(
+				dispute(candidate_hash, vec![
+					statement(CompactStatement::Invalid(candidate_hash), 1),
+					statement(CompactStatement::Invalid(other_hash), 2),
+				]),
+				Err(Error::<Test>::StatementForWrongCandidate.into()),
+			);
+			assert_eq!(
+				dispute(candidate_hash, vec![statement(CompactStatement::Invalid(candidate_hash), 0)]),
+				Err(Error::<Test>::DuplicateVote.into()),
+			);
+
+			let context = SigningContext { session_index: SESSION, parent_hash: Hash::default() };
+			let bad_signature = SignedStatement::sign(
+				CompactStatement::Invalid(candidate_hash),
+				&context,
+				1,
+				&Sr25519Keyring::Charlie.pair().into(),
+			);
+			assert_eq!(
+				dispute(candidate_hash, vec![bad_signature]),
+				Err(Error::<Test>::InvalidStatementSignature.into()),
+			);
+			assert_eq!(
+				dispute(candidate_hash, vec![
+					statement(CompactStatement::Invalid(candidate_hash), 1),
+					statement(CompactStatement::Invalid(candidate_hash), 1),
+					statement(CompactStatement::Invalid(candidate_hash), 2),
+					statement(CompactStatement::Invalid(candidate_hash), 2),
+					statement(CompactStatement::Invalid(candidate_hash), 3),
+				]),
+				Err(Error::<Test>::TooManyStatements.into()),
+			);
+			assert_eq!(
+				Validity::report_dispute(
+					Origin::signed(1),
+					candidate_hash,
+					vec![statement(CompactStatement::Invalid(candidate_hash), 1)],
+					0,
+				),
+				Err(Error::<Test>::IncludedCandidatesWitnessTooLow.into()),
+			);
+
+			Balances::make_free_balance_be(&1, 5);
+			assert!(dispute(candidate_hash, vec![
+				statement(CompactStatement::Invalid(candidate_hash), 1),
+			]).is_err());
+			assert!(<Validity as Store>::Disputes::get(&candidate_hash).is_none());
+		});
+	}
+
+	#[test]
+	fn candidates_leave_acceptance_period_unless_disputed() {
+		let para_id = ParaId::from(1);
+		let (hash_a, hash_b) = (Hash::from([1; 32]), Hash::from([2; 32]));
+
+		new_test_ext(genesis_config(para_id)).execute_with(|| {
+			start_session();
+			System::set_block_number(1);
+			include_candidate(para_id, hash_a, vec![0]);
+			include_candidate(para_id, hash_b, vec![0]);
+
+			assert!(dispute(hash_b, vec![statement(CompactStatement::Invalid(hash_b), 1)]).is_ok());
+
+			let now: BlockNumber = 7;
+			System::set_block_number(now);
+			Validity::initializer_initialize(now);
+
+			assert!(<Validity as Store>::IncludedCandidatesList::get().is_empty());
+			assert!(<Validity as Store>::IncludedCandidates::get(&hash_a).is_none());
+			assert_eq!(
+				dispute(hash_a, vec![statement(CompactStatement::Invalid(hash_a), 1)]),
+				Err(Error::<Test>::CandidateNotIncluded.into()),
+			);
+
+			// the dispute on `hash_b` stays open past the acceptance period.
+			assert!(dispute(hash_b, vec![statement(CompactStatement::Invalid(hash_b), 2)]).is_ok());
+			assert!(dispute(hash_b, vec![statement(CompactStatement::Invalid(hash_b), 3)]).is_ok());
+			assert_eq!(offences().len(), 1);
+		});
+	}
+
+	#[test]
+	fn disputes_expire_with_the_validators_of_their_session() {
+		let para_id = ParaId::from(1);
+		let candidate_hash = Hash::from([1; 32]);
+
+		new_test_ext(genesis_config(para_id)).execute_with(|| {
+			start_session();
+			include_candidate(para_id, candidate_hash, vec![0]);
+
+			assert!(dispute(candidate_hash, vec![
+				statement(CompactStatement::Invalid(candidate_hash), 1),
+			]).is_ok());
+			assert_eq!(Balances::reserved_balance(&1), 10);
+
+			// the validators of the session are still known in the next one.
+			new_session(SESSION + 1);
+			assert!(<Validity as Store>::Disputes::get(&candidate_hash).is_some());
+
+			new_session(SESSION + 2);
+			assert!(<Validity as Store>::SessionValidators::get(&SESSION).is_none());
+			assert!(<Validity as Store>::Disputes::get(&candidate_hash).is_none());
+			assert!(<Validity as Store>::IncludedCandidates::get(&candidate_hash).is_none());
+			assert!(<Validity as Store>::DisputeDeposits::get(&candidate_hash).is_none());
+			assert_eq!(Balances::reserved_balance(&1), 0);
+			assert_eq!(Balances::free_balance(&1), 100);
+			assert!(offences().is_empty());
+		});
+	}
+}
@@ -149,6 +149,17 @@ pub type Client<B> = SubstrateClient<
 /// A test client with default backend.
 pub type TestClient = Client<Backend>;
 
+/// Test client database backend over the block type of the node.
+pub type NodeBackend = substrate_test_client::Backend<polkadot_primitives::Block>;
+
+/// Test client over the block type of the node, which the node-side subsystems work with.
+pub type NodeClient = SubstrateClient<
+	NodeBackend,
+	LocalCallExecutor<NodeBackend, sc_executor::NativeExecutor<LocalExecutor>>,
+	polkadot_primitives::Block,
+	polkadot_test_runtime::RuntimeApi,
+>;
+
 /// A `TestClientBuilder` with default backend and executor.
 pub trait DefaultTestClientBuilderExt: Sized {
 	/// Create new `TestClientBuilder`
@@ -278,6 +289,14 @@ pub fn new() -> Client<Backend> {
 	TestClientBuilder::new().build()
 }
 
+/// Creates new client instance over the block type of the node used for tests.
+pub fn new_node_client() -> NodeClient {
+	substrate_test_client::TestClientBuilder::<polkadot_primitives::Block, _, _, GenesisParameters>
+		::with_default_backend()
+		.build_with_native_executor(None)
+		.0
+}
+
 /// Creates new light client instance used for tests.
 pub fn new_light() -> (
 	SubstrateClient<
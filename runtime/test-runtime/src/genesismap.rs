@@ -63,6 +63,7 @@ impl GenesisConfig {
 			staking: None,
 			session: None,
 			grandpa: None,
+			authority_discovery: None,
 			claims: None,
 			parachains: None,
 			registrar: None,
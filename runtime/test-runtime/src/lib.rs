@@ -32,6 +32,10 @@ use runtime_common::{
 	BlockHashCount, MaximumBlockWeight, AvailableBlockRatio,
 	MaximumBlockLength, BlockExecutionWeight, ExtrinsicBaseWeight,
 };
+use runtime_parachains::{
+	configuration, paras, scheduler, inclusion, initializer, router, hrmp, validity,
+	runtime_api_impl as parachains_api,
+};
 use sp_runtime::{
 	create_runtime_str, generic, impl_opaque_keys,
 	ApplyExtrinsicResult, Perbill, RuntimeDebug, KeyTypeId,
@@ -46,6 +50,7 @@ use sp_runtime::{
 };
 use version::RuntimeVersion;
 use grandpa::{AuthorityId as GrandpaId, fg_primitives};
+use authority_discovery_primitives::AuthorityId as AuthorityDiscoveryId;
 #[cfg(any(feature = "std", test))]
 use version::NativeVersion;
 use sp_core::OpaqueMetadata;
@@ -242,7 +247,56 @@ impl_opaque_keys! {
 	pub struct SessionKeys {
 		pub grandpa: Grandpa,
 		pub babe: Babe,
-		pub parachain_validator: Parachains,
+		pub parachain_validator: ParachainSessionHandler,
+		pub authority_discovery: AuthorityDiscovery,
+	}
+}
+
+/// Notifies both the legacy `Parachains` module and the `Initializer` of the parachains modules
+/// of session changes, as both of them are keyed by the parachain validator key.
+pub struct ParachainSessionHandler;
+
+impl sp_runtime::BoundToRuntimeAppPublic for ParachainSessionHandler {
+	type Public = parachain::ValidatorId;
+}
+
+impl session::OneSessionHandler<AccountId> for ParachainSessionHandler {
+	type Key = parachain::ValidatorId;
+
+	fn on_genesis_session<'a, I: 'a>(validators: I)
+		where I: Iterator<Item=(&'a AccountId, Self::Key)>
+	{
+		let validators: Vec<_> = validators.collect();
+
+		<Parachains as session::OneSessionHandler<AccountId>>::on_genesis_session(
+			validators.iter().cloned(),
+		);
+		<Initializer as session::OneSessionHandler<AccountId>>::on_genesis_session(
+			validators.into_iter(),
+		);
+	}
+
+	fn on_new_session<'a, I: 'a>(changed: bool, validators: I, queued: I)
+		where I: Iterator<Item=(&'a AccountId, Self::Key)>
+	{
+		let validators: Vec<_> = validators.collect();
+		let queued: Vec<_> = queued.collect();
+
+		<Parachains as session::OneSessionHandler<AccountId>>::on_new_session(
+			changed,
+			validators.iter().cloned(),
+			queued.iter().cloned(),
+		);
+		<Initializer as session::OneSessionHandler<AccountId>>::on_new_session(
+			changed,
+			validators.into_iter(),
+			queued.into_iter(),
+		);
+	}
+
+	fn on_disabled(validator_index: usize) {
+		<Parachains as session::OneSessionHandler<AccountId>>::on_disabled(validator_index);
+		<Initializer as session::OneSessionHandler<AccountId>>::on_disabled(validator_index);
 	}
 }
 
@@ -317,6 +371,8 @@ impl staking::Trait for Runtime {
 	type MinSolutionScoreBump = MinSolutionScoreBump;
 }
 
+impl authority_discovery::Trait for Runtime {}
+
 impl grandpa::Trait for Runtime {
 	type Event = Event;
 	type Call = Call;
@@ -466,6 +522,39 @@ impl slots::Trait for Runtime {
 	type Randomness = RandomnessCollectiveFlip;
 }
 
+impl configuration::Trait for Runtime { }
+
+impl paras::Trait for Runtime { }
+
+impl scheduler::Trait for Runtime {
+	type Currency = Balances;
+}
+
+impl inclusion::Trait for Runtime { }
+
+impl router::Trait for Runtime {
+	type Origin = Origin;
+	type Call = Call;
+}
+
+impl hrmp::Trait for Runtime {
+	type Origin = Origin;
+	type Currency = Balances;
+}
+
+impl validity::Trait for Runtime {
+	type Currency = Balances;
+	// The inclusion inherent isn't part of this runtime, so there is nothing to clean up.
+	type OnParaReverted = ();
+	type IdentificationTuple = parachain::ValidatorId;
+	type IdentifyValidator = ConvertInto;
+	type ReportOffence = ();
+}
+
+impl initializer::Trait for Runtime {
+	type Randomness = RandomnessCollectiveFlip;
+}
+
 parameter_types! {
 	pub Prefix: &'static [u8] = b"Pay KSMs to the Kusama account:";
 }
@@ -513,6 +602,7 @@ construct_runtime! {
 		Historical: session_historical::{Module},
 		Session: session::{Module, Call, Storage, Event, Config<T>},
 		Grandpa: grandpa::{Module, Call, Storage, Config, Event},
+		AuthorityDiscovery: authority_discovery::{Module, Call, Config},
 
 		// Claims. Usable initially.
 		Claims: claims::{Module, Call, Storage, Event<T>, Config<T>, ValidateUnsigned},
@@ -524,6 +614,17 @@ construct_runtime! {
 		Slots: slots::{Module, Call, Storage, Event<T>},
 		Registrar: registrar::{Module, Call, Storage, Event, Config<T>},
 
+		// Parachains runtime modules. The inclusion inherent is left out, as blocks of this
+		// runtime don't carry the inclusion inherent data. Must be after session.
+		Configuration: configuration::{Module, Call, Storage},
+		Paras: paras::{Module, Call, Storage},
+		Scheduler: scheduler::{Module, Call, Storage},
+		Inclusion: inclusion::{Module, Call, Storage},
+		Router: router::{Module, Call, Storage, Origin},
+		Hrmp: hrmp::{Module, Call, Storage},
+		Validity: validity::{Module, Call, Storage},
+		Initializer: initializer::{Module, Call, Storage},
+
 		// Vesting. Usable initially, but removed once all vesting is finished.
 		Vesting: vesting::{Module, Call, Storage, Event<T>, Config<T>},
 	}
@@ -661,6 +762,36 @@ sp_api::impl_runtime_apis! {
 		}
 	}
 
+	impl parachain::ParachainHostV2<Block> for Runtime {
+		fn validators() -> Vec<parachain::ValidatorId> {
+			parachains_api::validators::<Runtime>()
+		}
+		fn validator_groups() -> (Vec<Vec<parachain::ValidatorIndex>>, parachain::GroupRotationInfo) {
+			parachains_api::validator_groups::<Runtime>()
+		}
+		fn availability_cores() -> Vec<parachain::CoreState> {
+			parachains_api::availability_cores::<Runtime>()
+		}
+		fn scheduled_cores() -> Vec<parachain::CoreAssignment> {
+			parachains_api::scheduled_cores::<Runtime>()
+		}
+		fn persisted_validation_data(id: parachain::Id) -> Option<parachain::PersistedValidationData> {
+			parachains_api::persisted_validation_data::<Runtime>(id)
+		}
+		fn transient_validation_data(id: parachain::Id) -> Option<parachain::TransientValidationData> {
+			parachains_api::transient_validation_data::<Runtime>(id)
+		}
+		fn host_configuration() -> parachain::HostConfiguration<BlockNumber> {
+			parachains_api::host_configuration::<Runtime>()
+		}
+		fn validation_code(id: parachain::Id) -> Option<parachain::ValidationCode> {
+			parachains_api::validation_code::<Runtime>(id)
+		}
+		fn validation_code_by_hash(hash: HashT) -> Option<parachain::ValidationCode> {
+			parachains_api::validation_code_by_hash::<Runtime>(hash)
+		}
+	}
+
 	impl fg_primitives::GrandpaApi<Block> for Runtime {
 		fn grandpa_authorities() -> Vec<(GrandpaId, u64)> {
 			Grandpa::grandpa_authorities()
@@ -706,6 +837,12 @@ sp_api::impl_runtime_apis! {
 		}
 	}
 
+	impl authority_discovery_primitives::AuthorityDiscoveryApi<Block> for Runtime {
+		fn authorities() -> Vec<AuthorityDiscoveryId> {
+			AuthorityDiscovery::authorities()
+		}
+	}
+
 	impl sp_session::SessionKeys<Block> for Runtime {
 		fn generate_session_keys(seed: Option<Vec<u8>>) -> Vec<u8> {
 			SessionKeys::generate(seed)
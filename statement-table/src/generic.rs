@@ -397,6 +397,16 @@ impl<C: Context> Table<C> {
 		}
 	}
 
+	/// Get the attested candidate for the given digest, if it can be included.
+	pub fn attested_candidate(&self, digest: &C::Digest, context: &C)
+		-> Option<AttestedCandidate<C::GroupId, C::Candidate, C::AuthorityId, C::Signature>>
+	{
+		self.candidate_votes.get(digest).and_then(|data| {
+			let v_threshold = context.requisite_votes(&data.group_id);
+			data.attested(v_threshold)
+		})
+	}
+
 	/// Get a candidate by digest.
 	pub fn get_candidate(&self, digest: &C::Digest) -> Option<&C::Candidate> {
 		self.candidate_votes.get(digest).map(|d| &d.candidate)
@@ -1094,4 +1104,41 @@ mod tests {
 		assert_eq!(summary.group_id, GroupId(2));
 		assert_eq!(summary.validity_votes, 2);
 	}
+
+	#[test]
+	fn attested_candidate_requires_threshold() {
+		let context = TestContext {
+			authorities: {
+				let mut map = HashMap::new();
+				map.insert(AuthorityId(1), GroupId(2));
+				map.insert(AuthorityId(2), GroupId(2));
+				map.insert(AuthorityId(3), GroupId(2));
+				map
+			}
+		};
+
+		let mut table = create();
+		let candidate_digest = Digest(100);
+
+		table.import_statement(&context, SignedStatement {
+			statement: Statement::Candidate(Candidate(2, 100)),
+			signature: Signature(1),
+			sender: AuthorityId(1),
+		});
+
+		assert!(table.attested_candidate(&candidate_digest, &context).is_none());
+
+		table.import_statement(&context, SignedStatement {
+			statement: Statement::Valid(candidate_digest.clone()),
+			signature: Signature(2),
+			sender: AuthorityId(2),
+		});
+
+		let attested = table.attested_candidate(&candidate_digest, &context)
+			.expect("candidate has enough votes to be attested");
+
+		assert_eq!(attested.group_id, GroupId(2));
+		assert_eq!(attested.candidate, Candidate(2, 100));
+		assert_eq!(attested.validity_votes.len(), 2);
+	}
 }
@@ -18,6 +18,11 @@
 //!
 //! The `ProposerFactory` exported by this module will be wrapped by some
 //! consensus engine, and triggered when it is time to create a block.
+//!
+//! This is the proposer of the legacy service, which includes `AttestedCandidate`s from the
+//! validation tracker. Nodes built on the overseer use the proposer of
+//! `polkadot-node-core-proposer` instead, which includes the inherent data of the Provisioner
+//! subsystem.
 
 use std::{
 	pin::Pin,
@@ -151,6 +151,7 @@ impl<'a> ValidatedCandidate<'a> {
 			erasure_root,
 			new_validation_code: None,
 			processed_downward_messages,
+			hrmp: (),
 		};
 
 		Ok(FullOutput {